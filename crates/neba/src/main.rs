@@ -3,27 +3,177 @@ use std::fs;
 use std::process;
 
 use neba_parser::parse;
-use neba_vm::run;
+use neba_vm::{run, Compiler, Vm};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     match args.len() {
         1 => run_repl(),
-        2 => match fs::read_to_string(&args[1]) {
-            Ok(source) => run_source(&args[1], &source),
-            Err(e) => {
-                eprintln!("neba: cannot read '{}': {}", args[1], e);
-                process::exit(1);
-            }
-        },
+        2 => run_source(&args[1], &read_source(&args[1]), false, false),
+        3 if args[1] == "fmt" => format_file(&args[2]),
+        3 if args[1] == "test" => run_tests(&args[2], &read_source(&args[2])),
+        4 if args[1] == "test" && args[2] == "--doc" => run_doctests(&args[3], &read_source(&args[3])),
+        3 if args[1] == "--diagnostics=json" => println!("{}", neba_typecheck::diagnostics_json(&read_source(&args[2]))),
+        3 if args[1] == "--typed" => run_source(&args[2], &read_source(&args[2]), true, false),
+        3 if args[1] == "--checked-arrays" => run_source(&args[2], &read_source(&args[2]), false, true),
+        3 if args[1] == "--coverage" => run_interpreted(&args[2], &read_source(&args[2]), true, false),
+        3 if args[1] == "--contracts" => run_interpreted(&args[2], &read_source(&args[2]), false, true),
+        3 if args[1] == "--ast" => dump_ast(&read_source(&args[2])),
+        3 if args[1] == "--tokens" => dump_tokens(&read_source(&args[2])),
         _ => {
-            eprintln!("Usage: neba [script.neba]");
+            eprintln!("Usage: neba [--ast | --tokens | --typed | --checked-arrays | --coverage | --contracts | --diagnostics=json] [script.neba]");
+            eprintln!("       neba fmt script.neba");
+            eprintln!("       neba test [--doc] script.neba");
+            process::exit(1);
+        }
+    }
+}
+
+fn read_source(path: &str) -> String {
+    match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("neba: cannot read '{}': {}", path, e);
             process::exit(1);
         }
     }
 }
 
-fn run_source(path: &str, source: &str) {
+/// `neba --tokens script.neba` — stampa il flusso di token, gli errori e i warning del lexer.
+fn dump_tokens(source: &str) {
+    let (tokens, lex_errors, lex_warnings) = neba_lexer::tokenize(source);
+    print!("{}", neba_lexer::dump_tokens(&tokens));
+    for e in lex_errors.iter().chain(&lex_warnings) { eprintln!("{}", e); }
+    if !lex_errors.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// `neba fmt script.neba` — riscrive il file nella forma canonica del pretty-printer.
+/// Rifiuta file con errori di sintassi o con commenti (che andrebbero persi).
+fn format_file(path: &str) {
+    let source = read_source(path);
+    let (program, lex_errors, lex_warnings, parse_errors) = neba_parser::parse_with_warnings(&source);
+    for e in lex_errors.iter().chain(&lex_warnings) { eprintln!("{}", e); }
+    for e in &parse_errors { eprintln!("{}", e); }
+    if !lex_errors.is_empty() || !parse_errors.is_empty() {
+        process::exit(1);
+    }
+    if neba_parser::format::has_comments(&source) {
+        eprintln!("neba fmt: '{}' contains comments, which are not preserved yet; file left unchanged", path);
+        process::exit(1);
+    }
+    let formatted = neba_parser::format_program(&program);
+    if formatted != source {
+        if let Err(e) = fs::write(path, formatted) {
+            eprintln!("neba: cannot write '{}': {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// `neba test script.neba` — esegue il file, poi ogni funzione top-level `test_*`
+/// senza parametri, nell'ordine del sorgente. Un test fallisce se solleva un errore
+/// (`assert`, `assert_eq`, `assert_raises`, ...). Esce con 1 se almeno uno fallisce.
+fn run_tests(path: &str, source: &str) {
+    let (program, lex_errors, parse_errors) = parse(source);
+    for e in &lex_errors   { eprintln!("[LexError] {}", e); }
+    for e in &parse_errors { eprintln!("[ParseError] {}", e); }
+    if !lex_errors.is_empty() || !parse_errors.is_empty() {
+        process::exit(1);
+    }
+    let mut vm = load_for_tests(path, &program);
+    let tests: Vec<&str> = program.stmts.iter().filter_map(|s| match &s.inner {
+        neba_parser::ast::StmtKind::Fn { name, params, .. }
+            if name.starts_with("test_") && params.is_empty() => Some(name.as_str()),
+        _ => None,
+    }).collect();
+
+    println!("running {} test(s)", tests.len());
+    let mut failed = Vec::new();
+    for name in &tests {
+        match vm.call_function(name, Vec::new()) {
+            Ok(_)  => println!("test {} ... ok", name),
+            Err(e) => { println!("test {} ... FAILED", name); failed.push((name.to_string(), e.to_string())); }
+        }
+    }
+    report_tests(tests.len(), &failed);
+}
+
+/// `neba test --doc script.neba` — esegue il file, poi gli esempi dei commenti `##`
+/// (vedi `neba_parser::doc`) nella stessa VM; ogni `# => valore` viene valutato e
+/// confrontato con l'ultima espressione che lo precede.
+fn run_doctests(path: &str, source: &str) {
+    let (program, lex_errors, parse_errors) = parse(source);
+    for e in &lex_errors   { eprintln!("[LexError] {}", e); }
+    for e in &parse_errors { eprintln!("[ParseError] {}", e); }
+    if !lex_errors.is_empty() || !parse_errors.is_empty() {
+        process::exit(1);
+    }
+    let mut vm = load_for_tests(path, &program);
+    let examples = neba_parser::doc_examples(source);
+
+    println!("running {} doctest(s)", examples.len());
+    let mut failed = Vec::new();
+    for ex in &examples {
+        let name = format!("{}:{}", path, ex.line);
+        match run_doc_example(&mut vm, ex) {
+            Ok(())   => println!("doctest {} ... ok", name),
+            Err(msg) => { println!("doctest {} ... FAILED", name); failed.push((name, msg)); }
+        }
+    }
+    report_tests(examples.len(), &failed);
+}
+
+fn run_doc_example(vm: &mut Vm, ex: &neba_parser::DocExample) -> Result<(), String> {
+    for step in &ex.steps {
+        let got = vm.eval(&step.code).map_err(|e| format!("line {}: {}", step.line, e))?;
+        let Some(expected) = &step.expected else { continue };
+        let want = vm.eval(expected).map_err(|e| format!("line {}: invalid expected value: {}", step.line, e))?;
+        if got != want {
+            return Err(format!("line {}: expected {}, got {}", step.line, expected, got));
+        }
+    }
+    Ok(())
+}
+
+/// VM con il programma già eseguito, per chiamarne funzioni ed esempi.
+fn load_for_tests(path: &str, program: &neba_parser::ast::Program) -> Vm {
+    let mut vm = Vm::new();
+    if let Err(e) = vm.run_program(program) {
+        eprintln!("[RuntimeError] {} (in {})", e, path);
+        process::exit(1);
+    }
+    vm
+}
+
+/// Dettagli dei fallimenti e riepilogo; esce con 1 se qualcosa è fallito.
+fn report_tests(total: usize, failed: &[(String, String)]) {
+    for (name, e) in failed {
+        println!("\n---- {} ----\n{}", name, e);
+    }
+    let status = if failed.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed", status, total - failed.len(), failed.len());
+    if !failed.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// `neba --ast script.neba` — stampa l'AST come albero indentato.
+fn dump_ast(source: &str) {
+    let (program, lex_errors, parse_errors) = parse(source);
+    print!("{}", neba_parser::dump_program(&program));
+    for e in &lex_errors   { eprintln!("{}", e); }
+    for e in &parse_errors { eprintln!("{}", e); }
+    if !lex_errors.is_empty() || !parse_errors.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Esegue lo script con la VM; con `typed` (`neba --typed script.neba`) lo passa
+/// prima al type checker e non lo esegue se ci sono errori; con `checked_arrays`
+/// (`neba --checked-arrays script.neba`) gli `Array[T]` annotati controllano gli inserimenti.
+fn run_source(path: &str, source: &str, typed: bool, checked_arrays: bool) {
     // 1. Parse
     let (program, lex_errors, parse_errors) = parse(source);
     let has_errors = !lex_errors.is_empty() || !parse_errors.is_empty();
@@ -34,16 +184,58 @@ fn run_source(path: &str, source: &str) {
         process::exit(1);
     }
 
-    // 2. Esegui con la VM
-    match run(source) {
+    // 2. Type check opzionale: nessun effetto collaterale se fallisce
+    if typed {
+        let type_errors = neba_typecheck::check(source);
+        for e in &type_errors { eprintln!("[TypeError] {}", e); }
+        if !type_errors.is_empty() {
+            eprintln!("{} error(s).", type_errors.len());
+            process::exit(1);
+        }
+    }
+
+    // 3. Esegui con la VM
+    let mut vm = Vm::new();
+    vm.set_checked_arrays(checked_arrays);
+    // Gli script seguono IEEE 754 per `/` tra Float (vedi LANGUAGE.md)
+    vm.set_ieee_division(true);
+    match Compiler::compile(&program).and_then(|chunk| vm.run_chunk(chunk)) {
         Ok(_)  => {}
         Err(e) => {
-            eprintln!("[RuntimeError] {}", e);
+            match vm.error_position() {
+                Some((line, col)) => eprintln!("[RuntimeError] {} (at {}:{}:{})", e, path, line, col),
+                None              => eprintln!("[RuntimeError] {}", e),
+            }
             process::exit(1);
         }
     }
 }
 
+/// Esegue lo script con l'interprete invece che con la VM:
+/// - `neba --coverage script.neba` registra le righe eseguite e stampa su stderr il
+///   rapporto di copertura (anche dopo un errore)
+/// - `neba --contracts script.neba` verifica le clausole `requires`/`ensures`
+fn run_interpreted(path: &str, source: &str, coverage: bool, contracts: bool) {
+    let (program, lex_errors, parse_errors) = parse(source);
+    for e in &lex_errors   { eprintln!("[LexError] {}", e); }
+    for e in &parse_errors { eprintln!("[ParseError] {}", e); }
+    if !lex_errors.is_empty() || !parse_errors.is_empty() {
+        process::exit(1);
+    }
+    let mut interp = neba_interpreter::Interpreter::new();
+    interp.set_coverage(coverage).set_contracts(contracts);
+    let result = interp.run(&program);
+    if let Err(e) = &result {
+        eprintln!("[RuntimeError] {} (in {})", e, path);
+    }
+    if let Some(report) = interp.coverage_report(&program) {
+        eprintln!("{}", report);
+    }
+    if result.is_err() {
+        process::exit(1);
+    }
+}
+
 fn run_repl() {
     use std::io::{self, BufRead, Write};
     println!("Neba REPL v0.2.2 — Ctrl-D to exit");
@@ -0,0 +1,159 @@
+//! Test della CLI `neba`: `--diagnostics=json`, `--ast`, `fmt` e `test` (anche `--doc`).
+
+use std::process::Command;
+
+fn run_neba(args: &[&str], source: &str, file: &str) -> std::process::Output {
+    let path = std::env::temp_dir().join(file);
+    std::fs::write(&path, source).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_neba"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).ok();
+    out
+}
+
+// ── --diagnostics=json ─────────────────────────────────────────────────────
+
+/// Valore grezzo del campo `key` in un oggetto JSON piatto (stringhe senza virgolette).
+fn json_field<'a>(obj: &'a str, key: &str) -> &'a str {
+    let start = obj.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3;
+    let rest = &obj[start..];
+    if let Some(s) = rest.strip_prefix('"') {
+        &s[..s.find('"').unwrap()]
+    } else {
+        &rest[..rest.find([',', '}']).unwrap()]
+    }
+}
+
+#[test]
+fn diagnostics_json_reports_fields() {
+    let out = run_neba(&["--diagnostics=json"], "let a = 1\nlet b: Int = \"s\"\n", "neba_diag.neba");
+    assert!(out.status.success());
+    let json = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    assert!(json.starts_with("[{") && json.ends_with("}]"), "{}", json);
+    assert_eq!(json.matches("\"severity\"").count(), 1, "{}", json);
+    assert_eq!(json_field(&json, "severity"), "error");
+    assert_eq!(json_field(&json, "code"), "E0001");
+    assert_eq!(json_field(&json, "message"), "type mismatch: expected 'Int', got 'Str'");
+    assert_eq!(json_field(&json, "line"), "2");
+    assert_eq!(json_field(&json, "column"), "14");
+    assert_eq!(json_field(&json, "end_line"), "2");
+    assert_eq!(json_field(&json, "end_column"), "17");
+}
+
+#[test]
+fn diagnostics_json_reports_lex_warnings() {
+    let out = run_neba(&["--diagnostics=json"], "let x\t= 1\n", "neba_diag_tab.neba");
+    let json = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    assert_eq!(json_field(&json, "severity"), "warning");
+    assert_eq!(json_field(&json, "code"), "L0010");
+}
+
+#[test]
+fn diagnostics_json_clean_program() {
+    let out = run_neba(&["--diagnostics=json"], "let a = 1\n", "neba_diag_clean.neba");
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "[]");
+}
+
+#[test]
+fn diagnostics_json_clean_stdlib_program() {
+    let src = "var a = [2, 1]\nsort(a)\nlet ks = keys({\"k\": 1})\nprintln(math.sqrt(2.0), sum(a), ks)\n";
+    let out = run_neba(&["--diagnostics=json"], src, "neba_diag_stdlib.neba");
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "[]");
+}
+
+// ── --ast ──────────────────────────────────────────────────────────────────
+
+#[test]
+fn ast_dump_lists_nodes_in_order() {
+    let out = run_neba(&["--ast"], "let x = 1 + 2\nfn f(a)\n    return a\n", "neba_ast.neba");
+    assert!(out.status.success());
+    let dump = String::from_utf8_lossy(&out.stdout);
+    let labels = ["Program", "Let x @1:1", "Binary + @1:9", "Int 1", "Int 2 @1:13", "Fn f @2:1", "Param a", "Return @3:5", "Ident a"];
+    let mut rest = &dump[..];
+    for label in labels {
+        let at = rest.find(label).unwrap_or_else(|| panic!("{:?} mancante o fuori ordine in:\n{}", label, dump));
+        rest = &rest[at + label.len()..];
+    }
+    // Albero indentato, non la forma `{:?}`
+    assert!(dump.contains("\n  Let x") && dump.contains("\n    Binary") && !dump.contains("StmtKind"));
+}
+
+// ── fmt ────────────────────────────────────────────────────────────────────
+
+#[test]
+fn fmt_rewrites_file_in_place() {
+    let path = std::env::temp_dir().join("neba_fmt.neba");
+    std::fs::write(&path, "let  x=[1,2]\nif x[0]>0\n  println(r\"ok\")\n").unwrap();
+    let run = || Command::new(env!("CARGO_BIN_EXE_neba")).arg("fmt").arg(&path).output().unwrap();
+    assert!(run().status.success());
+    let expected = "let x = [1, 2]\nif x[0] > 0\n    println(\"ok\")\n";
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+    assert!(run().status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn fmt_prints_lex_warnings() {
+    let out = run_neba(&["fmt"], "let x\t= 1\n", "neba_fmt_tab.neba");
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("L0010"));
+}
+
+#[test]
+fn fmt_refuses_comments() {
+    let out = run_neba(&["fmt"], "let x = 1  # uno\nlet s = \"#no\"\n", "neba_fmt_comments.neba");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("comments"));
+    let out = run_neba(&["fmt"], "let s = \"#no\"\n", "neba_fmt_hash_in_str.neba");
+    assert!(out.status.success());
+}
+
+// ── test ───────────────────────────────────────────────────────────────────
+
+const TESTS: &str = "fn doppio(x)\n    return x * 2\n\nfn test_ok()\n    assert_eq(doppio(2), 4)\n    assert_raises(fn() => 1 / 0)\n\nfn test_ko()\n    assert_eq(doppio(2), 5)\n\nfn test_helper(x)\n    assert(false)\n";
+
+#[test]
+fn test_runner_reports_summary() {
+    let out = run_neba(&["test"], TESTS, "neba_test_runner.neba");
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("running 2 test(s)"), "{}", stdout);
+    assert!(stdout.contains("test test_ok ... ok"), "{}", stdout);
+    assert!(stdout.contains("test test_ko ... FAILED"), "{}", stdout);
+    assert!(stdout.contains("4 != 5"), "{}", stdout);
+    assert!(stdout.contains("test result: FAILED. 1 passed; 1 failed"), "{}", stdout);
+}
+
+#[test]
+fn test_runner_all_passing() {
+    let out = run_neba(&["test"], "fn test_a()\n    assert(true)\n", "neba_test_pass.neba");
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("test result: ok. 1 passed; 0 failed"));
+}
+
+#[test]
+fn contracts_flag_checks_requires() {
+    let src = "fn metà(n)\n    requires n % 2 == 0\n    return n / 2\nprintln(metà(3))\n";
+    let out = run_neba(&["--contracts"], src, "neba_contracts.neba");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Contract violation in 'metà': requires n % 2 == 0"));
+    assert!(run_neba(&[], src, "neba_contracts_off.neba").status.success());
+}
+
+const DOCTESTS: &str = "## ```neba\n## doppio(21)  # => 42\n## [doppio(1), doppio(2)]\n## # => [2, 4]\n## ```\nfn doppio(x)\n    return x * 2\n\n## ```\n## triplo(2)  # => 5\n## ```\nfn triplo(x)\n    return x * 3\n";
+
+#[test]
+fn doctest_runner_checks_expected_values() {
+    let out = run_neba(&["test", "--doc"], DOCTESTS, "neba_doctest.neba");
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("running 2 doctest(s)"), "{}", stdout);
+    assert!(stdout.contains("neba_doctest.neba:1 ... ok"), "{}", stdout);
+    assert!(stdout.contains("neba_doctest.neba:9 ... FAILED"), "{}", stdout);
+    assert!(stdout.contains("line 10: expected 5, got 6"), "{}", stdout);
+    assert!(stdout.contains("test result: FAILED. 1 passed; 1 failed"), "{}", stdout);
+}
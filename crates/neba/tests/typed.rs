@@ -0,0 +1,56 @@
+//! `neba --typed`: il type checker blocca l'esecuzione prima di qualunque effetto.
+
+use std::process::Command;
+
+fn run_neba(args: &[&str], source: &str, file: &str) -> std::process::Output {
+    let path = std::env::temp_dir().join(file);
+    std::fs::write(&path, source).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_neba"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).ok();
+    out
+}
+
+const ILL_TYPED: &str = "let x: Int = \"a\"\nprintln(\"ran\")\n";
+
+#[test]
+fn typed_rejects_type_errors() {
+    let out = run_neba(&["--typed"], ILL_TYPED, "neba_typed_reject.neba");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("[TypeError]"));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("ran"));
+}
+
+#[test]
+fn untyped_runs_anyway() {
+    let out = run_neba(&[], ILL_TYPED, "neba_typed_untyped.neba");
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("ran"));
+}
+
+#[test]
+fn typed_runs_well_typed() {
+    let out = run_neba(&["--typed"], "let x: Int = 1\nprintln(x + 1)\n", "neba_typed_ok.neba");
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "2");
+}
+
+#[test]
+fn typed_runs_stdlib_calls() {
+    let src = "var a = [3, 1, 2]\nsort(a)\nlet d = {\"k\": 1}\nprintln(keys(d), sum(a), math.sqrt(4.0), join(map(a, str), \",\"))\n";
+    let out = run_neba(&["--typed"], src, "neba_typed_stdlib.neba");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "[k] 6 2.0 1,2,3");
+}
+
+#[test]
+fn typed_knows_every_vm_global() {
+    let mut globals = Default::default();
+    neba_vm::stdlib::register_globals(&mut globals);
+    let env = neba_typecheck::TypeEnv::new();
+    let missing: Vec<_> = globals.keys().filter(|name| env.lookup(name).is_none()).collect();
+    assert!(missing.is_empty(), "globals unknown to the type checker: {:?}", missing);
+}
@@ -0,0 +1,111 @@
+//! Copertura per riga. Con `Interpreter::set_coverage(true)` ogni statement
+//! eseguito marca la propria riga in una bitmap; `CoverageReport` la confronta
+//! con le righe del programma che contengono statement.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use neba_parser::ast::{Expr, ExprKind, Program, Stmt, StmtKind};
+
+/// Righe (1-based, ordinate) eseguite e non eseguite di un programma.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CoverageReport {
+    pub covered: Vec<usize>,
+    pub uncovered: Vec<usize>,
+}
+
+impl CoverageReport {
+    /// `hits[line]` è true se almeno uno statement su `line` è stato eseguito.
+    pub fn new(program: &Program, hits: &[bool]) -> Self {
+        let (covered, uncovered) = executable_lines(program).into_iter()
+            .partition(|&l| hits.get(l).copied().unwrap_or(false));
+        CoverageReport { covered, uncovered }
+    }
+
+    /// Percentuale di righe eseguite (100 per un programma senza statement).
+    pub fn percent(&self) -> f64 {
+        let total = self.covered.len() + self.uncovered.len();
+        if total == 0 { 100.0 } else { self.covered.len() as f64 * 100.0 / total as f64 }
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.covered.len() + self.uncovered.len();
+        write!(f, "coverage: {}/{} lines ({:.1}%)", self.covered.len(), total, self.percent())?;
+        if !self.uncovered.is_empty() {
+            let lines: Vec<String> = self.uncovered.iter().map(|l| l.to_string()).collect();
+            write!(f, "\nuncovered lines: {}", lines.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Righe che contengono l'inizio di uno statement eseguibile.
+/// La riga `fn` dei metodi non conta: dichiararli non passa da `exec_stmt`.
+pub fn executable_lines(program: &Program) -> Vec<usize> {
+    let mut out = BTreeSet::new();
+    stmt_lines(&program.stmts, &mut out);
+    out.into_iter().collect()
+}
+
+fn stmt_lines(stmts: &[Stmt], out: &mut BTreeSet<usize>) {
+    for s in stmts {
+        out.insert(s.span.line);
+        match &s.inner {
+            StmtKind::Let { value, .. } | StmtKind::Var { value, .. } => expr_lines(value, out),
+            StmtKind::Assign { target, value, .. } => { expr_lines(target, out); expr_lines(value, out); }
+            StmtKind::Fn { body, .. } => stmt_lines(body, out),
+            StmtKind::Class { methods, impls, .. } => { method_lines(methods, out); method_lines(impls, out); }
+            StmtKind::Trait { methods, .. } | StmtKind::Impl { methods, .. } => method_lines(methods, out),
+            StmtKind::While { condition, body } => { expr_lines(condition, out); stmt_lines(body, out); }
+            StmtKind::For { iterable, body, .. } => { expr_lines(iterable, out); stmt_lines(body, out); }
+            StmtKind::Return(Some(e)) | StmtKind::Expr(e) => expr_lines(e, out),
+            _ => {}
+        }
+    }
+}
+
+/// Solo i corpi: i metodi (anche dentro un `impl` annidato) non sono statement eseguiti.
+fn method_lines(methods: &[Stmt], out: &mut BTreeSet<usize>) {
+    for m in methods {
+        match &m.inner {
+            StmtKind::Fn { body, .. } => stmt_lines(body, out),
+            StmtKind::Impl { methods, .. } => method_lines(methods, out),
+            _ => {}
+        }
+    }
+}
+
+/// Blocchi annidati nelle espressioni: if, match e lambda a più righe.
+fn expr_lines(e: &Expr, out: &mut BTreeSet<usize>) {
+    match &e.inner {
+        ExprKind::If { condition, then_block, elif_branches, else_block } => {
+            expr_lines(condition, out);
+            stmt_lines(then_block, out);
+            for (c, b) in elif_branches { expr_lines(c, out); stmt_lines(b, out); }
+            if let Some(b) = else_block { stmt_lines(b, out); }
+        }
+        ExprKind::Match { subject, arms } => {
+            expr_lines(subject, out);
+            for arm in arms { stmt_lines(&arm.body, out); }
+        }
+        ExprKind::Lambda { body, .. } => stmt_lines(body, out),
+        ExprKind::Binary { left, right, .. } | ExprKind::Pipe { value: left, func: right } => {
+            expr_lines(left, out);
+            expr_lines(right, out);
+        }
+        ExprKind::Call { callee, args, kwargs } => {
+            expr_lines(callee, out);
+            for a in args { expr_lines(a, out); }
+            for (_, a) in kwargs { expr_lines(a, out); }
+        }
+        ExprKind::Array(items) | ExprKind::Tuple(items) => for i in items { expr_lines(i, out); },
+        ExprKind::Dict(pairs) => for (k, v) in pairs { expr_lines(k, out); expr_lines(v, out); },
+        ExprKind::Unary { operand: x, .. } | ExprKind::Field { object: x, .. }
+        | ExprKind::Spawn(x) | ExprKind::Await(x) | ExprKind::Some(x) | ExprKind::Ok(x)
+        | ExprKind::Err(x) | ExprKind::Try(x) => expr_lines(x, out),
+        ExprKind::Index { object, index } => { expr_lines(object, out); expr_lines(index, out); }
+        _ => {}
+    }
+}
@@ -4,67 +4,68 @@ use std::rc::Rc;
 
 use crate::value::Value;
 
-/// Un singolo frame dello scope.
-#[derive(Debug, Clone)]
-struct Frame {
-    vars: HashMap<String, (Value, bool)>, // (valore, è_mutabile)
+/// Un singolo scope: le variabili locali più il link allo scope che lo racchiude.
+#[derive(Debug)]
+struct Scope {
+    vars:   RefCell<HashMap<String, (Value, bool)>>, // (valore, è_mutabile)
+    parent: Option<Rc<Scope>>,
 }
 
-impl Frame {
-    fn new() -> Self {
-        Frame { vars: HashMap::new() }
+impl Scope {
+    fn new(parent: Option<Rc<Scope>>) -> Rc<Self> {
+        Rc::new(Scope { vars: RefCell::new(HashMap::new()), parent })
+    }
+
+    /// Itera dallo scope corrente verso il globale.
+    fn chain(self: &Rc<Self>) -> impl Iterator<Item = &Scope> {
+        std::iter::successors(Some(&**self), |s| s.parent.as_deref())
     }
 }
 
-/// Environment con scope annidati (stack di frame).
-/// Clonare un Env produce una "closure" che condivide i frame superiori.
+/// Environment con scope annidati (catena di scope collegati al padre).
+/// Clonare un Env costa un solo incremento di refcount: la closure condivide
+/// lo scope corrente e tutti quelli che lo racchiudono.
 #[derive(Debug, Clone)]
 pub struct Env {
-    // Stack di frame: il primo è il globale, l'ultimo è il locale corrente.
-    // Usiamo Rc<RefCell<_>> per permettere la condivisione tra closures.
-    frames: Vec<Rc<RefCell<Frame>>>,
+    // Scope corrente; la catena dei `parent` termina nel globale.
+    scope: Rc<Scope>,
 }
 
 impl Env {
-    /// Crea un nuovo environment vuoto (solo frame globale).
+    /// Crea un nuovo environment vuoto (solo scope globale).
     pub fn new() -> Self {
-        Env { frames: vec![Rc::new(RefCell::new(Frame::new()))] }
+        Env { scope: Scope::new(None) }
     }
 
-    /// Apre un nuovo scope (push frame).
+    /// Apre un nuovo scope figlio di quello corrente.
     pub fn push_scope(&mut self) {
-        self.frames.push(Rc::new(RefCell::new(Frame::new())));
+        self.scope = Scope::new(Some(self.scope.clone()));
     }
 
-    /// Chiude lo scope corrente (pop frame).
+    /// Chiude lo scope corrente tornando al padre (il globale non si chiude mai).
     pub fn pop_scope(&mut self) {
-        if self.frames.len() > 1 {
-            self.frames.pop();
+        if let Some(parent) = self.scope.parent.clone() {
+            self.scope = parent;
         }
     }
 
-    /// Definisce una variabile nel frame corrente.
+    /// Definisce una variabile nello scope corrente.
     /// `mutable` = true per `var`, false per `let`.
     pub fn define(&mut self, name: impl Into<String>, value: Value, mutable: bool) {
-        let frame = self.frames.last().unwrap();
-        frame.borrow_mut().vars.insert(name.into(), (value, mutable));
+        self.scope.vars.borrow_mut().insert(name.into(), (value, mutable));
     }
 
     /// Legge una variabile cercando dallo scope corrente verso il globale.
     pub fn get(&self, name: &str) -> Option<Value> {
-        for frame in self.frames.iter().rev() {
-            if let Some((val, _)) = frame.borrow().vars.get(name) {
-                return Some(val.clone());
-            }
-        }
-        None
+        self.scope.chain()
+            .find_map(|s| s.vars.borrow().get(name).map(|(val, _)| val.clone()))
     }
 
     /// Aggiorna una variabile esistente. Errore se non esiste o non è mutabile.
     pub fn set(&mut self, name: &str, value: Value) -> Result<(), String> {
-        for frame in self.frames.iter().rev() {
-            let mut frame = frame.borrow_mut();
-            if let Some((val, mutable)) = frame.vars.get_mut(name) {
+        for scope in self.scope.chain() {
+            let mut vars = scope.vars.borrow_mut();
+            if let Some((val, mutable)) = vars.get_mut(name) {
                 if !*mutable {
                     return Err(format!("cannot assign to immutable variable '{}'", name));
                 }
@@ -75,11 +76,26 @@ impl Env {
         Err(format!("undefined variable '{}'", name))
     }
 
-    /// Crea una snapshot dell'environment corrente (per chiusure di funzioni).
-    /// I frame sono condivisi (Rc), quindi le closure vedono le modifiche successive
+    /// Modifica in place una variabile esistente, senza clonarne il valore.
+    /// Errore se non esiste o non è mutabile (come `set`).
+    pub fn update<R>(&mut self, name: &str, f: impl FnOnce(&mut Value) -> R) -> Result<R, String> {
+        for scope in self.scope.chain() {
+            let mut vars = scope.vars.borrow_mut();
+            if let Some((val, mutable)) = vars.get_mut(name) {
+                if !*mutable {
+                    return Err(format!("cannot assign to immutable variable '{}'", name));
+                }
+                return Ok(f(val));
+            }
+        }
+        Err(format!("undefined variable '{}'", name))
+    }
+
+    /// Cattura l'environment corrente (per chiusure di funzioni).
+    /// Gli scope sono condivisi (Rc), quindi le closure vedono le modifiche successive
     /// alle variabili catturate — comportamento identico a Python.
     pub fn snapshot(&self) -> Env {
-        Env { frames: self.frames.clone() }
+        self.clone()
     }
 }
 
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use neba_lexer::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeError {
     /// Variabile non definita
@@ -10,8 +12,8 @@ pub enum RuntimeError {
     TypeError { message: String },
     /// Divisione per zero
     DivisionByZero,
-    /// Indice fuori range
-    IndexOutOfBounds { index: i64, len: usize },
+    /// Indice fuori range; `span` è l'espressione `a[i]` che l'ha causato, se nota
+    IndexOutOfBounds { index: i64, len: usize, span: Option<Span> },
     /// Chiamata a non-callable
     NotCallable { type_name: String },
     /// Numero di argomenti errato
@@ -22,6 +24,13 @@ pub enum RuntimeError {
     Generic { message: String },
     /// Stack overflow (ricorsione infinita)
     StackOverflow,
+    /// Superato il budget di statement impostato con `set_step_limit`
+    StepLimitExceeded { limit: u64 },
+    /// Superato un limite di risorse (es. il budget di memoria di `set_alloc_limit`)
+    ResourceLimit { message: String },
+    /// Clausola `requires`/`ensures` falsa (con `set_contracts(true)`); `clause` è
+    /// la clausola riformattata, es. `requires x >= 0`
+    ContractViolation { function: String, clause: String },
 }
 
 impl fmt::Display for RuntimeError {
@@ -35,8 +44,11 @@ impl fmt::Display for RuntimeError {
                 write!(f, "[RuntimeError] Type error: {}", message),
             RuntimeError::DivisionByZero =>
                 write!(f, "[RuntimeError] Division by zero"),
-            RuntimeError::IndexOutOfBounds { index, len } =>
-                write!(f, "[RuntimeError] Index {} out of bounds for array of length {}", index, len),
+            RuntimeError::IndexOutOfBounds { index, len, span } => {
+                write!(f, "[RuntimeError] Index {} out of bounds for array of length {}", index, len)?;
+                if let Some(span) = span { write!(f, " at line {}, column {}", span.line, span.column)?; }
+                Ok(())
+            }
             RuntimeError::NotCallable { type_name } =>
                 write!(f, "[RuntimeError] '{}' is not callable", type_name),
             RuntimeError::ArityMismatch { name, expected, got } =>
@@ -47,7 +59,24 @@ impl fmt::Display for RuntimeError {
                 write!(f, "[RuntimeError] {}", message),
             RuntimeError::StackOverflow =>
                 write!(f, "[RuntimeError] Stack overflow (maximum recursion depth exceeded)"),
+            RuntimeError::ResourceLimit { message } =>
+                write!(f, "[RuntimeError] Resource limit: {}", message),
+            RuntimeError::StepLimitExceeded { limit } =>
+                write!(f, "[RuntimeError] Step limit {} exceeded (infinite loop?)", limit),
+            RuntimeError::ContractViolation { function, clause } =>
+                write!(f, "[RuntimeError] Contract violation in '{}': {}", function, clause),
+        }
+    }
+}
+
+impl RuntimeError {
+    /// Associa la posizione dell'espressione che ha sollevato l'errore, se l'errore
+    /// ne porta una e non è già stata impostata più in profondità.
+    pub fn at(mut self, at: &Span) -> Self {
+        if let RuntimeError::IndexOutOfBounds { span: span @ None, .. } = &mut self {
+            *span = Some(at.clone());
         }
+        self
     }
 }
 
@@ -1,14 +1,62 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
 
 use neba_parser::ast::*;
+use crate::coverage::CoverageReport;
 use crate::environment::Env;
+use crate::scheduler::{Channel, Scheduler, TaskBody, TaskState};
 use crate::error::{InterpResult, RuntimeError};
-use crate::value::{FunctionDef, Instance, Value};
+use crate::value::{dict_key, DictMap, FunctionDef, Instance, Memo, Value};
 use crate::stdlib;
 
 const MAX_DEPTH: usize = 50;
+/// Budget di step di default per `Interpreter::sandboxed`.
+pub const SANDBOX_STEP_LIMIT: u64 = 1_000_000;
+/// Budget di memoria di default (byte, stimati) per `Interpreter::sandboxed`.
+pub const SANDBOX_ALLOC_LIMIT: usize = 64 << 20;
+const VALUE_SIZE: usize = std::mem::size_of::<Value>();
+
+/// Memoria stimata di un valore per `alloc_limit`: contenitori e stringhe, non il contenuto annidato.
+fn footprint(v: &Value) -> usize {
+    match v {
+        Value::Array(a) => a.borrow().len() * VALUE_SIZE,
+        Value::Dict(d)  => d.borrow().len() * 2 * VALUE_SIZE,
+        Value::Str(s)   => s.len(),
+        _ => 0,
+    }
+}
+
+/// Valori che `display_string` può stampare diversamente da `Display` (`__str__`).
+fn may_hold_instance(v: &Value) -> bool {
+    matches!(v, Value::Instance(_) | Value::Array(_) | Value::Tuple(_) | Value::Dict(_)
+        | Value::Some(_) | Value::Ok(_) | Value::Err(_))
+}
+
+/// Indici scelti da `[start:end:step]` su una sequenza lunga `n` (step ≠ 0):
+/// negativi contati dalla fine, fuori range clampati.
+fn slice_indices(start: Option<i64>, end: Option<i64>, step: i64, n: i64) -> Vec<usize> {
+    if step > 0 {
+        let clamp = |i: i64| if i < 0 { (n + i).max(0) } else { i.min(n) };
+        let s = start.map_or(0, clamp);
+        let e = end.map_or(n, clamp);
+        (s..e).step_by(step as usize).map(|i| i as usize).collect()
+    } else {
+        let mut i = start.map_or(n - 1, |i| if i < 0 { (n + i).max(-1) } else { i.min(n - 1) });
+        let e = end.map_or(-1, |i| if i < 0 { (n + i).max(-1) } else { i.min(n) });
+        let mut out = Vec::new();
+        while i > e { out.push(i as usize); i += step; }
+        out
+    }
+}
+
+fn same_container(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Array(x), Value::Array(y)) => Rc::ptr_eq(x, y),
+        (Value::Dict(x), Value::Dict(y))   => Rc::ptr_eq(x, y),
+        _ => false,
+    }
+}
 
 // ── Metadati di una classe (registrati al momento della definizione) ────────
 
@@ -25,6 +73,25 @@ pub struct Interpreter {
     pub env: Env,
     pub class_registry: HashMap<String, ClassMeta>,
     depth: usize,
+    /// Massimo numero di statement eseguiti (0 = illimitato)
+    step_limit: u64,
+    steps: u64,
+    /// Tetto in byte alle allocazioni stimate (0 = illimitato)
+    alloc_limit: usize,
+    allocated: Cell<usize>,
+    /// `/` con divisore Float zero dà inf/-inf/nan invece di `DivisionByZero`
+    ieee_division: bool,
+    /// Indicizzazione e iterazione delle stringhe per grapheme cluster
+    #[cfg(feature = "graphemes")]
+    graphemes: bool,
+    /// Bitmap delle righe eseguite (indice = riga), attiva con `set_coverage`
+    coverage: Option<Vec<bool>>,
+    /// Task creati con `spawn`, eseguiti dagli `await` e a fine programma
+    scheduler: Scheduler,
+    /// Verifica le clausole `requires`/`ensures` delle funzioni
+    check_contracts: bool,
+    /// I task non attesi restano in coda a fine `run`: li esegue `step_task`
+    manual_scheduling: bool,
 }
 
 impl Interpreter {
@@ -33,26 +100,215 @@ impl Interpreter {
             env: Env::new(),
             class_registry: HashMap::new(),
             depth: 0,
+            step_limit: 0,
+            steps: 0,
+            alloc_limit: 0,
+            allocated: Cell::new(0),
+            ieee_division: false,
+            #[cfg(feature = "graphemes")]
+            graphemes: false,
+            coverage: None,
+            scheduler: Scheduler::default(),
+            manual_scheduling: false,
+            check_contracts: false,
         };
         stdlib::register(&mut interp.env);
         interp
     }
 
+    /// Interprete per codice non fidato: `print`, `println` e `input` falliscono
+    /// con errore, l'esecuzione si interrompe dopo `SANDBOX_STEP_LIMIT` statement
+    /// (`RuntimeError::StepLimitExceeded`) o `SANDBOX_ALLOC_LIMIT` byte allocati
+    /// (`RuntimeError::ResourceLimit`). Modificabili con `set_step_limit`/`set_alloc_limit`.
+    pub fn sandboxed() -> Self {
+        let mut interp = Self::new();
+        stdlib::disable_sandboxed(&mut interp.env);
+        interp.step_limit = SANDBOX_STEP_LIMIT;
+        interp.alloc_limit = SANDBOX_ALLOC_LIMIT;
+        interp
+    }
+
+    /// Limita il numero di statement eseguiti da ogni `run` (0 = nessun limite);
+    /// oltre il limite l'esecuzione si interrompe con `RuntimeError::StepLimitExceeded`.
+    pub fn set_step_limit(&mut self, limit: u64) -> &mut Self {
+        self.step_limit = limit;
+        self
+    }
+
+    /// Con `true`, la divisione `/` per zero in cui almeno un operando è Float segue
+    /// IEEE 754 (`1.0 / 0.0 == inf`, `0.0 / 0.0` è `nan`); `Int / 0` resta un errore.
+    /// Di default (`false`) ogni divisione per zero è `RuntimeError::DivisionByZero`.
+    pub fn set_ieee_division(&mut self, on: bool) -> &mut Self {
+        self.ieee_division = on;
+        self
+    }
+
+    /// Con `true`, `s[i]` e `for c in s` lavorano su grapheme cluster estesi
+    /// (`"e\u{301}"` è un solo carattere) invece che su code point (default).
+    /// `len(s)` conta sempre i code point.
+    #[cfg(feature = "graphemes")]
+    pub fn set_grapheme_strings(&mut self, on: bool) -> &mut Self {
+        self.graphemes = on;
+        self
+    }
+
+    /// Con `true` registra le righe degli statement eseguiti (azzerando quelle già
+    /// registrate); si accumulano tra più `run`/`call_function` fino al prossimo
+    /// `set_coverage`. Il rapporto si ottiene con `coverage_report`.
+    pub fn set_coverage(&mut self, on: bool) -> &mut Self {
+        self.coverage = if on { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// Con `true` verifica i contratti delle funzioni: le clausole `requires` dopo il
+    /// legame dei parametri, le `ensures` sul valore restituito (legato a `result`).
+    /// Una clausola falsa è `RuntimeError::ContractViolation`. Di default (`false`)
+    /// le clausole non vengono valutate e non costano nulla.
+    pub fn set_contracts(&mut self, on: bool) -> &mut Self {
+        self.check_contracts = on;
+        self
+    }
+
+    /// Righe coperte e non coperte di `program` (`None` se la copertura è spenta).
+    pub fn coverage_report(&self, program: &Program) -> Option<CoverageReport> {
+        self.coverage.as_ref().map(|hits| CoverageReport::new(program, hits))
+    }
+
+    /// Con `true`, `run`/`run_value` non eseguono a fine programma i task mai
+    /// attesi: l'host li fa avanzare uno alla volta con `step_task`, in ordine di
+    /// `spawn`, e può verificare l'interleaving passo per passo. `await`, `race`,
+    /// `join` e `recv()` eseguono comunque i task di cui hanno bisogno.
+    pub fn set_manual_scheduling(&mut self, on: bool) -> &mut Self {
+        self.manual_scheduling = on;
+        self
+    }
+
+    /// Esegue fino al completamento il prossimo task in coda e ne restituisce
+    /// l'id (`None` se non ce ne sono); l'esito si legge con `task_result`.
+    pub fn step_task(&mut self) -> Option<usize> {
+        self.run_next_task()
+    }
+
+    /// Numero di task in coda non ancora avviati.
+    pub fn pending_tasks(&self) -> usize {
+        self.scheduler.pending()
+    }
+
+    /// Esito del task `id` (l'indice di `Task(id)`), `None` se non è terminato.
+    pub fn task_result(&self, id: usize) -> Option<Result<Value, RuntimeError>> {
+        match self.scheduler.state(id)? {
+            TaskState::Done(v)   => Some(Ok(v.clone())),
+            TaskState::Failed(e) => Some(Err(e.clone())),
+            _ => None,
+        }
+    }
+
+    /// Unità di indicizzazione di una stringa: code point o, con `set_grapheme_strings`, grafemi.
+    fn str_units<'a>(&self, s: &'a str) -> Vec<&'a str> {
+        #[cfg(feature = "graphemes")]
+        if self.graphemes { return neba_runtime::graphemes(s); }
+        s.char_indices().map(|(i, c)| &s[i..i + c.len_utf8()]).collect()
+    }
+
+    /// Tetto approssimativo ai byte allocati da ogni `run` (0 = nessun limite): si
+    /// contano array, range, istanze e stringhe costruite dal programma o dai builtin
+    /// (anche la crescita in place, come `push`), non i rilasci.
+    pub fn set_alloc_limit(&mut self, bytes: usize) -> &mut Self {
+        self.alloc_limit = bytes;
+        self
+    }
+
+    /// Addebita `bytes` al budget di memoria, prima di allocarli.
+    fn charge(&self, bytes: usize) -> Result<(), RuntimeError> {
+        if self.alloc_limit == 0 { return Ok(()); }
+        let total = self.allocated.get().saturating_add(bytes);
+        self.allocated.set(total);
+        if total > self.alloc_limit {
+            return Err(RuntimeError::ResourceLimit {
+                message: format!("allocation limit of {} bytes exceeded", self.alloc_limit),
+            });
+        }
+        Ok(())
+    }
+
+    /// Definisce la variabile `name` nello scope globale (mutabile, come una `var`),
+    /// visibile ai programmi eseguiti dopo la chiamata.
+    pub fn set_global(&mut self, name: &str, value: Value) -> &mut Self {
+        self.env.define(name, value, true);
+        self
+    }
+
+    /// Valore della variabile globale `name`, ad esempio un risultato del programma.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env.get(name)
+    }
+
+    /// Espone agli script una funzione dell'host come globale `name`.
+    /// Firma: `Fn(Vec<Value>) -> Result<Value, String>`; la closure può catturare
+    /// stato dell'host. Un `Err` diventa un errore runtime dello script.
+    pub fn register_native<F>(&mut self, name: &str, f: F) -> &mut Self
+    where F: Fn(Vec<Value>) -> Result<Value, String> + 'static {
+        self.env.define(name, Value::NativeFunction(name.to_string(), Rc::new(f)), false);
+        self
+    }
+
+    /// Chiama la funzione globale `name` (definita da un programma già eseguito o
+    /// registrata dall'host) con `args`: per usare Neba come linguaggio di plugin.
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> InterpResult {
+        let func = self.env.get(name)
+            .ok_or_else(|| RuntimeError::UndefinedVariable { name: name.to_string() })?;
+        self.steps = 0;
+        self.allocated.set(0);
+        let (env, depth) = (self.env.clone(), self.depth);
+        let result = self.call(func, args);
+        if result.is_err() {
+            // Un errore a metà chiamata può lasciare scope e profondità parziali
+            self.env = env;
+            self.depth = depth;
+        }
+        result
+    }
+
     // ── Programma ─────────────────────────────────────────────────────────
 
     pub fn run(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        self.steps = 0;
+        self.allocated.set(0);
         for stmt in &program.stmts {
-            match self.exec_stmt(stmt)? {
-                Value::__Return(_) => break,
-                _ => {}
-            }
+            if let Value::__Return(_) = self.exec_stmt(stmt)? { break }
         }
-        Ok(())
+        self.run_pending_tasks()
+    }
+
+    /// Come `run`, ma restituisce il valore dell'ultimo statement top-level se è
+    /// un'espressione (`None` se il programma termina con una dichiarazione/assegnazione).
+    pub fn run_value(&mut self, program: &Program) -> InterpResult {
+        self.steps = 0;
+        self.allocated.set(0);
+        let mut last = Value::None;
+        for stmt in &program.stmts {
+            let v = self.exec_stmt(stmt)?;
+            if let Value::__Return(inner) = v { return Ok(*inner); }
+            last = if matches!(stmt.inner, StmtKind::Expr(_)) { v } else { Value::None };
+        }
+        self.run_pending_tasks()?;
+        Ok(last)
     }
 
     // ── Statement ─────────────────────────────────────────────────────────
 
     pub fn exec_stmt(&mut self, stmt: &Stmt) -> InterpResult {
+        if self.step_limit > 0 {
+            self.steps += 1;
+            if self.steps > self.step_limit {
+                return Err(RuntimeError::StepLimitExceeded { limit: self.step_limit });
+            }
+        }
+        if let Some(hits) = &mut self.coverage {
+            let line = stmt.span.line;
+            if line >= hits.len() { hits.resize(line + 1, false); }
+            hits[line] = true;
+        }
         match &stmt.inner {
             StmtKind::Let { name, value, .. } => {
                 let v = self.eval_expr(value)?;
@@ -68,13 +324,14 @@ impl Interpreter {
                 let rhs = self.eval_expr(value)?;
                 self.do_assign(target, op, rhs)
             }
-            StmtKind::Fn { name, params, body, is_async, .. } => {
+            StmtKind::Fn { name, params, body, is_async, contracts, .. } => {
                 let f = Value::Function(Rc::new(FunctionDef {
                     name: name.clone(),
                     params: params.clone(),
                     body: body.clone(),
                     closure: self.env.snapshot(),
                     is_async: *is_async,
+                    contracts: contracts.clone(),
                 }));
                 self.env.define(name, f, false);
                 Ok(Value::None)
@@ -89,15 +346,23 @@ impl Interpreter {
                 let ctor = Value::Function(Rc::new(FunctionDef {
                     name: name.clone(),
                     params: Vec::new(),
-                    body: Vec::new(),
+                    body: Rc::from([]),
                     closure: self.env.snapshot(),
                     is_async: false,
+                    contracts: Rc::default(),
                 }));
                 self.env.define(name, ctor, false);
                 Ok(Value::None)
             }
             StmtKind::Return(expr) => {
-                let v = match expr { Some(e) => self.eval_expr(e)?, None => Value::None };
+                let v = match expr {
+                    // `x.recv()` resta a eval_expr, che gestisce la sospensione sui canali
+                    Some(Expr { inner: ExprKind::Call { callee, args, kwargs }, .. })
+                        if self.depth > 0 && !matches!(&callee.inner, ExprKind::Field { field, .. } if field == "recv") =>
+                        self.eval_tail_call(callee, args, kwargs)?,
+                    Some(e) => self.eval_expr(e)?,
+                    None => Value::None,
+                };
                 Ok(Value::__Return(Box::new(v)))
             }
             StmtKind::While { condition, body } => {
@@ -164,19 +429,40 @@ impl Interpreter {
     fn do_assign(&mut self, target: &Expr, op: &AssignOp, rhs: Value) -> InterpResult {
         match &target.inner {
             ExprKind::Ident(name) => {
+                // `s += x` su una Str: accoda in place invece di ricopiare `s` (O(n) → O(len(x)))
+                if let (AssignOp::AddAssign, Value::Str(r)) = (op, &rhs) {
+                    self.charge(r.len())?;
+                    let appended = self.env.update(name, |cur| match cur {
+                        Value::Str(s) => { s.push_str(r); true }
+                        _ => false,
+                    });
+                    match appended {
+                        Ok(true) => return Ok(Value::None),
+                        Ok(false) => {}
+                        Err(e) if e.starts_with("undefined") => return Err(RuntimeError::UndefinedVariable { name: name.clone() }),
+                        Err(message) => return Err(RuntimeError::AssignError { message }),
+                    }
+                }
                 let val = self.apply_op(op, name, rhs)?;
                 self.env.set(name, val)
                     .map_err(|e| RuntimeError::AssignError { message: e })?;
             }
             ExprKind::Slice { object, start, end, step } => {
-                let obj = self.eval_expr(object)?;
-                return Err(crate::RuntimeError::Generic { message: "slices not supported in tree-walking interpreter".to_string() });
+                if !matches!(op, AssignOp::Assign) {
+                    return Err(RuntimeError::Generic { message: "compound assignment to a slice is not supported".to_string() });
+                }
+                if step.is_some() {
+                    return Err(RuntimeError::Generic { message: "slice assignment does not support a step".to_string() });
+                }
+                self.assign_slice(object, start.as_deref(), end.as_deref(), rhs)?;
             }
             ExprKind::Index { object, index } => {
                 let obj = self.eval_expr(object)?;
                 let idx = self.eval_expr(index)?;
                 if let Value::Array(arr) = obj {
-                    let i = self.idx(idx, arr.borrow().len())?;
+                    crate::value::check_mutable(&arr, "index assignment")
+                        .map_err(|message| RuntimeError::TypeError { message })?;
+                    let i = self.idx(idx, arr.borrow().len()).map_err(|e| e.at(&target.span))?;
                     let new_val = if let AssignOp::Assign = op {
                         rhs
                     } else {
@@ -184,15 +470,25 @@ impl Interpreter {
                         self.compute(op, cur, rhs)?
                     };
                     arr.borrow_mut()[i] = new_val;
+                } else if let Value::Dict(d) = obj {
+                    let key = self.key_in(&d, &idx).map_err(|e| e.at(&index.span))?;
+                    let new_val = if let AssignOp::Assign = op {
+                        rhs
+                    } else {
+                        let cur = d.borrow().get(&key).map(|(_, v)| v.clone()).ok_or_else(|| RuntimeError::Generic {
+                            message: format!("key not found in Dict: {}", idx),
+                        }.at(&target.span))?;
+                        self.compute(op, cur, rhs)?
+                    };
+                    // Solo una chiave nuova fa crescere il Dict
+                    if !d.borrow().contains_key(&key) { self.charge(2 * VALUE_SIZE)?; }
+                    d.borrow_mut().insert(key, (idx, new_val));
                 } else {
                     return Err(RuntimeError::TypeError {
-                        message: "index assignment requires Array".to_string(),
+                        message: "index assignment requires Array or Dict".to_string(),
                     });
                 }
             }
-            ExprKind::Slice { .. } => {
-                return Err(crate::RuntimeError::Generic { message: "slice assignment not supported".to_string() });
-            }
             ExprKind::Field { object, field } => {
                 let obj = self.eval_expr(object)?;
                 if let Value::Instance(inst) = obj {
@@ -243,6 +539,7 @@ impl Interpreter {
             ExprKind::Float(f)  => Ok(Value::Float(*f)),
             ExprKind::Bool(b)   => Ok(Value::Bool(*b)),
             ExprKind::Str(s)    => Ok(Value::Str(s.clone())),
+            ExprKind::Char(c)   => Ok(Value::Char(*c)),
             ExprKind::None      => Ok(Value::None),
             ExprKind::FStr(t)   => self.eval_fstring(t),
             ExprKind::Ident(n)  => self.env.get(n)
@@ -265,6 +562,14 @@ impl Interpreter {
                         if l.is_truthy() { return Ok(Value::Bool(true)); }
                         return Ok(Value::Bool(self.eval_expr(right)?.is_truthy()));
                     }
+                    BinOp::Is | BinOp::IsNot => {
+                        // `x is Int`: confronta il nome del tipo, senza valutare `Int`
+                        if let Some(ty) = self.builtin_type_operand(right) {
+                            let l = self.eval_expr(left)?;
+                            let is = match l.type_name() { "NativeFunction" => "Function", t => t } == ty;
+                            return Ok(Value::Bool(is == matches!(op, BinOp::Is)));
+                        }
+                    }
                     _ => {}
                 }
                 let l = self.eval_expr(left)?;
@@ -300,11 +605,13 @@ impl Interpreter {
             }
 
             ExprKind::Call { callee, args, kwargs } => {
+                if let ExprKind::Field { object, field } = &callee.inner {
+                    if field == "recv" || field == "format" {
+                        return self.eval_special_method(object, field, args, kwargs);
+                    }
+                }
                 let fv = self.eval_expr(callee)?;
-                let mut av: Vec<Value> = args.iter()
-                    .map(|a| self.eval_expr(a))
-                    .collect::<Result<_, _>>()?;
-                for (_, v) in kwargs { av.push(self.eval_expr(v)?); }
+                let av = self.eval_args(args, kwargs)?;
                 self.call(fv, av)
             }
 
@@ -315,30 +622,30 @@ impl Interpreter {
             ExprKind::Index { object, index } => {
                 let obj = self.eval_expr(object)?;
                 let i   = self.eval_expr(index)?;
-                self.eval_index(obj, i)
+                self.eval_index(obj, i).map_err(|e| e.at(&expr.span))
             }
             ExprKind::Array(items) => {
+                self.charge(items.len() * VALUE_SIZE)?;
                 let vs: Vec<Value> = items.iter()
                     .map(|i| self.eval_expr(i))
                     .collect::<Result<_, _>>()?;
                 Ok(Value::Array(Rc::new(RefCell::new(vs))))
             }
-            // Dict literal — l'interpreter tree-walking non supporta ancora Dict nativamente,
-            // ma lo gestiamo come stub per compatibilità (la VM è il path principale)
-            ExprKind::Dict(_) => {
-                Err(crate::error::RuntimeError::Generic { message: "Dict literals require the bytecode VM (v0.2.5+)".into() })
-            }
+            ExprKind::Dict(pairs) => self.eval_dict(pairs),
+            ExprKind::Tuple(items) => self.eval_tuple(items),
             ExprKind::Range { start, end, inclusive } => {
                 let s = self.eval_expr(start)?;
                 let e = self.eval_expr(end)?;
                 self.eval_range(s, e, *inclusive)
             }
 
-            ExprKind::Spawn(inner) => {
-                eprintln!("[warn] spawn is synchronous in v0.1.x");
-                self.eval_expr(inner)
-            }
-            ExprKind::Await(inner) => self.eval_expr(inner),
+            ExprKind::Spawn(inner) => self.eval_spawn(inner),
+            ExprKind::Pipe { value, func } => self.eval_pipe(value, func, &expr.span),
+            ExprKind::Await(inner) => match self.eval_expr(inner)? {
+                Value::Task(id) => self.await_task(id),
+                // `await` su un valore già pronto (es. il risultato di una `async fn`)
+                other => Ok(other),
+            },
 
             ExprKind::Some(inner) => { let v = self.eval_expr(inner)?; Ok(Value::Some(Box::new(v))) }
             ExprKind::Ok(inner)   => { let v = self.eval_expr(inner)?; Ok(Value::Ok(Box::new(v)))  }
@@ -360,13 +667,59 @@ impl Interpreter {
                 message: "Lambda expressions require the bytecode VM (use `cargo run --bin neba`)".to_string(),
             }),
 
-            ExprKind::Slice { .. } => Err(RuntimeError::Generic { message: "slices not supported in tree-walking interpreter".to_string() }),
+            ExprKind::Slice { object, start, end, step } =>
+                self.eval_slice(object, start.as_deref(), end.as_deref(), step.as_deref()),
             ExprKind::Error => Err(RuntimeError::Generic { message: "AST error node".to_string() }),
         }
     }
 
     // ── f-string ───────────────────────────────────────────────────────────
 
+    /// Rappresentazione testuale per print/str/f-string: usa `__str__` se l'istanza lo definisce,
+    /// anche dentro Array, Tuple, Dict e Option/Result.
+    fn display_string(&mut self, v: Value) -> Result<String, RuntimeError> {
+        self.display_nested(v, &mut Vec::new())
+    }
+
+    /// `path`: contenitori in corso di stampa, come `fmt_guarded` per `Display`.
+    fn display_nested(&mut self, v: Value, path: &mut Vec<usize>) -> Result<String, RuntimeError> {
+        Ok(match v {
+            Value::Instance(_) => match self.get_field(v.clone(), "__str__") {
+                Ok(method @ Value::Function(_)) => self.call(method, Vec::new())?.to_string(),
+                _ => v.to_string(),
+            },
+            Value::Array(ref a) => {
+                let ptr = Rc::as_ptr(a) as usize;
+                if path.contains(&ptr) { return Ok("[...]".to_string()); }
+                path.push(ptr);
+                let items = a.borrow().clone();
+                let parts = items.into_iter().map(|x| self.display_nested(x, path)).collect::<Result<Vec<_>, _>>()?;
+                path.pop();
+                format!("[{}]", parts.join(", "))
+            }
+            Value::Tuple(t) => {
+                let parts = t.iter().map(|x| self.display_nested(x.clone(), path)).collect::<Result<Vec<_>, _>>()?;
+                if parts.len() == 1 { format!("({},)", parts[0]) } else { format!("({})", parts.join(", ")) }
+            }
+            Value::Dict(ref d) => {
+                let ptr = Rc::as_ptr(d) as usize;
+                if path.contains(&ptr) { return Ok("{...}".to_string()); }
+                path.push(ptr);
+                let pairs: Vec<(Value, Value)> = d.borrow().values().cloned().collect();
+                let mut parts = Vec::with_capacity(pairs.len());
+                for (k, v) in pairs {
+                    parts.push(format!("{}: {}", self.display_nested(k, path)?, self.display_nested(v, path)?));
+                }
+                path.pop();
+                format!("{{{}}}", parts.join(", "))
+            }
+            Value::Some(x) => format!("Some({})", self.display_nested(*x, path)?),
+            Value::Ok(x)   => format!("Ok({})", self.display_nested(*x, path)?),
+            Value::Err(x)  => format!("Err({})", self.display_nested(*x, path)?),
+            other => other.to_string(),
+        })
+    }
+
     fn eval_fstring(&mut self, template: &str) -> InterpResult {
         let mut out = String::new();
         let chars: Vec<char> = template.chars().collect();
@@ -384,7 +737,8 @@ impl Interpreter {
                 let (prog, _, _) = neba_parser::parse(&expr_src);
                 if let Some(stmt) = prog.stmts.first() {
                     if let StmtKind::Expr(e) = &stmt.inner {
-                        out.push_str(&self.eval_expr(e)?.to_string());
+                        let v = self.eval_expr(e)?;
+                        out.push_str(&self.display_string(v)?);
                     }
                 }
                 i = j;
@@ -418,7 +772,16 @@ impl Interpreter {
 
     // ── Operatori binari ───────────────────────────────────────────────────
 
-    fn eval_binary(&self, op: &BinOp, l: Value, r: Value) -> InterpResult {
+    /// Nome del tipo built-in a destra di `is`, se è un identificatore come `Int` non ridefinito.
+    fn builtin_type_operand<'e>(&self, right: &'e Expr) -> Option<&'e str> {
+        match &right.inner {
+            ExprKind::Ident(name) if BUILTIN_TYPE_NAMES.contains(&name.as_str()) && self.env.get(name).is_none() => Some(name),
+            _ => None,
+        }
+    }
+
+    fn eval_binary(&mut self, op: &BinOp, l: Value, r: Value) -> InterpResult {
+        let (l, r) = if matches!(op, BinOp::Is | BinOp::IsNot) { (l, r) } else { (l.char_as_str(), r.char_as_str()) };
         match op {
             BinOp::Add    => self.add(l, r),
             BinOp::Sub    => self.sub(l, r),
@@ -442,6 +805,9 @@ impl Interpreter {
             BinOp::Is     => Ok(Value::Bool(
                 std::mem::discriminant(&l) == std::mem::discriminant(&r)
             )),
+            BinOp::IsNot  => Ok(Value::Bool(
+                std::mem::discriminant(&l) != std::mem::discriminant(&r)
+            )),
             BinOp::In    => self.eval_in(l, r),
             BinOp::NotIn => {
                 let found = self.eval_in(l, r)?;
@@ -456,7 +822,13 @@ impl Interpreter {
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
             (Value::Int(a),   Value::Float(b)) => Ok(Value::Float(*a as f64 + b)),
             (Value::Float(a), Value::Int(b))   => Ok(Value::Float(a + *b as f64)),
-            (Value::Str(a),   Value::Str(b))   => Ok(Value::Str(format!("{}{}", a, b))),
+            (Value::Str(a),   Value::Str(b))   => {
+                self.charge(a.len() + b.len())?;
+                // Riusa il buffer dell'operando sinistro, già posseduto
+                let Value::Str(mut a) = l else { unreachable!() };
+                a.push_str(b);
+                Ok(Value::Str(a))
+            }
             _ => Err(RuntimeError::TypeError { message: format!("'+' between {} and {}", l.type_name(), r.type_name()) }),
         }
     }
@@ -475,15 +847,19 @@ impl Interpreter {
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
             (Value::Int(a),   Value::Float(b)) => Ok(Value::Float(*a as f64 * b)),
             (Value::Float(a), Value::Int(b))   => Ok(Value::Float(a * *b as f64)),
-            (Value::Str(s),   Value::Int(n))   => Ok(Value::Str(s.repeat((*n).max(0) as usize))),
-            (Value::Int(n),   Value::Str(s))   => Ok(Value::Str(s.repeat((*n).max(0) as usize))),
+            (Value::Str(s),   Value::Int(n)) | (Value::Int(n), Value::Str(s)) => {
+                let n = (*n).max(0) as usize;
+                self.charge(s.len().saturating_mul(n))?;
+                Ok(Value::Str(s.repeat(n)))
+            }
             _ => Err(RuntimeError::TypeError { message: format!("'*' between {} and {}", l.type_name(), r.type_name()) }),
         }
     }
     fn div(&self, l: Value, r: Value) -> InterpResult {
         let b = r.as_float().ok_or_else(|| RuntimeError::TypeError { message: format!("'/' on {}", r.type_name()) })?;
-        if b == 0.0 { return Err(RuntimeError::DivisionByZero); }
         let a = l.as_float().ok_or_else(|| RuntimeError::TypeError { message: format!("'/' on {}", l.type_name()) })?;
+        let int_div = matches!((&l, &r), (Value::Int(_), Value::Int(_)));
+        if b == 0.0 && (int_div || !self.ieee_division) { return Err(RuntimeError::DivisionByZero); }
         Ok(Value::Float(a / b))
     }
     fn intdiv(&self, l: Value, r: Value) -> InterpResult {
@@ -527,9 +903,65 @@ impl Interpreter {
             _ => Err(RuntimeError::TypeError { message: format!("'{}' requires Int", op) }),
         }
     }
-    fn eval_in(&self, needle: Value, haystack: Value) -> InterpResult {
+    /// `{k: v, ...}`: una chiave ripetuta tiene la posizione della prima e l'ultimo valore.
+    fn eval_dict(&mut self, pairs: &[(Expr, Expr)]) -> InterpResult {
+        self.charge(pairs.len() * 2 * VALUE_SIZE)?;
+        let dict = Rc::new(RefCell::new(DictMap::with_capacity(pairs.len())));
+        for (k, v) in pairs {
+            let key = self.eval_expr(k)?;
+            let val = self.eval_expr(v)?;
+            let canon = self.key_in(&dict, &key).map_err(|e| e.at(&k.span))?;
+            dict.borrow_mut().insert(canon, (key, val));
+        }
+        Ok(Value::Dict(dict))
+    }
+
+    /// Chiave canonica di `key` in `d`: `dict_key`, o per un'istanza il suo bucket (`instance_key`).
+    fn key_in(&mut self, d: &Rc<RefCell<DictMap>>, key: &Value) -> Result<String, RuntimeError> {
+        match key {
+            Value::Instance(_) => self.instance_key(d, key),
+            _ => dict_key(key).map_err(|message| RuntimeError::TypeError { message }),
+        }
+    }
+
+    /// Un'istanza usata come chiave deve definire `__hash__` e `__eq__`. Sta nel bucket
+    /// `I(<hash>)`; istanze non uguali con lo stesso hash vanno in `I(<hash>)#1`, `#2`, ...
+    /// (un Dict non perde chiavi, quindi la catena non ha buchi). `__eq__` viene chiamato
+    /// solo sulle chiavi dello stesso bucket.
+    fn instance_key(&mut self, d: &Rc<RefCell<DictMap>>, key: &Value) -> Result<String, RuntimeError> {
+        let hash_m = self.get_field(key.clone(), "__hash__").ok().filter(|m| matches!(m, Value::Function(_)));
+        let eq_m = self.get_field(key.clone(), "__eq__").ok().filter(|m| matches!(m, Value::Function(_)));
+        let (Some(hash_m), Some(eq_m), Value::Instance(inst)) = (hash_m, eq_m, key) else {
+            let class = match key { Value::Instance(i) => i.borrow().class_name.clone(), _ => key.type_name().to_string() };
+            return Err(RuntimeError::TypeError { message: format!(
+                "unhashable instance of {}: define both __hash__ and __eq__ to use it as a Dict key", class) });
+        };
+        let h = self.call(hash_m, Vec::new())?;
+        let h = dict_key(&h).map_err(|message| RuntimeError::TypeError { message: format!("__hash__: {}", message) })?;
+        let mut n = 0;
+        loop {
+            let slot = if n == 0 { format!("I({})", h) } else { format!("I({})#{}", h, n) };
+            let Some(other) = d.borrow().get(&slot).map(|(k, _)| k.clone()) else { return Ok(slot) };
+            let same = matches!(&other, Value::Instance(o) if Rc::ptr_eq(o, inst));
+            if same || self.call(eq_m.clone(), vec![other])?.is_truthy() { return Ok(slot); }
+            n += 1;
+        }
+    }
+
+    fn eval_tuple(&mut self, items: &[Expr]) -> InterpResult {
+        self.charge(items.len() * VALUE_SIZE)?;
+        let vs: Vec<Value> = items.iter().map(|i| self.eval_expr(i)).collect::<Result<_, _>>()?;
+        Ok(Value::Tuple(vs.into()))
+    }
+
+    fn eval_in(&mut self, needle: Value, haystack: Value) -> InterpResult {
         match haystack {
             Value::Array(arr) => Ok(Value::Bool(arr.borrow().contains(&needle))),
+            Value::Dict(d) if matches!(needle, Value::Instance(_)) => {
+                let key = self.key_in(&d, &needle)?;
+                Ok(Value::Bool(d.borrow().contains_key(&key)))
+            }
+            Value::Dict(d) => Ok(Value::Bool(dict_key(&needle).is_ok_and(|k| d.borrow().contains_key(&k)))),
             Value::Str(s) => match needle {
                 Value::Str(n) => Ok(Value::Bool(s.contains(n.as_str()))),
                 _             => Ok(Value::Bool(false)),
@@ -542,7 +974,10 @@ impl Interpreter {
 
     fn eval_range(&self, start: Value, end: Value, inclusive: bool) -> InterpResult {
         match (&start, &end) {
+            // sempre crescente: con s > e il range è vuoto (per scendere `range(s, e, -1)`)
             (Value::Int(s), Value::Int(e)) => {
+                let len = (*e as i128 - *s as i128 + inclusive as i128).max(0) as usize;
+                self.charge(len.saturating_mul(VALUE_SIZE))?;
                 let v: Vec<Value> = if inclusive {
                     (*s..=*e).map(Value::Int).collect()
                 } else {
@@ -557,6 +992,7 @@ impl Interpreter {
     // ── Field access ──────────────────────────────────────────────────────
 
     pub fn get_field(&mut self, obj: Value, field: &str) -> InterpResult {
+        let obj = obj.char_as_str();
         match &obj {
             Value::Instance(inst) => {
                 // 1. Campi dell'istanza
@@ -576,7 +1012,7 @@ impl Interpreter {
                         }))
                         .collect();
                     for m in all {
-                        if let StmtKind::Fn { name, params, body, is_async, .. } = &m.inner {
+                        if let StmtKind::Fn { name, params, body, is_async, contracts, .. } = &m.inner {
                             if name == field {
                                 let mut closure = self.env.snapshot();
                                 closure.push_scope();
@@ -584,6 +1020,7 @@ impl Interpreter {
                                 return Ok(Value::Function(Rc::new(FunctionDef {
                                     name: name.clone(), params: params.clone(),
                                     body: body.clone(), closure, is_async: *is_async,
+                                    contracts: contracts.clone(),
                                 })));
                             }
                         }
@@ -601,13 +1038,32 @@ impl Interpreter {
             Value::Str(s) => {
                 let s = s.clone();
                 match field {
+                    // `len` conta i code point, `byte_len()` i byte UTF-8
                     "len"   => Ok(Value::Int(s.chars().count() as i64)),
+                    "byte_len" => { let n = s.len() as i64; Ok(Value::NativeFunction("byte_len".into(), std::rc::Rc::new(move |_: Vec<Value>| Ok(Value::Int(n))))) }
                     "upper" => Ok(Value::NativeFunction("upper".into(), std::rc::Rc::new(move |_: Vec<Value>| Ok(Value::Str(s.to_uppercase()))))),
                     "lower" => { let s2 = s.clone(); Ok(Value::NativeFunction("lower".into(), std::rc::Rc::new(move |_: Vec<Value>| Ok(Value::Str(s2.to_lowercase()))))) }
                     "trim"  => { let s2 = s.clone(); Ok(Value::NativeFunction("trim".into(),  std::rc::Rc::new(move |_: Vec<Value>| Ok(Value::Str(s2.trim().to_string()))))) }
                     _ => Err(RuntimeError::UnknownField { type_name: "Str".to_string(), field: field.to_string() }),
                 }
             }
+            Value::Sender(ch) => {
+                let ch = ch.clone();
+                match field {
+                    "send" => Ok(Value::NativeFunction("send".into(), Rc::new(move |args: Vec<Value>| {
+                        let [v]: [Value; 1] = args.try_into().map_err(|_| "send() takes 1 argument".to_string())?;
+                        let mut ch = ch.borrow_mut();
+                        if ch.closed { return Err("send on a closed channel".into()); }
+                        ch.queue.push_back(v);
+                        Ok(Value::None)
+                    }))),
+                    "close" => Ok(Value::NativeFunction("close".into(), Rc::new(move |_: Vec<Value>| {
+                        ch.borrow_mut().closed = true;
+                        Ok(Value::None)
+                    }))),
+                    _ => Err(RuntimeError::UnknownField { type_name: "Sender".to_string(), field: field.to_string() }),
+                }
+            }
             _ => Err(RuntimeError::UnknownField {
                 type_name: obj.type_name().to_string(),
                 field: field.to_string(),
@@ -617,16 +1073,26 @@ impl Interpreter {
 
     // ── Index ─────────────────────────────────────────────────────────────
 
-    fn eval_index(&self, obj: Value, idx: Value) -> InterpResult {
+    fn eval_index(&mut self, obj: Value, idx: Value) -> InterpResult {
+        if let Value::Dict(d) = &obj {
+            let key = self.key_in(d, &idx)?;
+            return d.borrow().get(&key).map(|(_, v)| v.clone())
+                .ok_or_else(|| RuntimeError::Generic { message: format!("key not found in Dict: {}", idx) });
+        }
         let i = match &idx { Value::Int(n) => *n, _ => return Err(RuntimeError::TypeError { message: "index must be Int".to_string() }) };
+        let obj = obj.char_as_str();
         match obj {
             Value::Array(arr) => {
                 let len = arr.borrow().len();
                 let actual = self.idx_val(i, len)?;
                 Ok(arr.borrow()[actual].clone())
             }
+            Value::Tuple(t) => {
+                let actual = self.idx_val(i, t.len())?;
+                Ok(t[actual].clone())
+            }
             Value::Str(s) => {
-                let chars: Vec<char> = s.chars().collect();
+                let chars = self.str_units(&s);
                 let actual = self.idx_val(i, chars.len())?;
                 Ok(Value::Str(chars[actual].to_string()))
             }
@@ -634,12 +1100,68 @@ impl Interpreter {
         }
     }
 
+    // ── Slice ─────────────────────────────────────────────────────────────
+
+    /// Estremo di uno slice: assente o `None` → `None`.
+    fn slice_bound(&mut self, e: Option<&Expr>, what: &str) -> Result<Option<i64>, RuntimeError> {
+        match e.map(|e| self.eval_expr(e)).transpose()? {
+            None | Some(Value::None) => Ok(None),
+            Some(Value::Int(n)) => Ok(Some(n)),
+            Some(other) => Err(RuntimeError::TypeError { message: format!("slice {} must be Int, got {}", what, other.type_name()) }),
+        }
+    }
+
+    /// `obj[s:e:step]` su Array e Str, con gli stessi estremi della VM.
+    fn eval_slice(&mut self, object: &Expr, start: Option<&Expr>, end: Option<&Expr>, step: Option<&Expr>) -> InterpResult {
+        let obj = self.eval_expr(object)?.char_as_str();
+        let (start, end) = (self.slice_bound(start, "index")?, self.slice_bound(end, "index")?);
+        let step = self.slice_bound(step, "step")?.unwrap_or(1);
+        if step == 0 { return Err(RuntimeError::Generic { message: "slice step cannot be zero".to_string() }); }
+        match obj {
+            Value::Array(arr) => {
+                let arr = arr.borrow();
+                let picked = slice_indices(start, end, step, arr.len() as i64);
+                self.charge(picked.len() * VALUE_SIZE)?;
+                Ok(Value::Array(Rc::new(RefCell::new(picked.into_iter().map(|i| arr[i].clone()).collect()))))
+            }
+            Value::Str(s) => {
+                let units = self.str_units(&s);
+                let out: String = slice_indices(start, end, step, units.len() as i64).into_iter().map(|i| units[i]).collect();
+                self.charge(out.len())?;
+                Ok(Value::Str(out))
+            }
+            other => Err(RuntimeError::TypeError { message: format!("cannot slice {}", other.type_name()) }),
+        }
+    }
+
+    /// `a[s:e] = valori`: sostituisce il tratto (anche di lunghezza diversa), estremi clampati.
+    fn assign_slice(&mut self, object: &Expr, start: Option<&Expr>, end: Option<&Expr>, val: Value) -> Result<(), RuntimeError> {
+        let obj = self.eval_expr(object)?;
+        let (start, end) = (self.slice_bound(start, "index")?, self.slice_bound(end, "index")?);
+        let Value::Array(arr) = obj else {
+            return Err(RuntimeError::TypeError { message: format!("slice assignment requires Array, got {}", obj.type_name()) });
+        };
+        crate::value::check_mutable(&arr, "slice assignment").map_err(|message| RuntimeError::TypeError { message })?;
+        let items: Vec<Value> = match val {
+            // copia prima del borrow_mut: `a[0:1] = a` è valido
+            Value::Array(src) => src.borrow().clone(),
+            other => return Err(RuntimeError::TypeError { message: format!("slice assignment requires an Array value, got {}", other.type_name()) }),
+        };
+        let n = arr.borrow().len() as i64;
+        let clamp = |i: i64| if i < 0 { (n + i).max(0) } else { i.min(n) } as usize;
+        let s = start.map_or(0, clamp);
+        let e = end.map_or(n as usize, clamp).max(s);
+        self.charge(items.len().saturating_sub(e - s) * VALUE_SIZE)?;
+        arr.borrow_mut().splice(s..e, items);
+        Ok(())
+    }
+
     fn idx(&self, idx: Value, len: usize) -> Result<usize, RuntimeError> {
         match idx { Value::Int(i) => self.idx_val(i, len), _ => Err(RuntimeError::TypeError { message: "index must be Int".to_string() }) }
     }
     fn idx_val(&self, i: i64, len: usize) -> Result<usize, RuntimeError> {
         let a = if i < 0 { len as i64 + i } else { i };
-        if a < 0 || a as usize >= len { Err(RuntimeError::IndexOutOfBounds { index: i, len }) }
+        if a < 0 || a as usize >= len { Err(RuntimeError::IndexOutOfBounds { index: i, len, span: None }) }
         else { Ok(a as usize) }
     }
 
@@ -648,13 +1170,34 @@ impl Interpreter {
     pub fn call(&mut self, func: Value, args: Vec<Value>) -> InterpResult {
         if self.depth >= MAX_DEPTH { return Err(RuntimeError::StackOverflow); }
         match func {
-            Value::NativeFunction(_, f) => {
-                f(args).map_err(|e| RuntimeError::Generic { message: e })
+            Value::NativeFunction(name, _) if name == "race" => self.race(args),
+            Value::NativeFunction(name, _) if name == "join" => self.join(args),
+            Value::NativeFunction(name, _) if matches!(name.as_str(), "and_then" | "or_else" | "map_err") =>
+                self.combinator(&name, args),
+            Value::NativeFunction(name, f) if name == "sleep" => self.sleep(f, args),
+            Value::NativeFunction(name, f) => {
+                // print/println/str vedono le istanze (anche annidate) già convertite tramite `__str__`
+                let args = if matches!(name.as_str(), "print" | "println" | "str")
+                    && args.iter().any(may_hold_instance) {
+                    args.into_iter()
+                        .map(|a| if may_hold_instance(&a) { self.display_string(a).map(Value::Str) } else { Ok(a) })
+                        .collect::<Result<Vec<_>, _>>()?
+                } else { args };
+                if self.alloc_limit == 0 { return f(args).map_err(|e| RuntimeError::Generic { message: e }); }
+                // Con `alloc_limit`: crescita dei contenitori ricevuti (push, ...) più il risultato nuovo
+                let held: Vec<Value> = args.iter().filter(|a| matches!(a, Value::Array(_) | Value::Dict(_))).cloned().collect();
+                let before: usize = held.iter().map(footprint).sum();
+                let result = f(args).map_err(|e| RuntimeError::Generic { message: e })?;
+                let grown = held.iter().map(footprint).sum::<usize>().saturating_sub(before);
+                let fresh = if held.iter().any(|h| same_container(h, &result)) { 0 } else { footprint(&result) };
+                self.charge(grown + fresh)?;
+                Ok(result)
             }
             Value::Function(def) => {
                 self.depth += 1;
                 // Costruttore di classe?
                 if let Some(meta) = self.class_registry.get(&def.name).cloned() {
+                    self.charge(std::mem::size_of::<Instance>())?;
                     let inst = Rc::new(RefCell::new(Instance::new(&def.name)));
                     for field in &meta.fields {
                         let v = if let Some(e) = &field.default {
@@ -677,11 +1220,94 @@ impl Interpreter {
                 self.depth -= 1;
                 r
             }
-            other => Err(RuntimeError::NotCallable { type_name: other.type_name().to_string() }),
+            other => self.call_derived(other, args),
+        }
+    }
+
+    fn eval_args(&mut self, args: &[Expr], kwargs: &[(String, Expr)]) -> Result<Vec<Value>, RuntimeError> {
+        let mut av: Vec<Value> = args.iter()
+            .map(|a| self.eval_expr(a))
+            .collect::<Result<_, _>>()?;
+        for (_, v) in kwargs { av.push(self.eval_expr(v)?); }
+        Ok(av)
+    }
+
+    /// Metodi che non si esprimono come closure native:
+    /// - `rx.recv()` può sospendersi, serve lo scheduler
+    /// - `"...".format(...)` usa i nomi degli argomenti e `__str__` delle istanze
+    ///
+    /// Su altri oggetti sono chiamate di metodo normali.
+    fn eval_special_method(&mut self, object: &Expr, field: &str, args: &[Expr], kwargs: &[(String, Expr)]) -> InterpResult {
+        let obj = self.eval_expr(object)?;
+        match (&obj, field) {
+            (Value::Receiver(ch), "recv") => {
+                if !args.is_empty() || !kwargs.is_empty() {
+                    return Err(RuntimeError::Generic { message: "recv() takes no arguments".into() });
+                }
+                self.channel_recv(ch)
+            }
+            (Value::Str(template), "format") => {
+                let mut positional = Vec::with_capacity(args.len());
+                for a in args {
+                    let v = self.eval_expr(a)?;
+                    positional.push(self.display_string(v)?);
+                }
+                let mut named = Vec::with_capacity(kwargs.len());
+                for (name, e) in kwargs {
+                    let v = self.eval_expr(e)?;
+                    named.push((name.clone(), self.display_string(v)?));
+                }
+                neba_runtime::format_template(template, &positional, &named)
+                    .map(Value::Str)
+                    .map_err(|message| RuntimeError::Generic { message })
+            }
+            _ => {
+                let fv = self.get_field(obj, field)?;
+                let av = self.eval_args(args, kwargs)?;
+                self.call(fv, av)
+            }
         }
     }
 
+    /// `spawn expr`: una chiamata valuta subito callee e argomenti, il resto è differito.
+    fn eval_spawn(&mut self, inner: &Expr) -> InterpResult {
+        let body = match &inner.inner {
+            ExprKind::Call { callee, args, kwargs } => {
+                let f = self.eval_expr(callee)?;
+                TaskBody::Call(f, self.eval_args(args, kwargs)?)
+            }
+            _ => TaskBody::Expr(inner.clone(), self.env.clone()),
+        };
+        Ok(Value::Task(self.scheduler.spawn(body)))
+    }
+
+    /// `value |> func` valutata come la chiamata equivalente.
+    fn eval_pipe(&mut self, value: &Expr, func: &Expr, span: &neba_lexer::Span) -> InterpResult {
+        self.eval_expr(&Node::new(ExprKind::pipe_call(value, func), span.clone()))
+    }
+
+    /// `return f(args)` dentro una funzione: se `f` è una funzione utente (non un
+    /// costruttore) restituisce `__TailCall` invece di chiamarla, altrimenti chiama.
+    fn eval_tail_call(&mut self, callee: &Expr, args: &[Expr], kwargs: &[(String, Expr)]) -> InterpResult {
+        let fv = self.eval_expr(callee)?;
+        let av = self.eval_args(args, kwargs)?;
+        match fv {
+            Value::Function(def) if !self.class_registry.contains_key(&def.name) => Ok(Value::__TailCall(def, av)),
+            other => self.call(other, av),
+        }
+    }
+
+    /// Trampolino: le chiamate in coda tornano qui come `__TailCall` e vengono
+    /// eseguite allo stesso livello, senza crescere lo stack Rust né `depth`.
     fn call_fn(&mut self, def: &FunctionDef, args: Vec<Value>) -> InterpResult {
+        let mut r = self.exec_fn(def, args)?;
+        while let Value::__TailCall(next, args) = r {
+            r = self.exec_fn(&next, args)?;
+        }
+        Ok(r)
+    }
+
+    fn exec_fn(&mut self, def: &FunctionDef, args: Vec<Value>) -> InterpResult {
         let non_self: Vec<_> = def.params.iter().filter(|p| p.name != "self").collect();
         let required = non_self.iter().filter(|p| p.default.is_none()).count();
         let expected = non_self.len();
@@ -699,7 +1325,11 @@ impl Interpreter {
             };
             self.env.define(&p.name, v, true);
         }
-        let r = self.exec_block_raw(&def.body);
+        let r = if self.check_contracts && !def.contracts.is_empty() {
+            self.exec_with_contracts(def)
+        } else {
+            self.exec_block_raw(&def.body)
+        };
         self.env.pop_scope();
         self.env = saved;
         match r? {
@@ -708,12 +1338,221 @@ impl Interpreter {
         }
     }
 
+    /// Corpo di `def` tra le clausole `requires` e `ensures`, nello scope dei parametri.
+    fn exec_with_contracts(&mut self, def: &FunctionDef) -> InterpResult {
+        for cond in &def.contracts.requires {
+            self.check_clause(def, "requires", cond)?;
+        }
+        let mut r = match self.exec_block_raw(&def.body)? {
+            Value::__Return(v) => *v,
+            other              => other,
+        };
+        if !def.contracts.ensures.is_empty() {
+            // `ensures` vede il risultato finale: la chiamata in coda si esegue qui
+            if let Value::__TailCall(next, args) = r {
+                r = self.call(Value::Function(next), args)?;
+            }
+            self.env.define("result", r.clone(), false);
+            for cond in &def.contracts.ensures {
+                self.check_clause(def, "ensures", cond)?;
+            }
+        }
+        Ok(r)
+    }
+
+    fn check_clause(&mut self, def: &FunctionDef, kind: &str, cond: &Expr) -> Result<(), RuntimeError> {
+        if self.eval_expr(cond)?.is_truthy() { return Ok(()); }
+        Err(RuntimeError::ContractViolation {
+            function: def.name.clone(),
+            clause: format!("{} {}", kind, neba_parser::format_expr(cond)),
+        })
+    }
+
+    // ── Task (spawn/await) ────────────────────────────────────────────────
+
+    /// Esegue i task pronti, in ordine di spawn, finché `id` non è terminato.
+    fn await_task(&mut self, id: usize) -> InterpResult {
+        loop {
+            match self.scheduler.state(id) {
+                Some(TaskState::Done(v))   => return Ok(v.clone()),
+                Some(TaskState::Failed(e)) => return Err(e.clone()),
+                Some(TaskState::Running)   => return Err(RuntimeError::Generic {
+                    message: format!("deadlock: task {} is waiting for itself", id),
+                }),
+                Some(TaskState::Pending(_)) => {}
+                None => return Err(RuntimeError::Generic { message: format!("unknown task {}", id) }),
+            }
+            if self.run_next_task().is_none() {
+                return Err(RuntimeError::Generic { message: format!("deadlock: task {} can never run", id) });
+            }
+        }
+    }
+
+    /// Ultimo ramo di `call`: funzioni costruite da memoize/compose/partial.
+    /// Tenerle fuori da `call` ne limita il frame, che decide la profondità di
+    /// ricorsione raggiungibile.
+    fn call_derived(&mut self, func: Value, args: Vec<Value>) -> InterpResult {
+        match func {
+            Value::Memoized(m) => self.call_memoized(&m, args),
+            // `compose(f, g)(args)` = `f(g(args))`
+            Value::Composed(fg) => {
+                let inner = self.call(fg.1.clone(), args)?;
+                self.call(fg.0.clone(), vec![inner])
+            }
+            // `partial(f, a)(args)` = `f(a, args)`
+            Value::Partial(f, bound) => {
+                let all = bound.iter().cloned().chain(args).collect();
+                self.call((*f).clone(), all)
+            }
+            other => Err(RuntimeError::NotCallable { type_name: other.type_name().to_string() }),
+        }
+    }
+
+    /// Chiamata di una funzione `memoize(f)`: `f` gira una sola volta per argomenti.
+    fn call_memoized(&mut self, memo: &Memo, args: Vec<Value>) -> InterpResult {
+        let key = Memo::key(&args).map_err(|message| RuntimeError::TypeError { message })?;
+        if let Some(v) = memo.cache.borrow().get(&key) {
+            return Ok(v.clone());
+        }
+        let v = self.call(memo.func.clone(), args)?;
+        memo.cache.borrow_mut().insert(key, v.clone());
+        Ok(v)
+    }
+
+    /// Combinatori Option/Result che chiamano una callback:
+    /// - `and_then(v, f)`: `Some(x)`/`Ok(x)` → `f(x)`; `None`/`Err` restano invariati
+    /// - `or_else(v, f)`: `None` → `f()`, `Err(e)` → `f(e)`; `Some`/`Ok` restano invariati
+    /// - `map_err(r, f)`: `Err(e)` → `Err(f(e))`; `Ok` resta invariato
+    fn combinator(&mut self, name: &str, args: Vec<Value>) -> InterpResult {
+        let [v, f] = <[Value; 2]>::try_from(args).map_err(|_| RuntimeError::Generic {
+            message: format!("{}(value, fn) requires 2 arguments", name),
+        })?;
+        match (name, v) {
+            ("and_then", Value::Some(x) | Value::Ok(x)) => self.call(f, vec![*x]),
+            ("and_then", v @ (Value::None | Value::Err(_))) => Ok(v),
+            ("or_else", Value::None) => self.call(f, Vec::new()),
+            ("or_else", Value::Err(e)) => self.call(f, vec![*e]),
+            ("or_else", v @ (Value::Some(_) | Value::Ok(_))) => Ok(v),
+            ("map_err", Value::Err(e)) => Ok(Value::Err(Box::new(self.call(f, vec![*e])?))),
+            ("map_err", v @ Value::Ok(_)) => Ok(v),
+            ("map_err", v) => Err(RuntimeError::TypeError { message: format!("map_err: expected Result, got {}", v.type_name()) }),
+            (_, v) => Err(RuntimeError::TypeError { message: format!("{}: expected Option or Result, got {}", name, v.type_name()) }),
+        }
+    }
+
+    /// Argomento di `race`/`join`: un Array di task (o valori già pronti, come per `await`).
+    fn awaitables(name: &str, args: Vec<Value>) -> Result<Vec<Value>, RuntimeError> {
+        match args.as_slice() {
+            [Value::Array(items)] => Ok(items.borrow().clone()),
+            _ => Err(RuntimeError::TypeError { message: format!("{}() requires an Array of tasks", name) }),
+        }
+    }
+
+    /// `race(tasks)`: il valore del primo task a terminare. Gli altri non vengono
+    /// annullati: restano in coda e girano quando attesi o a fine programma.
+    fn race(&mut self, args: Vec<Value>) -> InterpResult {
+        let items = Self::awaitables("race", args)?;
+        let mut ids = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                Value::Task(id) => ids.push(id),
+                ready => return Ok(ready),
+            }
+        }
+        if ids.is_empty() {
+            return Err(RuntimeError::Generic { message: "race() on an empty Array".into() });
+        }
+        loop {
+            if let Some(id) = self.scheduler.first_finished(&ids) {
+                return self.await_task(id);
+            }
+            if self.run_next_task().is_none() {
+                return Err(RuntimeError::Generic { message: "deadlock: none of the raced tasks can finish".into() });
+            }
+        }
+    }
+
+    /// `join(tasks)`: attende tutti i task e ne restituisce i valori nell'ordine dato.
+    fn join(&mut self, args: Vec<Value>) -> InterpResult {
+        let results = Self::awaitables("join", args)?.into_iter()
+            .map(|item| match item {
+                Value::Task(id) => self.await_task(id),
+                ready => Ok(ready),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.charge(results.len() * VALUE_SIZE)?;
+        Ok(Value::Array(Rc::new(RefCell::new(results))))
+    }
+
+    /// `sleep(ms)`: fuori da un task blocca e basta (`f` è il builtin, o lo stub della
+    /// sandbox). Dentro un task esegue gli altri task pronti finché non arriva il
+    /// risveglio, e blocca solo per il tempo che resta.
+    fn sleep(&mut self, f: Rc<dyn Fn(Vec<Value>) -> Result<Value, String>>, args: Vec<Value>) -> InterpResult {
+        let args = match self.scheduler.current() {
+            None => args,
+            Some(id) => {
+                let dur = stdlib::sleep_duration(&args).map_err(|e| RuntimeError::Generic { message: e })?;
+                self.scheduler.sleep_until(id, std::time::Instant::now() + dur);
+                while self.scheduler.is_asleep(id) && self.run_next_task().is_some() {}
+                vec![Value::Float(self.scheduler.wake(id).as_secs_f64() * 1000.0)]
+            }
+        };
+        f(args).map_err(|e| RuntimeError::Generic { message: e })
+    }
+
+    /// `rx.recv()`: il prossimo valore del canale; finché è vuoto fa avanzare
+    /// gli altri task. Canale chiuso e svuotato → `None`.
+    fn channel_recv(&mut self, ch: &Rc<RefCell<Channel>>) -> InterpResult {
+        loop {
+            if let Some(v) = ch.borrow_mut().queue.pop_front() {
+                return Ok(v);
+            }
+            if ch.borrow().closed {
+                return Ok(Value::None);
+            }
+            if self.run_next_task().is_none() {
+                return Err(RuntimeError::Generic {
+                    message: "deadlock: recv() on an empty channel with no task left to send".into(),
+                });
+            }
+        }
+    }
+
+    /// Esegue il prossimo task pronto; l'esito resta nel task per chi lo attende.
+    fn run_next_task(&mut self) -> Option<usize> {
+        let (id, body) = self.scheduler.next_ready()?;
+        let (env, depth) = (self.env.clone(), self.depth);
+        let result = match body {
+            TaskBody::Call(f, args) => self.call(f, args),
+            TaskBody::Expr(e, task_env) => {
+                self.env = task_env;
+                self.eval_expr(&e)
+            }
+        };
+        self.env = env;
+        self.depth = depth;
+        self.scheduler.finish(id, result);
+        Some(id)
+    }
+
+    /// Fine programma: esegue i task mai attesi; il primo che fallisce è l'errore del programma.
+    fn run_pending_tasks(&mut self) -> Result<(), RuntimeError> {
+        if self.manual_scheduling { return Ok(()); }
+        while let Some(id) = self.run_next_task() {
+            if let Some(TaskState::Failed(e)) = self.scheduler.state(id) {
+                return Err(e.clone());
+            }
+        }
+        Ok(())
+    }
+
     // ── Iterazione ────────────────────────────────────────────────────────
 
     fn to_iter(&self, val: Value) -> Result<Vec<Value>, RuntimeError> {
+        let val = val.char_as_str();
         match val {
             Value::Array(arr) => Ok(arr.borrow().clone()),
-            Value::Str(s)     => Ok(s.chars().map(|c| Value::Str(c.to_string())).collect()),
+            Value::Str(s)     => Ok(self.str_units(&s).into_iter().map(|c| Value::Str(c.to_string())).collect()),
             _ => Err(RuntimeError::TypeError { message: format!("'{}' is not iterable", val.type_name()) }),
         }
     }
@@ -730,6 +1569,7 @@ impl Interpreter {
                     ExprKind::Float(f) => Value::Float(*f),
                     ExprKind::Bool(b)  => Value::Bool(*b),
                     ExprKind::Str(s)   => Value::Str(s.clone()),
+                    ExprKind::Char(c)  => Value::Char(*c),
                     ExprKind::None     => Value::None,
                     _ => return Ok(false),
                 };
@@ -753,6 +1593,15 @@ impl Interpreter {
                 for p in pats { if self.match_pat(p, val)? { return Ok(true); } }
                 Ok(false)
             }
+            Pattern::StrAffix { prefix, suffix, .. } =>
+                Ok(matches!(val, Value::Str(s) if Pattern::affix_middle(s, prefix, suffix).is_some())),
+            Pattern::Tuple(pats) => match val {
+                Value::Tuple(t) if t.len() == pats.len() => {
+                    for (p, v) in pats.iter().zip(t.iter()) { if !self.match_pat(p, v)? { return Ok(false); } }
+                    Ok(true)
+                }
+                _ => Ok(false),
+            },
             Pattern::Error => Ok(false),
         }
     }
@@ -779,6 +1628,18 @@ impl Interpreter {
             Pattern::Or(pats) => {
                 if let Some(p) = pats.first() { self.bind_pat(p, val); }
             }
+            Pattern::StrAffix { prefix, rest, suffix } => {
+                if let Value::Str(s) = val {
+                    if let Some(mid) = Pattern::affix_middle(s, prefix, suffix) {
+                        self.bind_pat(rest, &Value::Str(mid.to_string()));
+                    }
+                }
+            }
+            Pattern::Tuple(pats) => {
+                if let Value::Tuple(t) = val {
+                    for (p, v) in pats.iter().zip(t.iter()) { self.bind_pat(p, v); }
+                }
+            }
             _ => {}
         }
     }
@@ -1,15 +1,21 @@
+pub mod coverage;
 pub mod environment;
 pub mod error;
 pub mod interpreter;
+pub mod json;
+pub mod scheduler;
 pub mod stdlib;
 pub mod value;
 
+pub use coverage::CoverageReport;
 pub use environment::Env;
 pub use error::{InterpResult, RuntimeError};
 pub use interpreter::{ClassMeta, Interpreter};
 pub use value::Value;
 
 /// Convenience: parsa + interpreta in un colpo solo.
+/// Esegue il programma solo per i suoi effetti e restituisce sempre `Value::None`;
+/// per ottenere il valore dell'ultima espressione usare `eval_value`.
 pub fn eval(source: &str) -> Result<Value, Box<dyn std::error::Error>> {
     let (program, lex_errors, parse_errors) = neba_parser::parse(source);
     if let Some(e) = lex_errors.into_iter().next()   { return Err(Box::new(e)); }
@@ -19,6 +25,16 @@ pub fn eval(source: &str) -> Result<Value, Box<dyn std::error::Error>> {
     Ok(Value::None)
 }
 
+/// Parsa + interpreta e restituisce il valore dell'ultima espressione top-level
+/// (`Value::None` se il programma termina con uno statement). Per REPL ed embedding.
+pub fn eval_value(source: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    let (program, lex_errors, parse_errors) = neba_parser::parse(source);
+    if let Some(e) = lex_errors.into_iter().next()   { return Err(Box::new(e)); }
+    if let Some(e) = parse_errors.into_iter().next() { return Err(Box::new(e)); }
+    let mut interp = Interpreter::new();
+    Ok(interp.run_value(&program)?)
+}
+
 // ── Test ──────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -47,6 +63,23 @@ mod tests {
         panic!("Expected RuntimeError but got none");
     }
 
+    // ── Slice ─────────────────────────────────────────────────────────────
+    #[test] fn t_slice_read() {
+        assert_eq!(run("str([1, 2, 3, 4, 5][1:3])"), Value::Str("[2, 3]".into()));
+        assert_eq!(run("str([1, 2, 3, 4, 5][4:0:-2])"), Value::Str("[5, 3]".into()));
+        assert_eq!(run(r#""hello"[-3:10]"#), Value::Str("llo".into()));
+        assert!(run_err("[1, 2][0:2:0]").to_string().contains("step cannot be zero"));
+    }
+    #[test] fn t_slice_assign_same_len() { assert_eq!(run("var a = [1, 2, 3]\na[0:2] = [7, 8]\nstr(a)"), Value::Str("[7, 8, 3]".into())); }
+    #[test] fn t_slice_assign_grow()     { assert_eq!(run("var a = [1, 2, 3]\na[1:2] = [7, 8, 9]\nstr(a)"), Value::Str("[1, 7, 8, 9, 3]".into())); }
+    #[test] fn t_slice_assign_shrink()   { assert_eq!(run("var a = [1, 2, 3, 4]\na[1:3] = [9]\nstr(a)"), Value::Str("[1, 9, 4]".into())); }
+    #[test] fn t_slice_assign_insert()   { assert_eq!(run("var a = [1, 2]\na[1:1] = [5]\nstr(a)"), Value::Str("[1, 5, 2]".into())); }
+    #[test] fn t_slice_assign_clamped()  { assert_eq!(run("var a = [1, 2]\na[1:100] = [3]\na[-50:0] = [0]\nstr(a)"), Value::Str("[0, 1, 3]".into())); }
+    #[test] fn t_slice_assign_errors() {
+        assert!(run_err("var a = [1, 2]\na[0:1] += [3]").to_string().contains("compound assignment to a slice"));
+        assert!(run_err("var a = [1, 2]\na[0:2:1] = [3]").to_string().contains("does not support a step"));
+    }
+
     // ── Aritmetica ────────────────────────────────────────────────────────
     #[test] fn t_add()    { assert_eq!(run("1 + 2"),       Value::Int(3)); }
     #[test] fn t_sub()    { assert_eq!(run("10 - 3"),      Value::Int(7)); }
@@ -58,7 +91,511 @@ mod tests {
     #[test] fn t_mixed()  { assert_eq!(run("1 + 2.5"),     Value::Float(3.5)); }
     #[test] fn t_strcat() { assert_eq!(run(r#""ab" + "cd""#), Value::Str("abcd".into())); }
     #[test] fn t_repeat() { assert_eq!(run(r#""ha" * 3"#), Value::Str("hahaha".into())); }
+    #[test] fn t_special_floats() {
+        assert_eq!(run("inf > 1e308"), Value::Bool(true));
+        assert_eq!(run("let x = nan\nx != x"), Value::Bool(true));
+        assert_eq!(run("1.0 / inf == 0.0"), Value::Bool(true));
+    }
+    #[test] fn t_instance_str_hook() {
+        assert_eq!(run("class P\n    x: Int = 0\n    fn __str__(self)\n        return \"P<\" + str(self.x) + \">\"\nlet p = P()\nstr(p)"), Value::Str("P<0>".into()));
+        assert_eq!(run("class P\n    x: Int = 0\n    fn __str__(self)\n        return \"P<\" + str(self.x) + \">\"\nlet p = P()\nf\"[{p}]\""), Value::Str("[P<0>]".into()));
+    }
+    #[test] fn t_instance_str_nested() {
+        let cls = "class P\n    x: Int = 0\n    fn __str__(self)\n        return \"P<\" + str(self.x) + \">\"\n";
+        assert_eq!(run(&format!("{}let p = P()\nstr([p, (p,)])", cls)), Value::Str("[P<0>, (P<0>,)]".into()));
+        assert_eq!(run(&format!("{}let d = {{\"k\": Some(P())}}\nf\"{{d}}\"", cls)), Value::Str("{k: Some(P<0>)}".into()));
+        assert_eq!(run(&format!("{}var a = [P()]\npush(a, a)\nstr(a)", cls)), Value::Str("[P<0>, [...]]".into()));
+    }
+    #[test] fn t_instance_dict_key() {
+        let src = "class K\n    id: Int = 0\n    fn __init__(self, id)\n        self.id = id\n    fn __hash__(self)\n        return self.id\n    fn __eq__(self, o)\n        return self.id == o.id\nlet d = {K(1): \"a\"}\nd[K(2)] = \"b\"\nd[K(1)] = \"c\"\nd[K(1)] + d[K(2)] + str(len(d)) + str(K(2) in d)";
+        assert_eq!(run(src), Value::Str("cb2true".into()));
+        assert!(run_err("class K\n    id: Int = 0\nlet d = {}\nd[K()] = 1").to_string().contains("unhashable instance of K"));
+    }
+    #[test] fn t_instance_dict_key_buckets() {
+        // Una lettura: un __hash__ e un __eq__ (solo sulle chiavi dello stesso bucket); hash uguali ma istanze diverse restano distinte
+        let cls = "var calls = 0\nclass K\n    id: Int = 0\n    h: Int = 0\n    fn __init__(self, id, h)\n        self.id = id\n        self.h = h\n    fn __hash__(self)\n        calls += 1\n        return self.h\n    fn __eq__(self, o)\n        calls += 1\n        return self.id == o.id\n";
+        let src = format!("{}var d = {{}}\nvar i = 0\nwhile i < 50\n    d[K(i, i)] = i\n    i += 1\ncalls = 0\nlet v = d[K(25, 25)]\nstr(v) + \" \" + str(calls)", cls);
+        assert_eq!(run(&src), Value::Str("25 2".into()));
+        let src = format!("{}var d = {{}}\nd[K(1, 0)] = \"a\"\nd[K(2, 0)] = \"b\"\nd[K(1, 0)] = \"c\"\nd[K(1, 0)] + d[K(2, 0)] + str(len(d))", cls);
+        assert_eq!(run(&src), Value::Str("cb2".into()));
+    }
+    #[test] fn t_instance_str_fallback() {
+        assert_eq!(run("class Q\n    y: Int = 1\n    x: Int = 2\nstr(Q())"), Value::Str("Q(x=2, y=1)".into()));
+    }
+    #[test] fn t_deep_eq_nested() {
+        assert_eq!(run("deep_eq([1, [2, [3]]], [1, [2, [3]]])"), Value::Bool(true));
+        assert_eq!(run("deep_eq([1, [2, [3]]], [1, [2, [4]]])"), Value::Bool(false));
+        // Dict e Tuple ricorrono sui valori: istanze distinte ma uguali campo per campo
+        let cls = "class P\n    x: Int = 0\n";
+        assert_eq!(run(&format!("{cls}[deep_eq({{\"a\": P()}}, {{\"a\": P()}}), deep_eq((1, P()), (1, P())), deep_eq({{\"a\": 1}}, {{\"b\": 1}}), deep_eq((1, [2]), (1, [3]))]")),
+                   run("[true, true, false, false]"));
+    }
+    #[test] fn t_deep_eq_ignores_custom_eq() {
+        let src = "class P\n    x: Int = 0\n    fn __eq__(self, o)\n        return true\nlet a = P()\nlet b = P()\nb.x = 2\nstr(deep_eq(a, P())) + str(deep_eq(a, b))";
+        assert_eq!(run(src), Value::Str("truefalse".into()));
+    }
+    #[test] fn t_freeze_read() { assert_eq!(run("let a = freeze([1, 2, 3])\na[1] + len(a)"), Value::Int(5)); }
+    #[test] fn t_freeze_mutation_errors() {
+        assert!(run_err("let a = freeze([1])\na[0] = 2").to_string().contains("frozen"));
+        assert!(run_err("let a = freeze([1])\npush(a, 2)").to_string().contains("frozen"));
+    }
+    #[test] fn t_cyclic_array_str() {
+        assert_eq!(run("var a = [1]\npush(a, a)\nstr(a)"), Value::Str("[1, [...]]".into()));
+    }
+    #[test] fn t_clear_array_alias() { assert_eq!(run("var a = [1, 2, 3]\nlet b = a\nclear(a)\npush(b, 9)\nlen(a) * 10 + a[0]"), Value::Int(19)); }
+    #[test] fn t_range_start_gt_end_empty() {
+        // `..` con start > end è vuoto; si scende solo con uno step esplicito
+        assert_eq!(run("var s = \"\"\nfor i in 5..0\n    s = s + str(i)\nlet n = 0\nfor i in 1..n\n    s = s + str(i)\nfor i in 3..=0\n    s = s + str(i)\ns"), Value::Str("".into()));
+        assert_eq!(run("var s = \"\"\nfor i in range(3, -1, -1)\n    s = s + str(i)\ns"), Value::Str("3210".into()));
+        assert_eq!(run("str(range(3, 0)) + str(range(3, 0, -1))"), Value::Str("[][3, 2, 1]".into()));
+    }
+    #[test] fn t_eval_value_expr()  { assert_eq!(eval_value("1 + 2").unwrap(), Value::Int(3)); }
+    #[test] fn t_eval_value_stmt()  { assert_eq!(eval_value("let x = 1 + 2").unwrap(), Value::None); }
+    #[test] fn t_eval_value_last()  { assert_eq!(eval_value("let x = 2\nx * 21").unwrap(), Value::Int(42)); }
+    #[test] fn t_eval_value_error() { assert!(eval_value("1 +").is_err()); }
+    #[test] fn t_register_native() {
+        let factor = 2;
+        let (program, _, _) = neba_parser::parse("double(21)");
+        let mut interp = Interpreter::new();
+        interp.register_native("double", move |args| match args.as_slice() {
+            [Value::Int(n)] => Ok(Value::Int(n * factor)),
+            _ => Err("double(Int)".into()),
+        });
+        assert_eq!(interp.run_value(&program).unwrap(), Value::Int(42));
+    }
+    #[test] fn t_call_function() {
+        let (program, _, _) = neba_parser::parse("fn add(a, b = 10)\n    return a + b\nfn boom()\n    return 1 / 0");
+        let mut interp = Interpreter::new();
+        interp.run(&program).unwrap();
+        assert_eq!(interp.call_function("add", vec![Value::Int(2), Value::Int(3)]), Ok(Value::Int(5)));
+        assert_eq!(interp.call_function("add", vec![Value::Int(2)]), Ok(Value::Int(12)));
+        assert!(matches!(interp.call_function("add", vec![]), Err(RuntimeError::ArityMismatch { .. })));
+        assert!(matches!(interp.call_function("nope", vec![]), Err(RuntimeError::UndefinedVariable { .. })));
+        assert!(interp.call_function("boom", vec![]).is_err());
+        assert_eq!(interp.call_function("add", vec![Value::Int(1), Value::Int(1)]), Ok(Value::Int(2)));
+    }
+    #[test] fn t_coverage() {
+        let src = "fn segno(x)\n    if x < 0\n        return -1\n    return 1\nclass C\n    fn m(self)\n        return 0\nlet s = segno(5)\nwhile s < 0\n    s -= 1\n";
+        let (program, _, _) = neba_parser::parse(src);
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.coverage_report(&program), None);
+        interp.set_coverage(true).run(&program).unwrap();
+        let report = interp.coverage_report(&program).unwrap();
+        assert_eq!(report.covered, vec![1, 2, 4, 5, 8, 9]);
+        assert_eq!(report.uncovered, vec![3, 7, 10]);
+        assert_eq!(report.to_string(), "coverage: 6/9 lines (66.7%)\nuncovered lines: 3, 7, 10");
+        // Le righe si accumulano tra le chiamate
+        interp.call_function("segno", vec![Value::Int(-1)]).unwrap();
+        assert_eq!(interp.coverage_report(&program).unwrap().uncovered, vec![7, 10]);
+    }
+    #[test] fn t_tail_calls() {
+        assert_eq!(run("fn loop(n)\n    if n == 0\n        return 0\n    return loop(n-1)\nloop(100000)"), Value::Int(0));
+        assert_eq!(run("fn somma(n, acc)\n    if n == 0\n        return acc\n    return somma(n - 1, acc + n)\nsomma(100000, 0)"), Value::Int(5000050000));
+        // Ricorsione mutua in coda
+        assert_eq!(run("fn pari(n)\n    if n == 0\n        return true\n    return dispari(n - 1)\nfn dispari(n)\n    if n == 0\n        return false\n    return pari(n - 1)\npari(50001)"), Value::Bool(false));
+        // Non in coda: il limite di profondità resta
+        let (program, _, _) = neba_parser::parse("fn f(n)\n    if n == 0\n        return 0\n    return 1 + f(n - 1)\nf(100000)");
+        assert!(matches!(Interpreter::new().run(&program), Err(RuntimeError::StackOverflow)));
+    }
+    #[test] fn t_spawn_await() {
+        assert_eq!(run("fn sq(x)\n    return x * x\nlet t = spawn sq(7)\nawait t"), Value::Int(49));
+        assert_eq!(run("let x = 5\nlet t = spawn x + 1\nawait t"), Value::Int(6));
+        // spawn non esegue subito; await b esegue prima a (FIFO), poi b
+        let src = "var eventi = []\nfn lavoro(nome, n)\n    push(eventi, nome)\n    return n * 2\nlet a = spawn lavoro(\"a\", 1)\nlet b = spawn lavoro(\"b\", 2)\npush(eventi, \"main\")\nlet rb = await b\nlet ra = await a\n[ra, rb, eventi]";
+        let Value::Array(items) = run(src) else { panic!() };
+        let items = items.borrow();
+        assert_eq!(items[..2], [Value::Int(2), Value::Int(4)]);
+        assert_eq!(items[2].to_string(), "[main, a, b]");
+        assert!(matches!(run_err("fn boom()\n    return 1 / 0\nlet t = spawn boom()\nawait t"), RuntimeError::DivisionByZero));
+        assert_eq!(run("await 3"), Value::Int(3));
+        // I task mai attesi girano a fine programma
+        let (program, _, _) = neba_parser::parse("var fatto = false\nfn f()\n    fatto = true\nspawn f()");
+        let mut interp = Interpreter::new();
+        interp.run(&program).unwrap();
+        assert_eq!(interp.get_global("fatto"), Some(Value::Bool(true)));
+    }
+    #[test] fn t_channels() {
+        // Il consumatore parte per primo, trova il canale vuoto e cede al produttore
+        let src = "let ch = channel()\nlet tx = ch[0]\nlet rx = ch[1]\nfn consuma()\n    var out = []\n    var v = rx.recv()\n    while v != None\n        push(out, v)\n        v = rx.recv()\n    return out\nfn produci()\n    for i in 1..=3\n        tx.send(i)\n    tx.close()\nlet c = spawn consuma()\nspawn produci()\nawait c";
+        assert_eq!(run(src).to_string(), "[1, 2, 3]");
+        let err = run_err("let ch = channel()\nch[1].recv()");
+        assert!(err.to_string().contains("deadlock"), "{}", err);
+        let err = run_err("let ch = channel()\nlet tx = ch[0]\ntx.close()\ntx.send(1)");
+        assert!(err.to_string().contains("closed channel"), "{}", err);
+    }
+    #[test] fn t_race_join() {
+        // `lento` parte per primo ma si sospende su recv: `veloce` termina prima
+        let src = "let ch = channel()\nfn lento()\n    return ch[1].recv()\nfn veloce()\n    return \"veloce\"\nfn invia()\n    ch[0].send(\"lento\")\nlet l = spawn lento()\nlet v = spawn veloce()\nspawn invia()\nlet primo = race([l, v])\n[primo, await l]";
+        assert_eq!(run(src).to_string(), "[veloce, lento]");
+        let src = "fn doppio(n)\n    return n * 2\nlet ts = [spawn doppio(3), spawn doppio(1), spawn doppio(2)]\njoin(ts)";
+        assert_eq!(run(src).to_string(), "[6, 2, 4]");
+        assert_eq!(run("join([])").to_string(), "[]");
+        assert!(run_err("race([])").to_string().contains("empty"));
+        assert!(matches!(run_err("fn boom()\n    return 1 / 0\njoin([spawn boom()])"), RuntimeError::DivisionByZero));
+    }
+    #[test] fn t_manual_scheduling() {
+        let src = "var eventi = []\nfn lavoro(nome)\n    push(eventi, nome)\n    return len(eventi)\nspawn lavoro(\"a\")\nspawn lavoro(\"b\")\npush(eventi, \"main\")";
+        let (program, _, _) = neba_parser::parse(src);
+        let mut interp = Interpreter::new();
+        interp.set_manual_scheduling(true);
+        interp.run(&program).unwrap();
+        let eventi = |i: &Interpreter| i.get_global("eventi").unwrap().to_string();
+        assert_eq!(eventi(&interp), "[main]");
+        assert_eq!(interp.pending_tasks(), 2);
+        assert_eq!(interp.task_result(0), None);
+        assert_eq!(interp.step_task(), Some(0));
+        assert_eq!(eventi(&interp), "[main, a]");
+        assert_eq!(interp.task_result(0), Some(Ok(Value::Int(2))));
+        assert_eq!(interp.step_task(), Some(1));
+        assert_eq!(eventi(&interp), "[main, a, b]");
+        assert_eq!(interp.step_task(), None);
+        assert_eq!(interp.pending_tasks(), 0);
+    }
+    #[test] fn t_sleep_yields() {
+        // `dorme` parte per primo; durante la sua sleep gira `lavora`
+        let src = "var eventi = []\nfn dorme()\n    push(eventi, \"sleep\")\n    sleep(20)\n    push(eventi, \"sveglio\")\nfn lavora()\n    push(eventi, \"lavoro\")\nlet a = spawn dorme()\nlet b = spawn lavora()\nawait a\neventi";
+        let start = std::time::Instant::now();
+        assert_eq!(run(src).to_string(), "[sleep, lavoro, sveglio]");
+        assert!(start.elapsed() >= std::time::Duration::from_millis(20));
+        // Fuori da un task blocca e basta
+        assert_eq!(run("sleep(1)\n2"), Value::Int(2));
+        assert!(run_err("sleep(-1)").to_string().contains("non-negative"));
+    }
+    #[test] fn t_option_result_combinators() {
+        let src = "fn meta(x)\n    if x % 2 == 0\n        return Some(int(x / 2))\n    return None\n";
+        assert_eq!(run(&format!("{}and_then(and_then(Some(8), meta), meta)", src)).to_string(), "Some(2)");
+        assert_eq!(run(&format!("{}and_then(and_then(Some(6), meta), meta)", src)), Value::None);
+        assert_eq!(run(&format!("{}and_then(None, meta)", src)), Value::None);
+        let src = "fn annota(e)\n    return \"errore: \" + e\n";
+        assert_eq!(run(&format!("{}map_err(Err(\"io\"), annota)", src)).to_string(), "Err(errore: io)");
+        assert_eq!(run(&format!("{}map_err(Ok(1), annota)", src)).to_string(), "Ok(1)");
+        assert_eq!(run(&format!("{}or_else(Err(\"io\"), annota)", src)).to_string(), "errore: io");
+        assert_eq!(run("ok_or(None, 0)").to_string(), "Err(0)");
+        assert!(matches!(run_err("fn f(e)\n    return e\nmap_err(None, f)"), RuntimeError::TypeError { .. }));
+    }
+    #[test] fn t_match_str_affix() {
+        let src = "fn host(url)\n    return match url\n        \"http://\" + rest => rest\n        nome + \".txt\" => nome\n        Some(\"k=\" + v) => v\n        _ => \"altro\"\n";
+        for (arg, expected) in [("\"http://neba.dev\"", "neba.dev"), ("\"ftp://neba.dev\"", "altro"),
+                                ("\"note.txt\"", "note"), ("Some(\"k=1\")", "1"), ("42", "altro")] {
+            assert_eq!(run(&format!("{}host({})", src, arg)), Value::Str(expected.into()), "{}", arg);
+        }
+    }
+    #[test] fn t_memoize() {
+        let src = "var calls = 0\nfn quadrato(n)\n    calls += 1\n    return n * n\nlet q = memoize(quadrato)\n";
+        assert_eq!(run(&format!("{}[q(3), q(4), q(3), q(3), q(4)]", src)).to_string(), "[9, 16, 9, 9, 16]");
+        assert_eq!(run(&format!("{}q(3)\nq(3)\nq(5)\nq(5)\ncalls", src)), Value::Int(2));
+        let src2 = "var calls = 0\nfn somma(xs, k)\n    calls += 1\n    return [len(xs), k]\nlet s = memoize(somma)\n";
+        assert_eq!(run(&format!("{}s([1, 2], Some(1))\ns([1, 2], Some(1))\ns([1, 2], None)\ncalls", src2)), Value::Int(2));
+        assert!(matches!(run_err(&format!("{}q(quadrato)", src)), RuntimeError::TypeError { .. }));
+        assert!(matches!(run_err("memoize(1)"), RuntimeError::Generic { .. }));
+    }
+    #[test] fn t_compose_partial() {
+        let src = "fn doppio(x)\n    return x * 2\nfn inc(x)\n    return x + 1\nfn sottrai(a, b)\n    return a - b\n";
+        assert_eq!(run(&format!("{}compose(doppio, inc)(5)", src)), Value::Int(12));
+        assert_eq!(run(&format!("{}compose(inc, doppio)(5)", src)), Value::Int(11));
+        assert_eq!(run(&format!("{}let da10 = partial(sottrai, 10)\nda10(3)", src)), Value::Int(7));
+        assert_eq!(run(&format!("{}partial(sottrai, 10, 4)()", src)), Value::Int(6));
+        assert_eq!(run(&format!("{}compose(str, partial(sottrai, 1))(3)", src)), Value::Str("-2".into()));
+        assert_eq!(run(&format!("{}typeof(partial(inc))", src)), Value::Str("Function".into()));
+        assert!(matches!(run_err("compose(1, str)"), RuntimeError::Generic { .. }));
+    }
+    #[test] fn t_pipe() {
+        let src = "fn somma(xs)\n    var t = 0\n    for x in xs\n        t += x\n    return t\nfn add(x, n)\n    return x + n\n";
+        assert_eq!(run(&format!("{}[1, 2, 3] |> somma", src)), Value::Int(6));
+        assert_eq!(run(&format!("{}5 |> add(1) |> add(10) |> str", src)), Value::Str("16".into()));
+        assert_eq!(run(&format!("{}let r = [1, 2] |> somma |> add(1)\nr * 2", src)), Value::Int(8));
+    }
+    #[test] fn t_str_format() {
+        assert_eq!(run("\"{0} e {1}, {0}\".format(\"a\", 2)"), Value::Str("a e 2, a".into()));
+        assert_eq!(run("let t = \"{saluto}, {0}! {{ok}}\"\nt.format(\"Neba\", saluto=\"Ciao\")"), Value::Str("Ciao, Neba! {ok}".into()));
+        assert_eq!(run("class P\n    x: Int\n    fn __str__(self)\n        return \"P\"\n\"<{0}>\".format(P(1))"), Value::Str("<P>".into()));
+        assert!(run_err("\"{0} {1}\".format(1)").to_string().contains("no argument for placeholder {1}"));
+        assert!(run_err("\"{x}\".format(y=1)").to_string().contains("no argument named 'x'"));
+    }
+    #[test] fn t_csv() {
+        let src = "let righe = [[\"nome\", \"nota\"], [\"Rossi, Anna\", \"riga 1\\nriga 2\"], [\"dice \\\"ciao\\\"\", \"\"]]\nlet testo = csv_write(righe)\n";
+        assert_eq!(run(&format!("{}testo", src)),
+            Value::Str("nome,nota\n\"Rossi, Anna\",\"riga 1\nriga 2\"\n\"dice \"\"ciao\"\"\",\n".into()));
+        assert_eq!(run(&format!("{}deep_eq(csv_parse(testo), righe)", src)), Value::Bool(true));
+        assert_eq!(run("len(csv_parse(\"a,b\\r\\nc,d\"))"), Value::Int(2));
+        assert_eq!(run("csv_parse(\"a,,b\\n\")[0][1]"), Value::Str("".into()));
+        assert!(run_err("csv_parse(\"\\\"aperto\")").to_string().contains("unterminated quoted field"));
+    }
+    #[test] fn t_dict() {
+        let src = "var m = {\"nome\": \"Neba\", \"dati\": {\"v\": [1, 2]}, 3: true,}\n";
+        assert_eq!(run(&format!("{}m[\"nome\"]", src)), Value::Str("Neba".into()));
+        assert_eq!(run(&format!("{}m[\"dati\"][\"v\"][1]", src)), Value::Int(2));
+        assert_eq!(run(&format!("{}len(m) + len(m[\"dati\"])", src)), Value::Int(4));
+        assert_eq!(run(&format!("{}m[3]", src)), Value::Bool(true));
+        assert_eq!(run(&format!("{}m[\"nuova\"] = 1\nm[\"nuova\"] += 1\nstr(m)", src)),
+            Value::Str("{nome: Neba, dati: {v: [1, 2]}, 3: true, nuova: 2}".into()));
+        assert_eq!(run("let e = {}\nlen(e)"), Value::Int(0));
+        assert_eq!(run("{'a': 1}[\"a\"] + len({\"x\": 0, \"x\": 1})"), Value::Int(2));
+        assert_eq!(run("\"a\" in {\"a\": 1} and {\"a\": 1, \"b\": 2} == {\"b\": 2, \"a\": 1}"), Value::Bool(true));
+        assert!(run_err("{\"a\": 1}[\"b\"]").to_string().contains("key not found in Dict: b"));
+        assert!(run_err("{[1]: 2}").to_string().contains("unhashable Dict key of type Array"));
+    }
+    #[test] fn t_tuple() {
+        assert_eq!(run("let t = (1, \"a\", true)\nt[1]"), Value::Str("a".into()));
+        assert_eq!(run("let t = (1, \"a\", true)\nstr(t) + \" \" + str(len(t))"), Value::Str("(1, a, true) 3".into()));
+        assert_eq!(run("let u = (7,)\nstr(u) + str(u[0]) + str(u[-1])"), Value::Str("(7,)77".into()));
+        assert_eq!(run("(1 + 2) * 3"), Value::Int(9));
+        assert_eq!(run("(1, (2, 3)) == (1, (2, 3)) and (1, 2) != (2, 1)"), Value::Bool(true));
+        assert_eq!(run("let t = (1, 2)\n[t is Tuple, t is not Tuple, [1] is Tuple]"), run("[true, false, false]"));
+        let sum = "fn sum(pair)\n    match pair\n        case (a, b) => a + b\n        case (a, b, c) => a + b + c\n        case (x,) => x\n        case _ => 0\n";
+        assert_eq!(run(&format!("{}sum((2, 3))", sum)), Value::Int(5));
+        assert_eq!(run(&format!("{}sum((1, 2, 3))", sum)), Value::Int(6));
+        assert_eq!(run(&format!("{}sum((4,))", sum)), Value::Int(4));
+        assert_eq!(run(&format!("{}sum([2, 3])", sum)), Value::Int(0));
+        assert_eq!(run("match (\"x\", Some(2))\n    (\"y\", _) => 0\n    (\"x\", Some(n)) => n\n"), Value::Int(2));
+        assert_eq!(run("{(1, \"a\"): 5}[(1, \"a\")]"), Value::Int(5));
+        assert!(run_err("(1, 2)[2]").to_string().contains("out of bounds"));
+    }
+    #[test] fn t_char() {
+        assert_eq!(run("'x'"), Value::Char('x'));
+        assert_eq!(run("\"xyz\"[0] == 'x'"), Value::Bool(true));
+        assert_eq!(run("'y' == \"xyz\"[0]"), Value::Bool(false));
+        assert_eq!(run("'x' == \"xy\""), Value::Bool(false));
+        assert_eq!(run("\"ab\" + 'c' + \"d\""), Value::Str("abcd".into()));
+        assert_eq!(run("'é' in \"caffé\""), Value::Bool(true));
+        assert_eq!(run("typeof('\\n')"), Value::Str("Char".into()));
+        assert_eq!(run("match \"q\"[0]\n    'p' => 1\n    'q' => 2\n    _ => 3"), Value::Int(2));
+        assert_eq!(run("hash('a') == hash(\"a\")"), Value::Bool(true));
+        // Fuori da `is`/`typeof` un Char vale come la Str del suo carattere
+        assert_eq!(run("f\"{'='*3}\" + 'a' + 'b'"), Value::Str("===ab".into()));
+        assert_eq!(run("\"abc\"[0] < 'b' and 'b' > \"a\""), Value::Bool(true));
+        assert_eq!(run("len('x') + int('7') + len(\"it's\")"), Value::Int(12));
+        assert_eq!(run("'a' is Char and not (\"a\" is Char) and 'x'[0] is Str"), Value::Bool(true));
+        assert_eq!(run("'q'.upper()"), Value::Str("Q".into()));
+    }
+    #[test] fn t_paths() {
+        let sep = std::path::MAIN_SEPARATOR;
+        assert_eq!(run("path_join(\"dati\", \"2024\", \"a.csv\")"), Value::Str(format!("dati{0}2024{0}a.csv", sep)));
+        assert_eq!(run(&format!("path_join(\"dati{}\", \"a.csv\")", sep)), Value::Str(format!("dati{}a.csv", sep)));
+        assert_eq!(run("path_join(\"dati\", \"/tmp\")"), Value::Str("/tmp".into()));
+        assert_eq!(run("basename(\"/tmp/dati/a.csv\")"), Value::Str("a.csv".into()));
+        assert_eq!(run("dirname(\"/tmp/dati/a.csv\")"), Value::Str("/tmp/dati".into()));
+        assert_eq!(run("basename(\"/\") + dirname(\"a.csv\")"), Value::Str("".into()));
+        let file = std::env::temp_dir().join("neba_t_paths.txt");
+        std::fs::write(&file, "x").unwrap();
+        let src = format!("exists(\"{}\")", file.display());
+        assert_eq!(run(&src), Value::Bool(true));
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(run(&src), Value::Bool(false));
+        assert!(run_err("path_join(1)").to_string().contains("expected Str"));
+    }
+    #[test] fn t_list_dir_glob() {
+        let dir = std::env::temp_dir().join("neba_t_list_dir_glob");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for f in ["b.csv", "a.csv", "note.txt", "sub/c.csv"] { std::fs::write(dir.join(f), "").unwrap(); }
+        let d = dir.display();
+        let paths = |names: &[&str]| names.iter().map(|n| dir.join(n).display().to_string()).collect::<Vec<_>>().join(", ");
+        assert_eq!(run(&format!("str(list_dir(\"{}\"))", d)), Value::Str("[a.csv, b.csv, note.txt, sub]".into()));
+        assert_eq!(run(&format!("str(glob(\"{}/*.csv\"))", d)), Value::Str(format!("[{}]", paths(&["a.csv", "b.csv"]))));
+        assert_eq!(run(&format!("str(glob(\"{}/**/*.csv\"))", d)), Value::Str(format!("[{}]", paths(&["a.csv", "b.csv", "sub/c.csv"]))));
+        assert_eq!(run(&format!("str(glob(\"{}/[!a]*.[ct]*\"))", d)), Value::Str(format!("[{}]", paths(&["b.csv", "note.txt"]))));
+        assert_eq!(run(&format!("len(glob(\"{}/*.md\"))", d)), Value::Int(0));
+        assert!(matches!(run(&format!("glob(\"{}/[a.csv\")", d)), Value::Err(_)));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(run(&format!("list_dir(\"{}\")", d)), Value::Err(_)));
+    }
+    #[test] fn t_hash() {
+        use std::hash::{Hash, Hasher};
+        let h = run("hash(\"neba\")");
+        assert_eq!(run("hash(\"ne\" + \"ba\")"), h);
+        // Stesso valore della VM: `Hash` di Str sullo `StableHasher`
+        let mut state = neba_runtime::StableHasher::default();
+        "neba".hash(&mut state);
+        assert_eq!(h, Value::Int(state.finish() as i64));
+        assert_ne!(run("hash(1)"), run("hash(\"1\")"));
+        assert_eq!(run("hash(Ok(2.5)) == hash(Ok(2.5))"), Value::Bool(true));
+        // -0.0 == 0.0: stesso hash e stessa chiave
+        assert_eq!(run("hash(-0.0) == hash(0.0)"), Value::Bool(true));
+        assert_eq!(run("let d = {0.0: \"zero\"}\nd[-0.0]"), Value::Str("zero".into()));
+        assert!(run_err("hash([1])").to_string().contains("unhashable type"));
+        assert_eq!(run("sha256_hex(\"abc\")"), Value::Str("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".into()));
+    }
+    #[test] fn t_json() {
+        use crate::json::{from_json, to_json};
+        let v = run("{\"nome\": \"β\\n\", \"n\": [1, 2.5, true, none], \"t\": (1, Some(Err(\"x\")))}");
+        let text = to_json(&v).unwrap();
+        assert_eq!(text, r#"{"nome":"β\n","n":[1,2.5,true,null],"t":[1,{"$some":{"$err":"x"}}]}"#);
+        assert_eq!(to_json(&from_json(&text).unwrap()).unwrap(), text);
+        assert!(from_json("01").is_err());
+        assert!(from_json("[1,]").is_err());
+        assert!(to_json(&run("{1: 2}")).is_err());
+        assert_eq!(run("to_json(Ok([1, 2]))"), Value::Str(r#"{"$ok":[1,2]}"#.into()));
+        assert_eq!(run("deep_eq(from_json(\"{\\\"a\\\": [1]}\"), Ok({\"a\": [1]}))"), Value::Bool(true));
+        assert!(matches!(run("from_json(\"-01\")"), Value::Err(_)));
+        assert!(run_err("to_json(print)").to_string().contains("cannot serialize"));
+    }
+    #[test] fn t_contracts() {
+        let src = "fn radice(x)\n    requires x >= 0\n    ensures result * result <= x\n    var r = 0\n    while (r + 1) * (r + 1) <= x\n        r += 1\n    return r\n";
+        let eval = |call: &str, on: bool| {
+            let (program, _, _) = neba_parser::parse(&format!("{}{}", src, call));
+            let mut interp = Interpreter::new();
+            interp.set_contracts(on);
+            interp.run_value(&program)
+        };
+        assert_eq!(eval("radice(10)", true), Ok(Value::Int(3)));
+        assert_eq!(eval("radice(-4)", true), Err(RuntimeError::ContractViolation {
+            function: "radice".into(), clause: "requires x >= 0".into(),
+        }));
+        // Senza il flag le clausole non vengono valutate
+        assert_eq!(eval("radice(-4)", false), Ok(Value::Int(0)));
+        // ensures con una chiamata in coda vede il risultato finale
+        let (program, _, _) = neba_parser::parse("fn giu(n)\n    ensures result == 1\n    if n == 0\n        return 0\n    return giu(n - 1)\ngiu(2)");
+        let err = Interpreter::new().set_contracts(true).run_value(&program).unwrap_err();
+        assert_eq!(err.to_string(), "[RuntimeError] Contract violation in 'giu': ensures result == 1");
+    }
+    #[test] fn t_value_conversions() {
+        assert_eq!(i64::try_from(Value::from(42i64)), Ok(42));
+        assert_eq!(f64::try_from(Value::from(2.5)), Ok(2.5));
+        assert_eq!(f64::try_from(Value::from(2i64)), Ok(2.0));
+        assert_eq!(bool::try_from(Value::from(true)), Ok(true));
+        assert_eq!(String::try_from(Value::from("ciao")), Ok("ciao".to_string()));
+        assert_eq!(String::try_from(Value::from("ciao".to_string())), Ok("ciao".to_string()));
+        let v = vec![Value::from(1i64), Value::from("a"), Value::from(false)];
+        assert_eq!(Vec::<Value>::try_from(Value::from(v.clone())), Ok(v));
+        assert_eq!(i64::try_from(Value::from("1")), Err("expected Int, got Str".to_string()));
+        assert!(Vec::<Value>::try_from(Value::None).is_err());
+    }
+    #[test] fn t_set_get_global() {
+        let (program, _, _) = neba_parser::parse("let result = limit * 2\nlimit = 0");
+        let mut interp = Interpreter::new();
+        interp.set_global("limit", Value::Int(21));
+        interp.run(&program).unwrap();
+        assert_eq!(interp.get_global("result"), Some(Value::Int(42)));
+        assert_eq!(interp.get_global("limit"), Some(Value::Int(0)));
+        assert_eq!(interp.get_global("missing"), None);
+    }
+    #[test] fn t_sandboxed_no_io() {
+        let mut interp = Interpreter::sandboxed();
+        let calls = [
+            ("input", "input(\"> \")"), ("print", "print(1)"), ("sleep", "sleep(1)"),
+            ("exists", "exists(\"/etc/passwd\")"), ("list_dir", "list_dir(\".\")"), ("glob", "glob(\"*\")"),
+        ];
+        for (name, src) in calls {
+            let (program, _, _) = neba_parser::parse(src);
+            let err = interp.run(&program).unwrap_err().to_string();
+            assert!(err.contains(&format!("'{}' is disabled in sandboxed mode", name)), "{}: {}", src, err);
+        }
+        // Fuori dalla sandbox gli stessi builtin del filesystem funzionano
+        let (program, _, _) = neba_parser::parse("exists(\"/\")\nlist_dir(\".\")\nglob(\"*\")");
+        Interpreter::new().run(&program).unwrap();
+    }
+    #[test] fn t_sandboxed_step_limit() {
+        let (program, _, _) = neba_parser::parse("var i = 0\nwhile true\n    i += 1");
+        let mut interp = Interpreter::sandboxed();
+        interp.set_step_limit(1000);
+        assert!(matches!(interp.run(&program), Err(RuntimeError::StepLimitExceeded { .. })));
+        assert!(matches!(interp.get_global("i"), Some(Value::Int(n)) if n > 900));
+    }
+    #[test] fn t_index_error_span() {
+        match run_err("let a = [1, 2, 3]\nlet i = 1\nlet x = a[i] + a[i + 5]") {
+            RuntimeError::IndexOutOfBounds { index: 6, len: 3, span: Some(span) } => assert_eq!((span.line, span.column), (3, 16)),
+            other => panic!("{:?}", other),
+        }
+        match run_err("var a = [1]\na[3] = 0") {
+            RuntimeError::IndexOutOfBounds { span: Some(span), .. } => assert_eq!(span.line, 2),
+            other => panic!("{:?}", other),
+        }
+    }
+    #[test] fn t_step_limit() {
+        let mut interp = Interpreter::new();
+        interp.set_step_limit(1000);
+        let (spin, _, _) = neba_parser::parse("while true\n    pass");
+        assert_eq!(interp.run(&spin), Err(RuntimeError::StepLimitExceeded { limit: 1000 }));
+        let (finite, _, _) = neba_parser::parse("var s = 0\nfor i in range(10)\n    s += i");
+        interp.run(&finite).unwrap();
+        assert_eq!(interp.get_global("s"), Some(Value::Int(45)));
+    }
+    #[test] fn t_alloc_limit() {
+        let mut interp = Interpreter::new();
+        interp.set_alloc_limit(1 << 20);
+        for src in ["let a = 0..100000000", "let s = \"x\" * 1000000000"] {
+            let (program, _, _) = neba_parser::parse(src);
+            assert!(matches!(interp.run(&program), Err(RuntimeError::ResourceLimit { .. })), "{}", src);
+        }
+        let (program, _, _) = neba_parser::parse("let a = 0..100");
+        assert!(interp.run(&program).is_ok());
+        // I builtin che fanno crescere un contenitore sono addebitati
+        let mut interp = Interpreter::new();
+        interp.set_alloc_limit(1 << 16);
+        let (grow, _, _) = neba_parser::parse("var a = []\nwhile true\n    push(a, 0)");
+        assert!(matches!(interp.run(&grow), Err(RuntimeError::ResourceLimit { .. })));
+        // Ogni chiave nuova di `d[k] = v` è addebitata; riassegnare no
+        let mut interp = Interpreter::new();
+        interp.set_alloc_limit(1 << 16);
+        let (grow, _, _) = neba_parser::parse("var d = {}\nvar i = 0\nwhile true\n    d[i] = i\n    i += 1");
+        assert!(matches!(interp.run(&grow), Err(RuntimeError::ResourceLimit { .. })));
+        let mut interp = Interpreter::new();
+        interp.set_alloc_limit(1 << 16);
+        let (same, _, _) = neba_parser::parse("var d = {}\nvar i = 0\nwhile i < 100000\n    d[0] = i\n    i += 1");
+        assert!(interp.run(&same).is_ok());
+    }
+    #[test] fn t_approx_eq() {
+        assert_eq!(run("approx_eq(0.1 + 0.2, 0.3)"), Value::Bool(true));
+        assert_eq!(run("approx_eq(1.0, 1.1)"), Value::Bool(false));
+        assert_eq!(run("approx_eq(nan, nan)"), Value::Bool(false));
+    }
     #[test] fn t_dbz()    { assert!(matches!(run_err("1 / 0"), RuntimeError::DivisionByZero)); }
+    #[test] fn t_byte_len() {
+        assert_eq!(run("len(\"héllo\")"), Value::Int(5));
+        assert_eq!(run("\"héllo\".byte_len()"), Value::Int(6));
+        assert_eq!(run("\"abc\".byte_len()"), run("len(\"abc\")"));
+        assert_eq!(run("byte_len(\"héllo\") * 10 + byte_len('é')"), Value::Int(62));
+        assert!(run_err("byte_len(1)").to_string().contains("expects Str"));
+    }
+    #[test] fn t_string_append_in_place() {
+        // `s += x` accoda nel buffer della variabile: O(n) ammortizzato invece di O(n²)
+        let src = "var s = \"\"\nfor i in 0..100000\n    s += \"x\"\nvar t = \"\"\nfor i in 0..50000\n    t = t + \"ab\"\n[len(s), len(t)]";
+        let Value::Array(items) = run(src) else { panic!("expected array") };
+        assert_eq!(*items.borrow(), vec![Value::Int(100000), Value::Int(100000)]);
+        let Value::Array(items) = run("var s = \"ab\"\nlet t = s\ns += \"c\"\n[s, t]") else { panic!("expected array") };
+        assert_eq!(*items.borrow(), vec![Value::Str("abc".into()), Value::Str("ab".into())]);
+        let (program, _, _) = neba_parser::parse("let s = \"a\"\ns += \"b\"");
+        assert!(matches!(Interpreter::new().run_value(&program), Err(RuntimeError::AssignError { .. })));
+    }
+    #[test] fn t_is_not() {
+        assert_eq!(run("let x = 42\n[x is Int, x is not Int, x is not Str, x is not None, None is not None]"), run("[true, false, true, true, false]"));
+        // Un nome di tipo ridefinito come variabile torna un normale `is`
+        assert_eq!(run("let Int = \"a\"\n1 is not Int"), Value::Bool(true));
+        assert_eq!(run("let r = 0..3\n[r is Array, r is not Array, r is Int]"), run("[true, false, false]"));
+    }
+    #[test] fn t_trivia_only_program() {
+        for src in ["", "# solo commenti\n# altro", "\n\n   \n", "    # indentato\n\n"] {
+            let (program, _, _) = neba_parser::parse(src);
+            assert!(program.stmts.is_empty(), "{:?}", src);
+            assert_eq!(Interpreter::new().run_value(&program), Ok(Value::None));
+        }
+    }
+    #[cfg(feature = "graphemes")]
+    #[test] fn t_grapheme_strings() {
+        // "cafe" + U+0301 (accento combinante) + "!": 6 code point, 5 grafemi
+        let (program, _, _) = neba_parser::parse("let s = \"cafe\u{301}!\"\nvar n = 0\nfor c in s\n    n += 1\n[s[3], s[4], n]");
+        let eval = |graphemes: bool| {
+            let Ok(Value::Array(items)) = Interpreter::new().set_grapheme_strings(graphemes).run_value(&program) else { panic!("expected array") };
+            let items = items.borrow().clone();
+            items
+        };
+        assert_eq!(eval(false), vec![Value::Str("e".into()), Value::Str("\u{301}".into()), Value::Int(6)]);
+        assert_eq!(eval(true), vec![Value::Str("e\u{301}".into()), Value::Str("!".into()), Value::Int(5)]);
+    }
+    #[test] fn t_ieee_division() {
+        let div = |ieee: bool, src: &str| {
+            let (program, _, _) = neba_parser::parse(src);
+            Interpreter::new().set_ieee_division(ieee).run_value(&program)
+        };
+        for src in ["1.0 / 0.0", "1 / 0.0", "1 / 0"] {
+            assert_eq!(div(false, src), Err(RuntimeError::DivisionByZero), "{}", src);
+        }
+        assert_eq!(div(true, "1.0 / 0.0"), Ok(Value::Float(f64::INFINITY)));
+        assert_eq!(div(true, "-1 / 0.0"), Ok(Value::Float(f64::NEG_INFINITY)));
+        assert!(matches!(div(true, "0.0 / 0.0"), Ok(Value::Float(f)) if f.is_nan()));
+        assert_eq!(div(true, "1 / 0"), Err(RuntimeError::DivisionByZero));
+    }
 
     // ── Confronto ─────────────────────────────────────────────────────────
     #[test] fn t_eq()  { assert_eq!(run("1 == 1"),        Value::Bool(true)); }
@@ -115,11 +652,38 @@ mod tests {
         let src = "fn make_adder(n: Int)\n    fn add(x: Int)\n        return x + n\n    return add\nlet add5 = make_adder(5)\nadd5(3)";
         assert_eq!(run(src), Value::Int(8));
     }
+    #[test] fn t_closure_sees_later_update() {
+        let src = "var n = 1\nfn get()\n    return n\nn = 42\nget()";
+        assert_eq!(run(src), Value::Int(42));
+    }
+    #[test] fn t_closure_captures_its_scope() {
+        let src = "fn make(k)\n    fn inner(x)\n        return x * k\n    return inner\nlet a = make(2)\nlet b = make(10)\na(3) + b(3)";
+        assert_eq!(run(src), Value::Int(36));
+    }
+    #[test] fn t_mutual_recursion() {
+        let src = "fn is_even(n)\n    if n == 0\n        return true\n    return is_odd(n - 1)\nfn is_odd(n)\n    if n == 0\n        return false\n    return is_even(n - 1)\nis_even(10) and is_odd(7)";
+        assert_eq!(run(src), Value::Bool(true));
+    }
+    #[test] fn t_fn_defined_in_loop() {
+        // Ogni iterazione ridefinisce `step`: il corpo è condiviso (Rc), non ricopiato
+        let src = "var s = 0\nvar first = None\nvar last = None\nfor i in 0..500\n    fn step(x)\n        return x + i\n    s = step(s)\n    if i == 0\n        first = step\n    last = step\n[s, first, last]";
+        let Value::Array(items) = run(src) else { panic!("expected array") };
+        let items = items.borrow();
+        assert_eq!(items[0], Value::Int(124750));
+        match (&items[1], &items[2]) {
+            (Value::Function(a), Value::Function(b)) => assert!(std::rc::Rc::ptr_eq(&a.body, &b.body)),
+            other => panic!("expected functions, got {:?}", other),
+        }
+    }
     #[test] fn t_arity_err() {
         assert!(matches!(run_err("fn f(a: Int)\n    return a\nf()"), RuntimeError::ArityMismatch { .. }));
     }
     #[test] fn t_stackoverflow() {
-        assert!(matches!(run_err("fn inf()\n    return inf()\ninf()"), RuntimeError::StackOverflow));
+        assert!(matches!(run_err("fn inf()\n    return 1 + inf()\ninf()"), RuntimeError::StackOverflow));
+        // In coda non consuma stack: come un `while true`, la ferma il limite di passi
+        let (program, _, _) = neba_parser::parse("fn inf()\n    return inf()\ninf()");
+        let err = Interpreter::new().set_step_limit(10_000).run(&program).unwrap_err();
+        assert!(matches!(err, RuntimeError::StepLimitExceeded { .. }));
     }
 
     // ── Match ─────────────────────────────────────────────────────────────
@@ -135,6 +699,9 @@ mod tests {
     #[test] fn t_match_none() {
         assert_eq!(run("let x: Option[Int] = None\nmatch x\n    case Some(v) => v\n    case None => 0\n"), Value::Int(0));
     }
+    #[test] fn t_match_subject_once() {
+        assert_eq!(run("var calls = 0\nfn next(v)\n    calls += 1\n    return v\nmatch next(7)\n    case 1 => 0\n    case 2 | 3 => 0\n    case Some(x) => 0\n    case 0..=5 => 0\nmatch next(Ok(3))\n    case Err(e) => 0\n    case Ok(4) => 1\n    case Ok(v) => v\ncalls"), Value::Int(2));
+    }
     #[test] fn t_match_range() {
         assert_eq!(run("match 75\n    case 0..=59 => 0\n    case 60..=100 => 1\n    case _ => 2\n"), Value::Int(1));
     }
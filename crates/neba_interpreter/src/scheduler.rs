@@ -0,0 +1,122 @@
+//! Scheduler cooperativo di `spawn`/`await` (single thread, green thread).
+//!
+//! `spawn expr` accoda un task e restituisce subito un handle (`Value::Task`);
+//! `await handle` esegue i task pronti in ordine FIFO finché quello atteso non
+//! termina e ne restituisce il valore. Un task gira fino al completamento: i punti
+//! di attesa (`await`, `recv()` su un canale vuoto, `sleep`) sono quelli in cui altri task
+//! possono avanzare. I task mai attesi vengono eseguiti alla fine del programma.
+//! `race(tasks)` e `join(tasks)` attendono rispettivamente il primo e tutti.
+//!
+//! L'ordine è deterministico: a ogni punto di attesa parte il task pronto creato
+//! per primo. Con `Interpreter::set_manual_scheduling` l'host fa avanzare i task
+//! uno alla volta (`step_task`) invece di eseguirli a fine programma.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use neba_parser::ast::Expr;
+
+use crate::environment::Env;
+use crate::error::RuntimeError;
+use crate::value::Value;
+
+/// Lavoro di un task ancora da eseguire.
+pub(crate) enum TaskBody {
+    /// `spawn f(args)`: callee e argomenti valutati allo spawn, chiamata differita
+    Call(Value, Vec<Value>),
+    /// Qualsiasi altra espressione, valutata nell'environment dello spawn
+    Expr(Expr, Env),
+}
+
+pub(crate) enum TaskState {
+    Pending(TaskBody),
+    Running,
+    Done(Value),
+    Failed(RuntimeError),
+}
+
+/// Coda condivisa da `Value::Sender` e `Value::Receiver` (vedi `channel()`).
+/// Illimitata: `send` non blocca mai; `recv` su coda vuota fa avanzare gli
+/// altri task finché non arriva un valore.
+#[derive(Debug, Default)]
+pub struct Channel {
+    pub queue: VecDeque<Value>,
+    /// Dopo `close()`, `recv` su coda vuota restituisce `None`
+    pub closed: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    tasks: Vec<TaskState>,
+    ready: VecDeque<usize>,
+    /// Task terminati (con successo o no), in ordine di completamento
+    finished: Vec<usize>,
+    /// Task in esecuzione: ognuno gira dentro il punto di attesa di quello sotto
+    running: Vec<usize>,
+    /// Task fermi in `sleep(ms)` e istante del risveglio
+    sleeping: HashMap<usize, Instant>,
+}
+
+impl Scheduler {
+    pub(crate) fn spawn(&mut self, body: TaskBody) -> usize {
+        let id = self.tasks.len();
+        self.tasks.push(TaskState::Pending(body));
+        self.ready.push_back(id);
+        id
+    }
+
+    /// Prossimo task pronto, segnato come in esecuzione.
+    pub(crate) fn next_ready(&mut self) -> Option<(usize, TaskBody)> {
+        while let Some(id) = self.ready.pop_front() {
+            if let TaskState::Pending(body) = std::mem::replace(&mut self.tasks[id], TaskState::Running) {
+                self.running.push(id);
+                return Some((id, body));
+            }
+        }
+        None
+    }
+
+    pub(crate) fn finish(&mut self, id: usize, result: Result<Value, RuntimeError>) {
+        self.tasks[id] = match result {
+            Ok(v)  => TaskState::Done(v),
+            Err(e) => TaskState::Failed(e),
+        };
+        self.finished.push(id);
+        self.running.retain(|&r| r != id);
+    }
+
+    /// Il task in esecuzione più interno (`None` fuori da ogni task).
+    pub(crate) fn current(&self) -> Option<usize> {
+        self.running.last().copied()
+    }
+
+    pub(crate) fn sleep_until(&mut self, id: usize, wake: Instant) {
+        self.sleeping.insert(id, wake);
+    }
+
+    /// Vero finché `id` dorme e il suo risveglio non è arrivato.
+    pub(crate) fn is_asleep(&self, id: usize) -> bool {
+        self.sleeping.get(&id).is_some_and(|&wake| Instant::now() < wake)
+    }
+
+    /// Toglie `id` dai task dormienti e restituisce il tempo che manca al risveglio.
+    pub(crate) fn wake(&mut self, id: usize) -> std::time::Duration {
+        self.sleeping.remove(&id)
+            .map(|wake| wake.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    /// Il primo di `ids` a essere terminato, se ce n'è uno.
+    pub(crate) fn first_finished(&self, ids: &[usize]) -> Option<usize> {
+        self.finished.iter().copied().find(|id| ids.contains(id))
+    }
+
+    /// Task in coda e non ancora avviati.
+    pub(crate) fn pending(&self) -> usize {
+        self.ready.len()
+    }
+
+    pub(crate) fn state(&self, id: usize) -> Option<&TaskState> {
+        self.tasks.get(id)
+    }
+}
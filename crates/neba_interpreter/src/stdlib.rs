@@ -1,11 +1,12 @@
 use crate::environment::Env;
-use crate::value::Value;
+use crate::value::{Memo, Value, check_mutable};
 
 pub fn register(env: &mut Env) {
     env.define("print",   Value::NativeFunction("print".into(), std::rc::Rc::new(neba_print)),   false);
     env.define("println", Value::NativeFunction("println".into(), std::rc::Rc::new(neba_println)), false);
     env.define("input",   Value::NativeFunction("input".into(), std::rc::Rc::new(neba_input)),   false);
     env.define("len",     Value::NativeFunction("len".into(), std::rc::Rc::new(neba_len)),     false);
+    env.define("byte_len", Value::NativeFunction("byte_len".into(), std::rc::Rc::new(neba_byte_len)), false);
     env.define("str",     Value::NativeFunction("str".into(), std::rc::Rc::new(neba_str)),     false);
     env.define("int",     Value::NativeFunction("int".into(), std::rc::Rc::new(neba_int)),     false);
     env.define("float",   Value::NativeFunction("float".into(), std::rc::Rc::new(neba_float)),   false);
@@ -17,7 +18,54 @@ pub fn register(env: &mut Env) {
     env.define("range",   Value::NativeFunction("range".into(), std::rc::Rc::new(neba_range)),   false);
     env.define("push",    Value::NativeFunction("push".into(), std::rc::Rc::new(neba_push)),    false);
     env.define("pop",     Value::NativeFunction("pop".into(), std::rc::Rc::new(neba_pop)),     false);
+    env.define("clear",   Value::NativeFunction("clear".into(), std::rc::Rc::new(neba_clear)),   false);
     env.define("assert",  Value::NativeFunction("assert".into(), std::rc::Rc::new(neba_assert)),  false);
+    env.define("approx_eq", Value::NativeFunction("approx_eq".into(), std::rc::Rc::new(neba_approx_eq)), false);
+    env.define("deep_eq", Value::NativeFunction("deep_eq".into(), std::rc::Rc::new(neba_deep_eq)), false);
+    env.define("freeze",  Value::NativeFunction("freeze".into(), std::rc::Rc::new(neba_freeze)),  false);
+    env.define("channel", Value::NativeFunction("channel".into(), std::rc::Rc::new(neba_channel)), false);
+    // race/join: segnaposto, la chiamata è intercettata da `Interpreter::call` (serve lo scheduler)
+    env.define("race",    Value::NativeFunction("race".into(), std::rc::Rc::new(neba_intercepted)),  false);
+    env.define("join",    Value::NativeFunction("join".into(), std::rc::Rc::new(neba_intercepted)),  false);
+    // Combinatori con callback: anche questi intercettati da `Interpreter::call`
+    env.define("and_then", Value::NativeFunction("and_then".into(), std::rc::Rc::new(neba_intercepted)), false);
+    env.define("or_else",  Value::NativeFunction("or_else".into(), std::rc::Rc::new(neba_intercepted)),  false);
+    env.define("map_err",  Value::NativeFunction("map_err".into(), std::rc::Rc::new(neba_intercepted)),  false);
+    env.define("ok_or",    Value::NativeFunction("ok_or".into(), std::rc::Rc::new(neba_ok_or)),          false);
+    env.define("memoize", Value::NativeFunction("memoize".into(), std::rc::Rc::new(neba_memoize)), false);
+    env.define("compose", Value::NativeFunction("compose".into(), std::rc::Rc::new(neba_compose)), false);
+    env.define("partial", Value::NativeFunction("partial".into(), std::rc::Rc::new(neba_partial)), false);
+    env.define("csv_parse", Value::NativeFunction("csv_parse".into(), std::rc::Rc::new(neba_csv_parse)), false);
+    env.define("csv_write", Value::NativeFunction("csv_write".into(), std::rc::Rc::new(neba_csv_write)), false);
+    env.define("hash",    Value::NativeFunction("hash".into(), std::rc::Rc::new(neba_hash)),    false);
+    env.define("sha256_hex", Value::NativeFunction("sha256_hex".into(), std::rc::Rc::new(neba_sha256_hex)), false);
+    // JSON (vedi json.rs); documento non valido → Err(msg)
+    env.define("to_json",   Value::NativeFunction("to_json".into(), std::rc::Rc::new(neba_to_json)),     false);
+    env.define("from_json", Value::NativeFunction("from_json".into(), std::rc::Rc::new(neba_from_json)), false);
+    // Percorsi (std::path): solo `exists` tocca il filesystem
+    env.define("path_join", Value::NativeFunction("path_join".into(), std::rc::Rc::new(neba_path_join)), false);
+    env.define("basename",  Value::NativeFunction("basename".into(), std::rc::Rc::new(neba_basename)),   false);
+    env.define("dirname",   Value::NativeFunction("dirname".into(), std::rc::Rc::new(neba_dirname)),     false);
+    env.define("exists",    Value::NativeFunction("exists".into(), std::rc::Rc::new(neba_exists)),       false);
+    // Errori di I/O → valore `Err(msg)`, come i pattern non validi delle regex nella VM
+    env.define("list_dir",  Value::NativeFunction("list_dir".into(), std::rc::Rc::new(neba_list_dir)),   false);
+    env.define("glob",      Value::NativeFunction("glob".into(), std::rc::Rc::new(neba_glob)),           false);
+    // Dentro un task `Interpreter::call` cede prima agli altri task e blocca solo per il resto
+    env.define("sleep",   Value::NativeFunction("sleep".into(), std::rc::Rc::new(neba_sleep)),   false);
+    // Float speciali IEEE: `-inf` è la negazione unaria di `inf`
+    env.define("inf",     Value::Float(f64::INFINITY), false);
+    env.define("nan",     Value::Float(f64::NAN),      false);
+}
+
+/// Builtin di I/O sostituiti da uno stub in `Interpreter::sandboxed`.
+/// Builtin futuri di file, rete o ambiente vanno aggiunti qui.
+pub const SANDBOX_DISABLED: &[&str] = &["print", "println", "input", "sleep", "exists", "list_dir", "glob"];
+
+pub fn disable_sandboxed(env: &mut Env) {
+    for name in SANDBOX_DISABLED {
+        let msg = format!("'{}' is disabled in sandboxed mode", name);
+        env.define(*name, Value::NativeFunction(name.to_string(), std::rc::Rc::new(move |_| Err(msg.clone()))), false);
+    }
 }
 
 fn neba_print(args: Vec<Value>) -> Result<Value, String> {
@@ -38,11 +86,22 @@ fn neba_input(args: Vec<Value>) -> Result<Value, String> {
 fn neba_len(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(Value::Array(a)) => Ok(Value::Int(a.borrow().len() as i64)),
+        Some(Value::Dict(d))  => Ok(Value::Int(d.borrow().len() as i64)),
+        Some(Value::Tuple(t)) => Ok(Value::Int(t.len() as i64)),
         Some(Value::Str(s))   => Ok(Value::Int(s.chars().count() as i64)),
+        Some(Value::Char(_))  => Ok(Value::Int(1)),
         Some(v) => Err(format!("len() not supported for {}", v.type_name())),
         None    => Err("len() requires 1 argument".into()),
     }
 }
+/// Lunghezza in byte UTF-8; `len(s)` conta invece i code point.
+fn neba_byte_len(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(v) => text_of(v).map(|s| Value::Int(s.len() as i64))
+            .ok_or_else(|| format!("byte_len() expects Str, got {}", v.type_name())),
+        None => Err("byte_len() requires 1 argument".into()),
+    }
+}
 fn neba_str(args: Vec<Value>) -> Result<Value, String> {
     Ok(Value::Str(args.first().map_or("None".to_string(), |v| v.to_string())))
 }
@@ -51,7 +110,10 @@ fn neba_int(args: Vec<Value>) -> Result<Value, String> {
         Some(Value::Int(n))   => Ok(Value::Int(*n)),
         Some(Value::Float(f)) => Ok(Value::Int(*f as i64)),
         Some(Value::Bool(b))  => Ok(Value::Int(*b as i64)),
-        Some(Value::Str(s))   => s.trim().parse::<i64>().map(Value::Int).map_err(|_| format!("cannot convert '{}' to Int", s)),
+        Some(v @ (Value::Str(_) | Value::Char(_))) => {
+            let s = v.to_string();
+            s.trim().parse::<i64>().map(Value::Int).map_err(|_| format!("cannot convert '{}' to Int", s))
+        }
         Some(v) => Err(format!("cannot convert {} to Int", v.type_name())),
         None    => Err("int() requires 1 argument".into()),
     }
@@ -61,13 +123,16 @@ fn neba_float(args: Vec<Value>) -> Result<Value, String> {
         Some(Value::Float(f)) => Ok(Value::Float(*f)),
         Some(Value::Int(n))   => Ok(Value::Float(*n as f64)),
         Some(Value::Bool(b))  => Ok(Value::Float(*b as i64 as f64)),
-        Some(Value::Str(s))   => s.trim().parse::<f64>().map(Value::Float).map_err(|_| format!("cannot convert '{}' to Float", s)),
+        Some(v @ (Value::Str(_) | Value::Char(_))) => {
+            let s = v.to_string();
+            s.trim().parse::<f64>().map(Value::Float).map_err(|_| format!("cannot convert '{}' to Float", s))
+        }
         Some(v) => Err(format!("cannot convert {} to Float", v.type_name())),
         None    => Err("float() requires 1 argument".into()),
     }
 }
 fn neba_bool(args: Vec<Value>) -> Result<Value, String> {
-    Ok(Value::Bool(args.first().map_or(false, |v| v.is_truthy())))
+    Ok(Value::Bool(args.first().is_some_and(|v| v.is_truthy())))
 }
 fn neba_type(args: Vec<Value>) -> Result<Value, String> {
     Ok(Value::Str(args.first().map_or("None", |v| v.type_name()).to_string()))
@@ -119,16 +184,31 @@ fn neba_range(args: Vec<Value>) -> Result<Value, String> {
 }
 fn neba_push(args: Vec<Value>) -> Result<Value, String> {
     match args.as_slice() {
-        [Value::Array(arr), val] => { arr.borrow_mut().push(val.clone()); Ok(Value::None) }
+        [Value::Array(arr), val] => { check_mutable(arr, "push")?; arr.borrow_mut().push(val.clone()); Ok(Value::None) }
         _ => Err("push(array, value) requires Array and value".into()),
     }
 }
 fn neba_pop(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
-        Some(Value::Array(arr)) => arr.borrow_mut().pop().ok_or_else(|| "pop() on empty array".into()),
+        Some(Value::Array(arr)) => { check_mutable(arr, "pop")?; arr.borrow_mut().pop().ok_or_else(|| "pop() on empty array".into()) }
         _ => Err("pop(array) requires an Array".into()),
     }
 }
+/// clear(array | dict) → None  (svuota in-place)
+fn neba_clear(args: Vec<Value>) -> Result<Value, String> {
+    match args.first() {
+        Some(Value::Array(arr)) => { check_mutable(arr, "clear")?; arr.borrow_mut().clear(); Ok(Value::None) }
+        Some(Value::Dict(d))    => { d.borrow_mut().clear(); Ok(Value::None) }
+        _ => Err("clear(array) requires an Array or a Dict".into()),
+    }
+}
+/// freeze(array) → Array  (congela l'array in-place e lo restituisce)
+fn neba_freeze(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [Value::Array(arr)] => { crate::value::freeze_array(arr); Ok(Value::Array(arr.clone())) }
+        _ => Err("freeze(array) requires an Array".into()),
+    }
+}
 fn neba_assert(args: Vec<Value>) -> Result<Value, String> {
     match args.first() {
         Some(v) if v.is_truthy() => Ok(Value::None),
@@ -139,3 +219,416 @@ fn neba_assert(args: Vec<Value>) -> Result<Value, String> {
         None => Err("assert() requires 1 argument".into()),
     }
 }
+/// `approx_eq(a, b, eps = 1e-9)` — stessa semantica della VM:
+/// `|a - b| <= eps * max(1, |a|, |b|)`, NaN mai uguale, infiniti uguali solo a sé stessi.
+fn neba_approx_eq(args: Vec<Value>) -> Result<Value, String> {
+    let num = |v: &Value| match v {
+        Value::Int(n)   => Ok(*n as f64),
+        Value::Float(f) => Ok(*f),
+        other => Err(format!("approx_eq: expected numeric, got {}", other.type_name())),
+    };
+    let (a, b, eps) = match args.as_slice() {
+        [a, b]      => (num(a)?, num(b)?, 1e-9),
+        [a, b, eps] => (num(a)?, num(b)?, num(eps)?),
+        _ => return Err("approx_eq(a, b, eps?) requires 2 or 3 arguments".into()),
+    };
+    if a == b { return Ok(Value::Bool(true)); }
+    if !a.is_finite() || !b.is_finite() { return Ok(Value::Bool(false)); }
+    Ok(Value::Bool((a - b).abs() <= eps * 1f64.max(a.abs()).max(b.abs())))
+}
+
+/// `deep_eq(a, b)` — uguaglianza strutturale che ignora `__eq__`:
+/// Array e Tuple elemento per elemento, Dict valore per valore (stesse chiavi),
+/// Instance campo per campo.
+fn neba_deep_eq(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [a, b] => Ok(Value::Bool(deep_eq(a, b))),
+        _ => Err("deep_eq(a, b) requires 2 arguments".into()),
+    }
+}
+
+fn deep_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Array(x), Value::Array(y)) => {
+            if std::rc::Rc::ptr_eq(x, y) { return true; }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| deep_eq(a, b))
+        }
+        (Value::Tuple(x), Value::Tuple(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| deep_eq(a, b))
+        }
+        (Value::Dict(x), Value::Dict(y)) => {
+            if std::rc::Rc::ptr_eq(x, y) { return true; }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().all(|(k, (_, v))| y.get(k).is_some_and(|(_, w)| deep_eq(v, w)))
+        }
+        (Value::Instance(x), Value::Instance(y)) => {
+            if std::rc::Rc::ptr_eq(x, y) { return true; }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.class_name == y.class_name && x.fields.len() == y.fields.len()
+                && x.fields.iter().all(|(k, v)| y.fields.get(k).is_some_and(|w| deep_eq(v, w)))
+        }
+        (Value::Function(x), Value::Function(y)) => std::rc::Rc::ptr_eq(x, y),
+        (Value::NativeFunction(x, _), Value::NativeFunction(y, _)) => x == y,
+        (Value::Some(x), Value::Some(y)) | (Value::Ok(x), Value::Ok(y)) | (Value::Err(x), Value::Err(y)) => deep_eq(x, y),
+        _ => a == b,
+    }
+}
+
+/// channel() → [sender, receiver]: `tx.send(v)`, `tx.close()`, `rx.recv()`
+fn neba_channel(args: Vec<Value>) -> Result<Value, String> {
+    if !args.is_empty() { return Err("channel() takes no arguments".into()); }
+    let ch = std::rc::Rc::new(std::cell::RefCell::new(crate::scheduler::Channel::default()));
+    let pair = vec![Value::Sender(ch.clone()), Value::Receiver(ch)];
+    Ok(Value::Array(std::rc::Rc::new(std::cell::RefCell::new(pair))))
+}
+
+fn neba_memoize(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [f] if f.is_callable() =>
+            Ok(Value::Memoized(std::rc::Rc::new(Memo { func: f.clone(), cache: Default::default() }))),
+        [other] => Err(format!("memoize: expected a function, got {}", other.type_name())),
+        _ => Err("memoize(fn) requires 1 argument".into()),
+    }
+}
+
+fn neba_compose(args: Vec<Value>) -> Result<Value, String> {
+    match <[Value; 2]>::try_from(args) {
+        Ok([f, g]) if f.is_callable() && g.is_callable() => Ok(Value::Composed(std::rc::Rc::new((f, g)))),
+        Ok([f, g]) => Err(format!("compose: expected two functions, got {} and {}", f.type_name(), g.type_name())),
+        Err(_) => Err("compose(f, g) requires 2 arguments".into()),
+    }
+}
+
+fn neba_partial(mut args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() {
+        return Err("partial(fn, args...) requires at least 1 argument".into());
+    }
+    let f = args.remove(0);
+    if !f.is_callable() {
+        return Err(format!("partial: expected a function, got {}", f.type_name()));
+    }
+    Ok(Value::Partial(std::rc::Rc::new(f), args.into()))
+}
+
+/// csv_parse(text) → Array di righe, ciascuna Array di Str (RFC 4180: campi tra
+/// virgolette con `""` per la virgoletta letterale, virgole e a capo ammessi dentro)
+fn neba_csv_parse(args: Vec<Value>) -> Result<Value, String> {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    let text = match args.as_slice() {
+        [v] if text_of(v).is_some() => v.to_string(),
+        _ => return Err("csv_parse(text) requires a Str".into()),
+    };
+    let row_value = |row: Vec<String>| Value::Array(Rc::new(RefCell::new(row.into_iter().map(Value::Str).collect())));
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    // `pending`: c'è una riga iniziata da chiudere a fine testo (niente riga vuota dopo l'ultimo a capo)
+    let mut pending = false;
+    while let Some(c) = chars.next() {
+        pending = true;
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err("csv_parse: unterminated quoted field".into()),
+                }
+            },
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(row_value(std::mem::take(&mut row)));
+                pending = false;
+            }
+            c => field.push(c),
+        }
+    }
+    if pending {
+        row.push(field);
+        rows.push(row_value(row));
+    }
+    Ok(Value::Array(Rc::new(RefCell::new(rows))))
+}
+
+/// csv_write(rows) → Str: virgolette solo sui campi che contengono `,`, `"` o a capo
+fn neba_csv_write(args: Vec<Value>) -> Result<Value, String> {
+    let rows = match args.as_slice() {
+        [Value::Array(rows)] => rows.borrow(),
+        _ => return Err("csv_write(rows) requires an Array of Arrays".into()),
+    };
+    let mut out = String::new();
+    for row in rows.iter() {
+        let Value::Array(fields) = row else {
+            return Err(format!("csv_write: expected each row to be an Array, got {}", row.type_name()));
+        };
+        let fields: Vec<String> = fields.borrow().iter().map(|f| {
+            let s = f.to_string();
+            if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s }
+        }).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    Ok(Value::Str(out))
+}
+
+/// hash(v) → Int stabile tra esecuzioni. Stessa sequenza di `Hash` delle chiavi
+/// Dict della VM, quindi stesso risultato sui due backend.
+fn neba_hash(args: Vec<Value>) -> Result<Value, String> {
+    use std::hash::{Hash, Hasher};
+    fn feed(v: &Value, state: &mut neba_runtime::StableHasher) -> Result<(), String> {
+        match v {
+            Value::Str(s)   => s.hash(state),
+            // Come la Str di un carattere, a cui è uguale
+            Value::Char(c)  => c.encode_utf8(&mut [0; 4]).hash(state),
+            Value::Int(n)   => { 0u8.hash(state); n.hash(state); }
+            Value::Float(f) => { 1u8.hash(state); neba_runtime::float_key_bits(*f).hash(state); }
+            Value::Bool(b)  => { 2u8.hash(state); b.hash(state); }
+            Value::None     => 4u8.hash(state),
+            Value::Some(x)  => { 10u8.hash(state); feed(x, state)?; }
+            Value::Ok(x)    => { 11u8.hash(state); feed(x, state)?; }
+            Value::Err(x)   => { 12u8.hash(state); feed(x, state)?; }
+            other => return Err(format!("hash: unhashable type {}", other.type_name())),
+        }
+        Ok(())
+    }
+    match args.as_slice() {
+        [v] => {
+            let mut state = neba_runtime::StableHasher::default();
+            feed(v, &mut state)?;
+            Ok(Value::Int(state.finish() as i64))
+        }
+        _ => Err("hash(value) requires 1 argument".into()),
+    }
+}
+
+/// sha256_hex(s) → digest SHA-256 dei byte UTF-8 di `s`, in esadecimale
+fn neba_sha256_hex(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [v] => match text_of(v) {
+            Some(s) => Ok(Value::Str(neba_runtime::sha256_hex(s.as_bytes()))),
+            None => Err("sha256_hex(text) requires a Str".into()),
+        },
+        _ => Err("sha256_hex(text) requires a Str".into()),
+    }
+}
+
+fn neba_to_json(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [v] => crate::json::to_json(v).map(Value::Str),
+        _ => Err("to_json(v) requires 1 argument".into()),
+    }
+}
+
+fn neba_from_json(args: Vec<Value>) -> Result<Value, String> {
+    match args.as_slice() {
+        [v] => match text_of(v) {
+            Some(s) => Ok(match crate::json::from_json(&s) {
+                Ok(v)  => Value::Ok(Box::new(v)),
+                Err(e) => Value::Err(Box::new(Value::Str(e))),
+            }),
+            None => Err("from_json(text) requires a Str".into()),
+        },
+        _ => Err("from_json(text) requires a Str".into()),
+    }
+}
+
+/// path_join(a, b, ...) → Str: separatore della piattaforma; un componente
+/// assoluto sostituisce i precedenti (come `PathBuf::push`)
+fn neba_path_join(args: Vec<Value>) -> Result<Value, String> {
+    if args.is_empty() { return Err("path_join(a, b, ...) requires at least 1 argument".into()); }
+    let mut path = std::path::PathBuf::new();
+    for a in &args {
+        match text_of(a) {
+            Some(s) => path.push(&*s),
+            None => return Err(format!("path_join: expected Str, got {}", a.type_name())),
+        }
+    }
+    Ok(Value::Str(path.to_string_lossy().into_owned()))
+}
+
+/// Testo di una Str, o di un Char come Str del suo carattere.
+fn text_of(v: &Value) -> Option<std::borrow::Cow<'_, str>> {
+    match v {
+        Value::Str(s)  => Some(std::borrow::Cow::Borrowed(s.as_str())),
+        Value::Char(c) => Some(std::borrow::Cow::Owned(c.to_string())),
+        _ => None,
+    }
+}
+
+fn path_arg(args: &[Value], name: &str) -> Result<std::path::PathBuf, String> {
+    match args {
+        [v] => text_of(v).map(|s| std::path::PathBuf::from(&*s)),
+        _ => None,
+    }.ok_or_else(|| format!("{}(path) requires a Str", name))
+}
+
+/// basename(p) → ultimo componente, "" se non c'è (es. "/" o "..")
+fn neba_basename(args: Vec<Value>) -> Result<Value, String> {
+    let path = path_arg(&args, "basename")?;
+    let name = path.file_name();
+    Ok(Value::Str(name.map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()))
+}
+
+/// dirname(p) → percorso senza l'ultimo componente, "" se non c'è
+fn neba_dirname(args: Vec<Value>) -> Result<Value, String> {
+    let path = path_arg(&args, "dirname")?;
+    let parent = path.parent();
+    Ok(Value::Str(parent.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default()))
+}
+
+fn neba_exists(args: Vec<Value>) -> Result<Value, String> {
+    Ok(Value::Bool(path_arg(&args, "exists")?.exists()))
+}
+
+fn str_array(items: Vec<String>) -> Value {
+    Value::Array(std::rc::Rc::new(std::cell::RefCell::new(items.into_iter().map(Value::Str).collect())))
+}
+
+/// list_dir(path) → nomi delle voci in ordine alfabetico, o `Err(msg)`
+fn neba_list_dir(args: Vec<Value>) -> Result<Value, String> {
+    let dir = path_arg(&args, "list_dir")?;
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(e) => return Ok(Value::Err(Box::new(Value::Str(format!("list_dir: cannot read '{}': {}", dir.display(), e))))),
+    };
+    let mut names: Vec<String> = entries.filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    Ok(str_array(names))
+}
+
+/// glob(pattern) → percorsi esistenti che corrispondono, ordinati. Per componente:
+/// `*`, `?`, `[abc]`/`[a-z]`/`[!...]`; `**` da solo è zero o più directory.
+/// Pattern non valido → `Err(msg)`.
+fn neba_glob(args: Vec<Value>) -> Result<Value, String> {
+    use std::path::{Component, PathBuf};
+    let pattern = path_arg(&args, "glob")?;
+    let mut bases = vec![PathBuf::new()];
+    for comp in pattern.components() {
+        let Component::Normal(part) = comp else {
+            for b in &mut bases { b.push(comp); }
+            continue;
+        };
+        let part: Vec<char> = part.to_string_lossy().chars().collect();
+        if part == ['*', '*'] {
+            let mut all = Vec::new();
+            for b in bases { collect_dirs(b, &mut all); }
+            bases = all;
+        } else if part.iter().any(|c| matches!(c, '*' | '?' | '[')) {
+            if let Err(e) = glob_check(&part) {
+                return Ok(Value::Err(Box::new(Value::Str(e))));
+            }
+            let mut next = Vec::new();
+            for b in &bases {
+                let Ok(entries) = std::fs::read_dir(if b.as_os_str().is_empty() { std::path::Path::new(".") } else { b }) else { continue };
+                for e in entries.filter_map(|e| e.ok()) {
+                    let name: Vec<char> = e.file_name().to_string_lossy().chars().collect();
+                    if glob_component(&part, &name) { next.push(b.join(e.file_name())); }
+                }
+            }
+            bases = next;
+        } else {
+            for b in &mut bases { b.push(comp); }
+        }
+    }
+    let mut found: Vec<String> = bases.into_iter()
+        .filter(|p| !p.as_os_str().is_empty() && p.exists())
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    found.sort();
+    found.dedup();
+    Ok(str_array(found))
+}
+
+/// `dir` e tutte le sue sottodirectory (per `**`).
+fn collect_dirs(dir: std::path::PathBuf, out: &mut Vec<std::path::PathBuf>) {
+    let read = if dir.as_os_str().is_empty() { std::path::PathBuf::from(".") } else { dir.clone() };
+    let subdirs: Vec<_> = std::fs::read_dir(read).into_iter().flatten().filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| dir.join(e.file_name()))
+        .collect();
+    out.push(dir);
+    for d in subdirs { collect_dirs(d, out); }
+}
+
+/// Fine della classe `[...]` che inizia in `class` (dopo `[` ed eventuale `!`):
+/// un `]` subito dopo l'apertura è un carattere della classe.
+fn class_end(class: &[char]) -> Option<usize> {
+    class.iter().skip(1).position(|&c| c == ']').map(|i| i + 1)
+}
+
+/// Confronta un componente del pattern (già validato da `glob_check`) con un nome.
+fn glob_component(pat: &[char], name: &[char]) -> bool {
+    match pat.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_component(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_component(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let negate = rest.first() == Some(&'!');
+            let class = &rest[negate as usize..];
+            let Some(end) = class_end(class) else { return false };
+            let Some((&c, name_rest)) = name.split_first() else { return false };
+            let (items, mut hit, mut i) = (&class[..end], false, 0);
+            while i < items.len() {
+                if i + 2 < items.len() && items[i + 1] == '-' {
+                    hit |= items[i] <= c && c <= items[i + 2];
+                    i += 3;
+                } else {
+                    hit |= items[i] == c;
+                    i += 1;
+                }
+            }
+            hit != negate && glob_component(&class[end + 1..], name_rest)
+        }
+        Some((&p, rest)) => name.first() == Some(&p) && glob_component(rest, &name[1..]),
+    }
+}
+
+fn glob_check(pat: &[char]) -> Result<(), String> {
+    let mut i = 0;
+    while i < pat.len() {
+        if pat[i] == '[' {
+            let start = i + 1 + (pat.get(i + 1) == Some(&'!')) as usize;
+            let end = class_end(&pat[start..]).ok_or("glob: unclosed '[' in pattern")?;
+            i = start + end;
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+fn neba_intercepted(_: Vec<Value>) -> Result<Value, String> {
+    Err("builtin should have been intercepted by Interpreter::call".into())
+}
+
+/// ok_or(opt, err) — `Some(x)` → `Ok(x)`, `None` → `Err(err)`
+fn neba_ok_or(args: Vec<Value>) -> Result<Value, String> {
+    match <[Value; 2]>::try_from(args) {
+        Ok([Value::Some(x), _]) => Ok(Value::Ok(x)),
+        Ok([Value::None, err])  => Ok(Value::Err(Box::new(err))),
+        Ok([v, _]) => Err(format!("ok_or: expected Option, got {}", v.type_name())),
+        Err(_) => Err("ok_or(opt, err) requires 2 arguments".into()),
+    }
+}
+
+/// Durata di `sleep(ms)`: Int o Float non negativo, in millisecondi.
+pub(crate) fn sleep_duration(args: &[Value]) -> Result<std::time::Duration, String> {
+    let ms = match args {
+        [Value::Int(n)] if *n >= 0 => *n as f64,
+        [Value::Float(f)] if *f >= 0.0 && f.is_finite() => *f,
+        _ => return Err("sleep(ms) requires a non-negative number".into()),
+    };
+    Ok(std::time::Duration::from_secs_f64(ms / 1000.0))
+}
+
+/// sleep(ms) — blocca il thread
+fn neba_sleep(args: Vec<Value>) -> Result<Value, String> {
+    std::thread::sleep(sleep_duration(&args)?);
+    Ok(Value::None)
+}
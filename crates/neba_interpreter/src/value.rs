@@ -2,9 +2,31 @@ use std::fmt;
 use std::collections::HashMap;
 use std::cell::RefCell;
 use std::rc::Rc;
+use indexmap::IndexMap;
 
-use neba_parser::ast::{Param, Stmt};
+use neba_parser::ast::{Contracts, Param, Stmt};
 use crate::environment::Env;
+use crate::scheduler::Channel;
+
+// Array congelati con freeze(): registro per identità come nella VM.
+// Il Weak tiene viva l'allocazione, così l'indirizzo non viene riusato.
+thread_local! {
+    static FROZEN: RefCell<HashMap<usize, std::rc::Weak<RefCell<Vec<Value>>>>> = RefCell::new(HashMap::new());
+}
+
+pub fn freeze_array(arr: &Rc<RefCell<Vec<Value>>>) {
+    FROZEN.with(|f| {
+        let mut f = f.borrow_mut();
+        f.retain(|_, w| w.strong_count() > 0);
+        f.insert(Rc::as_ptr(arr) as usize, Rc::downgrade(arr));
+    });
+}
+
+/// Errore se l'array è congelato; `op` è il nome dell'operazione per il messaggio.
+pub fn check_mutable(arr: &Rc<RefCell<Vec<Value>>>, op: &str) -> Result<(), String> {
+    let frozen = FROZEN.with(|f| f.borrow().contains_key(&(Rc::as_ptr(arr) as usize)));
+    if frozen { Err(format!("{}: cannot mutate a frozen Array", op)) } else { Ok(()) }
+}
 
 /// Il tipo runtime di Neba.
 /// Tutti i valori sono immutabili dal punto di vista di Value,
@@ -16,6 +38,8 @@ pub enum Value {
     Float(f64),
     Bool(bool),
     Str(String),
+    // `'a'`: uguale alla Str di un solo carattere
+    Char(char),
 
     // Option
     Some(Box<Value>),
@@ -28,6 +52,12 @@ pub enum Value {
     // Array (mutabile tramite RefCell)
     Array(Rc<RefCell<Vec<Value>>>),
 
+    // Tupla `(a, b)`: lunghezza fissa, immutabile
+    Tuple(Rc<[Value]>),
+
+    // Dict `{k: v}`, in ordine di inserimento (vedi `dict_key`)
+    Dict(Rc<RefCell<DictMap>>),
+
     // Funzione definita dall'utente
     Function(Rc<FunctionDef>),
 
@@ -37,11 +67,27 @@ pub enum Value {
     // Istanza di classe
     Instance(Rc<RefCell<Instance>>),
 
+    // Handle di un task creato con `spawn` (indice nello scheduler)
+    Task(usize),
+
+    // Estremità di un canale creato con `channel()`
+    Sender(Rc<RefCell<Channel>>),
+    Receiver(Rc<RefCell<Channel>>),
+
+    // Funzione avvolta da `memoize(f)`: risultati in cache per argomenti
+    Memoized(Rc<Memo>),
+    // `compose(f, g)`: chiamata come `f(g(args))`
+    Composed(Rc<(Value, Value)>),
+    // `partial(f, a, b)`: chiamata come `f(a, b, args)`
+    Partial(Rc<Value>, Rc<[Value]>),
+
     // Valore sentinella usato da return/break/continue
     // (non esposto all'utente, solo per il flow control interno)
     __Return(Box<Value>),
     __Break,
     __Continue,
+    // `return f(args)` in coda: la chiamata la esegue il trampolino di `call_fn`
+    __TailCall(Rc<FunctionDef>, Vec<Value>),
 }
 
 
@@ -52,17 +98,27 @@ impl std::fmt::Debug for Value {
             Value::Float(n)  => write!(f, "Float({})", n),
             Value::Bool(b)   => write!(f, "Bool({})", b),
             Value::Str(s)    => write!(f, "Str({:?})", s),
+            Value::Char(c)   => write!(f, "Char({:?})", c),
             Value::None      => write!(f, "None"),
             Value::Some(v)   => write!(f, "Some({:?})", v),
             Value::Ok(v)     => write!(f, "Ok({:?})", v),
             Value::Err(v)    => write!(f, "Err({:?})", v),
             Value::Array(a)  => write!(f, "Array({:?})", a.borrow()),
+            Value::Tuple(t)  => write!(f, "Tuple({:?})", t),
+            Value::Dict(d)   => write!(f, "Dict({:?})", d.borrow().values().collect::<Vec<_>>()),
             Value::Function(d) => write!(f, "Function({})", d.name),
             Value::NativeFunction(n, _) => write!(f, "NativeFunction({})", n),
             Value::Instance(i) => write!(f, "Instance({})", i.borrow().class_name),
+            Value::Task(id)    => write!(f, "Task({})", id),
+            Value::Sender(_)   => write!(f, "Sender"),
+            Value::Receiver(_) => write!(f, "Receiver"),
+            Value::Memoized(m) => write!(f, "Memoized({:?})", m.func),
+            Value::Composed(fg) => write!(f, "Composed({:?}, {:?})", fg.0, fg.1),
+            Value::Partial(func, args) => write!(f, "Partial({:?}, {:?})", func, args),
             Value::__Return(v) => write!(f, "__Return({:?})", v),
             Value::__Break     => write!(f, "__Break"),
             Value::__Continue  => write!(f, "__Continue"),
+            Value::__TailCall(d, _) => write!(f, "__TailCall({})", d.name),
         }
     }
 }
@@ -72,9 +128,77 @@ impl std::fmt::Debug for Value {
 pub struct FunctionDef {
     pub name: String,
     pub params: Vec<Param>,
-    pub body: Vec<Stmt>,
+    /// Condiviso con l'AST: ridefinire la funzione (es. in un loop) costa un incremento di refcount
+    pub body: Rc<[Stmt]>,
     pub closure: Env,  // cattura l'environment al momento della definizione
     pub is_async: bool,
+    /// Verificati solo con `Interpreter::set_contracts(true)`
+    pub contracts: Rc<Contracts>,
+}
+
+/// Contenuto di un Dict: chiave canonica → (chiave originale, valore).
+pub type DictMap = IndexMap<String, (Value, Value)>;
+
+/// Chiave canonica di un Dict. Come per `hash` sono ammessi solo valori con
+/// identità data dal contenuto; un Char e la Str dello stesso carattere sono
+/// la stessa chiave.
+pub fn dict_key(v: &Value) -> Result<String, String> {
+    Ok(match v {
+        Value::Str(s)   => format!("s{}", s),
+        Value::Char(c)  => format!("s{}", c),
+        Value::Int(n)   => format!("i{}", n),
+        Value::Float(f) => format!("f{}", neba_runtime::float_key_bits(*f)),
+        Value::Bool(b)  => format!("b{}", b),
+        Value::None     => "n".to_string(),
+        Value::Some(x)  => format!("S({})", dict_key(x)?),
+        Value::Ok(x)    => format!("O({})", dict_key(x)?),
+        Value::Err(x)   => format!("E({})", dict_key(x)?),
+        Value::Tuple(t) => format!("T({})", t.iter().map(dict_key).collect::<Result<Vec<_>, _>>()?.join(",")),
+        other => return Err(format!("unhashable Dict key of type {}", other.type_name())),
+    })
+}
+
+/// Stato di `memoize(f)`: la funzione avvolta e i risultati già calcolati.
+#[derive(Debug)]
+pub struct Memo {
+    pub func: Value,
+    /// Chiave: vedi `Memo::key`
+    pub cache: RefCell<HashMap<String, Value>>,
+}
+
+impl Memo {
+    /// Chiave di cache per `args`: solo valori "dati" (primitivi, Option/Result,
+    /// Array, Tuple) hanno un'identità stabile; funzioni, istanze e task danno errore.
+    pub fn key(args: &[Value]) -> Result<String, String> {
+        fn write_key(out: &mut String, v: &Value) -> Result<(), String> {
+            match v {
+                Value::Int(_) | Value::Float(_) | Value::Bool(_) | Value::Str(_) | Value::Char(_) | Value::None => {
+                    out.push_str(&format!("{:?}", v));
+                }
+                Value::Some(x) | Value::Ok(x) | Value::Err(x) => {
+                    out.push_str(v.type_name());
+                    out.push('(');
+                    write_key(out, x)?;
+                    out.push(')');
+                }
+                Value::Array(a) => {
+                    out.push('[');
+                    for x in a.borrow().iter() { write_key(out, x)?; out.push(','); }
+                    out.push(']');
+                }
+                Value::Tuple(t) => {
+                    out.push('(');
+                    for x in t.iter() { write_key(out, x)?; out.push(','); }
+                    out.push(')');
+                }
+                other => return Err(format!("memoize: unhashable argument of type {}", other.type_name())),
+            }
+            Ok(())
+        }
+        let mut out = String::new();
+        for a in args { write_key(&mut out, a)?; out.push(';'); }
+        Ok(out)
+    }
 }
 
 /// Istanza di una classe.
@@ -109,24 +233,73 @@ impl fmt::Display for Value {
             }
             Value::Bool(b)   => write!(f, "{}", if *b { "true" } else { "false" }),
             Value::Str(s)    => write!(f, "{}", s),
+            Value::Char(c)   => write!(f, "{}", c),
             Value::None      => write!(f, "None"),
             Value::Some(v)   => write!(f, "Some({})", v),
             Value::Ok(v)     => write!(f, "Ok({})", v),
             Value::Err(v)    => write!(f, "Err({})", v),
-            Value::Array(arr)=> {
+            Value::Array(arr)=> fmt_guarded(f, Rc::as_ptr(arr) as usize, "[...]", |f| {
                 let items: Vec<String> = arr.borrow().iter().map(|v| format!("{}", v)).collect();
                 write!(f, "[{}]", items.join(", "))
+            }),
+            Value::Tuple(t) if t.len() == 1 => write!(f, "({},)", t[0]),
+            Value::Tuple(t)  => {
+                let items: Vec<String> = t.iter().map(|v| format!("{}", v)).collect();
+                write!(f, "({})", items.join(", "))
             }
+            Value::Dict(d)   => fmt_guarded(f, Rc::as_ptr(d) as usize, "{...}", |f| {
+                let items: Vec<String> = d.borrow().values().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", items.join(", "))
+            }),
             Value::Function(def) => write!(f, "<fn {}>", def.name),
             Value::NativeFunction(name, _) => write!(f, "<built-in fn {}>", name),
-            Value::Instance(inst) => write!(f, "<{} instance>", inst.borrow().class_name),
+            Value::Task(id) => write!(f, "<task {}>", id),
+            Value::Sender(_)   => write!(f, "<sender>"),
+            Value::Receiver(_) => write!(f, "<receiver>"),
+            Value::Memoized(m) => write!(f, "<memoized {}>", m.func),
+            Value::Composed(fg) => write!(f, "<compose {} {}>", fg.0, fg.1),
+            Value::Partial(func, _) => write!(f, "<partial {}>", func),
+            Value::Instance(rc) => fmt_guarded(f, Rc::as_ptr(rc) as usize, &format!("{}(...)", rc.borrow().class_name), |f| {
+                // Fallback senza `__str__`: `Point(x=1, y=2)`, campi in ordine alfabetico
+                let inst = rc.borrow();
+                let mut fields: Vec<_> = inst.fields.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                write!(f, "{}(", inst.class_name)?;
+                for (n, (k, v)) in fields.into_iter().enumerate() {
+                    if n > 0 { write!(f, ", ")?; }
+                    write!(f, "{}={}", k, v)?;
+                }
+                write!(f, ")")
+            }),
             Value::__Return(v) => write!(f, "{}", v),
             Value::__Break    => write!(f, "<break>"),
             Value::__Continue => write!(f, "<continue>"),
+            Value::__TailCall(d, _) => write!(f, "<tail call {}>", d.name),
         }
     }
 }
 
+// Contenitori in corso di stampa, per stampare `[...]` sui cicli (`push(a, a)`).
+thread_local! {
+    static DISPLAY_PATH: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Esegue `body` con `ptr` sul percorso di stampa; se `ptr` è già presente
+/// (ciclo) scrive `marker`.
+fn fmt_guarded(
+    f: &mut fmt::Formatter<'_>, ptr: usize, marker: &str,
+    body: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    let cyclic = DISPLAY_PATH.with(|p| {
+        let mut p = p.borrow_mut();
+        if p.contains(&ptr) { true } else { p.push(ptr); false }
+    });
+    if cyclic { return write!(f, "{}", marker); }
+    let r = body(f);
+    DISPLAY_PATH.with(|p| { p.borrow_mut().pop(); });
+    r
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -136,11 +309,27 @@ impl PartialEq for Value {
             (Value::Float(a), Value::Int(b))   => *a == (*b as f64),
             (Value::Bool(a),  Value::Bool(b))  => a == b,
             (Value::Str(a),   Value::Str(b))   => a == b,
+            (Value::Char(a),  Value::Char(b))  => a == b,
+            (Value::Char(c),  Value::Str(s)) | (Value::Str(s), Value::Char(c)) => {
+                let mut chars = s.chars();
+                chars.next() == Some(*c) && chars.next().is_none()
+            }
             (Value::None,     Value::None)     => true,
             (Value::Some(a),  Value::Some(b))  => a == b,
             (Value::Ok(a),    Value::Ok(b))    => a == b,
             (Value::Err(a),   Value::Err(b))   => a == b,
             (Value::Array(a), Value::Array(b)) => *a.borrow() == *b.borrow(),
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            // Come nella VM: stesse coppie, l'ordine non conta
+            (Value::Dict(a),  Value::Dict(b))  => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().all(|(k, (_, v))| b.get(k).is_some_and(|(_, w)| v == w))
+            }
+            (Value::Task(a),  Value::Task(b))  => a == b,
+            (Value::Sender(a), Value::Sender(b)) | (Value::Receiver(a), Value::Receiver(b)) => Rc::ptr_eq(a, b),
+            (Value::Memoized(a), Value::Memoized(b)) => Rc::ptr_eq(a, b),
+            (Value::Composed(a), Value::Composed(b)) => Rc::ptr_eq(a, b),
+            (Value::Partial(a, _), Value::Partial(b, _)) => Rc::ptr_eq(a, b),
             _ => false,
         }
     }
@@ -154,6 +343,9 @@ impl PartialOrd for Value {
             (Value::Int(a),   Value::Float(b)) => (*a as f64).partial_cmp(b),
             (Value::Float(a), Value::Int(b))   => a.partial_cmp(&(*b as f64)),
             (Value::Str(a),   Value::Str(b))   => a.partial_cmp(b),
+            (Value::Char(a),  Value::Char(b))  => a.partial_cmp(b),
+            (Value::Char(a),  Value::Str(b))   => (*a.encode_utf8(&mut [0; 4])).partial_cmp(b.as_str()),
+            (Value::Str(a),   Value::Char(b))  => a.as_str().partial_cmp(&*b.encode_utf8(&mut [0; 4])),
             _ => None,
         }
     }
@@ -170,10 +362,17 @@ impl Value {
             Value::None      => false,
             Value::Some(_)   => true,
             Value::Array(a)  => !a.borrow().is_empty(),
+            Value::Dict(d)   => !d.borrow().is_empty(),
             _                => true,
         }
     }
 
+    /// Un Char vale come la Str del suo carattere in operatori, indici e builtin;
+    /// resta distinto solo per `is` e `typeof`.
+    pub fn char_as_str(self) -> Value {
+        match self { Value::Char(c) => Value::Str(c.to_string()), v => v }
+    }
+
     /// Restituisce il nome del tipo (per messaggi di errore)
     pub fn type_name(&self) -> &'static str {
         match self {
@@ -181,20 +380,34 @@ impl Value {
             Value::Float(_)          => "Float",
             Value::Bool(_)           => "Bool",
             Value::Str(_)            => "Str",
+            Value::Char(_)           => "Char",
             Value::None              => "None",
             Value::Some(_)           => "Some",
             Value::Ok(_)             => "Ok",
             Value::Err(_)            => "Err",
             Value::Array(_)          => "Array",
+            Value::Tuple(_)          => "Tuple",
+            Value::Dict(_)           => "Dict",
             Value::Function(_)       => "Function",
             Value::NativeFunction(_, _) => "NativeFunction",
             Value::Instance(_)       => "Instance",
+            Value::Task(_)           => "Task",
+            Value::Sender(_)         => "Sender",
+            Value::Receiver(_)       => "Receiver",
+            Value::Memoized(_) | Value::Composed(_) | Value::Partial(..) => "Function",
             Value::__Return(_)       => "__Return",
             Value::__Break           => "__Break",
             Value::__Continue        => "__Continue",
+            Value::__TailCall(_, _)  => "__TailCall",
         }
     }
 
+    /// Funzioni utente e native, e quelle costruite da memoize/compose/partial.
+    pub fn is_callable(&self) -> bool {
+        matches!(self, Value::Function(_) | Value::NativeFunction(..)
+            | Value::Memoized(_) | Value::Composed(_) | Value::Partial(..))
+    }
+
     /// Tenta la conversione a Int (per operazioni miste)
     pub fn as_float(&self) -> Option<f64> {
         match self {
@@ -204,3 +417,54 @@ impl Value {
         }
     }
 }
+
+// ── Conversioni Rust ↔ Value (per l'embedding) ───────────────────────────
+
+impl From<i64> for Value { fn from(n: i64) -> Self { Value::Int(n) } }
+impl From<f64> for Value { fn from(f: f64) -> Self { Value::Float(f) } }
+impl From<bool> for Value { fn from(b: bool) -> Self { Value::Bool(b) } }
+impl From<String> for Value { fn from(s: String) -> Self { Value::Str(s) } }
+impl From<&str> for Value { fn from(s: &str) -> Self { Value::Str(s.to_string()) } }
+impl From<Vec<Value>> for Value { fn from(v: Vec<Value>) -> Self { Value::Array(Rc::new(RefCell::new(v))) } }
+
+/// Messaggio d'errore comune dei `TryFrom<Value>`
+fn expected(ty: &str, got: &Value) -> String {
+    format!("expected {}, got {}", ty, got.type_name())
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Int(n) => Ok(n), other => Err(expected("Int", &other)) }
+    }
+}
+
+/// Accetta anche un Int, come le operazioni aritmetiche miste.
+impl TryFrom<Value> for f64 {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        v.as_float().ok_or_else(|| expected("Float", &v))
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Bool(b) => Ok(b), other => Err(expected("Bool", &other)) }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Str(s) => Ok(s), other => Err(expected("Str", &other)) }
+    }
+}
+
+/// Copia superficiale degli elementi dell'array.
+impl TryFrom<Value> for Vec<Value> {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Array(a) => Ok(a.borrow().clone()), other => Err(expected("Array", &other)) }
+    }
+}
@@ -0,0 +1,15 @@
+//! Dump del flusso di token (usato da `neba --tokens`).
+
+use crate::token::Token;
+
+/// Formatta un token per riga: `linea:colonna  Kind  "lexeme"  [start..end]`.
+pub fn dump_tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for tok in tokens {
+        let pos = format!("{}:{}", tok.span.line, tok.span.column);
+        let kind = format!("{:?}", tok.kind);
+        out.push_str(&format!("{:<8} {:<28} {:<16} [{}..{}]\n",
+            pos, kind, format!("{:?}", tok.lexeme), tok.span.start, tok.span.end));
+    }
+    out
+}
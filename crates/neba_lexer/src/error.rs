@@ -6,26 +6,86 @@ pub enum LexError {
     UnexpectedCharacter { ch: char, span: Span },
     UnterminatedString { span: Span },
     InvalidEscapeSequence { seq: String, span: Span },
+    /// `\u{...}` ben formato ma fuori da 0..=0x10FFFF o surrogato
+    InvalidUnicodeEscape { seq: String, span: Span },
+    /// `#[` senza il `]#` di chiusura; lo span è quello dell'apertura
+    UnterminatedComment { span: Span },
+    /// `'...'` vuoto o con più di un carattere (per le stringhe servono le `"`)
+    InvalidCharLiteral { content: String, span: Span },
     InvalidNumber { raw: String, span: Span },
     InconsistentIndentation { span: Span },
     TabSpaceMixing { span: Span },
+    IndentTooDeep { max: usize, span: Span },
+    /// Tab dopo l'inizio della riga (allineamento): solo un warning, il tab vale come spazio
+    TabInLine { span: Span },
 }
 
 impl fmt::Display for LexError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LexError::UnexpectedCharacter { ch, span } =>
-                write!(f, "[LexError] Unexpected character '{}' at line {}, column {}", ch, span.line, span.column),
+                write!(f, "[LexError {}] Unexpected character '{}' at line {}, column {}", self.code(), ch, span.line, span.column),
             LexError::UnterminatedString { span } =>
-                write!(f, "[LexError] Unterminated string at line {}, column {}", span.line, span.column),
+                write!(f, "[LexError {}] Unterminated string at line {}, column {}", self.code(), span.line, span.column),
             LexError::InvalidEscapeSequence { seq, span } =>
-                write!(f, "[LexError] Invalid escape sequence '{}' at line {}, column {}", seq, span.line, span.column),
+                write!(f, "[LexError {}] Invalid escape sequence '{}' at line {}, column {}", self.code(), seq, span.line, span.column),
+            LexError::InvalidUnicodeEscape { seq, span } =>
+                write!(f, "[LexError {}] Invalid unicode escape '{}' at line {}, column {}", self.code(), seq, span.line, span.column),
+            LexError::UnterminatedComment { span } =>
+                write!(f, "[LexError {}] Unterminated block comment opened at line {}, column {}", self.code(), span.line, span.column),
+            LexError::InvalidCharLiteral { content, span } =>
+                write!(f, "[LexError {}] Invalid character literal '{}' at line {}, column {} (use double quotes for strings)", self.code(), content, span.line, span.column),
             LexError::InvalidNumber { raw, span } =>
-                write!(f, "[LexError] Invalid number '{}' at line {}, column {}", raw, span.line, span.column),
+                write!(f, "[LexError {}] Invalid number '{}' at line {}, column {}", self.code(), raw, span.line, span.column),
             LexError::InconsistentIndentation { span } =>
-                write!(f, "[LexError] Inconsistent indentation at line {}", span.line),
+                write!(f, "[LexError {}] Inconsistent indentation at line {}", self.code(), span.line),
             LexError::TabSpaceMixing { span } =>
-                write!(f, "[LexError] Mixed tabs and spaces at line {}", span.line),
+                write!(f, "[LexError {}] Mixed tabs and spaces at line {}", self.code(), span.line),
+            LexError::IndentTooDeep { max, span } =>
+                write!(f, "[LexError {}] Indentation nested too deeply (max {} levels) at line {}", self.code(), max, span.line),
+            LexError::TabInLine { span } =>
+                write!(f, "[LexWarning {}] Tab used for alignment at line {}, column {}", self.code(), span.line, span.column),
+        }
+    }
+}
+
+impl LexError {
+    /// Codice stabile dell'errore (`L0001`…), citato nel messaggio.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexError::UnexpectedCharacter { .. }     => "L0001",
+            LexError::UnterminatedString { .. }      => "L0002",
+            LexError::InvalidEscapeSequence { .. }   => "L0003",
+            LexError::InvalidNumber { .. }           => "L0004",
+            LexError::InconsistentIndentation { .. } => "L0005",
+            LexError::TabSpaceMixing { .. }          => "L0006",
+            LexError::IndentTooDeep { .. }           => "L0007",
+            LexError::InvalidUnicodeEscape { .. }    => "L0008",
+            LexError::UnterminatedComment { .. }     => "L0009",
+            LexError::TabInLine { .. }               => "L0010",
+            LexError::InvalidCharLiteral { .. }      => "L0011",
+        }
+    }
+
+    /// Diagnostico non bloccante: il lexer lo mette in `Lexer::warnings`, non in `errors`.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, LexError::TabInLine { .. })
+    }
+
+    /// Posizione nel sorgente a cui si riferisce l'errore.
+    pub fn span(&self) -> &Span {
+        match self {
+            LexError::UnexpectedCharacter { span, .. }
+            | LexError::UnterminatedString { span }
+            | LexError::InvalidEscapeSequence { span, .. }
+            | LexError::InvalidUnicodeEscape { span, .. }
+            | LexError::UnterminatedComment { span }
+            | LexError::InvalidCharLiteral { span, .. }
+            | LexError::InvalidNumber { span, .. }
+            | LexError::InconsistentIndentation { span }
+            | LexError::TabSpaceMixing { span }
+            | LexError::TabInLine { span }
+            | LexError::IndentTooDeep { span, .. } => span,
         }
     }
 }
@@ -1,5 +1,9 @@
 use crate::error::{LexError, LexResult};
 use crate::token::{lookup_keyword, Span, Token, TokenKind};
+use std::collections::VecDeque;
+
+/// Numero massimo di livelli di indentazione aperti contemporaneamente.
+pub const MAX_INDENT_DEPTH: usize = 256;
 
 pub struct Lexer {
     source: Vec<char>,
@@ -7,8 +11,16 @@ pub struct Lexer {
     line: usize,
     column: usize,
     indent_stack: Vec<usize>,
-    pending: Vec<Token>,
+    pending: VecDeque<Token>,
+    /// L'ultimo token emesso chiude una riga (o non ce n'è ancora uno)
+    after_break: bool,
+    /// `Eof` già emesso: l'iteratore è esaurito
+    finished: bool,
+    /// La riga corrente prosegue la precedente (`\` a fine riga): niente indentazione
+    continuation: bool,
     pub errors: Vec<LexError>,
+    /// Diagnostici non bloccanti (`LexError::is_warning`): i token restano validi
+    pub warnings: Vec<LexError>,
 }
 
 impl Lexer {
@@ -19,8 +31,12 @@ impl Lexer {
             line: 1,
             column: 1,
             indent_stack: vec![0],
-            pending: Vec::new(),
+            pending: VecDeque::new(),
+            after_break: true,
+            finished: false,
+            continuation: false,
             errors: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -47,32 +63,61 @@ impl Lexer {
         Token::new(kind, Span::new(self.line, start_col, start, self.pos), lexeme)
     }
 
+    /// Tutti i token fino a `Eof` compreso; gli errori finiscono in `self.errors`.
     pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tokens: Vec<Token> = Vec::new();
+        let mut tokens = Vec::new();
+        while let Some(res) = self.next_token_public() {
+            match res {
+                Ok(tok) => tokens.push(tok),
+                Err(e) => self.errors.push(e),
+            }
+        }
+        tokens
+    }
+
+    /// Prossimo token (compresi `Indent`/`Dedent`), `None` dopo `Eof`.
+    /// Dopo un errore il carattere che lo ha causato diventa un token `Unknown`,
+    /// così la lettura può proseguire.
+    pub fn next_token_public(&mut self) -> Option<LexResult<Token>> {
         loop {
-            tokens.extend(self.pending.drain(..));
+            if let Some(tok) = self.pending.pop_front() {
+                self.after_break = matches!(tok.kind, TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent);
+                return Some(Ok(tok));
+            }
+            if self.finished { return None; }
             if self.pos >= self.source.len() {
-                tokens.extend(self.close_all_blocks());
-                tokens.push(Token::new(TokenKind::Eof, Span::new(self.line, self.column, self.pos, self.pos), ""));
-                break;
+                // File che termina dentro un blocco senza '\n' finale:
+                // chiude l'ultimo statement prima dei Dedent.
+                let eof_span = Span::new(self.line, self.column, self.pos, self.pos);
+                if self.indent_stack.len() > 1 && !self.after_break {
+                    self.pending.push_back(Token::new(TokenKind::Newline, eof_span.clone(), "\n"));
+                }
+                let dedents = self.close_all_blocks();
+                self.pending.extend(dedents);
+                self.pending.push_back(Token::new(TokenKind::Eof, eof_span, ""));
+                self.finished = true;
+                continue;
             }
             match self.next_token() {
-                Ok(Some(tok)) => tokens.push(tok),
+                Ok(Some(tok)) => {
+                    self.after_break = matches!(tok.kind, TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent);
+                    return Some(Ok(tok));
+                }
                 Ok(None) => {}
                 Err(e) => {
-                    self.errors.push(e);
                     let start = self.pos;
                     let col = self.column;
                     let ch = self.advance().unwrap_or('\0');
-                    tokens.push(self.make_token(TokenKind::Unknown(ch), start, col, &ch.to_string()));
+                    let tok = self.make_token(TokenKind::Unknown(ch), start, col, &ch.to_string());
+                    self.pending.push_back(tok);
+                    return Some(Err(e));
                 }
             }
         }
-        tokens
     }
 
     fn next_token(&mut self) -> LexResult<Option<Token>> {
-        if self.column == 1 {
+        if self.column == 1 && !std::mem::take(&mut self.continuation) {
             if let Some(t) = self.process_indentation()? {
                 return Ok(Some(t));
             }
@@ -86,12 +131,28 @@ impl Lexer {
             None => return Ok(None),
         };
 
-        if ch == ' ' || ch == '\t' || ch == '\r' { self.advance(); return Ok(None); }
+        if ch == '\t' {
+            while self.peek() == Some('\t') { self.advance(); }
+            self.warnings.push(LexError::TabInLine { span: Span::new(self.line, start_col, start, self.pos) });
+            return Ok(None);
+        }
+        if ch == ' ' || ch == '\r' { self.advance(); return Ok(None); }
+        if ch == '#' && self.peek_next() == Some('[') { return self.skip_block_comment().map(|_| None); }
         if ch == '#' { while self.peek() != Some('\n') && self.peek().is_some() { self.advance(); } return Ok(None); }
         if ch == '\n' {
             self.advance();
             return Ok(Some(self.make_token(TokenKind::Newline, start, start_col, "\n")));
         }
+        // `\` a fine riga (anche seguito da spazi): la riga logica continua sotto
+        if ch == '\\' {
+            let rest = self.source[self.pos + 1..].iter().position(|c| !matches!(c, ' ' | '\t' | '\r'));
+            let end = rest.map_or(self.source.len(), |n| self.pos + 1 + n);
+            if self.source.get(end).is_none_or(|&c| c == '\n') {
+                while self.pos <= end && self.advance().is_some() {}
+                self.continuation = true;
+                return Ok(None);
+            }
+        }
 
         self.advance();
 
@@ -106,7 +167,7 @@ impl Lexer {
             ';' => TokenKind::Semicolon,
             '~' => TokenKind::Tilde,
             '@' => TokenKind::At,
-            '_' if !self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') => TokenKind::Underscore,
+            '_' if !self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') => TokenKind::Underscore,
             '+' => if self.match_char('=') { TokenKind::PlusEqual } else { TokenKind::Plus },
             '-' => if self.match_char('>') { TokenKind::Arrow } else if self.match_char('=') { TokenKind::MinusEqual } else { TokenKind::Minus },
             '*' => if self.match_char('*') { TokenKind::StarStar } else if self.match_char('=') { TokenKind::StarEqual } else { TokenKind::Star },
@@ -117,7 +178,9 @@ impl Lexer {
             '<' => if self.match_char('<') { TokenKind::LessLess } else if self.match_char('=') { TokenKind::LessEqual } else { TokenKind::Less },
             '>' => if self.match_char('>') { TokenKind::GreaterGreater } else if self.match_char('=') { TokenKind::GreaterEqual } else { TokenKind::Greater },
             '&' => if self.match_char('&') { TokenKind::Ampersand2 } else { TokenKind::Ampersand },
-            '|' => if self.match_char('|') { TokenKind::Pipe2 } else { TokenKind::Pipe },
+            '|' => if self.match_char('|') { TokenKind::Pipe2 }
+                   else if self.match_char('>') { TokenKind::PipeGreater }
+                   else { TokenKind::Pipe },
             '^' => TokenKind::Caret,
             '?' => TokenKind::QuestionMark,
             ':' => if self.match_char(':') { TokenKind::ColonColon } else { TokenKind::Colon },
@@ -127,10 +190,18 @@ impl Lexer {
                     if self.match_char('=') { TokenKind::DotDotEqual } else { TokenKind::DotDot }
                 } else { TokenKind::Dot }
             },
-            '"' | '\'' => return Ok(Some(self.lex_string(ch, false, start, start_col)?)),
+            '"' => return Ok(Some(self.lex_string(ch, false, false, start, start_col)?)),
+            // `'''...'''` resta una stringa multilinea; `'x'` è un carattere
+            '\'' if self.peek() == Some('\'') && self.peek_next() == Some('\'') =>
+                return Ok(Some(self.lex_string(ch, false, false, start, start_col)?)),
+            '\'' => return Ok(Some(self.lex_char(start, start_col)?)),
             'f' if self.peek() == Some('"') || self.peek() == Some('\'') => {
                 let quote = self.advance().unwrap();
-                return Ok(Some(self.lex_string(quote, true, start, start_col)?));
+                return Ok(Some(self.lex_string(quote, true, false, start, start_col)?));
+            },
+            'r' if self.peek() == Some('"') || self.peek() == Some('\'') => {
+                let quote = self.advance().unwrap();
+                return Ok(Some(self.lex_string(quote, false, true, start, start_col)?));
             },
             c if c.is_ascii_digit() => return Ok(Some(self.lex_number(c, start, start_col)?)),
             c if c.is_alphabetic() || c == '_' => return Ok(Some(self.lex_identifier(c, start, start_col))),
@@ -157,6 +228,9 @@ impl Lexer {
         }
         let current = *self.indent_stack.last().unwrap_or(&0);
         if indent > current {
+            if self.indent_stack.len() > MAX_INDENT_DEPTH {
+                return Err(LexError::IndentTooDeep { max: MAX_INDENT_DEPTH, span: Span::new(self.line, 1, start, self.pos) });
+            }
             self.indent_stack.push(indent);
             return Ok(Some(Token::new(TokenKind::Indent, Span::new(self.line, 1, start, self.pos), "<indent>")));
         }
@@ -178,6 +252,23 @@ impl Lexer {
         Ok(None)
     }
 
+    /// Commento a blocco `#[ ... ]#`, annidabile. Vale come spazio: le righe che
+    /// attraversa non producono `Newline` né indentazione.
+    fn skip_block_comment(&mut self) -> LexResult<()> {
+        let open = Span::new(self.line, self.column, self.pos, self.pos + 2);
+        self.advance(); self.advance();
+        let mut depth = 1usize;
+        while depth > 0 {
+            match (self.peek(), self.peek_next()) {
+                (None, _) => return Err(LexError::UnterminatedComment { span: open }),
+                (Some('#'), Some('[')) => { depth += 1; self.advance(); self.advance(); }
+                (Some(']'), Some('#')) => { depth -= 1; self.advance(); self.advance(); }
+                _ => { self.advance(); }
+            }
+        }
+        Ok(())
+    }
+
     fn close_all_blocks(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
         while self.indent_stack.len() > 1 {
@@ -187,7 +278,9 @@ impl Lexer {
         tokens
     }
 
-    fn lex_string(&mut self, quote: char, is_fstring: bool, start: usize, start_col: usize) -> LexResult<Token> {
+    /// `raw` (`r"..."`): i backslash restano nel contenuto così come sono; `\"` non
+    /// chiude la stringa ma produce anch'esso i due caratteri.
+    fn lex_string(&mut self, quote: char, is_fstring: bool, raw: bool, start: usize, start_col: usize) -> LexResult<Token> {
         let triple = self.peek() == Some(quote) && self.peek_next() == Some(quote);
         if triple { self.advance(); self.advance(); }
         let mut content = String::new();
@@ -195,6 +288,14 @@ impl Lexer {
             match self.peek() {
                 None => return Err(LexError::UnterminatedString { span: Span::new(self.line, start_col, start, self.pos) }),
                 Some('\n') if !triple => return Err(LexError::UnterminatedString { span: Span::new(self.line, start_col, start, self.pos) }),
+                Some('\\') if raw => {
+                    content.push('\\');
+                    self.advance();
+                    if let Some(c @ ('\\' | '"' | '\'')) = self.peek() {
+                        content.push(c);
+                        self.advance();
+                    }
+                }
                 Some('\\') => {
                     self.advance();
                     let esc_start = self.pos;
@@ -207,6 +308,8 @@ impl Lexer {
                         Some('\'') => content.push('\''),
                         Some('"')  => content.push('"'),
                         Some('0')  => content.push('\0'),
+                        Some('u')  => content.push(self.lex_unicode_escape(esc_start, esc_col)?),
+                        Some('x')  => content.push(self.lex_hex_escape(esc_start, esc_col)?),
                         Some(c) => return Err(LexError::InvalidEscapeSequence { seq: format!("\\{}", c), span: Span::new(self.line, esc_col, esc_start, self.pos) }),
                         None => return Err(LexError::UnterminatedString { span: Span::new(self.line, start_col, start, self.pos) }),
                     }
@@ -227,6 +330,54 @@ impl Lexer {
         Ok(Token::new(kind, Span::new(self.line, start_col, start, self.pos), lexeme))
     }
 
+    /// `'a'`: stesse escape delle stringhe, ma il contenuto deve essere un solo carattere.
+    fn lex_char(&mut self, start: usize, start_col: usize) -> LexResult<Token> {
+        let tok = self.lex_string('\'', false, false, start, start_col)?;
+        let TokenKind::StringLiteral(content) = &tok.kind else { unreachable!("lex_string senza f-string") };
+        let mut chars = content.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Token { kind: TokenKind::CharLiteral(c), ..tok }),
+            _ => Err(LexError::InvalidCharLiteral { content: content.clone(), span: tok.span }),
+        }
+    }
+
+    /// `\u{1F600}`: da 1 a 6 cifre esadecimali, codice Unicode valido (no surrogati).
+    /// Chiamata dopo la `u`; `esc_start` punta al carattere dopo il backslash.
+    fn lex_unicode_escape(&mut self, esc_start: usize, esc_col: usize) -> LexResult<char> {
+        let mut digits = String::new();
+        let closed = if self.peek() == Some('{') {
+            self.advance();
+            while let Some(c) = self.peek().filter(|c| c.is_ascii_hexdigit()) {
+                digits.push(c);
+                self.advance();
+            }
+            self.peek() == Some('}') && { self.advance(); true }
+        } else { false };
+        let seq = || format!("\\{}", self.source[esc_start..self.pos].iter().collect::<String>());
+        let span = Span::new(self.line, esc_col, esc_start, self.pos);
+        if !closed || digits.is_empty() || digits.len() > 6 {
+            return Err(LexError::InvalidEscapeSequence { seq: seq(), span });
+        }
+        u32::from_str_radix(&digits, 16).ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| LexError::InvalidUnicodeEscape { seq: seq(), span })
+    }
+
+    /// `\xNN`: esattamente due cifre esadecimali, il carattere U+0000..U+00FF.
+    fn lex_hex_escape(&mut self, esc_start: usize, esc_col: usize) -> LexResult<char> {
+        let mut value = 0;
+        for _ in 0..2 {
+            match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(d) => { value = value * 16 + d; self.advance(); }
+                None => {
+                    let seq = format!("\\{}", self.source[esc_start..self.pos].iter().collect::<String>());
+                    return Err(LexError::InvalidEscapeSequence { seq, span: Span::new(self.line, esc_col, esc_start, self.pos) });
+                }
+            }
+        }
+        Ok(char::from_u32(value).unwrap())
+    }
+
     fn lex_number(&mut self, first: char, start: usize, start_col: usize) -> LexResult<Token> {
         let mut raw = String::new();
         raw.push(first);
@@ -235,7 +386,7 @@ impl Lexer {
             match self.peek() {
                 Some('x') | Some('X') => {
                     raw.push(self.advance().unwrap());
-                    while self.peek().map_or(false, |c| c.is_ascii_hexdigit() || c == '_') {
+                    while self.peek().is_some_and(|c| c.is_ascii_hexdigit() || c == '_') {
                         let c = self.advance().unwrap(); if c != '_' { raw.push(c); }
                     }
                     let val = i64::from_str_radix(&raw[2..], 16).map_err(|_| LexError::InvalidNumber { raw: raw.clone(), span: Span::new(self.line, start_col, start, self.pos) })?;
@@ -244,7 +395,7 @@ impl Lexer {
                 }
                 Some('o') | Some('O') => {
                     raw.push(self.advance().unwrap());
-                    while self.peek().map_or(false, |c| matches!(c, '0'..='7') || c == '_') {
+                    while self.peek().is_some_and(|c| matches!(c, '0'..='7') || c == '_') {
                         let c = self.advance().unwrap(); if c != '_' { raw.push(c); }
                     }
                     let val = i64::from_str_radix(&raw[2..], 8).map_err(|_| LexError::InvalidNumber { raw: raw.clone(), span: Span::new(self.line, start_col, start, self.pos) })?;
@@ -253,7 +404,7 @@ impl Lexer {
                 }
                 Some('b') | Some('B') => {
                     raw.push(self.advance().unwrap());
-                    while self.peek().map_or(false, |c| c == '0' || c == '1' || c == '_') {
+                    while self.peek().is_some_and(|c| c == '0' || c == '1' || c == '_') {
                         let c = self.advance().unwrap(); if c != '_' { raw.push(c); }
                     }
                     let val = i64::from_str_radix(&raw[2..], 2).map_err(|_| LexError::InvalidNumber { raw: raw.clone(), span: Span::new(self.line, start_col, start, self.pos) })?;
@@ -264,15 +415,15 @@ impl Lexer {
             }
         }
 
-        while self.peek().map_or(false, |c| c.is_ascii_digit() || c == '_') {
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '_') {
             let c = self.advance().unwrap(); if c != '_' { raw.push(c); }
         }
 
         let mut is_float = false;
-        if self.peek() == Some('.') && self.peek_next().map_or(false, |c| c.is_ascii_digit()) {
+        if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
             is_float = true;
             raw.push(self.advance().unwrap());
-            while self.peek().map_or(false, |c| c.is_ascii_digit() || c == '_') {
+            while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '_') {
                 let c = self.advance().unwrap(); if c != '_' { raw.push(c); }
             }
         }
@@ -280,7 +431,20 @@ impl Lexer {
             is_float = true;
             raw.push(self.advance().unwrap());
             if self.peek() == Some('+') || self.peek() == Some('-') { raw.push(self.advance().unwrap()); }
-            while self.peek().map_or(false, |c| c.is_ascii_digit()) { raw.push(self.advance().unwrap()); }
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) { raw.push(self.advance().unwrap()); }
+        }
+
+        // Suffisso di tipo: `1f` → Float, `3i` → Int (solo se non segue un identificatore)
+        if let Some(suffix @ ('f' | 'i')) = self.peek() {
+            if !self.peek_next().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                self.advance();
+                if suffix == 'f' {
+                    is_float = true;
+                } else if is_float {
+                    // `1.5i`: un valore con parte decimale/esponente non può essere Int
+                    return Err(LexError::InvalidNumber { raw: format!("{}i", raw), span: Span::new(self.line, start_col, start, self.pos) });
+                }
+            }
         }
 
         let lexeme: String = self.source[start..self.pos].iter().collect();
@@ -296,10 +460,19 @@ impl Lexer {
     fn lex_identifier(&mut self, first: char, start: usize, start_col: usize) -> Token {
         let mut name = String::new();
         name.push(first);
-        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_') {
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
             name.push(self.advance().unwrap());
         }
         let kind = lookup_keyword(&name).unwrap_or(TokenKind::Identifier(name.clone()));
         Token::new(kind, Span::new(self.line, start_col, start, self.pos), name)
     }
 }
+
+/// Lettura incrementale: i token arrivano uno alla volta, senza costruire il `Vec`.
+impl Iterator for Lexer {
+    type Item = LexResult<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token_public()
+    }
+}
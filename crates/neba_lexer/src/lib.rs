@@ -1,15 +1,18 @@
+pub mod dump;
 pub mod error;
 pub mod lexer;
 pub mod token;
 
+pub use dump::dump_tokens;
 pub use error::{LexError, LexResult};
 pub use lexer::Lexer;
 pub use token::{lookup_keyword, Span, Token, TokenKind};
 
-pub fn tokenize(source: &str) -> (Vec<Token>, Vec<LexError>) {
+/// Token, errori e warning del lexer (es. `TabInLine`: non bloccano l'esecuzione).
+pub fn tokenize(source: &str) -> (Vec<Token>, Vec<LexError>, Vec<LexError>) {
     let mut lexer = Lexer::new(source);
     let tokens = lexer.tokenize();
-    (tokens, lexer.errors)
+    (tokens, lexer.errors, lexer.warnings)
 }
 
 #[cfg(test)]
@@ -17,18 +20,28 @@ mod tests {
     use super::*;
 
     fn kinds(src: &str) -> Vec<TokenKind> {
-        let (tokens, errors) = tokenize(src);
+        let (tokens, errors, _) = tokenize(src);
         assert!(errors.is_empty(), "Unexpected errors: {:?}", errors);
         tokens.into_iter().map(|t| t.kind).collect()
     }
 
     #[test]
     fn test_empty_source() {
-        let (tokens, errors) = tokenize("");
+        let (tokens, errors, _) = tokenize("");
         assert!(errors.is_empty());
         assert_eq!(tokens[0].kind, TokenKind::Eof);
     }
 
+    #[test]
+    fn test_trivia_only_sources() {
+        // Solo commenti/righe vuote (anche indentati): nessun token significativo
+        for src in ["# solo un commento", "# a\n\n    # b\n", "\n\n   \n", "  \n    \n\n", "\r\n\r\n"] {
+            let k = kinds(src);
+            assert!(k.iter().all(|t| matches!(t, TokenKind::Newline | TokenKind::Eof)), "{:?}: {:?}", src, k);
+            assert_eq!(k.last(), Some(&TokenKind::Eof));
+        }
+    }
+
     #[test]
     fn test_integer_literals() {
         let k = kinds("42 0xFF 0o77 0b1010 1_000_000");
@@ -40,6 +53,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_float_literals() {
         let k = kinds("3.14 2.0e10 1.5E-3");
         assert_eq!(k[0], TokenKind::FloatLiteral(3.14));
@@ -47,16 +61,30 @@ mod tests {
         assert_eq!(k[2], TokenKind::FloatLiteral(1.5e-3));
     }
 
+    #[test]
+    fn test_number_suffixes() {
+        let k = kinds("1f 3i 1 2.5f 1e3f 0xff");
+        assert_eq!(k[0], TokenKind::FloatLiteral(1.0));
+        assert_eq!(k[1], TokenKind::IntLiteral(3));
+        assert_eq!(k[2], TokenKind::IntLiteral(1));
+        assert_eq!(k[3], TokenKind::FloatLiteral(2.5));
+        assert_eq!(k[4], TokenKind::FloatLiteral(1e3));
+        assert_eq!(k[5], TokenKind::IntLiteral(255));
+        // `1.5i` non è un intero valido
+        let (_, errors, _) = tokenize("1.5i");
+        assert!(matches!(errors[0], LexError::InvalidNumber { .. }));
+    }
+
     #[test]
     fn test_string_literal() {
-        let (tokens, errors) = tokenize(r#""hello world""#);
+        let (tokens, errors, _) = tokenize(r#""hello world""#);
         assert!(errors.is_empty());
         assert_eq!(tokens[0].kind, TokenKind::StringLiteral("hello world".to_string()));
     }
 
     #[test]
     fn test_fstring_literal() {
-        let (tokens, errors) = tokenize(r#"f"hello {name}""#);
+        let (tokens, errors, _) = tokenize(r#"f"hello {name}""#);
         assert!(errors.is_empty());
         assert_eq!(tokens[0].kind, TokenKind::FStringLiteral("hello {name}".to_string()));
     }
@@ -117,6 +145,29 @@ mod tests {
         assert!(k.contains(&TokenKind::Dedent));
     }
 
+    #[test]
+    fn test_newline_before_dedent_at_eof() {
+        let k = kinds("fn f()\n    return 1");
+        let n = k.len();
+        assert_eq!(&k[n - 3..], &[TokenKind::Newline, TokenKind::Dedent, TokenKind::Eof]);
+    }
+
+    #[test]
+    fn test_indent_too_deep() {
+        let src: String = (0..300).map(|i| format!("{}if x\n", " ".repeat(i))).collect();
+        let (_tokens, errors, _) = tokenize(&src);
+        assert!(matches!(errors[0], LexError::IndentTooDeep { .. }));
+    }
+
+    #[test]
+    fn test_token_dump() {
+        let (tokens, _, _) = tokenize("if x\n    pass\n");
+        let dump = dump_tokens(&tokens);
+        let kinds: Vec<&str> = dump.lines().map(|l| l.split_whitespace().nth(1).unwrap()).collect();
+        assert_eq!(kinds, ["If", "Identifier(\"x\")", "Newline", "Indent", "Pass", "Newline", "Dedent", "Eof"]);
+        assert!(dump.lines().nth(3).unwrap().starts_with("2:1 "));
+    }
+
     #[test]
     fn test_comment_skipped() {
         let k = kinds("let x = 1 # commento");
@@ -126,11 +177,129 @@ mod tests {
 
     #[test]
     fn test_escape_sequences() {
-        let (tokens, errors) = tokenize(r#""\n\t\\""#);
+        let (tokens, errors, _) = tokenize(r#""\n\t\\""#);
         assert!(errors.is_empty());
         assert_eq!(tokens[0].kind, TokenKind::StringLiteral("\n\t\\".to_string()));
     }
 
+    #[test]
+    fn test_unicode_and_hex_escapes() {
+        let k = kinds(r#""\u{41}" == "A" "\u{1F600}\x41\x7e" f"\u{e8}{x}""#);
+        assert_eq!(k[0], TokenKind::StringLiteral("A".to_string()));
+        assert_eq!(k[2], TokenKind::StringLiteral("A".to_string()));
+        assert_eq!(k[3], TokenKind::StringLiteral("\u{1F600}A~".to_string()));
+        assert_eq!(k[4], TokenKind::FStringLiteral("è{x}".to_string()));
+        for (src, seq) in [(r#""\u{110000}""#, "\\u{110000}"), (r#""\u{D800}""#, "\\u{D800}")] {
+            let (_, errors, _) = tokenize(src);
+            assert!(matches!(&errors[0], LexError::InvalidUnicodeEscape { seq: s, span } if s == seq && span.column == 3), "{}", src);
+            assert_eq!(errors[0].code(), "L0008");
+        }
+        for src in [r#""\u41""#, r#""\u{}""#, r#""\u{1234567}""#, r#""\xg1""#, r#""\x4""#] {
+            let (_, errors, _) = tokenize(src);
+            assert!(matches!(errors[0], LexError::InvalidEscapeSequence { .. }), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let (tokens, errors, _) = tokenize(r#"r"\d+" r'C:\dir\n' r"a\"b" r"""x\y
+z""" r"#);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let k: Vec<_> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(k[0], TokenKind::StringLiteral(r"\d+".to_string()));
+        assert_eq!(k[1], TokenKind::StringLiteral(r"C:\dir\n".to_string()));
+        assert_eq!(k[2], TokenKind::StringLiteral(r#"a\"b"#.to_string()));
+        assert_eq!(k[3], TokenKind::StringLiteral("x\\y\nz".to_string()));
+        // `r` da solo resta un identificatore
+        assert_eq!(k[4], TokenKind::Identifier("r".to_string()));
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let k = kinds("if x\n    let y = 1 + \\  \n2 +\\\n        3\n    y\n");
+        assert_eq!(k, vec![
+            TokenKind::If, TokenKind::Identifier("x".into()), TokenKind::Newline, TokenKind::Indent,
+            TokenKind::Let, TokenKind::Identifier("y".into()), TokenKind::Equal, TokenKind::IntLiteral(1),
+            TokenKind::Plus, TokenKind::IntLiteral(2), TokenKind::Plus, TokenKind::IntLiteral(3), TokenKind::Newline,
+            TokenKind::Identifier("y".into()), TokenKind::Newline, TokenKind::Dedent, TokenKind::Eof,
+        ]);
+        // Dentro una stringa il backslash resta un escape
+        let k = kinds("let s = \"a\\\\\"\nlet t = 1");
+        assert_eq!(k[3], TokenKind::StringLiteral("a\\".into()));
+        assert_eq!(k[4], TokenKind::Newline);
+        // Un `\` non a fine riga resta un errore
+        let (_, errors, _) = tokenize("1 \\ 2");
+        assert!(matches!(errors[0], LexError::UnexpectedCharacter { ch: '\\', .. }));
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let k = kinds("'a' '\\n' '\\'' 'é' '''due\nrighe'''");
+        assert_eq!(k[..5], [
+            TokenKind::CharLiteral('a'), TokenKind::CharLiteral('\n'), TokenKind::CharLiteral('\''),
+            TokenKind::CharLiteral('é'), TokenKind::StringLiteral("due\nrighe".into()),
+        ]);
+        let (_, errors, _) = tokenize("let s = 'ab'");
+        assert!(matches!(&errors[0], LexError::InvalidCharLiteral { content, .. } if content == "ab"));
+        assert_eq!(errors[0].code(), "L0011");
+        let (_, errors, _) = tokenize("''");
+        assert!(matches!(&errors[0], LexError::InvalidCharLiteral { content, .. } if content.is_empty()));
+    }
+
+    #[test]
+    fn test_tab_in_line_warning() {
+        let (tokens, errors, warnings) = tokenize("let x\t= 1\t\t# nota");
+        assert!(errors.is_empty());
+        let k: Vec<TokenKind> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(k, vec![TokenKind::Let, TokenKind::Identifier("x".into()), TokenKind::Equal, TokenKind::IntLiteral(1), TokenKind::Eof]);
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(&warnings[0], LexError::TabInLine { span } if span.column == 6));
+        assert!(warnings[0].is_warning() && warnings[0].code() == "L0010");
+        assert_eq!(warnings[1].span().end - warnings[1].span().start, 2);
+        // Il tab in indentazione resta un errore
+        let (_, errors, warnings) = tokenize("if x\n\ty");
+        assert!(matches!(errors[0], LexError::TabSpaceMixing { .. }));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_streaming_matches_tokenize() {
+        let src = "fn f(x)\n    if x\n        return 1\n  return @\nlet s = f\"{f(1)}\"";
+        let (tokens, errors, _) = tokenize(src);
+        let mut streamed = Vec::new();
+        let mut stream_errors = Vec::new();
+        for res in Lexer::new(src) {
+            match res {
+                Ok(tok) => streamed.push(tok),
+                Err(e) => stream_errors.push(e),
+            }
+        }
+        assert_eq!(streamed, tokens);
+        assert_eq!(stream_errors, errors);
+        assert!(!errors.is_empty());
+        // Dopo Eof l'iteratore è esaurito
+        let mut lexer = Lexer::new("x");
+        assert_eq!(lexer.by_ref().count(), 2);
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn test_block_comments() {
+        assert_eq!(kinds("1 #[ commento ]# + 2"), kinds("1 + 2"));
+        // Annidati e su più righe: nessun Newline/Indent dalle righe commentate
+        let k = kinds("let x = 1\n#[ fuori\n    #[ dentro ]#\n  ancora fuori ]#\nlet y = 2\n");
+        assert_eq!(k, kinds("let x = 1\n\nlet y = 2\n"));
+        let (_, errors, _) = tokenize("let a = 1\n  #[ aperto #[ ]#\n");
+        assert!(matches!(&errors[0], LexError::UnterminatedComment { span } if span.line == 2 && span.column == 3));
+        assert_eq!(errors[0].code(), "L0009");
+    }
+
+    #[test]
+    fn test_pipe_tokens() {
+        assert_eq!(kinds("a |> f | b || c")[1..6], [TokenKind::PipeGreater, TokenKind::Identifier("f".into()),
+            TokenKind::Pipe, TokenKind::Identifier("b".into()), TokenKind::Pipe2]);
+    }
+
     #[test]
     fn test_spawn_keyword() {
         let k = kinds("spawn task()");
@@ -146,15 +315,24 @@ mod tests {
 
     #[test]
     fn test_unterminated_string_error() {
-        let (_tokens, errors) = tokenize("\"hello");
+        let (_tokens, errors, _) = tokenize("\"hello");
         assert!(!errors.is_empty());
         assert!(matches!(errors[0], LexError::UnterminatedString { .. }));
     }
 
     #[test]
     fn test_tab_error() {
-        let (_tokens, errors) = tokenize("if x\n\tlet y = 1");
+        let (_tokens, errors, _) = tokenize("if x\n\tlet y = 1");
         assert!(!errors.is_empty());
         assert!(matches!(errors[0], LexError::TabSpaceMixing { .. }));
     }
+
+    #[test]
+    fn test_error_codes() {
+        let (_tokens, errors, _) = tokenize("\"hello");
+        assert_eq!(errors[0].code(), "L0002");
+        assert!(errors[0].to_string().starts_with("[LexError L0002] "));
+        let (_tokens, errors, _) = tokenize("if x\n\tlet y = 1");
+        assert_eq!(errors[0].code(), "L0006");
+    }
 }
@@ -32,6 +32,8 @@ pub enum TokenKind {
     FloatLiteral(f64),
     StringLiteral(String),
     FStringLiteral(String),
+    /// `'a'`: un solo carattere tra apici singoli
+    CharLiteral(char),
     BoolLiteral(bool),
     NoneLiteral,
 
@@ -68,6 +70,8 @@ pub enum TokenKind {
     // Other operators
     Arrow, FatArrow, Dot, DotDot, DotDotEqual, ColonColon,
     QuestionMark, Bang, At, Pipe2, Ampersand2,
+    /// `|>` (pipeline)
+    PipeGreater,
 
     // Delimiters
     LParen, RParen, LBracket, RBracket, LBrace, RBrace,
@@ -1,3 +1,4 @@
+use std::rc::Rc;
 use neba_lexer::Span;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,6 +36,7 @@ pub enum ExprKind {
     Bool(bool),
     Str(String),
     FStr(String),
+    Char(char),
     None,
     Ident(String),
     Binary { op: BinOp, left: Box<Expr>, right: Box<Expr> },
@@ -47,6 +49,8 @@ pub enum ExprKind {
     Array(Vec<Expr>),
     /// Dizionario letterale: `{key: value, ...}` oppure `{key => value, ...}`
     Dict(Vec<(Expr, Expr)>),
+    /// Tupla letterale: `(a, b)`, `(a,)`; almeno un elemento
+    Tuple(Vec<Expr>),
     Range  { start: Box<Expr>, end: Box<Expr>, inclusive: bool },
     If {
         condition: Box<Expr>,
@@ -62,6 +66,8 @@ pub enum ExprKind {
     Err(Box<Expr>),
     /// Operatore `?`: propaga Err early-return, unwrappa Ok.
     Try(Box<Expr>),
+    /// Pipeline `value |> func`: vedi `ExprKind::pipe_call`
+    Pipe   { value: Box<Expr>, func: Box<Expr> },
     /// Funzione anonima: `fn(x, y) => expr` oppure blocco indentato
     Lambda {
         params: Vec<Param>,
@@ -70,6 +76,35 @@ pub enum ExprKind {
     Error,
 }
 
+impl ExprKind {
+    /// La chiamata equivalente a `value |> func`: `x |> f` è `f(x)`,
+    /// `x |> f(a, k=b)` è `f(x, a, k=b)`. I backend valutano questa.
+    pub fn pipe_call(value: &Expr, func: &Expr) -> ExprKind {
+        match &func.inner {
+            ExprKind::Call { callee, args, kwargs } => ExprKind::Call {
+                callee: callee.clone(),
+                args: std::iter::once(value.clone()).chain(args.iter().cloned()).collect(),
+                kwargs: kwargs.clone(),
+            },
+            _ => ExprKind::Call { callee: Box::new(func.clone()), args: vec![value.clone()], kwargs: Vec::new() },
+        }
+    }
+}
+
+/// Design by contract: `requires cond` è valutata all'ingresso con i parametri
+/// legati, `ensures cond` all'uscita con in più `result` (il valore restituito).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Contracts {
+    pub requires: Vec<Expr>,
+    pub ensures: Vec<Expr>,
+}
+
+impl Contracts {
+    pub fn is_empty(&self) -> bool {
+        self.requires.is_empty() && self.ensures.is_empty()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchArm {
     pub pattern: Pattern,
@@ -85,18 +120,36 @@ pub enum Pattern {
     Constructor(String, Vec<Pattern>),
     Range { start: Box<Pattern>, end: Box<Pattern>, inclusive: bool },
     Or(Vec<Pattern>),
+    /// `(p1, p2, ...)`: tupla della stessa lunghezza, elemento per elemento
+    Tuple(Vec<Pattern>),
+    /// `"pre" + rest`, `rest + "suf"` o `"pre" + rest + "suf"`: stringa che inizia
+    /// e finisce con i letterali; `rest` (Ident o `_`) si lega alla parte centrale
+    StrAffix { prefix: String, rest: Box<Pattern>, suffix: String },
     Error,
 }
 
+impl Pattern {
+    /// Parte centrale di `s` per un pattern `StrAffix`, se `s` ha prefisso e suffisso
+    /// (senza sovrapposizioni: `"ab" + x + "ba"` non accetta `"aba"`).
+    pub fn affix_middle<'a>(s: &'a str, prefix: &str, suffix: &str) -> Option<&'a str> {
+        s.strip_prefix(prefix)?.strip_suffix(suffix)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum BinOp {
     Add, Sub, Mul, Div, IntDiv, Mod, Pow,
     Eq, Ne, Lt, Le, Gt, Ge,
     And, Or,
     BitAnd, BitOr, BitXor, Shl, Shr,
-    Is, In, NotIn,
+    Is, IsNot, In, NotIn,
 }
 
+/// Nomi dei tipi built-in riconosciuti a destra di `is` / `is not` (`x is Int`),
+/// se non ridefiniti come variabili. Niente `Range`: l'interprete materializza i
+/// range in Array, quindi per `is` un range è un `Array` in entrambi i backend.
+pub const BUILTIN_TYPE_NAMES: &[&str] = &["Int", "Float", "Bool", "Str", "Char", "Array", "Dict", "Tuple", "Function"];
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp { Neg, Not, BitNot }
 
@@ -114,8 +167,13 @@ pub enum StmtKind {
         name: String,
         params: Vec<Param>,
         return_ty: Option<TypeExpr>,
-        body: Vec<Stmt>,
+        /// Condiviso via `Rc`: chi definisce la funzione (interprete) non copia l'AST del corpo
+        body: Rc<[Stmt]>,
         is_async: bool,
+        /// `pure fn`: il type checker verifica che non abbia effetti collaterali
+        is_pure: bool,
+        /// Clausole `requires`/`ensures` in testa al corpo (vuote quasi sempre)
+        contracts: Rc<Contracts>,
     },
     Class  { name: String, fields: Vec<Field>, methods: Vec<Stmt>, impls: Vec<Stmt> },
     Trait  { name: String, methods: Vec<Stmt> },
@@ -0,0 +1,82 @@
+//! Esempi nei commenti di documentazione (`##`), per `neba test --doc`.
+//!
+//! Un esempio è un blocco recintato da tre backtick (senza linguaggio o `neba`)
+//! dentro righe `##` consecutive. Una riga `# => valore`, da sola o in coda al
+//! codice, indica il valore atteso dell'ultima espressione che la precede:
+//!
+//! ```text
+//! ## Raddoppia `x`.
+//! ## ```neba
+//! ## doppio(21)  # => 42
+//! ## let y = doppio(1)
+//! ## y + 1
+//! ## # => 3
+//! ## ```
+//! fn doppio(x)
+//! ```
+
+/// Codice da eseguire e, se annotato, il sorgente del valore atteso.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocStep {
+    pub code: String,
+    pub expected: Option<String>,
+    /// Riga (1-based) dell'annotazione `# =>`, o dell'ultima riga di codice
+    pub line: usize,
+}
+
+/// Un blocco di esempio; i passi vanno eseguiti in ordine nello stesso stato.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocExample {
+    /// Riga (1-based) del backtick di apertura
+    pub line: usize,
+    pub steps: Vec<DocStep>,
+}
+
+/// Estrae gli esempi dai commenti `##` di `source`, in ordine di sorgente.
+pub fn doc_examples(source: &str) -> Vec<DocExample> {
+    let mut examples = Vec::new();
+    // (riga di apertura, esegui?, passi, codice accumulato)
+    let mut open: Option<(usize, bool, Vec<DocStep>, String)> = None;
+    for (i, raw) in source.lines().enumerate() {
+        let line = i + 1;
+        let Some(text) = raw.trim_start().strip_prefix("##") else {
+            // Un blocco non chiuso termina con il commento
+            open = None;
+            continue;
+        };
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        if let Some(lang) = text.trim_end().strip_prefix("```") {
+            match open.take() {
+                Some((start, run, mut steps, code)) => {
+                    if run {
+                        if !code.trim().is_empty() {
+                            steps.push(DocStep { code, expected: None, line: line - 1 });
+                        }
+                        examples.push(DocExample { line: start, steps });
+                    }
+                }
+                None => open = Some((line, matches!(lang.trim(), "" | "neba"), Vec::new(), String::new())),
+            }
+            continue;
+        }
+        let Some((_, _, steps, code)) = &mut open else { continue };
+        match text.split_once("# =>") {
+            Some((before, expected)) => {
+                if !before.trim().is_empty() {
+                    code.push_str(before);
+                    code.push('\n');
+                }
+                steps.push(DocStep {
+                    code: std::mem::take(code),
+                    expected: Some(expected.trim().to_string()),
+                    line,
+                });
+            }
+            None => {
+                code.push_str(text);
+                code.push('\n');
+            }
+        }
+    }
+    examples
+}
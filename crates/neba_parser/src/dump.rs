@@ -0,0 +1,350 @@
+//! Dump leggibile dell'AST come albero indentato (usato da `neba --ast`).
+//!
+//! Ogni nodo occupa una riga: `Tipo dettagli @linea:colonna`, con i figli
+//! indentati di due spazi rispetto al padre.
+
+use std::fmt::{self, Write};
+
+use crate::ast::*;
+
+/// Wrapper che implementa `Display` stampando il programma come albero.
+pub struct AstDump<'a>(pub &'a Program);
+
+impl fmt::Display for AstDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut w = TreeWriter { out: f, depth: 0 };
+        w.line("Program")?;
+        w.nested(|w| w.stmts(&self.0.stmts))
+    }
+}
+
+/// Restituisce il dump ad albero di un programma.
+pub fn dump_program(program: &Program) -> String {
+    AstDump(program).to_string()
+}
+
+struct TreeWriter<'a, 'b> {
+    out: &'a mut fmt::Formatter<'b>,
+    depth: usize,
+}
+
+impl TreeWriter<'_, '_> {
+    fn line(&mut self, text: &str) -> fmt::Result {
+        for _ in 0..self.depth { self.out.write_str("  ")?; }
+        self.out.write_str(text)?;
+        self.out.write_char('\n')
+    }
+
+    fn node<T>(&mut self, label: &str, node: &Node<T>) -> fmt::Result {
+        self.line(&format!("{} @{}:{}", label, node.span.line, node.span.column))
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> fmt::Result) -> fmt::Result {
+        self.depth += 1;
+        let r = f(self);
+        self.depth -= 1;
+        r
+    }
+
+    /// Etichetta senza span seguita da un sotto-albero (es. `then`, `else`, `args`).
+    fn section(&mut self, label: &str, f: impl FnOnce(&mut Self) -> fmt::Result) -> fmt::Result {
+        self.line(label)?;
+        self.nested(f)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> fmt::Result {
+        for s in stmts { self.stmt(s)?; }
+        Ok(())
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> fmt::Result {
+        for e in exprs { self.expr(e)?; }
+        Ok(())
+    }
+
+    fn params(&mut self, params: &[Param]) -> fmt::Result {
+        for p in params {
+            let ty = p.ty.as_ref().map(|t| format!(": {}", type_str(&t.inner))).unwrap_or_default();
+            self.line(&format!("Param {}{} @{}:{}", p.name, ty, p.span.line, p.span.column))?;
+            if let Some(d) = &p.default {
+                self.nested(|w| w.expr(d))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> fmt::Result {
+        match &stmt.inner {
+            StmtKind::Let { name, ty, value } | StmtKind::Var { name, ty, value } => {
+                let kw = if matches!(stmt.inner, StmtKind::Let { .. }) { "Let" } else { "Var" };
+                let ty = ty.as_ref().map(|t| format!(": {}", type_str(&t.inner))).unwrap_or_default();
+                self.node(&format!("{} {}{}", kw, name, ty), stmt)?;
+                self.nested(|w| w.expr(value))
+            }
+            StmtKind::Assign { target, op, value } => {
+                self.node(&format!("Assign {}", assign_op_str(op)), stmt)?;
+                self.nested(|w| { w.expr(target)?; w.expr(value) })
+            }
+            StmtKind::Fn { name, params, return_ty, body, is_async, is_pure, contracts } => {
+                let prefix = if *is_async { "AsyncFn" } else if *is_pure { "PureFn" } else { "Fn" };
+                let ret = return_ty.as_ref().map(|t| format!(" -> {}", type_str(&t.inner))).unwrap_or_default();
+                self.node(&format!("{} {}{}", prefix, name, ret), stmt)?;
+                self.nested(|w| {
+                    if !params.is_empty() { w.section("params", |w| w.params(params))?; }
+                    if !contracts.requires.is_empty() { w.section("requires", |w| contracts.requires.iter().try_for_each(|c| w.expr(c)))?; }
+                    if !contracts.ensures.is_empty() { w.section("ensures", |w| contracts.ensures.iter().try_for_each(|c| w.expr(c)))?; }
+                    w.section("body", |w| w.stmts(body))
+                })
+            }
+            StmtKind::Class { name, fields, methods, impls } => {
+                self.node(&format!("Class {}", name), stmt)?;
+                self.nested(|w| {
+                    for fld in fields {
+                        let ty = fld.ty.as_ref().map(|t| format!(": {}", type_str(&t.inner))).unwrap_or_default();
+                        w.line(&format!("Field {}{} @{}:{}", fld.name, ty, fld.span.line, fld.span.column))?;
+                        if let Some(d) = &fld.default { w.nested(|w| w.expr(d))?; }
+                    }
+                    w.stmts(methods)?;
+                    w.stmts(impls)
+                })
+            }
+            StmtKind::Trait { name, methods } => {
+                self.node(&format!("Trait {}", name), stmt)?;
+                self.nested(|w| w.stmts(methods))
+            }
+            StmtKind::Impl { trait_name, for_type, methods } => {
+                let target = for_type.as_ref().map(|t| format!(" for {}", t)).unwrap_or_default();
+                self.node(&format!("Impl {}{}", trait_name, target), stmt)?;
+                self.nested(|w| w.stmts(methods))
+            }
+            StmtKind::While { condition, body } => {
+                self.node("While", stmt)?;
+                self.nested(|w| {
+                    w.expr(condition)?;
+                    w.section("body", |w| w.stmts(body))
+                })
+            }
+            StmtKind::For { var, iterable, body } => {
+                self.node(&format!("For {}", var), stmt)?;
+                self.nested(|w| {
+                    w.expr(iterable)?;
+                    w.section("body", |w| w.stmts(body))
+                })
+            }
+            StmtKind::Return(value) => {
+                self.node("Return", stmt)?;
+                match value {
+                    Some(v) => self.nested(|w| w.expr(v)),
+                    None    => Ok(()),
+                }
+            }
+            StmtKind::Break    => self.node("Break", stmt),
+            StmtKind::Continue => self.node("Continue", stmt),
+            StmtKind::Pass     => self.node("Pass", stmt),
+            StmtKind::Mod(name)  => self.node(&format!("Mod {}", name), stmt),
+            StmtKind::Use(path)  => self.node(&format!("Use {}", path.join("::")), stmt),
+            StmtKind::Expr(e) => {
+                self.node("ExprStmt", stmt)?;
+                self.nested(|w| w.expr(e))
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> fmt::Result {
+        match &expr.inner {
+            ExprKind::Int(n)   => self.node(&format!("Int {}", n), expr),
+            ExprKind::Float(f) => self.node(&format!("Float {:?}", f), expr),
+            ExprKind::Bool(b)  => self.node(&format!("Bool {}", b), expr),
+            ExprKind::Str(s)   => self.node(&format!("Str {:?}", s), expr),
+            ExprKind::FStr(s)  => self.node(&format!("FStr {:?}", s), expr),
+            ExprKind::Char(c)  => self.node(&format!("Char {:?}", c), expr),
+            ExprKind::None     => self.node("None", expr),
+            ExprKind::Ident(n) => self.node(&format!("Ident {}", n), expr),
+            ExprKind::Binary { op, left, right } => {
+                self.node(&format!("Binary {}", bin_op_str(op)), expr)?;
+                self.nested(|w| { w.expr(left)?; w.expr(right) })
+            }
+            ExprKind::Unary { op, operand } => {
+                let op = match op { UnaryOp::Neg => "-", UnaryOp::Not => "not", UnaryOp::BitNot => "~" };
+                self.node(&format!("Unary {}", op), expr)?;
+                self.nested(|w| w.expr(operand))
+            }
+            ExprKind::Pipe { value, func } => {
+                self.node("Pipe", expr)?;
+                self.nested(|w| { w.expr(value)?; w.expr(func) })
+            }
+            ExprKind::Call { callee, args, kwargs } => {
+                self.node("Call", expr)?;
+                self.nested(|w| {
+                    w.expr(callee)?;
+                    if !args.is_empty() { w.section("args", |w| w.exprs(args))?; }
+                    for (name, value) in kwargs {
+                        w.section(&format!("kwarg {}", name), |w| w.expr(value))?;
+                    }
+                    Ok(())
+                })
+            }
+            ExprKind::Field { object, field } => {
+                self.node(&format!("Field .{}", field), expr)?;
+                self.nested(|w| w.expr(object))
+            }
+            ExprKind::Index { object, index } => {
+                self.node("Index", expr)?;
+                self.nested(|w| { w.expr(object)?; w.expr(index) })
+            }
+            ExprKind::Slice { object, start, end, step } => {
+                self.node("Slice", expr)?;
+                self.nested(|w| {
+                    w.expr(object)?;
+                    for (label, part) in [("start", start), ("end", end), ("step", step)] {
+                        if let Some(p) = part { w.section(label, |w| w.expr(p))?; }
+                    }
+                    Ok(())
+                })
+            }
+            ExprKind::Array(items) => {
+                self.node(&format!("Array [{}]", items.len()), expr)?;
+                self.nested(|w| w.exprs(items))
+            }
+            ExprKind::Tuple(items) => {
+                self.node(&format!("Tuple ({})", items.len()), expr)?;
+                self.nested(|w| w.exprs(items))
+            }
+            ExprKind::Dict(pairs) => {
+                self.node(&format!("Dict {{{}}}", pairs.len()), expr)?;
+                self.nested(|w| {
+                    for (k, v) in pairs {
+                        w.section("entry", |w| { w.expr(k)?; w.expr(v) })?;
+                    }
+                    Ok(())
+                })
+            }
+            ExprKind::Range { start, end, inclusive } => {
+                self.node(if *inclusive { "Range ..=" } else { "Range .." }, expr)?;
+                self.nested(|w| { w.expr(start)?; w.expr(end) })
+            }
+            ExprKind::If { condition, then_block, elif_branches, else_block } => {
+                self.node("If", expr)?;
+                self.nested(|w| {
+                    w.expr(condition)?;
+                    w.section("then", |w| w.stmts(then_block))?;
+                    for (cond, block) in elif_branches {
+                        w.section("elif", |w| {
+                            w.expr(cond)?;
+                            w.stmts(block)
+                        })?;
+                    }
+                    if let Some(b) = else_block { w.section("else", |w| w.stmts(b))?; }
+                    Ok(())
+                })
+            }
+            ExprKind::Match { subject, arms } => {
+                self.node("Match", expr)?;
+                self.nested(|w| {
+                    w.expr(subject)?;
+                    for arm in arms {
+                        w.line(&format!("Case {} @{}:{}", pattern_str(&arm.pattern), arm.span.line, arm.span.column))?;
+                        w.nested(|w| w.stmts(&arm.body))?;
+                    }
+                    Ok(())
+                })
+            }
+            ExprKind::Spawn(e) => { self.node("Spawn", expr)?; self.nested(|w| w.expr(e)) }
+            ExprKind::Await(e) => { self.node("Await", expr)?; self.nested(|w| w.expr(e)) }
+            ExprKind::Some(e)  => { self.node("Some", expr)?;  self.nested(|w| w.expr(e)) }
+            ExprKind::Ok(e)    => { self.node("Ok", expr)?;    self.nested(|w| w.expr(e)) }
+            ExprKind::Err(e)   => { self.node("Err", expr)?;   self.nested(|w| w.expr(e)) }
+            ExprKind::Try(e)   => { self.node("Try ?", expr)?; self.nested(|w| w.expr(e)) }
+            ExprKind::Lambda { params, body } => {
+                self.node("Lambda", expr)?;
+                self.nested(|w| {
+                    if !params.is_empty() { w.section("params", |w| w.params(params))?; }
+                    w.section("body", |w| w.stmts(body))
+                })
+            }
+            ExprKind::Error => self.node("<error>", expr),
+        }
+    }
+}
+
+fn type_str(t: &TypeKind) -> String {
+    match t {
+        TypeKind::Named(n) => n.clone(),
+        TypeKind::Generic(n, args) => {
+            let args: Vec<String> = args.iter().map(|a| type_str(&a.inner)).collect();
+            format!("{}[{}]", n, args.join(", "))
+        }
+        TypeKind::Error => "<error>".to_string(),
+    }
+}
+
+fn pattern_str(p: &Pattern) -> String {
+    match p {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Literal(ExprKind::Int(n))   => n.to_string(),
+        Pattern::Literal(ExprKind::Float(f)) => format!("{:?}", f),
+        Pattern::Literal(ExprKind::Bool(b))  => b.to_string(),
+        Pattern::Literal(ExprKind::Str(s))   => format!("{:?}", s),
+        Pattern::Literal(ExprKind::Char(c))  => format!("{:?}", c),
+        Pattern::Literal(ExprKind::None)     => "None".to_string(),
+        Pattern::Literal(other) => format!("{:?}", other),
+        Pattern::Ident(n) => n.clone(),
+        Pattern::Constructor(n, inner) => {
+            let inner: Vec<String> = inner.iter().map(pattern_str).collect();
+            format!("{}({})", n, inner.join(", "))
+        }
+        Pattern::Range { start, end, inclusive } =>
+            format!("{}{}{}", pattern_str(start), if *inclusive { "..=" } else { ".." }, pattern_str(end)),
+        Pattern::Or(pats) => pats.iter().map(pattern_str).collect::<Vec<_>>().join(" | "),
+        Pattern::Tuple(items) if items.len() == 1 => format!("({},)", pattern_str(&items[0])),
+        Pattern::Tuple(items) => format!("({})", items.iter().map(pattern_str).collect::<Vec<_>>().join(", ")),
+        Pattern::StrAffix { prefix, rest, suffix } => {
+            let mut parts = Vec::new();
+            if !prefix.is_empty() { parts.push(format!("{:?}", prefix)); }
+            parts.push(pattern_str(rest));
+            if !suffix.is_empty() { parts.push(format!("{:?}", suffix)); }
+            parts.join(" + ")
+        }
+        Pattern::Error => "<error>".to_string(),
+    }
+}
+
+fn assign_op_str(op: &AssignOp) -> &'static str {
+    match op {
+        AssignOp::Assign    => "=",
+        AssignOp::AddAssign => "+=",
+        AssignOp::SubAssign => "-=",
+        AssignOp::MulAssign => "*=",
+        AssignOp::DivAssign => "/=",
+        AssignOp::ModAssign => "%=",
+    }
+}
+
+fn bin_op_str(op: &BinOp) -> &'static str {
+    match op {
+        BinOp::Add    => "+",
+        BinOp::Sub    => "-",
+        BinOp::Mul    => "*",
+        BinOp::Div    => "/",
+        BinOp::IntDiv => "//",
+        BinOp::Mod    => "%",
+        BinOp::Pow    => "**",
+        BinOp::Eq     => "==",
+        BinOp::Ne     => "!=",
+        BinOp::Lt     => "<",
+        BinOp::Le     => "<=",
+        BinOp::Gt     => ">",
+        BinOp::Ge     => ">=",
+        BinOp::And    => "and",
+        BinOp::Or     => "or",
+        BinOp::BitAnd => "&",
+        BinOp::BitOr  => "|",
+        BinOp::BitXor => "^",
+        BinOp::Shl    => "<<",
+        BinOp::Shr    => ">>",
+        BinOp::Is     => "is",
+        BinOp::IsNot  => "is not",
+        BinOp::In     => "in",
+        BinOp::NotIn  => "not in",
+    }
+}
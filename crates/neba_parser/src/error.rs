@@ -9,23 +9,79 @@ pub enum ParseError {
     MissingIndent      { span: Span },
     MissingDedent      { span: Span },
     InvalidPattern     { span: Span },
+    NestingTooDeep     { max: usize, span: Span },
+    ExpressionTooDeep  { max: usize, span: Span },
+    AssignInExpression { span: Span },
+    DuplicateMember    { class: String, name: String, span: Span },
+    RequiredAfterDefault { name: String, span: Span },
+    SelfNotFirst       { span: Span },
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ParseError::UnexpectedToken { expected, found, span } =>
-                write!(f, "[ParseError] Expected {} but found {:?} at line {}, column {}", expected, found, span.line, span.column),
+                write!(f, "[ParseError {}] Expected {} but found {:?} at line {}, column {}", self.code(), expected, found, span.line, span.column),
             ParseError::UnexpectedEof { expected, span } =>
-                write!(f, "[ParseError] Expected {} but reached end of file at line {}", expected, span.line),
+                write!(f, "[ParseError {}] Expected {} but reached end of file at line {}", self.code(), expected, span.line),
             ParseError::InvalidAssignTarget { span } =>
-                write!(f, "[ParseError] Invalid assignment target at line {}, column {}", span.line, span.column),
+                write!(f, "[ParseError {}] Invalid assignment target at line {}, column {}", self.code(), span.line, span.column),
             ParseError::MissingIndent { span } =>
-                write!(f, "[ParseError] Expected indented block at line {}", span.line),
+                write!(f, "[ParseError {}] Expected indented block at line {}", self.code(), span.line),
             ParseError::MissingDedent { span } =>
-                write!(f, "[ParseError] Missing dedent at line {}", span.line),
+                write!(f, "[ParseError {}] Missing dedent at line {}", self.code(), span.line),
             ParseError::InvalidPattern { span } =>
-                write!(f, "[ParseError] Invalid pattern in match arm at line {}, column {}", span.line, span.column),
+                write!(f, "[ParseError {}] Invalid pattern in match arm at line {}, column {}", self.code(), span.line, span.column),
+            ParseError::NestingTooDeep { max, span } =>
+                write!(f, "[ParseError {}] Blocks nested too deeply (max {}) at line {}", self.code(), max, span.line),
+            ParseError::ExpressionTooDeep { max, span } =>
+                write!(f, "[ParseError {}] Expression nested too deeply (max {}) at line {}, column {}", self.code(), max, span.line, span.column),
+            ParseError::AssignInExpression { span } =>
+                write!(f, "[ParseError {}] Assignment is a statement and cannot be used as an expression (did you mean '=='?) at line {}, column {}", self.code(), span.line, span.column),
+            ParseError::DuplicateMember { class, name, span } =>
+                write!(f, "[ParseError {}] Class '{}' already defines a member named '{}' at line {}, column {}", self.code(), class, name, span.line, span.column),
+            ParseError::RequiredAfterDefault { name, span } =>
+                write!(f, "[ParseError {}] Required parameter '{}' follows a parameter with a default value at line {}, column {}", self.code(), name, span.line, span.column),
+            ParseError::SelfNotFirst { span } =>
+                write!(f, "[ParseError {}] 'self' must be the first parameter at line {}, column {}", self.code(), span.line, span.column),
+        }
+    }
+}
+
+impl ParseError {
+    /// Codice stabile dell'errore (`P0001`…), citato nel messaggio.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. }     => "P0001",
+            ParseError::UnexpectedEof { .. }       => "P0002",
+            ParseError::InvalidAssignTarget { .. } => "P0003",
+            ParseError::MissingIndent { .. }       => "P0004",
+            ParseError::MissingDedent { .. }       => "P0005",
+            ParseError::InvalidPattern { .. }      => "P0006",
+            ParseError::NestingTooDeep { .. }      => "P0007",
+            ParseError::ExpressionTooDeep { .. }   => "P0008",
+            ParseError::AssignInExpression { .. }  => "P0009",
+            ParseError::DuplicateMember { .. }     => "P0010",
+            ParseError::RequiredAfterDefault { .. } => "P0011",
+            ParseError::SelfNotFirst { .. }        => "P0012",
+        }
+    }
+
+    /// Posizione nel sorgente a cui si riferisce l'errore.
+    pub fn span(&self) -> &Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEof { span, .. }
+            | ParseError::InvalidAssignTarget { span }
+            | ParseError::MissingIndent { span }
+            | ParseError::MissingDedent { span }
+            | ParseError::InvalidPattern { span }
+            | ParseError::NestingTooDeep { span, .. }
+            | ParseError::ExpressionTooDeep { span, .. }
+            | ParseError::AssignInExpression { span }
+            | ParseError::DuplicateMember { span, .. }
+            | ParseError::RequiredAfterDefault { span, .. }
+            | ParseError::SelfNotFirst { span } => span,
         }
     }
 }
@@ -0,0 +1,384 @@
+//! Pretty-printer: riscrive un `Program` come sorgente Neba canonico (usato da `neba fmt`).
+//!
+//! Regole: indentazione di 4 spazi, un solo spazio attorno agli operatori binari,
+//! stringhe sempre tra doppi apici, parentesi solo dove la precedenza lo richiede,
+//! una riga vuota attorno alle definizioni top-level. L'output è idempotente:
+//! riformattare codice già formattato non lo cambia. I commenti non sono ancora preservati.
+
+use crate::ast::*;
+use crate::parser::Prec;
+
+const INDENT: &str = "    ";
+
+/// Restituisce il sorgente canonico di un programma.
+pub fn format_program(program: &Program) -> String {
+    let mut out = String::new();
+    let mut prev_def = false;
+    for (i, stmt) in program.stmts.iter().enumerate() {
+        let def = is_definition(stmt);
+        if i > 0 && (def || prev_def) { out.push('\n'); }
+        stmt_into(&mut out, stmt, 0);
+        prev_def = def;
+    }
+    out
+}
+
+/// `true` se il sorgente contiene commenti (`#` fuori dalle stringhe), che il
+/// formatter non preserva ancora.
+pub fn has_comments(source: &str) -> bool {
+    let (tokens, _, _) = neba_lexer::tokenize(source);
+    let strings: Vec<(usize, usize)> = tokens.iter()
+        .filter(|t| matches!(t.kind, neba_lexer::TokenKind::StringLiteral(_) | neba_lexer::TokenKind::FStringLiteral(_) | neba_lexer::TokenKind::CharLiteral(_)))
+        .map(|t| (t.span.start, t.span.end))
+        .collect();
+    source.chars().enumerate()
+        .any(|(i, c)| c == '#' && !strings.iter().any(|&(s, e)| s <= i && i < e))
+}
+
+fn is_definition(stmt: &Stmt) -> bool {
+    matches!(stmt.inner, StmtKind::Fn { .. } | StmtKind::Class { .. } | StmtKind::Trait { .. } | StmtKind::Impl { .. })
+}
+
+fn push_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth { out.push_str(INDENT); }
+}
+
+/// Blocco indentato a `depth`; un blocco vuoto diventa `pass`.
+fn block_into(out: &mut String, stmts: &[Stmt], depth: usize) {
+    if stmts.is_empty() {
+        push_indent(out, depth);
+        out.push_str("pass\n");
+    }
+    for s in stmts { stmt_into(out, s, depth); }
+}
+
+/// Corpo di una classe/trait/impl: una riga vuota tra i metodi.
+fn members_into(out: &mut String, members: &[Stmt], depth: usize, blank_before: bool) {
+    for (i, m) in members.iter().enumerate() {
+        if i > 0 || blank_before { out.push('\n'); }
+        stmt_into(out, m, depth);
+    }
+}
+
+fn stmt_into(out: &mut String, stmt: &Stmt, depth: usize) {
+    push_indent(out, depth);
+    match &stmt.inner {
+        StmtKind::Let { name, ty, value } | StmtKind::Var { name, ty, value } => {
+            out.push_str(if matches!(stmt.inner, StmtKind::Var { .. }) { "var " } else { "let " });
+            out.push_str(name);
+            if let Some(t) = ty { out.push_str(": "); out.push_str(&type_str(t)); }
+            out.push_str(" = ");
+            out.push_str(&expr(value, depth));
+        }
+        StmtKind::Assign { target, op, value } => {
+            let op = match op {
+                AssignOp::Assign => "=", AssignOp::AddAssign => "+=", AssignOp::SubAssign => "-=",
+                AssignOp::MulAssign => "*=", AssignOp::DivAssign => "/=", AssignOp::ModAssign => "%=",
+            };
+            out.push_str(&format!("{} {} {}", expr(target, depth), op, expr(value, depth)));
+        }
+        StmtKind::Fn { name, params, return_ty, body, is_async, is_pure, contracts } => {
+            if *is_pure { out.push_str("pure "); }
+            if *is_async { out.push_str("async "); }
+            out.push_str(&format!("fn {}({})", name, params_str(params, depth)));
+            if let Some(t) = return_ty { out.push_str(" -> "); out.push_str(&type_str(t)); }
+            out.push('\n');
+            let clauses = contracts.requires.iter().map(|c| ("requires", c))
+                .chain(contracts.ensures.iter().map(|c| ("ensures", c)));
+            for (kw, cond) in clauses {
+                push_indent(out, depth + 1);
+                out.push_str(&format!("{} {}\n", kw, expr(cond, depth + 1)));
+            }
+            // Nei trait una fn senza corpo è solo una firma
+            if !body.is_empty() { block_into(out, body, depth + 1); }
+        }
+        StmtKind::Class { name, fields, methods, impls } => {
+            out.push_str(&format!("class {}\n", name));
+            for f in fields {
+                push_indent(out, depth + 1);
+                out.push_str(&f.name);
+                if let Some(t) = &f.ty { out.push_str(": "); out.push_str(&type_str(t)); }
+                if let Some(d) = &f.default { out.push_str(" = "); out.push_str(&expr(d, depth + 1)); }
+                out.push('\n');
+            }
+            members_into(out, methods, depth + 1, !fields.is_empty());
+            members_into(out, impls, depth + 1, !fields.is_empty() || !methods.is_empty());
+        }
+        StmtKind::Trait { name, methods } => {
+            out.push_str(&format!("trait {}\n", name));
+            members_into(out, methods, depth + 1, false);
+        }
+        StmtKind::Impl { trait_name, for_type, methods } => {
+            out.push_str("impl ");
+            out.push_str(trait_name);
+            if let Some(t) = for_type { out.push_str(" for "); out.push_str(t); }
+            out.push('\n');
+            members_into(out, methods, depth + 1, false);
+        }
+        StmtKind::While { condition, body } => {
+            out.push_str(&format!("while {}\n", expr(condition, depth)));
+            block_into(out, body, depth + 1);
+        }
+        StmtKind::For { var, iterable, body } => {
+            out.push_str(&format!("for {} in {}\n", var, expr(iterable, depth)));
+            block_into(out, body, depth + 1);
+        }
+        StmtKind::Return(value) => {
+            out.push_str("return");
+            if let Some(v) = value { out.push(' '); out.push_str(&expr(v, depth)); }
+        }
+        StmtKind::Break    => out.push_str("break"),
+        StmtKind::Continue => out.push_str("continue"),
+        StmtKind::Pass     => out.push_str("pass"),
+        StmtKind::Mod(name) => { out.push_str("mod "); out.push_str(name); }
+        StmtKind::Use(path) => { out.push_str("use "); out.push_str(&path.join("::")); }
+        StmtKind::Expr(e)  => out.push_str(&expr(e, depth)),
+    }
+    // if/match/lambda a blocchi terminano già con un newline
+    if !out.ends_with('\n') { out.push('\n'); }
+}
+
+fn params_str(params: &[Param], depth: usize) -> String {
+    params.iter().map(|p| {
+        let mut s = p.name.clone();
+        if let Some(t) = &p.ty { s.push_str(": "); s.push_str(&type_str(t)); }
+        if let Some(d) = &p.default { s.push_str(" = "); s.push_str(&expr(d, depth)); }
+        s
+    }).collect::<Vec<_>>().join(", ")
+}
+
+fn type_str(ty: &TypeExpr) -> String {
+    match &ty.inner {
+        TypeKind::Named(n) => n.clone(),
+        TypeKind::Generic(n, args) => format!("{}[{}]", n, args.iter().map(type_str).collect::<Vec<_>>().join(", ")),
+        TypeKind::Error => "?".to_string(),
+    }
+}
+
+fn binop_prec(op: &BinOp) -> Prec {
+    match op {
+        BinOp::Or => Prec::Or,
+        BinOp::And => Prec::And,
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge
+        | BinOp::Is | BinOp::IsNot | BinOp::In | BinOp::NotIn => Prec::Compare,
+        BinOp::BitOr => Prec::BitOr,
+        BinOp::BitXor => Prec::BitXor,
+        BinOp::BitAnd => Prec::BitAnd,
+        BinOp::Shl | BinOp::Shr => Prec::Shift,
+        BinOp::Add | BinOp::Sub => Prec::Add,
+        BinOp::Mul | BinOp::Div | BinOp::IntDiv | BinOp::Mod => Prec::Mul,
+        BinOp::Pow => Prec::Power,
+    }
+}
+
+fn binop_str(op: &BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+", BinOp::Sub => "-", BinOp::Mul => "*", BinOp::Div => "/",
+        BinOp::IntDiv => "//", BinOp::Mod => "%", BinOp::Pow => "**",
+        BinOp::Eq => "==", BinOp::Ne => "!=", BinOp::Lt => "<", BinOp::Le => "<=",
+        BinOp::Gt => ">", BinOp::Ge => ">=", BinOp::And => "and", BinOp::Or => "or",
+        BinOp::BitAnd => "&", BinOp::BitOr => "|", BinOp::BitXor => "^",
+        BinOp::Shl => "<<", BinOp::Shr => ">>", BinOp::Is => "is", BinOp::In => "in",
+        BinOp::IsNot => "is not", BinOp::NotIn => "not in",
+    }
+}
+
+/// Precedenza con cui un'espressione "lega" quando compare come operando.
+/// Le forme prefisse che consumano tutto ciò che segue (lambda, await, if...) valgono `None`.
+fn expr_prec(e: &Expr) -> Prec {
+    match &e.inner {
+        ExprKind::Binary { op, .. } => binop_prec(op),
+        ExprKind::Unary { op: UnaryOp::Not, .. } => Prec::Not,
+        ExprKind::Unary { .. } => Prec::Unary,
+        ExprKind::Range { .. } => Prec::Range,
+        ExprKind::Pipe { .. } => Prec::Pipe,
+        ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Lambda { .. }
+        | ExprKind::Spawn(_) | ExprKind::Await(_) => Prec::None,
+        _ => Prec::Call,
+    }
+}
+
+/// Stampa `e` tra parentesi se lega meno di `min` (o quanto `min`, se `strict`).
+fn operand(e: &Expr, min: Prec, strict: bool, depth: usize) -> String {
+    let p = expr_prec(e);
+    let s = expr(e, depth);
+    if p < min || (strict && p == min) { format!("({})", s) } else { s }
+}
+
+fn list(items: &[Expr], depth: usize) -> String {
+    items.iter().map(|e| expr(e, depth)).collect::<Vec<_>>().join(", ")
+}
+
+/// Un'espressione su una riga, come la scriverebbe il formatter.
+pub fn format_expr(e: &Expr) -> String {
+    expr(e, 0)
+}
+
+fn expr(e: &Expr, depth: usize) -> String {
+    match &e.inner {
+        ExprKind::Int(n)   => n.to_string(),
+        ExprKind::Float(f) => float_str(*f),
+        ExprKind::Bool(b)  => b.to_string(),
+        ExprKind::Str(s)   => quote(s),
+        ExprKind::FStr(s)  => format!("f{}", quote(s)),
+        ExprKind::Char(c)  => quote_char(*c),
+        ExprKind::None     => "none".to_string(),
+        ExprKind::Ident(n) => n.clone(),
+        ExprKind::Binary { op: op @ BinOp::Pow, left, right } => {
+            // `**` è associativo a destra: il lato destro è parsato a livello `Unary`
+            let p = binop_prec(op);
+            format!("{} ** {}", operand(left, p, true, depth), operand(right, Prec::Unary, false, depth))
+        }
+        ExprKind::Binary { op, left, right } => {
+            let p = binop_prec(op);
+            format!("{} {} {}", operand(left, p, false, depth), binop_str(op), operand(right, p, true, depth))
+        }
+        ExprKind::Unary { op, operand: inner } => {
+            let (sym, p) = match op {
+                UnaryOp::Neg => ("-", Prec::Unary), UnaryOp::BitNot => ("~", Prec::Unary), UnaryOp::Not => ("not ", Prec::Not),
+            };
+            format!("{}{}", sym, operand(inner, p, false, depth))
+        }
+        ExprKind::Pipe { value, func } =>
+            format!("{} |> {}", operand(value, Prec::Pipe, false, depth), operand(func, Prec::Pipe, true, depth)),
+        ExprKind::Call { callee, args, kwargs } => {
+            let mut parts: Vec<String> = args.iter().map(|a| expr(a, depth)).collect();
+            parts.extend(kwargs.iter().map(|(k, v)| format!("{}={}", k, expr(v, depth))));
+            format!("{}({})", operand(callee, Prec::Call, false, depth), parts.join(", "))
+        }
+        ExprKind::Field { object, field } => format!("{}.{}", operand(object, Prec::Call, false, depth), field),
+        ExprKind::Index { object, index } => format!("{}[{}]", operand(object, Prec::Call, false, depth), expr(index, depth)),
+        ExprKind::Slice { object, start, end, step } => {
+            let part = |x: &Option<Box<Expr>>| x.as_ref().map(|e| expr(e, depth)).unwrap_or_default();
+            let mut s = format!("{}[{}:{}", operand(object, Prec::Call, false, depth), part(start), part(end));
+            if step.is_some() { s.push(':'); s.push_str(&part(step)); }
+            s.push(']');
+            s
+        }
+        ExprKind::Array(items) => format!("[{}]", list(items, depth)),
+        ExprKind::Tuple(items) if items.len() == 1 => format!("({},)", expr(&items[0], depth)),
+        ExprKind::Tuple(items) => format!("({})", list(items, depth)),
+        ExprKind::Dict(pairs) => {
+            let items: Vec<String> = pairs.iter().map(|(k, v)| format!("{}: {}", expr(k, depth), expr(v, depth))).collect();
+            format!("{{{}}}", items.join(", "))
+        }
+        ExprKind::Range { start, end, inclusive } => {
+            // Il lato destro di un range è parsato a livello `Add`
+            format!("{}{}{}", operand(start, Prec::Range, false, depth),
+                if *inclusive { "..=" } else { ".." }, operand(end, Prec::Add, true, depth))
+        }
+        ExprKind::If { condition, then_block, elif_branches, else_block } => {
+            let mut s = format!("if {}\n", expr(condition, depth));
+            block_into(&mut s, then_block, depth + 1);
+            for (cond, body) in elif_branches {
+                push_indent(&mut s, depth);
+                s.push_str(&format!("elif {}\n", expr(cond, depth)));
+                block_into(&mut s, body, depth + 1);
+            }
+            if let Some(body) = else_block {
+                push_indent(&mut s, depth);
+                s.push_str("else\n");
+                block_into(&mut s, body, depth + 1);
+            }
+            s
+        }
+        ExprKind::Match { subject, arms } => {
+            let mut s = format!("match {}\n", expr(subject, depth));
+            for arm in arms {
+                push_indent(&mut s, depth + 1);
+                s.push_str(&pattern_str(&arm.pattern));
+                match arm.body.as_slice() {
+                    [single] if is_inline(single) => {
+                        let mut line = String::new();
+                        stmt_into(&mut line, single, 0);
+                        s.push_str(" => ");
+                        s.push_str(&line);
+                    }
+                    body => {
+                        s.push('\n');
+                        block_into(&mut s, body, depth + 2);
+                    }
+                }
+            }
+            s
+        }
+        ExprKind::Spawn(inner) => format!("spawn {}", expr(inner, depth)),
+        ExprKind::Await(inner) => format!("await {}", expr(inner, depth)),
+        ExprKind::Some(inner)  => format!("Some({})", expr(inner, depth)),
+        ExprKind::Ok(inner)    => format!("Ok({})", expr(inner, depth)),
+        ExprKind::Err(inner)   => format!("Err({})", expr(inner, depth)),
+        ExprKind::Try(inner)   => format!("{}?", operand(inner, Prec::Call, false, depth)),
+        ExprKind::Lambda { params, body } => match body.as_slice() {
+            [Node { inner: StmtKind::Return(Some(ret)), .. }] => format!("fn({}) => {}", params_str(params, depth), expr(ret, depth)),
+            _ => {
+                let mut s = format!("fn({})\n", params_str(params, depth));
+                block_into(&mut s, body, depth + 1);
+                s
+            }
+        },
+        ExprKind::Error => "?".to_string(),
+    }
+}
+
+/// Un braccio di match sta su una riga se il corpo è un solo statement senza blocchi.
+fn is_inline(stmt: &Stmt) -> bool {
+    let mut line = String::new();
+    stmt_into(&mut line, stmt, 0);
+    line.matches('\n').count() == 1
+        && !matches!(stmt.inner, StmtKind::Fn { .. } | StmtKind::Class { .. } | StmtKind::Trait { .. } | StmtKind::Impl { .. })
+}
+
+fn pattern_str(p: &Pattern) -> String {
+    match p {
+        Pattern::Wildcard => "_".to_string(),
+        Pattern::Literal(kind) => expr(&Node::new(kind.clone(), neba_lexer::Span::new(0, 0, 0, 0)), 0),
+        Pattern::Ident(n) => n.clone(),
+        // `Some`/`Ok`/`Err` senza argomenti si scrivono senza parentesi
+        Pattern::Constructor(n, inner) if inner.is_empty() && matches!(n.as_str(), "Some" | "Ok" | "Err") => n.clone(),
+        Pattern::Constructor(n, inner) => format!("{}({})", n, inner.iter().map(pattern_str).collect::<Vec<_>>().join(", ")),
+        Pattern::Range { start, end, inclusive } => format!("{}{}{}", pattern_str(start), if *inclusive { "..=" } else { ".." }, pattern_str(end)),
+        Pattern::Or(alts) => alts.iter().map(pattern_str).collect::<Vec<_>>().join(" | "),
+        Pattern::Tuple(items) if items.len() == 1 => format!("({},)", pattern_str(&items[0])),
+        Pattern::Tuple(items) => format!("({})", items.iter().map(pattern_str).collect::<Vec<_>>().join(", ")),
+        Pattern::StrAffix { prefix, rest, suffix } => {
+            let lit = |s: &str| pattern_str(&Pattern::Literal(ExprKind::Str(s.to_string())));
+            let mut parts = Vec::new();
+            if !prefix.is_empty() { parts.push(lit(prefix)); }
+            parts.push(pattern_str(rest));
+            if !suffix.is_empty() { parts.push(lit(suffix)); }
+            parts.join(" + ")
+        }
+        Pattern::Error => "?".to_string(),
+    }
+}
+
+/// Float con almeno una cifra decimale, così da essere riletto come Float.
+fn float_str(f: f64) -> String {
+    let s = format!("{:?}", f);
+    if s.contains(['.', 'e', 'E']) || !f.is_finite() { s } else { format!("{}.0", s) }
+}
+
+/// Come `quote`, tra apici singoli; l'apice va sempre escapato.
+fn quote_char(c: char) -> String {
+    if c == '\'' { return "'\\''".to_string(); }
+    let q = quote(&c.to_string());
+    format!("'{}'", &q[1..q.len() - 1])
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c    => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
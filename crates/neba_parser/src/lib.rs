@@ -1,18 +1,32 @@
 pub mod ast;
+pub mod doc;
+pub mod dump;
 pub mod error;
+pub mod format;
+pub mod outline;
 pub mod parser;
 
 pub use ast::*;
+pub use doc::{doc_examples, DocExample, DocStep};
+pub use dump::{dump_program, AstDump};
 pub use error::{ParseError, ParseResult};
+pub use format::{format_expr, format_program};
+pub use outline::{outline, Symbol, SymbolKind};
 pub use parser::Parser;
 
 use neba_lexer::tokenize as lex;
 
 pub fn parse(source: &str) -> (Program, Vec<neba_lexer::LexError>, Vec<ParseError>) {
-    let (tokens, lex_errors) = lex(source);
+    let (program, lex_errors, _, parse_errors) = parse_with_warnings(source);
+    (program, lex_errors, parse_errors)
+}
+
+/// Come `parse`, ma restituisce anche i warning del lexer (per `neba fmt` e i diagnostici).
+pub fn parse_with_warnings(source: &str) -> (Program, Vec<neba_lexer::LexError>, Vec<neba_lexer::LexError>, Vec<ParseError>) {
+    let (tokens, lex_errors, lex_warnings) = lex(source);
     let mut parser = Parser::new(tokens);
     let program = parser.parse();
-    (program, lex_errors, parser.errors)
+    (program, lex_errors, lex_warnings, parser.errors)
 }
 
 #[cfg(test)]
@@ -36,7 +50,7 @@ mod tests {
     }
 
     #[test] fn test_int_literal()   { assert_eq!(first_expr("42"), ExprKind::Int(42)); }
-    #[test] fn test_float_literal() { assert_eq!(first_expr("3.14"), ExprKind::Float(3.14)); }
+    #[test] #[allow(clippy::approx_constant)] fn test_float_literal() { assert_eq!(first_expr("3.14"), ExprKind::Float(3.14)); }
     #[test] fn test_bool_literal()  { assert_eq!(first_expr("true"), ExprKind::Bool(true)); }
     #[test] fn test_none_literal()  { assert_eq!(first_expr("None"), ExprKind::None); }
     #[test] fn test_string_literal()  { assert_eq!(first_expr(r#""hello""#), ExprKind::Str("hello".to_string())); }
@@ -86,6 +100,125 @@ mod tests {
 
     #[test] fn test_array_literal()   { match first_expr("[1, 2, 3]") { ExprKind::Array(v) => assert_eq!(v.len(), 3), _ => panic!() } }
     #[test] fn test_empty_array()     { assert!(matches!(first_expr("[]"), ExprKind::Array(v) if v.is_empty())); }
+    #[test] fn test_trailing_comma_array()  { assert!(matches!(first_expr("[1, 2,]"), ExprKind::Array(v) if v.len() == 2)); }
+    #[test] fn test_trailing_comma_dict()   { assert!(matches!(first_expr("{\"a\": 1,}"), ExprKind::Dict(v) if v.len() == 1)); }
+    #[test] fn test_dict_literals() {
+        assert!(matches!(first_expr("{}"), ExprKind::Dict(v) if v.is_empty()));
+        match first_expr("{\"a\": {\"b\": 1}, 2: [3]}") {
+            ExprKind::Dict(v) => {
+                assert_eq!(v.len(), 2);
+                assert!(matches!(&v[0].1.inner, ExprKind::Dict(inner) if inner.len() == 1));
+            }
+            _ => panic!(),
+        }
+        // Su più righe, con virgola finale
+        match first_stmt("let m = {\n    \"a\": 1,\n    \"b\": 2,\n}\nm") {
+            StmtKind::Let { value: e, .. } => assert!(matches!(e.inner, ExprKind::Dict(ref v) if v.len() == 2)),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(first_expr("{\"a\" => 1, \"b\": 2}"), ExprKind::Dict(v) if v.len() == 2));
+        let (_, _, errors) = parse("{\"a\" 1}");
+        assert!(matches!(&errors[0], ParseError::UnexpectedToken { expected, .. } if expected.contains("':' or '=>'")));
+    }
+    #[test] fn test_tuple_literals() {
+        assert!(matches!(first_expr("(1, \"a\")"), ExprKind::Tuple(v) if v.len() == 2));
+        assert!(matches!(first_expr("(1, \"a\", true,)"), ExprKind::Tuple(v) if v.len() == 3));
+        assert!(matches!(first_expr("(x,)"), ExprKind::Tuple(v) if v.len() == 1));
+        // Senza virgola è solo raggruppamento
+        assert!(matches!(first_expr("(x)"), ExprKind::Ident(n) if n == "x"));
+        assert!(matches!(first_expr("(1 + 2) * 3"), ExprKind::Binary { op: BinOp::Mul, .. }));
+        let ExprKind::Match { arms, .. } = first_expr("match t\n    case (a, (b,)) => a\n    case (_, 1, c) => c\n    case (x) => x\n") else { panic!() };
+        assert_eq!(arms[0].pattern, Pattern::Tuple(vec![Pattern::Ident("a".into()), Pattern::Tuple(vec![Pattern::Ident("b".into())])]));
+        assert!(matches!(&arms[1].pattern, Pattern::Tuple(ps) if ps.len() == 3));
+        assert_eq!(arms[2].pattern, Pattern::Ident("x".into()));
+    }
+    #[test] fn test_trailing_comma_call()   {
+        assert!(matches!(first_expr("f(1, x=2,)"), ExprKind::Call { args, kwargs, .. } if args.len() == 1 && kwargs.len() == 1));
+        assert!(matches!(first_expr("f(\n    1,\n    2,\n)"), ExprKind::Call { args, .. } if args.len() == 2));
+    }
+    #[test] fn test_trailing_comma_params() {
+        assert!(matches!(first_stmt("fn f(a, b = 1,)\n    pass\n"), StmtKind::Fn { params, .. } if params.len() == 2));
+        assert!(matches!(first_stmt("fn f(\n    a,\n    b: Int,\n) -> Int\n    return a\n"), StmtKind::Fn { params, body, .. } if params.len() == 2 && body.len() == 1));
+    }
+    #[test] fn test_trailing_comma_generic() {
+        match first_stmt("let d: Dict[Str, Int,] = {}") {
+            StmtKind::Let { ty: Some(ty), .. } => assert!(matches!(ty.inner, TypeKind::Generic(_, args) if args.len() == 2)),
+            other => panic!("{:?}", other),
+        }
+    }
+    #[test] fn test_trailing_comma_pattern() {
+        parse_ok("match x\n    Some(a,) => a\n    Point(p, q,) => p\n    _ => 0\n");
+    }
+    #[test] fn test_semicolon_two_stmts() {
+        let p = parse_ok("let x = 1; let y = 2");
+        assert!(matches!(&p.stmts[..], [a, b] if matches!(&a.inner, StmtKind::Let { name, .. } if name == "x")
+                                               && matches!(&b.inner, StmtKind::Let { name, .. } if name == "y")));
+    }
+    #[test] fn test_semicolon_three_stmts() {
+        let p = parse_ok("var x = 1; x += 2; print(x);\nfn f()\n    let a = 1; return a\n");
+        assert_eq!(p.stmts.len(), 4);
+        assert!(matches!(&p.stmts[1].inner, StmtKind::Assign { op: AssignOp::AddAssign, .. }));
+        assert!(matches!(&p.stmts[3].inner, StmtKind::Fn { body, .. } if body.len() == 2));
+    }
+    #[test] fn test_assign_in_expression_is_error() {
+        for src in ["let y = (x = 5)", "if x = 5\n    pass\n", "f(a.b = 1)", "x = y = 5", "[x += 1]"] {
+            let (_, _, errors) = parse(src);
+            assert!(matches!(errors.as_slice(), [ParseError::AssignInExpression { .. }]), "{}: {:?}", src, errors);
+        }
+        // Gli usi legittimi di `=` restano validi
+        parse_ok("x = 5\nobj.f = x == 5\na[i] += 1\nf(x=1)\nfn g(a = 1)\n    pass\nmatch x\n    _ => y = 2\n");
+        let (_, _, errors) = parse("a + b = 1");
+        assert!(matches!(errors.as_slice(), [ParseError::InvalidAssignTarget { .. }]));
+    }
+    #[test] fn test_not_in_with_logical() {
+        let is_not_in = |e: &Expr| matches!(e.inner, ExprKind::Binary { op: BinOp::NotIn, .. });
+        assert!(matches!(first_expr("a not in b or c"),  ExprKind::Binary { op: BinOp::Or,  left, .. }  if is_not_in(&left)));
+        assert!(matches!(first_expr("a not in b and c"), ExprKind::Binary { op: BinOp::And, left, .. }  if is_not_in(&left)));
+        assert!(matches!(first_expr("c or a not in b"),  ExprKind::Binary { op: BinOp::Or,  right, .. } if is_not_in(&right)));
+        assert!(matches!(first_expr("not a not in b"),   ExprKind::Unary { op: UnaryOp::Not, operand } if is_not_in(&operand)));
+    }
+    #[test] fn test_not_in_with_arithmetic() {
+        match first_expr("a + 1 not in b * 2") {
+            ExprKind::Binary { op: BinOp::NotIn, left, right } => {
+                assert!(matches!(left.inner,  ExprKind::Binary { op: BinOp::Add, .. }));
+                assert!(matches!(right.inner, ExprKind::Binary { op: BinOp::Mul, .. }));
+            }
+            other => panic!("{:?}", other),
+        }
+        // Stessa precedenza dei confronti, associativa a sinistra
+        assert!(matches!(first_expr("a not in b == c"), ExprKind::Binary { op: BinOp::Eq, left, .. }
+            if matches!(left.inner, ExprKind::Binary { op: BinOp::NotIn, .. })));
+    }
+    #[test] fn test_is_not() {
+        assert!(matches!(first_expr("x is not Int"), ExprKind::Binary { op: BinOp::IsNot, right, .. } if matches!(&right.inner, ExprKind::Ident(n) if n == "Int")));
+        assert!(matches!(first_expr("x is not None"), ExprKind::Binary { op: BinOp::IsNot, right, .. } if matches!(right.inner, ExprKind::None)));
+        assert!(matches!(first_expr("x is not None and y"), ExprKind::Binary { op: BinOp::And, left, .. }
+            if matches!(left.inner, ExprKind::Binary { op: BinOp::IsNot, .. })));
+        assert!(matches!(first_expr("x is Int"), ExprKind::Binary { op: BinOp::Is, .. }));
+    }
+    #[test] fn test_dot_without_field_name() {
+        for (src, found) in [("obj.", neba_lexer::TokenKind::Eof), ("obj.(1)", neba_lexer::TokenKind::LParen), ("a.b.5\nx", neba_lexer::TokenKind::IntLiteral(5))] {
+            let (program, _, errors) = parse(src);
+            assert!(matches!(errors.as_slice(), [ParseError::UnexpectedToken { expected, found: f, .. }]
+                if expected == "field name after '.'" && *f == found), "{}: {:?}", src, errors);
+            assert!(!format!("{:?}", program).contains("\"?\""));
+        }
+    }
+    #[test] fn test_duplicate_class_members() {
+        let dup = |src: &str| -> Vec<String> {
+            parse(src).2.into_iter().filter_map(|e| match e { ParseError::DuplicateMember { name, .. } => Some(name), _ => None }).collect()
+        };
+        assert_eq!(dup("class P\n    x: Int\n    x: Str\n"), vec!["x"]);
+        assert_eq!(dup("class P\n    fn f(self)\n        pass\n    fn f(self, a)\n        pass\n"), vec!["f"]);
+        assert_eq!(dup("class P\n    size: Int\n    fn size(self)\n        return 1\n"), vec!["size"]);
+        assert!(dup("class P\n    x: Int\n    y: Int\n    fn f(self)\n        pass\n").is_empty());
+    }
+    #[test] fn test_param_order() {
+        let errors = |src: &str| parse(src).2;
+        assert!(matches!(errors("fn f(a = 1, b)\n    pass\n").as_slice(), [ParseError::RequiredAfterDefault { name, .. }] if name == "b"));
+        assert!(matches!(errors("class P\n    fn m(a, self)\n        pass\n").as_slice(), [ParseError::SelfNotFirst { .. }]));
+        assert!(errors("fn f(a, b = 1, c = 2)\n    pass\nclass P\n    fn m(self, a = 1)\n        pass\n").is_empty());
+    }
     #[test] fn test_exclusive_range() { assert!(matches!(first_expr("0..10"), ExprKind::Range { inclusive: false, .. })); }
     #[test] fn test_inclusive_range() { assert!(matches!(first_expr("0..=10"), ExprKind::Range { inclusive: true, .. })); }
 
@@ -98,6 +231,8 @@ mod tests {
             _ => panic!()
         }
     }
+    #[test] fn test_pure_fn() { assert!(matches!(first_stmt("pure fn sq(x)\n    return x * x\n"), StmtKind::Fn { is_pure: true, .. })); }
+    #[test] fn test_pure_as_ident() { assert!(matches!(first_stmt("let pure = 1\n"), StmtKind::Let { .. })); }
     #[test] fn test_async_fn() { assert!(matches!(first_stmt("async fn fetch()\n    pass\n"), StmtKind::Fn { is_async: true, .. })); }
 
     #[test] fn test_if_stmt()  { assert!(matches!(first_stmt("if x > 0\n    pass\n"), StmtKind::Expr(e) if matches!(e.inner, ExprKind::If { .. }))); }
@@ -112,12 +247,16 @@ mod tests {
     #[test] fn test_return_with_value() {
         match first_stmt("fn f()\n    return 42\n") { StmtKind::Fn { body, .. } => assert!(matches!(body[0].inner, StmtKind::Return(Some(_)))), _ => panic!() }
     }
+    #[test] fn test_fn_body_last_line_no_trailing_newline() {
+        match first_stmt("fn f()\n    return 1") { StmtKind::Fn { body, .. } => assert!(matches!(body[0].inner, StmtKind::Return(Some(_)))), _ => panic!() }
+    }
     #[test] fn test_return_without_value() {
         match first_stmt("fn f()\n    return\n") { StmtKind::Fn { body, .. } => assert!(matches!(body[0].inner, StmtKind::Return(None))), _ => panic!() }
     }
 
     #[test] fn test_simple_assign()   { assert!(matches!(first_stmt("x = 10"), StmtKind::Assign { op: AssignOp::Assign, .. })); }
     #[test] fn test_compound_assign() { assert!(matches!(first_stmt("x += 5"), StmtKind::Assign { op: AssignOp::AddAssign, .. })); }
+    #[test] fn test_slice_assign()    { assert!(matches!(first_stmt("a[1:3] = b"), StmtKind::Assign { target: Expr { inner: ExprKind::Slice { .. }, .. }, .. })); }
 
     #[test] fn test_match_basic() {
         match first_stmt("match x\n    case 1 => pass\n    case _ => pass\n") {
@@ -142,7 +281,46 @@ mod tests {
         assert!(errors.is_empty(), "{:?}", errors);
     }
 
+    #[test] fn test_match_str_affix_pattern() {
+        let src = "match url\n    \"http://\" + rest => rest\n    nome + \".txt\" => nome\n    \"<\" + _ + \">\" => 0\n";
+        let ExprKind::Match { arms, .. } = first_expr(src) else { panic!() };
+        let affix = |i: usize| match &arms[i].pattern {
+            Pattern::StrAffix { prefix, rest, suffix } => (prefix.clone(), (**rest).clone(), suffix.clone()),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(affix(0), ("http://".into(), Pattern::Ident("rest".into()), "".into()));
+        assert_eq!(affix(1), ("".into(), Pattern::Ident("nome".into()), ".txt".into()));
+        assert_eq!(affix(2), ("<".into(), Pattern::Wildcard, ">".into()));
+        let (_, _, errors) = parse("match s\n    \"a\" + 1 => 0\n");
+        assert!(!errors.is_empty());
+    }
+
+    #[test] fn test_fn_contracts() {
+        let src = "fn radice(x)\n    requires x >= 0\n    ensures result * result <= x\n    requires = 1\n    return x\n";
+        let StmtKind::Fn { contracts, body, .. } = first_stmt(src) else { panic!() };
+        assert_eq!((contracts.requires.len(), contracts.ensures.len()), (1, 1));
+        // Solo in testa e seguite da un'espressione: qui `requires` è una variabile
+        assert_eq!(body.len(), 2);
+        assert_eq!(crate::format_program(&parse(src).0), src);
+    }
+
     #[test] fn test_spawn_expr() { assert!(matches!(first_expr("spawn compute(data)"), ExprKind::Spawn(_))); }
+    #[test] fn test_pipe_expr() {
+        // Più bassa di `or`, associativa a sinistra: ((a or b) |> f) |> g(1)
+        let ExprKind::Pipe { value, func } = first_expr("a or b |> f |> g(1)") else { panic!("atteso Pipe") };
+        assert!(matches!(func.inner, ExprKind::Call { .. }));
+        let ExprKind::Pipe { value: inner, .. } = value.inner else { panic!("atteso Pipe annidato") };
+        assert!(matches!(inner.inner, ExprKind::Binary { op: BinOp::Or, .. }));
+        let f = Node::new(ExprKind::Ident("f".into()), neba_lexer::Span::new(1, 1, 0, 1));
+        let x = Node::new(ExprKind::Int(1), neba_lexer::Span::new(1, 1, 0, 1));
+        assert!(matches!(ExprKind::pipe_call(&x, &f), ExprKind::Call { args, .. } if args == vec![x.clone()]));
+    }
+    #[test] fn test_line_continuation() {
+        let program = parse_ok("let x = 1 + \\\n    2 * \\\n3\nx");
+        assert_eq!(program.stmts.len(), 2);
+        assert!(matches!(&program.stmts[0].inner, StmtKind::Let { value, .. }
+            if matches!(value.inner, ExprKind::Binary { op: BinOp::Add, .. })));
+    }
     #[test] fn test_await_expr() { assert!(matches!(first_expr("await handle"), ExprKind::Await(_))); }
     #[test] fn test_some_expr()  { assert!(matches!(first_expr("Some(42)"), ExprKind::Some(_))); }
     #[test] fn test_ok_expr()    { assert!(matches!(first_expr("Ok(value)"), ExprKind::Ok(_))); }
@@ -171,6 +349,108 @@ mod tests {
         assert!(!program.stmts.is_empty());
     }
 
+    #[test] fn test_error_span() {
+        let (_, _, parse_errors) = parse("let x = 1\nlet = 42\n");
+        assert_eq!((parse_errors[0].span().line, parse_errors[0].span().column), (2, 5));
+    }
+
+    #[test] fn test_error_codes() {
+        let (_, _, parse_errors) = parse("let = 42\n");
+        assert_eq!(parse_errors[0].code(), "P0001");
+        assert!(parse_errors[0].to_string().starts_with("[ParseError P0001] "));
+    }
+
+    #[test] fn test_outline() {
+        let src = "fn main()\n    fn helper()\n        pass\n    pass\nclass Point\n    x: Int\n    y: Int\n    fn norm(self)\n        return 0\n    fn scale(self, k)\n        pass\ntrait Shape\n    fn area(self)\nimpl Shape for Point\n    fn area(self)\n        return 0\nlet z = 1";
+        let syms = outline(&parse_ok(src));
+        let flat = |s: &[Symbol]| s.iter().map(|s| (s.name.clone(), s.kind, s.span.line)).collect::<Vec<_>>();
+        assert_eq!(flat(&syms), vec![
+            ("main".into(), SymbolKind::Function, 1), ("Point".into(), SymbolKind::Class, 5),
+            ("Shape".into(), SymbolKind::Trait, 12), ("Shape for Point".into(), SymbolKind::Impl, 14),
+        ]);
+        assert_eq!(flat(&syms[0].children), vec![("helper".into(), SymbolKind::Function, 2)]);
+        assert_eq!(flat(&syms[1].children), vec![
+            ("x".into(), SymbolKind::Field, 6), ("y".into(), SymbolKind::Field, 7),
+            ("norm".into(), SymbolKind::Method, 8), ("scale".into(), SymbolKind::Method, 10),
+        ]);
+        assert_eq!(flat(&syms[2].children), vec![("area".into(), SymbolKind::Method, 13)]);
+        assert_eq!(flat(&syms[3].children), vec![("area".into(), SymbolKind::Method, 15)]);
+    }
+
+    fn fmt(src: &str) -> String { format_program(&parse_ok(src)) }
+    #[test] fn test_format_messy() {
+        let src = "let  x=1+2*3\nvar s=\"it's\"\nfn add( a:Int,b = (2) )->Int\n  return (a+b)\nclass P\n  x: Int\n  fn get(self)\n     return self.x\nif x>1 and not(x==3)\n  print(x)\nelif x<0\n  pass\nelse\n  print(-(x+1)**2)\nlet ys=[1,2,  3][0:2]\nlet d={'a':1}\nlet f=fn(v)=>v*(v-1)\nmatch x\n  1|2 => print(\"small\")\n  _ => pass\n";
+        let out = fmt(src);
+        assert_eq!(out, "let x = 1 + 2 * 3\nvar s = \"it's\"\n\nfn add(a: Int, b = 2) -> Int\n    return a + b\n\nclass P\n    x: Int\n\n    fn get(self)\n        return self.x\n\nif x > 1 and not x == 3\n    print(x)\nelif x < 0\n    pass\nelse\n    print(-(x + 1) ** 2)\nlet ys = [1, 2, 3][0:2]\nlet d = {'a': 1}\nlet f = fn(v) => v * (v - 1)\nmatch x\n    1 | 2 => print(\"small\")\n    _ => pass\n");
+        assert_eq!(fmt(&out), out);
+    }
+    #[test] fn test_format_parens_stable() {
+        for src in ["let a = (1 + 2) * 3", "let b = 2 ** 3 ** 2", "let c = (2 ** 3) ** 2", "let d = (-2) ** 2",
+                    "let e = (a - b) - (c - d)", "let f = (not a) == b", "let g = 0..(n + 1)", "let h = (x or y) and z",
+                    "let i = (-x).abs()", "let j = a not in b", "let m = xs |> map(f) |> (fn(v) => v)",
+                    "let n = a |> (b |> c)", "let o = (a |> f) + 1", "let k = f\"{n} \\\"q\\\"\"", "let l = 1.0e10 + 0.5",
+                    "let p = ['\\'', '\"', '\\n', 'é'] + [\"'\"]", "let q = ((1, 2), (x,))[0]",
+                    "let r = match t\n    (a, (b,)) => a + b\n"] {
+            let once = fmt(src);
+            assert_eq!(fmt(&once), once, "non idempotente: {}", src);
+            assert_eq!(dump_program(&parse_ok(&once)).lines().map(|l| l.split(" @").next().unwrap().to_string()).collect::<Vec<_>>(),
+                       dump_program(&parse_ok(src)).lines().map(|l| l.split(" @").next().unwrap().to_string()).collect::<Vec<_>>(), "{}", src);
+        }
+    }
+
+    #[test] fn test_nesting_too_deep() {
+        let mut src: String = (0..200).map(|i| format!("{}if x\n", "    ".repeat(i))).collect();
+        src.push_str(&format!("{}pass\nlet y = 1\n", "    ".repeat(200)));
+        let (program, lex_errors, parse_errors) = parse(&src);
+        assert!(lex_errors.is_empty(), "{:?}", lex_errors);
+        assert!(matches!(parse_errors[0], ParseError::NestingTooDeep { max: parser::MAX_NESTING_DEPTH, .. }));
+        assert!(matches!(program.stmts.last().unwrap().inner, StmtKind::Let { .. }));
+    }
+    #[test] fn test_nesting_custom_limit() {
+        let (tokens, _, _) = lex("if a\n    if b\n        pass\n");
+        let mut p = Parser::new(tokens);
+        p.set_max_nesting(1);
+        p.parse();
+        assert_eq!(p.errors.len(), 1);
+        assert!(matches!(p.errors[0], ParseError::NestingTooDeep { max: 1, .. }));
+    }
+
+    #[test] fn test_expression_too_deep() {
+        let src = format!("let x = {}1{}\nlet y = 2\n", "(".repeat(5000), ")".repeat(5000));
+        let (program, _, parse_errors) = parse(&src);
+        assert_eq!(parse_errors.len(), 1, "{:?}", parse_errors);
+        assert!(matches!(parse_errors[0], ParseError::ExpressionTooDeep { .. }));
+        assert!(matches!(&program.stmts[1].inner, StmtKind::Let { name, .. } if name == "y"));
+    }
+    #[test] fn test_nested_parens_within_limit() { assert_eq!(first_expr(&format!("{}7{}", "(".repeat(100), ")".repeat(100))), ExprKind::Int(7)); }
+
+    #[test] fn test_ast_dump() {
+        let dump = dump_program(&parse_ok("fn add(a, b)\n    return a + b\nlet x = add(1, 2)\n"));
+        let labels = ["Program", "Fn add @1:1", "params", "Param a", "Param b", "body", "Return @2:5",
+                      "Binary +", "Ident a", "Ident b", "Let x @3:1", "Call", "Ident add", "args", "Int 1", "Int 2"];
+        let mut rest = dump.as_str();
+        for label in labels {
+            let at = rest.find(label).unwrap_or_else(|| panic!("missing '{}' in:\n{}", label, dump));
+            rest = &rest[at + label.len()..];
+        }
+        assert!(dump.contains("\n        Binary + @2:12"), "{}", dump);
+    }
+
+    #[test] fn test_doc_examples() {
+        let src = "## Raddoppia.\n## ```neba\n## doppio(21)  # => 42\n## let y = 1\n## y + 1\n## # => 2\n## print(y)\n## ```\nfn doppio(x)\n    return x * 2\n## ```text\n## non eseguito\n## ```\n    ## ```\n    ## doppio(0)\n    ## ```\n# ```\n";
+        let ex = doc_examples(src);
+        assert_eq!(ex.len(), 2);
+        assert_eq!(ex[0].line, 2);
+        let steps: Vec<(&str, Option<&str>, usize)> = ex[0].steps.iter()
+            .map(|s| (s.code.as_str(), s.expected.as_deref(), s.line)).collect();
+        assert_eq!(steps, vec![
+            ("doppio(21)  \n", Some("42"), 3),
+            ("let y = 1\ny + 1\n", Some("2"), 6),
+            ("print(y)\n", None, 7),
+        ]);
+        assert_eq!(ex[1].steps[0].code, "doppio(0)\n");
+        assert!(doc_examples("## ```\n## 1  # => 1\nfn f()\n    pass\n").is_empty());
+    }
     #[test] fn test_full_program() {
         let src = "let x = 42\nvar name = \"Neba\"\n\nfn add(a: Int, b: Int) -> Int\n    return a + b\n\nlet result = add(10, 20)\n\nif result > 0\n    pass\nelse\n    pass\n\nfor i in 0..5\n    pass\n\nlet maybe: Option[Int] = Some(99)\n\nmatch maybe\n    case Some(v) => pass\n    case None => pass\n";
         let (program, lex_errors, parse_errors) = parse(src);
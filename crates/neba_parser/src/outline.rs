@@ -0,0 +1,67 @@
+//! Outline dei simboli di un programma (vista "struttura" degli editor):
+//! funzioni, classi con campi e metodi, trait e impl, in ordine di sorgente.
+
+use neba_lexer::Span;
+
+use crate::ast::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Class,
+    Field,
+    Method,
+    Trait,
+    Impl,
+}
+
+/// Un simbolo dell'outline; `children` contiene campi e metodi di classi,
+/// trait e impl, e le funzioni annidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name:     String,
+    pub kind:     SymbolKind,
+    pub span:     Span,
+    pub children: Vec<Symbol>,
+}
+
+/// Simboli definiti in `program`, in ordine di apparizione.
+pub fn outline(program: &Program) -> Vec<Symbol> {
+    block_symbols(&program.stmts)
+}
+
+fn block_symbols(stmts: &[Stmt]) -> Vec<Symbol> {
+    stmts.iter().filter_map(stmt_symbol).collect()
+}
+
+fn stmt_symbol(stmt: &Stmt) -> Option<Symbol> {
+    let symbol = |name: &str, kind, children| Symbol { name: name.to_string(), kind, span: stmt.span.clone(), children };
+    match &stmt.inner {
+        StmtKind::Fn { name, body, .. } => Some(symbol(name, SymbolKind::Function, block_symbols(body))),
+        StmtKind::Class { name, fields, methods, impls } => {
+            let mut children: Vec<Symbol> = fields.iter()
+                .map(|f| Symbol { name: f.name.clone(), kind: SymbolKind::Field, span: f.span.clone(), children: Vec::new() })
+                .collect();
+            children.extend(methods.iter().chain(impls).filter_map(method_symbol));
+            Some(symbol(name, SymbolKind::Class, children))
+        }
+        StmtKind::Trait { name, methods } => {
+            Some(symbol(name, SymbolKind::Trait, methods.iter().filter_map(method_symbol).collect()))
+        }
+        StmtKind::Impl { trait_name, for_type, methods } => {
+            let name = match for_type {
+                Some(ty) => format!("{} for {}", trait_name, ty),
+                None     => trait_name.clone(),
+            };
+            Some(symbol(&name, SymbolKind::Impl, methods.iter().filter_map(method_symbol).collect()))
+        }
+        _ => None,
+    }
+}
+
+fn method_symbol(stmt: &Stmt) -> Option<Symbol> {
+    stmt_symbol(stmt).map(|s| match s.kind {
+        SymbolKind::Function => Symbol { kind: SymbolKind::Method, ..s },
+        _ => s,
+    })
+}
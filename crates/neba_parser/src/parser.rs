@@ -1,16 +1,24 @@
+use std::rc::Rc;
+
 use neba_lexer::{Span, Token, TokenKind};
 use crate::ast::*;
 use crate::error::{ParseError, ParseResult};
 
+/// Profondità massima di blocchi annidati accettata di default dal parser.
+pub const MAX_NESTING_DEPTH: usize = 64;
+/// Profondità massima di ricorsione di `parse_expr` (parentesi, unari, catene annidate).
+pub const MAX_EXPR_DEPTH: usize = 128;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-enum Prec {
-    None=0, Or=1, And=2, Not=3, Compare=4, Range=5,
-    BitOr=6, BitXor=7, BitAnd=8, Shift=9, Add=10,
-    Mul=11, Unary=12, Power=13, Call=14,
+pub(crate) enum Prec {
+    None=0, Pipe=1, Or=2, And=3, Not=4, Compare=5, Range=6,
+    BitOr=7, BitXor=8, BitAnd=9, Shift=10, Add=11,
+    Mul=12, Unary=13, Power=14, Call=15,
 }
 
 fn infix_prec(tok: &TokenKind) -> Option<(Prec, bool)> {
     match tok {
+        TokenKind::PipeGreater                                      => Some((Prec::Pipe,    false)),
         TokenKind::Or | TokenKind::Pipe2                            => Some((Prec::Or,      false)),
         TokenKind::And | TokenKind::Ampersand2                      => Some((Prec::And,     false)),
         TokenKind::EqualEqual | TokenKind::BangEqual
@@ -32,15 +40,38 @@ fn infix_prec(tok: &TokenKind) -> Option<(Prec, bool)> {
     }
 }
 
+fn assign_op(kind: &TokenKind) -> Option<AssignOp> {
+    match kind {
+        TokenKind::Equal        => Some(AssignOp::Assign),
+        TokenKind::PlusEqual    => Some(AssignOp::AddAssign),
+        TokenKind::MinusEqual   => Some(AssignOp::SubAssign),
+        TokenKind::StarEqual    => Some(AssignOp::MulAssign),
+        TokenKind::SlashEqual   => Some(AssignOp::DivAssign),
+        TokenKind::PercentEqual => Some(AssignOp::ModAssign),
+        _ => None,
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
     pub errors: Vec<ParseError>,
+    block_depth: usize,
+    max_nesting: usize,
+    expr_depth: usize,
+    /// `true` solo mentre si parsa il lato sinistro di uno statement: lì `=` è
+    /// un'assegnazione, in ogni altro contesto è un errore (P0009)
+    stmt_target: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, pos: 0, errors: Vec::new() }
+        Parser { tokens, pos: 0, errors: Vec::new(), block_depth: 0, max_nesting: MAX_NESTING_DEPTH, expr_depth: 0, stmt_target: false }
+    }
+
+    /// Imposta la profondità massima di blocchi annidati (default: `MAX_NESTING_DEPTH`).
+    pub fn set_max_nesting(&mut self, depth: usize) {
+        self.max_nesting = depth;
     }
 
     fn peek(&self) -> &Token {
@@ -53,6 +84,11 @@ impl Parser {
         tok
     }
     fn current_span(&self) -> Span { self.peek().span.clone() }
+    /// `pure` è una keyword contestuale: vale solo se seguita da `fn`.
+    fn at_pure_fn(&self) -> bool {
+        matches!(self.peek_kind(), TokenKind::Identifier(s) if s == "pure")
+            && matches!(self.tokens.get(self.pos + 1).map(|t| &t.kind), Some(TokenKind::Fn))
+    }
     fn match_tok(&mut self, kind: &TokenKind) -> bool {
         if std::mem::discriminant(self.peek_kind()) == std::mem::discriminant(kind) {
             self.advance(); true
@@ -72,8 +108,13 @@ impl Parser {
     fn skip_newlines(&mut self) {
         while matches!(self.peek_kind(), TokenKind::Newline) { self.advance(); }
     }
+    /// Salta newline/indent/dedent dentro una lista tra parentesi su più righe
+    fn skip_layout(&mut self) {
+        while matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent) { self.advance(); }
+    }
+    /// Fine di uno statement: newline, fine file o `;` (più statement sulla stessa riga)
     fn expect_newline(&mut self) {
-        if matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Eof) { self.advance(); }
+        if matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Eof | TokenKind::Semicolon) { self.advance(); }
     }
     fn error_expr(&mut self, err: ParseError) -> Expr {
         let span = self.current_span();
@@ -86,7 +127,7 @@ impl Parser {
     fn error_stmt(&mut self, err: ParseError) -> Stmt {
         let span = self.current_span();
         self.errors.push(err);
-        while !matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Eof | TokenKind::Dedent) {
+        while !matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Eof | TokenKind::Dedent | TokenKind::Semicolon) {
             self.advance();
         }
         self.expect_newline();
@@ -98,7 +139,8 @@ impl Parser {
         self.skip_newlines();
         while !matches!(self.peek_kind(), TokenKind::Eof) {
             // Skip orphan Dedent tokens (can appear after match/if blocks at top level)
-            if matches!(self.peek_kind(), TokenKind::Dedent) { self.advance(); continue; }
+            // `;` isolati (es. `x = 1;;`) non producono statement
+            if matches!(self.peek_kind(), TokenKind::Dedent | TokenKind::Semicolon) { self.advance(); continue; }
             stmts.push(self.parse_stmt());
             self.skip_newlines();
         }
@@ -110,8 +152,9 @@ impl Parser {
         match self.peek_kind().clone() {
             TokenKind::Let      => self.parse_let(false),
             TokenKind::Var      => self.parse_let(true),
-            TokenKind::Fn       => self.parse_fn(false),
+            TokenKind::Fn       => self.parse_fn(false, false),
             TokenKind::Async    => self.parse_async_fn(),
+            TokenKind::Identifier(_) if self.at_pure_fn() => self.parse_pure_fn(),
             TokenKind::Class    => self.parse_class(),
             TokenKind::Trait    => self.parse_trait(),
             TokenKind::Impl     => self.parse_impl(),
@@ -144,7 +187,7 @@ impl Parser {
         Node::new(kind, span)
     }
 
-    fn parse_fn(&mut self, is_async: bool) -> Stmt {
+    fn parse_fn(&mut self, is_async: bool, is_pure: bool) -> Stmt {
         let span = self.current_span();
         self.advance();
         let name = match self.peek_kind().clone() {
@@ -158,14 +201,25 @@ impl Parser {
         if let Err(e) = self.expect(&TokenKind::RParen, "')'") { return self.error_stmt(e); }
         let return_ty = if self.match_tok(&TokenKind::Arrow) { Some(self.parse_type()) } else { None };
         self.expect_newline();
-        let body = self.parse_block();
-        Node::new(StmtKind::Fn { name, params, return_ty, body, is_async }, span)
+        let mut contracts = Contracts::default();
+        let body = self.parse_block_with(Some(&mut contracts));
+        let contracts = Rc::new(contracts);
+        Node::new(StmtKind::Fn { name, params, return_ty, body: body.into(), is_async, is_pure, contracts }, span)
     }
 
-    fn parse_async_fn(&mut self) -> Stmt { self.advance(); self.parse_fn(true) }
+    fn parse_async_fn(&mut self) -> Stmt { self.advance(); self.parse_fn(true, false) }
+
+    fn parse_pure_fn(&mut self) -> Stmt {
+        let span = self.current_span();
+        self.advance();
+        let mut stmt = self.parse_fn(false, true);
+        stmt.span = span;
+        stmt
+    }
 
     fn parse_params(&mut self) -> Vec<Param> {
         let mut params = Vec::new();
+        self.skip_layout();
         while !matches!(self.peek_kind(), TokenKind::RParen | TokenKind::Eof) {
             let span = self.current_span();
             let name = match self.peek_kind().clone() {
@@ -175,8 +229,15 @@ impl Parser {
             };
             let ty      = if self.match_tok(&TokenKind::Colon) { Some(self.parse_type()) } else { None };
             let default = if self.match_tok(&TokenKind::Equal) { Some(self.parse_expr(Prec::None)) } else { None };
+            if name == "self" && !params.is_empty() {
+                self.errors.push(ParseError::SelfNotFirst { span: span.clone() });
+            } else if default.is_none() && name != "self" && params.iter().any(|p: &Param| p.default.is_some()) {
+                self.errors.push(ParseError::RequiredAfterDefault { name: name.clone(), span: span.clone() });
+            }
             params.push(Param { name, ty, default, span });
-            if !self.match_tok(&TokenKind::Comma) { break; }
+            let comma = self.match_tok(&TokenKind::Comma);
+            self.skip_layout();
+            if !comma { break; }
         }
         params
     }
@@ -198,6 +259,7 @@ impl Parser {
             while !matches!(self.peek_kind(), TokenKind::Dedent | TokenKind::Eof) {
                 match self.peek_kind().clone() {
                     TokenKind::Fn | TokenKind::Async => methods.push(self.parse_stmt()),
+                    TokenKind::Identifier(_) if self.at_pure_fn() => methods.push(self.parse_stmt()),
                     TokenKind::Impl                  => impls.push(self.parse_stmt()),
                     TokenKind::Pass                  => { self.advance(); self.skip_newlines(); break; }
                     TokenKind::Newline               => { self.advance(); }
@@ -206,6 +268,17 @@ impl Parser {
             }
             self.match_tok(&TokenKind::Dedent);
         }
+        // Campi e metodi condividono lo spazio dei nomi dell'istanza: niente doppioni
+        let mut seen = std::collections::HashSet::new();
+        let members = fields.iter().map(|f| (&f.name, &f.span)).chain(methods.iter().filter_map(|m| match &m.inner {
+            StmtKind::Fn { name, .. } => Some((name, &m.span)),
+            _ => None,
+        }));
+        for (member, member_span) in members {
+            if member != "?" && !seen.insert(member.clone()) {
+                self.errors.push(ParseError::DuplicateMember { class: name.clone(), name: member.clone(), span: member_span.clone() });
+            }
+        }
         Node::new(StmtKind::Class { name, fields, methods, impls }, span)
     }
 
@@ -269,7 +342,9 @@ impl Parser {
         } else {
             Vec::new() // firma senza body
         };
-        Node::new(StmtKind::Fn { name, params, return_ty, body, is_async: false }, span)
+        Node::new(StmtKind::Fn {
+            name, params, return_ty, body: body.into(), is_async: false, is_pure: false, contracts: Rc::default(),
+        }, span)
     }
 
 
@@ -336,7 +411,7 @@ impl Parser {
     fn parse_return(&mut self) -> Stmt {
         let span = self.current_span();
         self.advance();
-        let value = if matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Eof) {
+        let value = if matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Eof | TokenKind::Semicolon) {
             None
         } else { Some(self.parse_expr(Prec::None)) };
         self.expect_newline();
@@ -358,11 +433,9 @@ impl Parser {
         let span = self.current_span();
         self.advance();
         let mut path = Vec::new();
-        loop {
-            match self.peek_kind().clone() {
-                TokenKind::Identifier(s) => { self.advance(); path.push(s); }
-                _ => break,
-            }
+        while let TokenKind::Identifier(s) = self.peek_kind().clone() {
+            self.advance();
+            path.push(s);
             if !self.match_tok(&TokenKind::ColonColon) { break; }
         }
         self.expect_newline();
@@ -371,19 +444,11 @@ impl Parser {
 
     fn parse_expr_or_assign(&mut self) -> Stmt {
         let span = self.current_span();
+        self.stmt_target = true;
         let expr = self.parse_expr(Prec::None);
-        let op = match self.peek_kind() {
-            TokenKind::Equal        => Some(AssignOp::Assign),
-            TokenKind::PlusEqual    => Some(AssignOp::AddAssign),
-            TokenKind::MinusEqual   => Some(AssignOp::SubAssign),
-            TokenKind::StarEqual    => Some(AssignOp::MulAssign),
-            TokenKind::SlashEqual   => Some(AssignOp::DivAssign),
-            TokenKind::PercentEqual => Some(AssignOp::ModAssign),
-            _ => None,
-        };
-        if let Some(op) = op {
+        if let Some(op) = assign_op(self.peek_kind()) {
             match &expr.inner {
-                ExprKind::Ident(_) | ExprKind::Field { .. } | ExprKind::Index { .. } => {}
+                ExprKind::Ident(_) | ExprKind::Field { .. } | ExprKind::Index { .. } | ExprKind::Slice { .. } => {}
                 _ => self.errors.push(ParseError::InvalidAssignTarget { span: expr.span.clone() }),
             }
             self.advance();
@@ -396,21 +461,75 @@ impl Parser {
     }
 
     fn parse_block(&mut self) -> Vec<Stmt> {
+        self.parse_block_with(None)
+    }
+
+    /// Come `parse_block`; con `contracts` le righe `requires cond` / `ensures cond`
+    /// in testa al blocco (corpo di una fn) finiscono lì invece che negli statement.
+    fn parse_block_with(&mut self, mut contracts: Option<&mut Contracts>) -> Vec<Stmt> {
         if !matches!(self.peek_kind(), TokenKind::Indent) {
             self.errors.push(ParseError::MissingIndent { span: self.current_span() });
             return Vec::new();
         }
+        if self.block_depth >= self.max_nesting {
+            self.errors.push(ParseError::NestingTooDeep { max: self.max_nesting, span: self.current_span() });
+            self.skip_block();
+            return Vec::new();
+        }
         self.advance();
+        self.block_depth += 1;
         let mut stmts = Vec::new();
         self.skip_newlines();
         while !matches!(self.peek_kind(), TokenKind::Dedent | TokenKind::Eof) {
-            if matches!(self.peek_kind(), TokenKind::Newline) { self.advance(); continue; }
+            if matches!(self.peek_kind(), TokenKind::Newline | TokenKind::Semicolon) { self.advance(); continue; }
+            if let Some(c) = contracts.as_deref_mut() {
+                if let Some(is_requires) = self.contract_keyword() {
+                    self.advance();
+                    let cond = self.parse_expr(Prec::None);
+                    if is_requires { c.requires.push(cond) } else { c.ensures.push(cond) }
+                    self.expect_newline();
+                    continue;
+                }
+                contracts = None;
+            }
             stmts.push(self.parse_stmt());
         }
         self.match_tok(&TokenKind::Dedent);
+        self.block_depth -= 1;
         stmts
     }
 
+    /// `requires`/`ensures` sono parole chiave solo a inizio riga e seguite da
+    /// un'espressione (`requires = 1` o `ensures(x)` restano normali).
+    /// `Some(true)` per `requires`, `Some(false)` per `ensures`.
+    fn contract_keyword(&self) -> Option<bool> {
+        let is_requires = match self.peek_kind() {
+            TokenKind::Identifier(s) if s == "requires" => true,
+            TokenKind::Identifier(s) if s == "ensures"  => false,
+            _ => return None,
+        };
+        let next = &self.tokens[(self.pos + 1).min(self.tokens.len() - 1)].kind;
+        matches!(next,
+            TokenKind::Identifier(_) | TokenKind::Self_ | TokenKind::Not
+            | TokenKind::IntLiteral(_) | TokenKind::FloatLiteral(_) | TokenKind::StringLiteral(_)
+            | TokenKind::CharLiteral(_) | TokenKind::BoolLiteral(_) | TokenKind::NoneLiteral)
+            .then_some(is_requires)
+    }
+
+    /// Salta un blocco indentato (e tutti i suoi sotto-blocchi) senza ricorsione.
+    fn skip_block(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.peek_kind() {
+                TokenKind::Indent => depth += 1,
+                TokenKind::Dedent => { depth -= 1; if depth == 0 { self.advance(); return; } }
+                TokenKind::Eof    => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
     fn parse_type(&mut self) -> TypeExpr {
         let span = self.current_span();
         match self.peek_kind().clone() {
@@ -418,7 +537,7 @@ impl Parser {
                 self.advance();
                 if self.match_tok(&TokenKind::LBracket) {
                     let mut args = Vec::new();
-                    loop {
+                    while !matches!(self.peek_kind(), TokenKind::RBracket | TokenKind::Eof) {
                         args.push(self.parse_type());
                         if !self.match_tok(&TokenKind::Comma) { break; }
                     }
@@ -438,13 +557,33 @@ impl Parser {
     }
 
     fn parse_expr(&mut self, min_prec: Prec) -> Expr {
+        let stmt_target = std::mem::take(&mut self.stmt_target);
+        if self.expr_depth >= MAX_EXPR_DEPTH {
+            return self.error_expr(ParseError::ExpressionTooDeep { max: MAX_EXPR_DEPTH, span: self.current_span() });
+        }
+        self.expr_depth += 1;
+        let expr = self.parse_expr_prec(min_prec);
+        self.expr_depth -= 1;
+        // L'assegnazione è uno statement: `(x = 5)`, `let y = x = 5`, `if x = 5` sono errori.
+        // Gli operandi (min_prec > None) lasciano `=` al contesto che li contiene.
+        if !stmt_target && min_prec == Prec::None && assign_op(self.peek_kind()).is_some() {
+            let span = self.current_span();
+            self.errors.push(ParseError::AssignInExpression { span: span.clone() });
+            self.advance();
+            self.parse_expr(Prec::None);
+            return Node::new(ExprKind::Error, span);
+        }
+        expr
+    }
+
+    fn parse_expr_prec(&mut self, min_prec: Prec) -> Expr {
         let mut left = self.parse_prefix();
         loop {
             let kind = self.peek_kind().clone();
             // not in
             if kind == TokenKind::Not {
                 let span = self.current_span();
-                let next_is_in = self.tokens.get(self.pos + 1).map_or(false, |t| t.kind == TokenKind::In);
+                let next_is_in = self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::In);
                 if next_is_in && Prec::Compare > min_prec {
                     self.advance(); self.advance();
                     let right = self.parse_expr(Prec::Compare);
@@ -452,6 +591,15 @@ impl Parser {
                     continue;
                 }
             }
+            // is not
+            if kind == TokenKind::Is && Prec::Compare > min_prec
+                && self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Not) {
+                let span = self.current_span();
+                self.advance(); self.advance();
+                let right = self.parse_expr(Prec::Compare);
+                left = Node::new(ExprKind::Binary { op: BinOp::IsNot, left: Box::new(left), right: Box::new(right) }, span);
+                continue;
+            }
             let (prec, right_assoc) = match infix_prec(&kind) { Some(p) => p, None => break };
             if prec <= min_prec && !right_assoc { break; }
             if prec < min_prec { break; }
@@ -468,6 +616,7 @@ impl Parser {
             TokenKind::BoolLiteral(b)    => { self.advance(); Node::new(ExprKind::Bool(b), span) }
             TokenKind::StringLiteral(s)  => { self.advance(); Node::new(ExprKind::Str(s), span) }
             TokenKind::FStringLiteral(s) => { self.advance(); Node::new(ExprKind::FStr(s), span) }
+            TokenKind::CharLiteral(c)    => { self.advance(); Node::new(ExprKind::Char(c), span) }
             TokenKind::NoneLiteral       => { self.advance(); Node::new(ExprKind::None, span) }
             TokenKind::Identifier(s)     => { self.advance(); Node::new(ExprKind::Ident(s), span) }
             TokenKind::Self_             => { self.advance(); Node::new(ExprKind::Ident("self".to_string()), span) }
@@ -486,12 +635,7 @@ impl Parser {
                 let op = self.parse_expr(Prec::Unary);
                 Node::new(ExprKind::Unary { op: UnaryOp::BitNot, operand: Box::new(op) }, span)
             }
-            TokenKind::LParen => {
-                self.advance();
-                let e = self.parse_expr(Prec::None);
-                self.match_tok(&TokenKind::RParen);
-                e
-            }
+            TokenKind::LParen   => self.parse_paren_or_tuple(),
             TokenKind::LBracket => self.parse_array_literal(),
             TokenKind::LBrace   => self.parse_dict_literal(),
             TokenKind::If       => self.parse_if_expr(),
@@ -562,6 +706,11 @@ impl Parser {
         let span = left.span.clone();
         let kind = self.peek_kind().clone();
         match &kind {
+            TokenKind::PipeGreater => {
+                self.advance();
+                let func = self.parse_expr(Prec::Pipe);
+                Node::new(ExprKind::Pipe { value: Box::new(left), func: Box::new(func) }, span)
+            }
             TokenKind::LParen => {
                 self.advance();
                 let (args, kwargs) = self.parse_call_args();
@@ -618,7 +767,13 @@ impl Parser {
                     TokenKind::Not           => { self.advance(); "not".to_string() }
                     TokenKind::In            => { self.advance(); "in".to_string() }
                     TokenKind::Is            => { self.advance(); "is".to_string() }
-                    _ => "?".to_string(),
+                    found => {
+                        // `obj.` senza nome: diagnostica e nodo Error (niente campo fittizio)
+                        self.errors.push(ParseError::UnexpectedToken {
+                            expected: "field name after '.'".to_string(), found, span: self.current_span(),
+                        });
+                        return Node::new(ExprKind::Error, span);
+                    }
                 };
                 Node::new(ExprKind::Field { object: Box::new(left), field }, span)
             }
@@ -688,7 +843,7 @@ impl Parser {
             // Dopo lo skip potremmo essere arrivati alla ')'
             if matches!(self.peek_kind(), TokenKind::RParen | TokenKind::Eof) { break; }
             let is_kwarg = matches!(self.peek_kind(), TokenKind::Identifier(_))
-                && self.tokens.get(self.pos + 1).map_or(false, |t| t.kind == TokenKind::Equal);
+                && self.tokens.get(self.pos + 1).is_some_and(|t| t.kind == TokenKind::Equal);
             if is_kwarg {
                 let name = match self.peek_kind().clone() {
                     TokenKind::Identifier(s) => { self.advance(); s }
@@ -720,26 +875,55 @@ impl Parser {
         Node::new(ExprKind::Array(items), span)
     }
 
+    /// `(e)` è solo raggruppamento; con almeno una virgola prima di `)` è una
+    /// tupla: `(a, b)`, `(a, b, c)`, `(a,)`.
+    fn parse_paren_or_tuple(&mut self) -> Expr {
+        let span = self.current_span();
+        self.advance(); // consuma `(`
+        let first = self.parse_expr(Prec::None);
+        if !self.match_tok(&TokenKind::Comma) {
+            self.match_tok(&TokenKind::RParen);
+            return first;
+        }
+        let mut items = vec![first];
+        self.skip_layout();
+        while !matches!(self.peek_kind(), TokenKind::RParen | TokenKind::Eof) {
+            items.push(self.parse_expr(Prec::None));
+            if !self.match_tok(&TokenKind::Comma) { break; }
+            self.skip_layout();
+        }
+        self.skip_layout();
+        if let Err(e) = self.expect(&TokenKind::RParen, "')'") {
+            return self.error_expr(e);
+        }
+        Node::new(ExprKind::Tuple(items), span)
+    }
+
     /// Parsa un dizionario letterale: `{key: value, ...}` oppure `{}` (dict vuoto).
-    /// Nota: il separatore è `:` (come in Python), non `=>`.
+    /// Il separatore è `:` (come in Python) oppure `=>`; virgola finale e a capo
+    /// tra le coppie sono ammessi.
     fn parse_dict_literal(&mut self) -> Expr {
         let span = self.current_span();
         self.advance(); // consuma `{`
         let mut pairs: Vec<(Expr, Expr)> = Vec::new();
+        self.skip_layout();
         while !matches!(self.peek_kind(), TokenKind::RBrace | TokenKind::Eof) {
             let key = self.parse_expr(Prec::None);
             // Accetta sia `:` che `=>` come separatore chiave-valore
-            if !self.match_tok(&TokenKind::Colon) {
-                // prova con Arrow (=>)
-                if !self.match_tok(&TokenKind::Arrow) {
-                    // errore: atteso separatore
+            if !self.match_tok(&TokenKind::FatArrow) {
+                if let Err(e) = self.expect(&TokenKind::Colon, "':' or '=>' in dict literal") {
+                    return self.error_expr(e);
                 }
             }
             let val = self.parse_expr(Prec::None);
             pairs.push((key, val));
             if !self.match_tok(&TokenKind::Comma) { break; }
+            self.skip_layout();
+        }
+        self.skip_layout();
+        if let Err(e) = self.expect(&TokenKind::RBrace, "'}'") {
+            return self.error_expr(e);
         }
-        self.match_tok(&TokenKind::RBrace);
         Node::new(ExprKind::Dict(pairs), span)
     }
 
@@ -791,7 +975,10 @@ impl Parser {
             self.match_tok(&TokenKind::Case); // consume 'case' if present, no-op if absent
             // After optional 'case', we must be at a valid pattern start; if not, stop
             if matches!(self.peek_kind(), TokenKind::Dedent | TokenKind::Eof | TokenKind::Newline) { break; }
+            let before = self.pos;
             let pattern = self.parse_pattern();
+            // Pattern non valido che non consuma token: avanza per non ciclare all'infinito
+            if self.pos == before { self.advance(); continue; }
             let body = if self.match_tok(&TokenKind::FatArrow) {
                 // Se dopo => c'è un newline seguito da un indent, è un blocco multi-linea
                 if matches!(self.peek_kind(), TokenKind::Newline) {
@@ -825,10 +1012,27 @@ impl Parser {
 
     fn parse_single_pattern(&mut self) -> Pattern {
         match self.peek_kind().clone() {
-            TokenKind::Underscore      => { self.advance(); Pattern::Wildcard }
+            TokenKind::Underscore      => { self.advance(); self.maybe_suffix_pattern(Pattern::Wildcard) }
             TokenKind::IntLiteral(n)   => { self.advance(); self.maybe_range_pattern(Pattern::Literal(ExprKind::Int(n))) }
             TokenKind::FloatLiteral(f) => { self.advance(); Pattern::Literal(ExprKind::Float(f)) }
-            TokenKind::StringLiteral(s)=> { self.advance(); Pattern::Literal(ExprKind::Str(s)) }
+            TokenKind::CharLiteral(c)  => { self.advance(); Pattern::Literal(ExprKind::Char(c)) }
+            TokenKind::StringLiteral(s)=> {
+                self.advance();
+                if !self.match_tok(&TokenKind::Plus) { return Pattern::Literal(ExprKind::Str(s)); }
+                let rest = match self.peek_kind().clone() {
+                    TokenKind::Identifier(n) => { self.advance(); Pattern::Ident(n) }
+                    TokenKind::Underscore    => { self.advance(); Pattern::Wildcard }
+                    _ => {
+                        self.errors.push(ParseError::InvalidPattern { span: self.current_span() });
+                        return Pattern::Error;
+                    }
+                };
+                let suffix = if self.match_tok(&TokenKind::Plus) { self.affix_literal() } else { Some(String::new()) };
+                match suffix {
+                    Some(suffix) => Pattern::StrAffix { prefix: s, rest: Box::new(rest), suffix },
+                    None => Pattern::Error,
+                }
+            }
             TokenKind::BoolLiteral(b)  => { self.advance(); Pattern::Literal(ExprKind::Bool(b)) }
             TokenKind::NoneLiteral     => { self.advance(); Pattern::Literal(ExprKind::None) }
             TokenKind::Some | TokenKind::Ok | TokenKind::Err => {
@@ -839,10 +1043,27 @@ impl Parser {
                 let mut inner = Vec::new();
                 if self.match_tok(&TokenKind::LParen) {
                     inner.push(self.parse_pattern());
+                    self.match_tok(&TokenKind::Comma);
                     self.match_tok(&TokenKind::RParen);
                 }
                 Pattern::Constructor(name, inner)
             }
+            TokenKind::LParen => {
+                // `(p)` raggruppa, `(p1, p2)` / `(p,)` è un pattern tupla
+                self.advance();
+                let first = self.parse_pattern();
+                if !self.match_tok(&TokenKind::Comma) {
+                    self.match_tok(&TokenKind::RParen);
+                    return first;
+                }
+                let mut items = vec![first];
+                while !matches!(self.peek_kind(), TokenKind::RParen | TokenKind::Eof) {
+                    items.push(self.parse_pattern());
+                    if !self.match_tok(&TokenKind::Comma) { break; }
+                }
+                self.match_tok(&TokenKind::RParen);
+                Pattern::Tuple(items)
+            }
             TokenKind::Identifier(s) => {
                 self.advance();
                 if self.match_tok(&TokenKind::LParen) {
@@ -854,7 +1075,7 @@ impl Parser {
                     self.match_tok(&TokenKind::RParen);
                     Pattern::Constructor(s, inner)
                 } else {
-                    Pattern::Ident(s)
+                    self.maybe_suffix_pattern(Pattern::Ident(s))
                 }
             }
             _ => {
@@ -864,6 +1085,26 @@ impl Parser {
         }
     }
 
+    /// `rest + "suf"` dopo un Ident o `_`.
+    fn maybe_suffix_pattern(&mut self, rest: Pattern) -> Pattern {
+        if !self.match_tok(&TokenKind::Plus) { return rest; }
+        match self.affix_literal() {
+            Some(suffix) => Pattern::StrAffix { prefix: String::new(), rest: Box::new(rest), suffix },
+            None => Pattern::Error,
+        }
+    }
+
+    /// Letterale stringa dopo il `+` di un pattern `StrAffix`.
+    fn affix_literal(&mut self) -> Option<String> {
+        if let TokenKind::StringLiteral(s) = self.peek_kind().clone() {
+            self.advance();
+            Some(s)
+        } else {
+            self.errors.push(ParseError::InvalidPattern { span: self.current_span() });
+            None
+        }
+    }
+
     fn maybe_range_pattern(&mut self, start: Pattern) -> Pattern {
         match self.peek_kind() {
             TokenKind::DotDot      => { self.advance(); let end = self.parse_single_pattern(); Pattern::Range { start: Box::new(start), end: Box::new(end), inclusive: false } }
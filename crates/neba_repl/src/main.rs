@@ -1,9 +1,12 @@
 //! neba_repl — REPL interattivo per Neba v0.1.2
 //! Supporta: espressioni, statement, definizioni di funzioni/classi multi-riga.
-//! Comandi speciali: :quit / :q, :clear, :help
+//! Comandi speciali: :quit / :q, :clear, :help, :disasm <codice>
+//! Con `--vm` l'input è eseguito dalla bytecode VM invece che dal tree-walker.
 
 use std::io::{self, BufRead, Write};
 use neba_interpreter::{Interpreter, Value};
+use neba_parser::ast::Program;
+use neba_vm::Vm;
 
 const BANNER: &str = r#"
   ███╗   ██╗███████╗██████╗  █████╗
@@ -14,12 +17,32 @@ const BANNER: &str = r#"
   ╚═╝  ╚═══╝╚══════╝╚═════╝ ╚═╝  ╚═╝  v0.1.2
 "#;
 
+/// Motore che esegue l'input: tree-walker (default) o bytecode VM (`--vm`).
+/// Entrambi conservano globali e definizioni tra un input e l'altro.
+enum Backend {
+    Tree(Box<Interpreter>),
+    Vm(Box<Vm>),
+}
+
+impl Backend {
+    fn new(use_vm: bool) -> Self {
+        if use_vm { Backend::Vm(Box::new(Vm::new())) } else { Backend::Tree(Box::new(Interpreter::new())) }
+    }
+}
+
 fn main() {
+    let use_vm = std::env::args().skip(1).any(|a| a == "--vm");
     println!("{}", BANNER);
-    println!("  Tree-walking interpreter — type :help for commands\n");
+    if use_vm {
+        println!("  Bytecode VM — type :help for commands\n");
+    } else {
+        println!("  Tree-walking interpreter — type :help for commands\n");
+    }
 
-    let mut interp = Interpreter::new();
+    let mut backend = Backend::new(use_vm);
     let mut pending = String::new();
+    // L'input in accumulo è stato aperto da `:disasm`: va disassemblato, non eseguito
+    let mut pending_disasm = false;
 
     loop {
         let prompt = if pending.is_empty() { ">>> " } else { "... " };
@@ -42,8 +65,9 @@ fn main() {
                 break;
             }
             ":clear" => {
-                interp = Interpreter::new();
+                backend = Backend::new(use_vm);
                 pending.clear();
+                pending_disasm = false;
                 println!("  Environment cleared.");
                 continue;
             }
@@ -54,6 +78,11 @@ fn main() {
             _ => {}
         }
 
+        let trimmed = match disasm_arg(trimmed) {
+            Some(rest) if pending.is_empty() => { pending_disasm = true; rest }
+            _ => trimmed,
+        };
+
         // Accumulo multiriga: se la linea termina con ':' o il blocco è aperto
         pending.push_str(trimmed);
         pending.push('\n');
@@ -66,27 +95,70 @@ fn main() {
 
         // Esegui
         let source = std::mem::take(&mut pending);
-        eval_and_print(&mut interp, &source);
+        if std::mem::take(&mut pending_disasm) {
+            match disasm(&source) {
+                Ok(listing) => print!("{}", listing),
+                Err(e)      => eprintln!("  \x1b[31m[Disasm]\x1b[0m {}", e),
+            }
+            continue;
+        }
+        eval_and_print(&mut backend, &source);
     }
 }
 
-fn eval_and_print(interp: &mut Interpreter, source: &str) {
+fn eval_and_print(backend: &mut Backend, source: &str) {
     let (program, lex_errors, parse_errors) = neba_parser::parse(source);
 
     for e in &lex_errors   { eprintln!("  \x1b[31m[Lex]\x1b[0m   {}", e); }
     for e in &parse_errors { eprintln!("  \x1b[31m[Parse]\x1b[0m {}", e); }
     if !lex_errors.is_empty() || !parse_errors.is_empty() { return; }
 
+    let interp = match backend {
+        Backend::Tree(interp) => interp,
+        Backend::Vm(vm) => {
+            match eval_vm(vm, &program) {
+                Ok(Some(v)) => println!("  \x1b[32m{}\x1b[0m", v),
+                Ok(None)    => {}
+                Err(e)      => eprintln!("  \x1b[31m[Runtime]\x1b[0m {}", e),
+            }
+            return;
+        }
+    };
     for stmt in &program.stmts {
         match interp.exec_stmt(stmt) {
             Ok(Value::None) => {}
-            Ok(v) if matches!(v, Value::__Return(_) | Value::__Break | Value::__Continue) => {}
+            Ok(Value::__Return(_) | Value::__Break | Value::__Continue) => {}
             Ok(v)  => println!("  \x1b[32m{}\x1b[0m", v),
             Err(e) => eprintln!("  \x1b[31m[Runtime]\x1b[0m {}", e),
         }
     }
 }
 
+/// Esegue un input completo sulla VM; `None` se non c'è un valore da mostrare
+/// (l'input termina con una dichiarazione o l'espressione vale `None`).
+fn eval_vm(vm: &mut Vm, program: &Program) -> Result<Option<neba_vm::Value>, neba_vm::VmError> {
+    match vm.run_program(program)? {
+        neba_vm::Value::None => Ok(None),
+        v => Ok(Some(v)),
+    }
+}
+
+/// Codice che segue `:disasm` / `:bytecode`, se la riga è uno di questi comandi.
+fn disasm_arg(line: &str) -> Option<&str> {
+    let rest = line.trim_start();
+    let rest = rest.strip_prefix(":disasm").or_else(|| rest.strip_prefix(":bytecode"))?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim_start())
+}
+
+/// Compila `source` con il compilatore della VM e restituisce il disassemblato.
+fn disasm(source: &str) -> Result<String, String> {
+    let (program, lex_errors, parse_errors) = neba_parser::parse(source);
+    if let Some(e) = lex_errors.first() { return Err(e.to_string()); }
+    if let Some(e) = parse_errors.first() { return Err(e.to_string()); }
+    let chunk = neba_vm::Compiler::compile(&program).map_err(|e| e.to_string())?;
+    Ok(chunk.disassemble("<repl>"))
+}
+
 /// Restituisce true se l'input sembra incompleto (blocco aperto).
 fn should_continue(src: &str) -> bool {
     use neba_parser::ParseError;
@@ -109,8 +181,41 @@ fn print_help() {
     println!("  :quit / :q    Esci dal REPL");
     println!("  :clear        Azzera l'environment (variabili/funzioni)");
     println!("  :help         Mostra questo messaggio");
+    println!("  :disasm <src> Mostra il bytecode generato per <src> (alias :bytecode)");
+    println!("  (avvio con --vm: esegue l'input con la bytecode VM)");
     println!();
     println!("  Inserisci codice Neba direttamente. Per blocchi multi-riga");
     println!("  (fn, class, if, while, for, match) continua su righe successive");
     println!("  indentate con 4 spazi. Una riga vuota chiude il blocco.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm_input(vm: &mut Vm, src: &str) -> Option<neba_vm::Value> {
+        let (program, _, errs) = neba_parser::parse(src);
+        assert!(errs.is_empty(), "{:?}", errs);
+        eval_vm(vm, &program).unwrap()
+    }
+
+    #[test] fn t_vm_fn_across_inputs() {
+        let mut vm = Vm::new();
+        assert_eq!(vm_input(&mut vm, "fn double(x)\n    return x * 2\n"), None);
+        assert_eq!(vm_input(&mut vm, "double(21)\n"), Some(neba_vm::Value::Int(42)));
+    }
+    #[test] fn t_disasm() {
+        // `1 + 2` viene ripiegato dal compilatore in `Const 3`: si mostra il bytecode reale
+        let folded = disasm(disasm_arg(":disasm 1 + 2").unwrap()).unwrap();
+        assert!(folded.contains("Const") && folded.contains("\"3\""), "{}", folded);
+        let out = disasm(disasm_arg(":disasm x + 2").unwrap()).unwrap();
+        assert!(out.contains("Const") && out.contains("Add"), "{}", out);
+        assert_eq!(disasm_arg(":bytecode x"), Some("x"));
+        assert_eq!(disasm_arg(":disasmx"), None);
+        assert!(disasm("1 +").is_err());
+    }
+    #[test] fn t_vm_multiline_accumulation() {
+        assert!(should_continue("fn f(x)\n"));
+        assert!(!should_continue("fn f(x)\n    return x\n\n"));
+    }
+}
@@ -0,0 +1,10 @@
+//! Segmentazione di una stringa in grapheme cluster estesi (UAX #29), usata dalla
+//! modalità di indicizzazione "per grafema" di interprete e VM. Le tabelle sono
+//! quelle di `unicode-segmentation` (feature `graphemes`).
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Divide `s` nei suoi grapheme cluster estesi, nell'ordine. Concatenati ridanno `s`.
+pub fn graphemes(s: &str) -> Vec<&str> {
+    s.graphemes(true).collect()
+}
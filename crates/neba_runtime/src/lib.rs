@@ -0,0 +1,66 @@
+//! Helper a runtime condivisi da interprete e VM, così i due backend danno
+//! lo stesso risultato.
+
+#[cfg(feature = "graphemes")]
+pub mod grapheme;
+pub mod hash;
+pub mod template;
+
+#[cfg(feature = "graphemes")]
+pub use grapheme::graphemes;
+pub use hash::{float_key_bits, sha256_hex, StableHasher};
+pub use template::format_template;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes() {
+        use std::hash::{Hash, Hasher};
+        let h = |s: &str| { let mut st = StableHasher::default(); s.hash(&mut st); st.finish() };
+        assert_eq!(h("neba"), h("neba"));
+        assert_ne!(h("neba"), h("nebb"));
+        assert_eq!(float_key_bits(-0.0), float_key_bits(0.0));
+        assert_ne!(float_key_bits(1.0), float_key_bits(-1.0));
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        // Messaggio di 56 byte: il padding richiede un secondo blocco
+        assert_eq!(sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn test_format_template() {
+        let pos = ["a".to_string(), "b".to_string()];
+        let named = [("nome".to_string(), "Neba".to_string())];
+        assert_eq!(format_template("{1}{0}{1} {nome} {{x}}", &pos, &named).unwrap(), "bab Neba {x}");
+        assert!(format_template("{2}", &pos, &named).unwrap_err().contains("placeholder {2}"));
+        assert!(format_template("{altro}", &pos, &named).unwrap_err().contains("'altro'"));
+        assert!(format_template("{0", &pos, &named).is_err());
+        assert!(format_template("0}", &pos, &named).is_err());
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn test_graphemes() {
+        assert_eq!(graphemes("e\u{301}x"), vec!["e\u{301}", "x"]);
+        assert_eq!(graphemes("a\r\nb"), vec!["a", "\r\n", "b"]);
+        // bandiere: coppie di regional indicator
+        assert_eq!(graphemes("\u{1F1EE}\u{1F1F9}\u{1F1EB}\u{1F1F7}").len(), 2);
+        // famiglia: emoji unite da ZWJ, con modificatore di carnagione
+        assert_eq!(graphemes("\u{1F468}\u{1F3FD}\u{200D}\u{1F469}\u{200D}\u{1F467}!").len(), 2);
+        assert_eq!(graphemes("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}"),
+            vec!["\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}"]);
+        // ZWJ dopo un carattere che non è un emoji: l'emoji successivo è a sé
+        assert_eq!(graphemes("a\u{200D}\u{1F469}"), vec!["a\u{200D}", "\u{1F469}"]);
+        // tamil: il virama U+0BCD e la vocale U+0BBF restano con la consonante
+        assert_eq!(graphemes("\u{0B95}\u{0BCD}\u{0B95}\u{0BBF}"), vec!["\u{0B95}\u{0BCD}", "\u{0B95}\u{0BBF}"]);
+        // congiunto devanagari "क्षि": consonante + virama + consonante (GB9c) e
+        // la vocale SpacingMark (GB9a) formano un solo grafema
+        assert_eq!(graphemes("\u{0915}\u{094D}\u{0937}\u{093F}").len(), 1);
+        // jamo Hangul L V T → una sola sillaba
+        assert_eq!(graphemes("\u{1100}\u{1161}\u{11A8}\u{AC00}"), vec!["\u{1100}\u{1161}\u{11A8}", "\u{AC00}"]);
+        assert!(graphemes("").is_empty());
+    }
+}
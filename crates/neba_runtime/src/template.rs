@@ -0,0 +1,45 @@
+//! Template di `"...".format(...)`, condivisi da interprete e VM.
+//!
+//! `{0}`, `{1}`... prendono l'argomento posizionale con quell'indice, `{nome}`
+//! l'argomento con nome; `{{` e `}}` sono graffe letterali. A differenza delle
+//! f-string il template è un valore a runtime, non codice.
+
+/// Sostituisce i segnaposto di `template` con i testi già convertiti degli argomenti.
+pub fn format_template(template: &str, positional: &[String], named: &[(String, String)]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let rest = chars.as_str();
+                if let Some(after) = rest.strip_prefix('{') {
+                    out.push('{');
+                    chars = after.chars();
+                    continue;
+                }
+                let Some(end) = rest.find('}') else {
+                    return Err("format: unclosed '{' in template".into());
+                };
+                let key = rest[..end].trim();
+                let value = if let Ok(i) = key.parse::<usize>() {
+                    positional.get(i).ok_or_else(|| format!(
+                        "format: no argument for placeholder {{{}}} ({} positional given)", i, positional.len()))?
+                } else {
+                    &named.iter().find(|(n, _)| n == key)
+                        .ok_or_else(|| format!("format: no argument named '{}'", key))?.1
+                };
+                out.push_str(value);
+                chars = rest[end + 1..].chars();
+            }
+            '}' => {
+                if !chars.as_str().starts_with('}') {
+                    return Err("format: single '}' in template (use '}}')".into());
+                }
+                chars.next();
+                out.push('}');
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
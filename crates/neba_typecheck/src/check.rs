@@ -4,6 +4,7 @@ use crate::types::Type;
 use crate::error::TypeError;
 use crate::env::{TypeEnv, ClassInfo};
 use crate::infer::infer_expr;
+use crate::purity::{check_pure_fn, impure_functions};
 
 /// Verifica un singolo statement.
 pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
@@ -22,7 +23,9 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
             } else {
                 val_ty
             };
-            env.define(name, final_ty, false);
+            if let Some(kind) = env.define_at(name, final_ty, false, span.clone()) {
+                errors.push(TypeError::shadowing(name, kind, span));
+            }
         }
 
         StmtKind::Var { name, ty, value } => {
@@ -36,17 +39,28 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
             } else {
                 val_ty
             };
-            env.define(name, final_ty, true);
+            if let Some(kind) = env.define_at(name, final_ty, true, span.clone()) {
+                errors.push(TypeError::shadowing(name, kind, span));
+            }
+            if declared_ty.is_some() { env.mark_annotated(name); }
         }
 
         // ── Assegnazione ──────────────────────────────────────────────────
-        StmtKind::Assign { target, op: _, value } => {
+        StmtKind::Assign { target, op, value } => {
             let val_ty = infer_expr(value, env, errors);
             if let ExprKind::Ident(name) = &target.inner {
                 match env.lookup(name) {
+                    // vale per ogni AssignOp: anche `x += 1` richiede `var`
                     Some(info) if !info.mutable => {
                         errors.push(TypeError::assign_immutable(name, span));
                     }
+                    // `var` senza annotazione: `=` può cambiare il tipo della variabile.
+                    // Senza seguire il flusso (l'assegnazione può stare in un ramo) il
+                    // tipo si allarga all'unificazione dei due, o ad Any se diversi.
+                    Some(info) if !info.annotated && matches!(op, AssignOp::Assign) => {
+                        let widened = Type::unify(&info.ty, &val_ty).unwrap_or(Type::Any);
+                        if let Some(info) = env.lookup_mut(name) { info.ty = widened; }
+                    }
                     Some(info) => {
                         let existing = info.ty.clone();
                         if !existing.is_compatible(&val_ty) {
@@ -63,7 +77,8 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
         }
 
         // ── Funzione ──────────────────────────────────────────────────────
-        StmtKind::Fn { name, params, return_ty, body, .. } => {
+        StmtKind::Fn { name, params, return_ty, body, is_pure, .. } => {
+            if *is_pure { check_pure_fn(name, params, body, &env.impure_fns, errors); }
             // Costruisci il tipo della funzione
             let param_types: Vec<Type> = params.iter()
                 .map(|p| p.ty.as_ref().map(|t| Type::from_ast(&t.inner)).unwrap_or(Type::Unknown))
@@ -76,17 +91,23 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
                 params: param_types.clone(),
                 ret:    Box::new(ret_ty.clone()),
             };
-            env.define(name, fn_ty, false);
+            env.define_at(name, fn_ty, false, span.clone());
 
             // Analizza il corpo in uno scope nuovo
             env.push_scope();
-            env.push_return(ret_ty.clone());
+            if return_ty.is_some() { env.push_return(ret_ty.clone()); } else { env.push_inferred_return(); }
             for (param, pty) in params.iter().zip(param_types.iter()) {
-                env.define(&param.name, pty.clone(), true);
+                env.define_at(&param.name, pty.clone(), true, param.span.clone());
             }
             check_block(body, env, errors);
-            env.pop_return();
+            let inferred = env.pop_return();
             env.pop_scope();
+            // Senza annotazione: il tipo inferito dai `return` diventa visibile ai chiamanti
+            if let Some(ret) = inferred {
+                if let Some(info) = env.lookup_mut(name) {
+                    info.ty = Type::Fn { params: param_types, ret: Box::new(inferred_return(ret, body)) };
+                }
+            }
         }
 
         // ── Classe ────────────────────────────────────────────────────────
@@ -119,26 +140,35 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
             env.register_class(name, ClassInfo { fields: field_map, methods: method_map });
 
             // Il tipo della classe stessa è un costruttore
-            env.define(name, Type::Class(name.clone()), false);
+            env.define_at(name, Type::Class(name.clone()), false, span.clone());
 
             // Analizza i corpi dei metodi
             for m in methods.iter().chain(impls.iter()) {
-                if let StmtKind::Fn { params, return_ty, body, .. } = &m.inner {
+                if let StmtKind::Fn { name: mname, params, return_ty, body, is_pure, .. } = &m.inner {
+                    if *is_pure { check_pure_fn(mname, params, body, &env.impure_fns, errors); }
                     env.push_scope();
                     env.define("self", Type::Class(name.clone()), false);
-                    let ret_ty = return_ty.as_ref()
-                        .map(|t| Type::from_ast(&t.inner))
-                        .unwrap_or(Type::None);
-                    env.push_return(ret_ty);
+                    match return_ty {
+                        Some(t) => env.push_return(Type::from_ast(&t.inner)),
+                        None    => env.push_inferred_return(),
+                    }
                     for p in params.iter().filter(|p| p.name != "self") {
                         let pty = p.ty.as_ref()
                             .map(|t| Type::from_ast(&t.inner))
                             .unwrap_or(Type::Unknown);
-                        env.define(&p.name, pty, true);
+                        env.define_at(&p.name, pty, true, p.span.clone());
                     }
                     check_block(body, env, errors);
-                    env.pop_return();
+                    let inferred = env.pop_return();
                     env.pop_scope();
+                    if let Some(ret) = inferred {
+                        let ret = inferred_return(ret, body);
+                        if let Some(Type::Fn { ret: r, .. }) = env.classes.get_mut(name.as_str())
+                            .and_then(|c| c.methods.get_mut(mname.as_str()))
+                        {
+                            **r = ret;
+                        }
+                    }
                 }
             }
         }
@@ -165,7 +195,7 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
                 }
             };
             env.push_scope();
-            env.define(var, elem_ty, true);
+            env.define_at(var, elem_ty, true, span.clone());
             check_block(body, env, errors);
             env.pop_scope();
         }
@@ -181,22 +211,58 @@ pub fn check_stmt(stmt: &Stmt, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
                 if !matches!(expected, Type::Unknown | Type::Any)
                     && !expected.is_compatible(&ret_ty)
                 {
-                    let span = expr.as_ref().map(|e| e.span.clone()).unwrap_or(span);
+                    let span = expr.as_ref().map(|e| e.span.clone()).unwrap_or(span.clone());
                     errors.push(TypeError::return_mismatch(&expected, &ret_ty, span));
                 }
             }
+            if let Err((prev, got)) = env.record_return(&ret_ty) {
+                let span = expr.as_ref().map(|e| e.span.clone()).unwrap_or(span);
+                errors.push(TypeError::conflicting_returns(&prev, &got, span));
+            }
         }
 
         // ── Espressione come statement ─────────────────────────────────────
         StmtKind::Expr(e) => { infer_expr(e, env, errors); }
 
-        // ── Trait / Impl / Mod / Use / Break / Continue / Pass ───────────
+        // ── Mod / Use: accettati dal parser ma ignorati a runtime ─────────
+        StmtKind::Mod(n) => errors.push(TypeError::deprecated("D0001",
+            &format!("'mod {}'", n), "modules are not implemented yet and the statement is ignored; remove it", span)),
+        StmtKind::Use(p) => errors.push(TypeError::deprecated("D0002",
+            &format!("'use {}'", p.join("::")),
+            "imports are not implemented yet; built-in modules such as `math` are always in scope", span)),
+
+        // ── Trait / Impl / Break / Continue / Pass ───────────────────────
         StmtKind::Trait { .. } | StmtKind::Impl { .. }
-        | StmtKind::Mod(_) | StmtKind::Use(_)
         | StmtKind::Break | StmtKind::Continue | StmtKind::Pass => {}
     }
 }
 
+/// Tipo di ritorno di una funzione senza annotazione, dati i `return` del corpo:
+/// nessun return → `None`; se il corpo può terminare senza `return` il risultato
+/// può anche essere `None`, quindi resta `Unknown` (salvo che tutti i return siano `None`).
+fn inferred_return(returns: Option<Type>, body: &[Stmt]) -> Type {
+    match returns {
+        None => Type::None,
+        Some(t) if t == Type::None || always_returns(body) => t,
+        Some(_) => Type::Unknown,
+    }
+}
+
+/// True se ogni percorso del blocco termina con un `return`.
+fn always_returns(stmts: &[Stmt]) -> bool {
+    match stmts.last().map(|s| &s.inner) {
+        Some(StmtKind::Return(_)) => true,
+        Some(StmtKind::Expr(Expr { inner: ExprKind::If { then_block, elif_branches, else_block: Some(else_block), .. }, .. })) =>
+            always_returns(then_block)
+                && elif_branches.iter().all(|(_, b)| always_returns(b))
+                && always_returns(else_block),
+        Some(StmtKind::Expr(Expr { inner: ExprKind::Match { arms, .. }, .. })) =>
+            arms.iter().any(|a| matches!(a.pattern, Pattern::Wildcard | Pattern::Ident(_)))
+                && arms.iter().all(|a| always_returns(&a.body)),
+        _ => false,
+    }
+}
+
 /// Verifica un blocco di statement.
 pub fn check_block(stmts: &[Stmt], env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
     for stmt in stmts {
@@ -207,10 +273,11 @@ pub fn check_block(stmts: &[Stmt], env: &mut TypeEnv, errors: &mut Vec<TypeError
 /// Verifica un intero programma.
 pub fn check_program(program: &neba_parser::ast::Program, env: &mut TypeEnv, errors: &mut Vec<TypeError>) {
     // Prima passata: registra tutte le funzioni e classi top-level
-    // (per gestire forward references)
+    // (forward references e ricorsione mutua in qualsiasi ordine di definizione)
     for stmt in &program.stmts {
         pre_register(stmt, env);
     }
+    env.impure_fns = impure_functions(&program.stmts);
     // Seconda passata: verifica tutto
     check_block(&program.stmts, env, errors);
 }
@@ -225,10 +292,10 @@ fn pre_register(stmt: &Stmt, env: &mut TypeEnv) {
             let ret = return_ty.as_ref()
                 .map(|t| Type::from_ast(&t.inner))
                 .unwrap_or(Type::Unknown);
-            env.define(name, Type::Fn { params: param_types, ret: Box::new(ret) }, false);
+            env.define_at(name, Type::Fn { params: param_types, ret: Box::new(ret) }, false, stmt.span.clone());
         }
         StmtKind::Class { name, .. } => {
-            env.define(name, Type::Class(name.clone()), false);
+            env.define_at(name, Type::Class(name.clone()), false, stmt.span.clone());
         }
         _ => {}
     }
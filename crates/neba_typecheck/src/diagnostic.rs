@@ -0,0 +1,89 @@
+use crate::error::{Severity, TypeError};
+
+/// Fase che ha prodotto un diagnostico.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    Lexer,
+    Parser,
+    TypeChecker,
+}
+
+/// Diagnostico pronto per un editor: posizioni di inizio e fine risolte
+/// (righe e colonne da 1, colonne in caratteri), indipendente dalla CLI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub origin:     Origin,
+    pub severity:   Severity,
+    pub code:       &'static str,
+    pub message:    String,
+    pub line:       usize,
+    pub column:     usize,
+    pub end_line:   usize,
+    pub end_column: usize,
+}
+
+impl Diagnostic {
+    /// La fine si ricava da `span.end` (offset in caratteri di `source`); se lo
+    /// span è vuoto coincide con l'inizio.
+    pub fn from_type_error(e: TypeError, source: &str) -> Self {
+        let (end_line, end_column) = if e.span.end > e.span.start {
+            line_col_at(source, e.span.end)
+        } else {
+            (e.span.line, e.span.column)
+        };
+        let origin = match e.code.as_bytes().first() {
+            Some(b'L') => Origin::Lexer,
+            Some(b'P') => Origin::Parser,
+            _          => Origin::TypeChecker,
+        };
+        Diagnostic {
+            origin, severity: e.severity, code: e.code, message: e.message,
+            line: e.span.line, column: e.span.column, end_line, end_column,
+        }
+    }
+
+    /// Oggetto JSON `{severity, code, message, line, column, end_line, end_column}`.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"severity\":\"{}\",\"code\":\"{}\",\"message\":\"{}\",\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+            self.severity.as_str(), self.code, json_escape(&self.message),
+            self.line, self.column, self.end_line, self.end_column,
+        )
+    }
+}
+
+/// Analizza un documento e restituisce tutti i diagnostici (lexer, parser, tipi)
+/// in ordine di posizione. Non fallisce mai: l'input malformato produce diagnostici.
+pub fn analyze_document(source: &str) -> Vec<Diagnostic> {
+    let mut diags: Vec<Diagnostic> = crate::analyse(source)
+        .into_iter()
+        .map(|e| Diagnostic::from_type_error(e, source))
+        .collect();
+    diags.sort_by_key(|d| (d.line, d.column));
+    diags
+}
+
+/// Riga e colonna (da 1) dell'offset `pos`, in caratteri, dentro `source`.
+fn line_col_at(source: &str, pos: usize) -> (usize, usize) {
+    let (mut line, mut col) = (1, 1);
+    for ch in source.chars().take(pos) {
+        if ch == '\n' { line += 1; col = 1; } else { col += 1; }
+    }
+    (line, col)
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use neba_lexer::Span;
 use crate::types::Type;
 
 /// Informazioni su una variabile nell'ambiente.
@@ -6,6 +7,10 @@ use crate::types::Type;
 pub struct VarInfo {
     pub ty:      Type,
     pub mutable: bool,
+    /// True se il tipo è stato dichiarato esplicitamente (`var x: Int = ...`).
+    pub annotated: bool,
+    /// Dove è stato definito il nome (`None` per built-in e binding da pattern).
+    pub def_span: Option<Span>,
 }
 
 /// Frame di un singolo scope.
@@ -14,14 +19,71 @@ struct Frame {
     vars: HashMap<String, VarInfo>,
 }
 
+/// Shadowing rilevato da `TypeEnv::define`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shadowing {
+    /// Il nome era già definito nello stesso scope.
+    SameScope,
+    /// Il nome nasconde una variabile di uno scope esterno.
+    OuterScope,
+}
+
 /// Ambiente dei tipi: stack di frame per gestire scope annidati.
+/// Il frame 0 contiene solo i built-in, il frame 1 i globali del programma.
 #[derive(Debug, Clone)]
 pub struct TypeEnv {
     frames: Vec<Frame>,
     /// Tipi di ritorno attesi per le funzioni annidate (stack).
     pub return_stack: Vec<Type>,
+    /// Parallelo a `return_stack`: per le funzioni senza annotazione, l'unificazione
+    /// dei `return` visti finora (`Some(None)` = nessun return ancora).
+    inferred_returns: Vec<Option<Option<Type>>>,
     /// Registro delle classi: nome → (campi, metodi)
     pub classes: HashMap<String, ClassInfo>,
+    /// Firme dei built-in (frame 0), usate per controllare arietà e argomenti.
+    builtins: HashMap<String, BuiltinSig>,
+    /// Tipi inferiti per identificatori e letterali, nell'ordine di visita (hover).
+    pub expr_types: Vec<(Span, Type)>,
+    /// Per ogni identificatore risolto: (span dell'uso, span della definizione).
+    pub references: Vec<(Span, Span)>,
+    /// Funzioni top-level con effetti collaterali, vietate nelle `pure fn`.
+    pub impure_fns: HashSet<String>,
+}
+
+/// Riga della tabella dei built-in: (nome, parametri, arietà min, arietà max, ritorno).
+type BuiltinRow = (&'static str, Vec<Type>, usize, Option<usize>, Type);
+const VARIADIC: Option<usize> = None;
+
+/// Built-in senza firma nella tabella, definiti come `Fn([Any]) -> Any`.
+const DYNAMIC_BUILTINS: &[&str] = &[
+    "map", "filter", "reduce", "find", "find_index", "join", "slice", "index",
+    "concat", "format", "index_of", "starts_with", "startswith", "ends_with",
+    "endswith", "choice", "shuffle", "sample", "and_then", "or_else", "map_err",
+    "assert_raises", "channel", "race", "memoize", "compose", "partial",
+    "Float64", "Float32", "Int64", "Int32", "TypedArray", "zeros", "ones",
+    "fill", "linspace", "mean", "dot", "min_elem", "max_elem", "to_list",
+];
+
+/// Firma di una funzione built-in.
+#[derive(Debug, Clone)]
+pub struct BuiltinSig {
+    /// Tipi dei parametri; per i variadici l'ultimo vale per gli argomenti extra.
+    pub params: Vec<Type>,
+    pub min:    usize,
+    /// `None` = variadico.
+    pub max:    Option<usize>,
+}
+
+impl BuiltinSig {
+    /// Tipo atteso per l'argomento `i`.
+    pub fn param(&self, i: usize) -> &Type {
+        self.params.get(i).or(self.params.last()).unwrap_or(&Type::Any)
+    }
+
+    /// Parametri del `Type::Fn` corrispondente (`[Any]` = variadico).
+    fn fn_params(&self) -> Vec<Type> {
+        if self.max.is_none() || self.min != self.params.len() { vec![Type::Any] } else { self.params.clone() }
+    }
 }
 
 /// Informazioni su una classe definita dall'utente.
@@ -31,41 +93,152 @@ pub struct ClassInfo {
     pub methods: HashMap<String, Type>,
 }
 
+impl Default for TypeEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TypeEnv {
     pub fn new() -> Self {
         let mut env = TypeEnv {
             frames:       vec![Frame::default()],
             return_stack: Vec::new(),
+            inferred_returns: Vec::new(),
             classes:      HashMap::new(),
+            builtins:     HashMap::new(),
+            expr_types:   Vec::new(),
+            references:   Vec::new(),
+            impure_fns:   HashSet::new(),
         };
         // Built-in globali
         env.register_builtins();
+        env.frames.push(Frame::default());
         env
     }
 
     fn register_builtins(&mut self) {
+        // (nome, tipi dei parametri, arietà minima, arietà massima, ritorno)
+        // VARIADIC → gli argomenti oltre `params` usano l'ultimo tipo
         use Type::*;
-        let builtins: &[(&str, Type)] = &[
-            ("print",   Fn { params: vec![Any], ret: Box::new(None) }),
-            ("println", Fn { params: vec![Any], ret: Box::new(None) }),
-            ("input",   Fn { params: vec![Str], ret: Box::new(Str)  }),
-            ("len",     Fn { params: vec![Any], ret: Box::new(Int)  }),
-            ("str",     Fn { params: vec![Any], ret: Box::new(Str)  }),
-            ("int",     Fn { params: vec![Any], ret: Box::new(Int)  }),
-            ("float",   Fn { params: vec![Any], ret: Box::new(Float)}),
-            ("bool",    Fn { params: vec![Any], ret: Box::new(Bool) }),
-            ("typeof",  Fn { params: vec![Any], ret: Box::new(Str)  }),
-            ("abs",     Fn { params: vec![Any], ret: Box::new(Float)}),
-            ("min",     Fn { params: vec![Any], ret: Box::new(Any)  }),
-            ("max",     Fn { params: vec![Any], ret: Box::new(Any)  }),
-            ("range",   Fn { params: vec![Int, Int], ret: Box::new(Array(Box::new(Int))) }),
-            ("push",    Fn { params: vec![Any, Any], ret: Box::new(None) }),
-            ("pop",     Fn { params: vec![Any], ret: Box::new(Any)  }),
-            ("assert",  Fn { params: vec![Bool], ret: Box::new(None)}),
+        let builtins: &[BuiltinRow] = &[
+            ("print",     vec![Any],               0, VARIADIC, None),
+            ("println",   vec![Any],               0, VARIADIC, None),
+            ("input",     vec![Str],               0, Some(1), Str),
+            ("len",       vec![Any],               1, Some(1), Int),
+            ("byte_len",  vec![Str],               1, Some(1), Int),
+            ("str",       vec![Any],               1, Some(1), Str),
+            ("int",       vec![Any],               1, Some(1), Int),
+            ("float",     vec![Any],               1, Some(1), Float),
+            ("bool",      vec![Any],               1, Some(1), Bool),
+            ("typeof",    vec![Any],               1, Some(1), Str),
+            ("abs",       vec![Float],             1, Some(1), Float),
+            ("min",       vec![Any],               1, VARIADIC, Any),
+            ("max",       vec![Any],               1, VARIADIC, Any),
+            ("range",     vec![Int, Int, Int],     1, Some(3), Array(Box::new(Int))),
+            ("push",      vec![Any, Any],          2, Some(2), None),
+            ("pop",       vec![Any],               1, Some(1), Any),
+            ("clear",     vec![Any],               1, Some(1), None),
+            ("assert",    vec![Bool, Any],         1, Some(2), None),
+            ("approx_eq", vec![Float, Float, Float], 2, Some(3), Bool),
+            ("deep_eq",   vec![Any, Any],          2, Some(2), Bool),
+            ("freeze",    vec![Any],               1, Some(1), Any),
+            ("to_json",   vec![Any],               1, Some(1), Str),
+            ("from_json", vec![Str],               1, Some(1), Any),
+            ("type",      vec![Any],               1, Some(1), Str),
+            ("assert_eq", vec![Any, Any, Any],     2, Some(3), None),
+            ("hash",      vec![Any],               1, Some(1), Int),
+            ("sha256_hex", vec![Str],              1, Some(1), Str),
+            ("clock",     vec![],                  0, Some(0), Float),
+            ("time_ms",   vec![],                  0, Some(0), Int),
+            ("sleep",     vec![Float],             1, Some(1), None),
+            ("sum",       vec![Any],               1, Some(1), Any),
+            ("zip",       vec![Any],               2, VARIADIC, Array(Box::new(Any))),
+            ("enumerate", vec![Any, Int],          1, Some(2), Array(Box::new(Any))),
+            ("sorted",    vec![Any, Bool],         1, Some(2), Array(Box::new(Any))),
+            ("any",       vec![Any],               1, Some(1), Bool),
+            ("all",       vec![Any],               1, Some(1), Bool),
+            ("chr",       vec![Int],               1, Some(1), Str),
+            ("ord",       vec![Str],               1, Some(1), Int),
+            ("copy",      vec![Any],               1, Some(1), Any),
+            ("hex",       vec![Int],               1, Some(1), Str),
+            ("bin",       vec![Int],               1, Some(1), Str),
+            ("oct",       vec![Int],               1, Some(1), Str),
+            ("repr",      vec![Any],               1, Some(1), Str),
+            ("keys",      vec![Any],               1, Some(1), Array(Box::new(Any))),
+            ("values",    vec![Any],               1, Some(1), Array(Box::new(Any))),
+            ("items",     vec![Any],               1, Some(1), Array(Box::new(Any))),
+            ("has_key",   vec![Any, Any],          2, Some(2), Bool),
+            ("del_key",   vec![Any, Any],          2, Some(2), Any),
+            ("dict_get",  vec![Any, Any, Any],     2, Some(3), Any),
+            ("merge",     vec![Any, Any],          2, Some(2), Any),
+            ("append",    vec![Any, Any],          2, Some(2), None),
+            ("remove",    vec![Any, Any],          2, Some(2), Any),
+            ("insert",    vec![Any, Int, Any],     3, Some(3), None),
+            ("contains",  vec![Any, Any],          2, Some(2), Bool),
+            ("count",     vec![Any, Any],          2, Some(2), Int),
+            ("sort",      vec![Any],               1, Some(1), None),
+            ("reverse",   vec![Any],               1, Some(1), Any),
+            ("flatten",   vec![Any],               1, Some(1), Array(Box::new(Any))),
+            ("unique",    vec![Any],               1, Some(1), Array(Box::new(Any))),
+            ("ok_or",     vec![Any, Any],          2, Some(2), Any),
+            ("upper",     vec![Str],               1, Some(1), Str),
+            ("lower",     vec![Str],               1, Some(1), Str),
+            ("strip",     vec![Str],               1, Some(1), Str),
+            ("lstrip",    vec![Str],               1, Some(1), Str),
+            ("rstrip",    vec![Str],               1, Some(1), Str),
+            ("split",     vec![Str, Any],          1, Some(2), Array(Box::new(Str))),
+            ("replace",   vec![Str, Str, Str],     3, Some(3), Str),
+            ("capitalize", vec![Str],              1, Some(1), Str),
+            ("title",     vec![Str],               1, Some(1), Str),
+            ("is_digit",  vec![Str],               1, Some(1), Bool),
+            ("is_alpha",  vec![Str],               1, Some(1), Bool),
+            ("pad_left",  vec![Str, Int, Str],     2, Some(3), Str),
+            ("pad_right", vec![Str, Int, Str],     2, Some(3), Str),
+            ("zfill",     vec![Str, Int],          2, Some(2), Str),
+            ("char_at",   vec![Str, Int],          2, Some(2), Str),
+            ("is_int",    vec![Any],               1, Some(1), Bool),
+            ("is_float",  vec![Any],               1, Some(1), Bool),
+            ("is_str",    vec![Any],               1, Some(1), Bool),
+            ("is_bool",   vec![Any],               1, Some(1), Bool),
+            ("is_none",   vec![Any],               1, Some(1), Bool),
+            ("is_array",  vec![Any],               1, Some(1), Bool),
+            ("is_dict",   vec![Any],               1, Some(1), Bool),
+            ("regex_match",    vec![Str, Str],     2, Some(2), Any),
+            ("regex_find_all", vec![Str, Str],     2, Some(2), Array(Box::new(Str))),
+            ("regex_replace",  vec![Str, Str, Str], 3, Some(3), Str),
+            ("path_join", vec![Str],               1, VARIADIC, Str),
+            ("basename",  vec![Str],               1, Some(1), Str),
+            ("dirname",   vec![Str],               1, Some(1), Str),
+            ("exists",    vec![Str],               1, Some(1), Bool),
+            ("list_dir",  vec![Str],               1, Some(1), Any),
+            ("glob",      vec![Str],               1, Some(1), Any),
+            ("csv_parse", vec![Str],               1, VARIADIC, Any),
+            ("csv_write", vec![Any],               1, VARIADIC, Any),
         ];
-        for (name, ty) in builtins {
-            self.define(name, ty.clone(), false);
+        for (name, params, min, max, ret) in builtins {
+            let sig = BuiltinSig { params: params.clone(), min: *min, max: *max };
+            self.define(name, Fn { params: sig.fn_params(), ret: Box::new(ret.clone()) }, false);
+            self.builtins.insert(name.to_string(), sig);
+        }
+        // Built-in polimorfi o con firme diverse tra VM e interprete
+        // (`join`, `find`, HOF, TypedArray, ...): niente controllo di arietà
+        let dynamic_fn = || Fn { params: vec![Any], ret: Box::new(Any) };
+        for name in DYNAMIC_BUILTINS {
+            self.define(name, dynamic_fn(), false);
+        }
+        // Moduli della stdlib: i membri non sono tipizzati
+        for name in ["math", "string", "io", "collections", "random", "nd"] {
+            self.define(name, Any, false);
         }
+        self.define("inf", Float, false);
+        self.define("nan", Float, false);
+    }
+
+    /// Firma del built-in `name`, se il nome non è ridefinito dal programma.
+    pub fn builtin_sig(&self, name: &str) -> Option<&BuiltinSig> {
+        if self.frames[1..].iter().any(|f| f.vars.contains_key(name)) { return None; }
+        self.builtins.get(name)
     }
 
     // ── Gestione scope ────────────────────────────────────────────────────
@@ -75,7 +248,7 @@ impl TypeEnv {
     }
 
     pub fn pop_scope(&mut self) {
-        if self.frames.len() > 1 {
+        if self.frames.len() > 2 {
             self.frames.pop();
         }
     }
@@ -83,10 +256,30 @@ impl TypeEnv {
     // ── Variabili ─────────────────────────────────────────────────────────
 
     /// Definisce una nuova variabile nello scope corrente.
-    pub fn define(&mut self, name: &str, ty: Type, mutable: bool) {
+    /// Restituisce lo shadowing rilevato (ignorato per i built-in e i nomi `_`-prefissi).
+    pub fn define(&mut self, name: &str, ty: Type, mutable: bool) -> Option<Shadowing> {
+        let shadowing = if name.starts_with('_') || self.frames.len() < 2 {
+            None
+        } else if self.frames.last().is_some_and(|f| f.vars.contains_key(name)) {
+            Some(Shadowing::SameScope)
+        } else if self.frames[1..self.frames.len() - 1].iter().any(|f| f.vars.contains_key(name)) {
+            Some(Shadowing::OuterScope)
+        } else {
+            None
+        };
         if let Some(frame) = self.frames.last_mut() {
-            frame.vars.insert(name.to_string(), VarInfo { ty, mutable });
+            frame.vars.insert(name.to_string(), VarInfo { ty, mutable, annotated: false, def_span: None });
+        }
+        shadowing
+    }
+
+    /// Come `define`, registrando `span` come punto di definizione (go-to-definition).
+    pub fn define_at(&mut self, name: &str, ty: Type, mutable: bool, span: Span) -> Option<Shadowing> {
+        let shadowing = self.define(name, ty, mutable);
+        if let Some(info) = self.frames.last_mut().and_then(|f| f.vars.get_mut(name)) {
+            info.def_span = Some(span);
         }
+        shadowing
     }
 
     /// Cerca una variabile risalendo gli scope.
@@ -99,6 +292,13 @@ impl TypeEnv {
         None
     }
 
+    /// Segna come annotata la variabile `name` appena definita nello scope corrente.
+    pub fn mark_annotated(&mut self, name: &str) {
+        if let Some(info) = self.frames.last_mut().and_then(|f| f.vars.get_mut(name)) {
+            info.annotated = true;
+        }
+    }
+
     /// Cerca una variabile (mut) per aggiornarne il tipo.
     pub fn lookup_mut(&mut self, name: &str) -> Option<&mut VarInfo> {
         for frame in self.frames.iter_mut().rev() {
@@ -111,10 +311,35 @@ impl TypeEnv {
 
     // ── Tipi di ritorno ───────────────────────────────────────────────────
 
-    pub fn push_return(&mut self, ty: Type) { self.return_stack.push(ty); }
-    pub fn pop_return(&mut self)            { self.return_stack.pop(); }
+    pub fn push_return(&mut self, ty: Type) { self.return_stack.push(ty); self.inferred_returns.push(None); }
     pub fn expected_return(&self) -> Option<&Type> { self.return_stack.last() }
 
+    /// Come `push_return`, ma il tipo di ritorno va inferito dai `return` del corpo.
+    pub fn push_inferred_return(&mut self) {
+        self.return_stack.push(Type::Unknown);
+        self.inferred_returns.push(Some(None));
+    }
+
+    /// Chiude la funzione corrente; restituisce l'unificazione dei `return` visti
+    /// se il ritorno era da inferire.
+    pub fn pop_return(&mut self) -> Option<Option<Type>> {
+        self.return_stack.pop();
+        self.inferred_returns.pop().flatten()
+    }
+
+    /// Registra un `return` nella funzione corrente, se il suo tipo va inferito.
+    /// `Err((precedente, nuovo))` se i due tipi non sono unificabili.
+    pub fn record_return(&mut self, ty: &Type) -> Result<(), (Type, Type)> {
+        let Some(Some(slot)) = self.inferred_returns.last_mut() else { return Ok(()) };
+        match slot {
+            None => { *slot = Some(ty.clone()); Ok(()) }
+            Some(prev) => match Type::unify(prev, ty) {
+                Some(t) => { *prev = t; Ok(()) }
+                None    => Err((prev.clone(), ty.clone())),
+            },
+        }
+    }
+
     // ── Classi ────────────────────────────────────────────────────────────
 
     pub fn register_class(&mut self, name: &str, info: ClassInfo) {
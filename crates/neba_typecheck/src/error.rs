@@ -1,34 +1,47 @@
 use std::fmt;
 use neba_lexer::Span;
 use crate::types::Type;
+use crate::env::Shadowing;
 
 /// Severità di un diagnostico.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Severity {
     Error,
     Warning,
+    /// Costrutto destinato a sparire o cambiare: funziona ancora, ma il messaggio
+    /// indica cosa usare al suo posto.
+    Deprecation,
 }
 
 /// Un errore/warning prodotto dal type checker.
+///
+/// `code` è un identificativo stabile del tipo di diagnostico (`E…` errori,
+/// `W…` warning, `D…` deprecazioni; `L…`/`P…` per errori di lexer/parser
+/// riportati da `analyse`), utile per documentazione e filtri.
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeError {
     pub severity: Severity,
+    pub code:     &'static str,
     pub message:  String,
     pub span:     Span,
 }
 
 impl TypeError {
-    pub fn error(message: impl Into<String>, span: Span) -> Self {
-        TypeError { severity: Severity::Error, message: message.into(), span }
+    pub fn error(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        TypeError { severity: Severity::Error, code, message: message.into(), span }
     }
-    pub fn warning(message: impl Into<String>, span: Span) -> Self {
-        TypeError { severity: Severity::Warning, message: message.into(), span }
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        TypeError { severity: Severity::Warning, code, message: message.into(), span }
+    }
+    pub fn deprecation(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        TypeError { severity: Severity::Deprecation, code, message: message.into(), span }
     }
 
     // ── Costruttori per i casi comuni ─────────────────────────────────────
 
     pub fn type_mismatch(expected: &Type, got: &Type, span: Span) -> Self {
         Self::error(
+            "E0001",
             format!("type mismatch: expected '{}', got '{}'", expected, got),
             span,
         )
@@ -36,52 +49,102 @@ impl TypeError {
 
     pub fn binary_op(op: &str, left: &Type, right: &Type, span: Span) -> Self {
         Self::error(
+            "E0002",
             format!("operator '{}' cannot be applied to '{}' and '{}'", op, left, right),
             span,
         )
     }
 
     pub fn not_callable(ty: &Type, span: Span) -> Self {
-        Self::error(format!("'{}' is not callable", ty), span)
+        Self::error("E0003", format!("'{}' is not callable", ty), span)
     }
 
     pub fn arity(name: &str, expected: usize, got: usize, span: Span) -> Self {
         Self::error(
+            "E0004",
             format!("'{}' expects {} argument(s), got {}", name, expected, got),
             span,
         )
     }
 
+    /// Arietà fuori dall'intervallo accettato da un built-in.
+    pub fn arity_range(name: &str, min: usize, max: Option<usize>, got: usize, span: Span) -> Self {
+        let expected = match max {
+            Some(max) if max == min => format!("{}", min),
+            Some(max)               => format!("{} to {}", min, max),
+            None                    => format!("at least {}", min),
+        };
+        Self::error("E0004", format!("'{}' expects {} argument(s), got {}", name, expected, got), span)
+    }
+
+    pub fn arg_type(name: &str, index: usize, expected: &Type, got: &Type, span: Span) -> Self {
+        Self::error(
+            "E0005",
+            format!("argument {} of '{}' expects '{}', got '{}'", index + 1, name, expected, got),
+            span,
+        )
+    }
+
     pub fn undefined(name: &str, span: Span) -> Self {
-        Self::error(format!("undefined variable '{}'", name), span)
+        Self::error("E0006", format!("undefined variable '{}'", name), span)
     }
 
     pub fn not_iterable(ty: &Type, span: Span) -> Self {
-        Self::error(format!("'{}' is not iterable", ty), span)
+        Self::error("E0007", format!("'{}' is not iterable", ty), span)
     }
 
     pub fn assign_immutable(name: &str, span: Span) -> Self {
-        Self::error(format!("cannot assign to immutable variable '{}'", name), span)
+        Self::error("E0008", format!("cannot assign to immutable variable '{}'", name), span)
+    }
+
+    pub fn conflicting_returns(first: &Type, got: &Type, span: Span) -> Self {
+        Self::error(
+            "E0009",
+            format!("conflicting return types: '{}' and '{}'", first, got),
+            span,
+        )
     }
 
     pub fn return_mismatch(expected: &Type, got: &Type, span: Span) -> Self {
         Self::error(
+            "E0010",
             format!("return type mismatch: function declares '{}', got '{}'", expected, got),
             span,
         )
     }
 
+    pub fn shadowing(name: &str, kind: Shadowing, span: Span) -> Self {
+        match kind {
+            Shadowing::OuterScope => Self::warning("W0001", format!("'{}' shadows outer binding '{}'", name, name), span),
+            Shadowing::SameScope  => Self::warning("W0002", format!("'{}' redefined in the same scope", name), span),
+        }
+    }
+
+    pub fn impure(fn_name: &str, what: &str, span: Span) -> Self {
+        Self::error("E0011", format!("pure function '{}' {}", fn_name, what), span)
+    }
+
+    pub fn deprecated(code: &'static str, what: &str, suggestion: &str, span: Span) -> Self {
+        Self::deprecation(code, format!("{} is deprecated: {}", what, suggestion), span)
+    }
+
     pub fn unknown_field(ty: &str, field: &str, span: Span) -> Self {
-        Self::error(format!("'{}' has no field '{}'", ty, field), span)
+        Self::error("E0012", format!("'{}' has no field '{}'", ty, field), span)
+    }
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error       => "error",
+            Severity::Warning     => "warning",
+            Severity::Deprecation => "deprecation",
+        }
     }
 }
 
 impl fmt::Display for TypeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let sev = match self.severity {
-            Severity::Error   => "error",
-            Severity::Warning => "warning",
-        };
-        write!(f, "[{}] {}:{}: {}", sev, self.span.line, self.span.column, self.message)
+        write!(f, "[{} {}] {}:{}: {}", self.severity.as_str(), self.code, self.span.line, self.span.column, self.message)
     }
 }
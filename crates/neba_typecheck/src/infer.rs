@@ -5,7 +5,18 @@ use crate::error::TypeError;
 use crate::env::TypeEnv;
 
 /// Inferisce il tipo di un'espressione, accumulando errori in `errors`.
+/// Il tipo di identificatori e letterali viene annotato in `env.expr_types`.
 pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -> Type {
+    let ty = infer_expr_inner(expr, env, errors);
+    if matches!(expr.inner, ExprKind::Int(_) | ExprKind::Float(_) | ExprKind::Bool(_)
+        | ExprKind::Str(_) | ExprKind::FStr(_) | ExprKind::Char(_) | ExprKind::None | ExprKind::Ident(_))
+    {
+        env.expr_types.push((expr.span.clone(), ty.clone()));
+    }
+    ty
+}
+
+fn infer_expr_inner(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -> Type {
     let span = expr.span.clone();
     match &expr.inner {
         // ── Letterali ─────────────────────────────────────────────────────
@@ -13,6 +24,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
         ExprKind::Float(_) => Type::Float,
         ExprKind::Bool(_)  => Type::Bool,
         ExprKind::Str(_) | ExprKind::FStr(_) => Type::Str,
+        ExprKind::Char(_)  => Type::Char,
         ExprKind::None     => Type::None,
         ExprKind::Slice { .. } => Type::Unknown,
         ExprKind::Error    => Type::Unknown,
@@ -20,7 +32,13 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
         // ── Identificatore ────────────────────────────────────────────────
         ExprKind::Ident(name) => {
             match env.lookup(name) {
-                Some(info) => info.ty.clone(),
+                Some(info) => {
+                    let ty = info.ty.clone();
+                    if let Some(def) = info.def_span.clone() {
+                        env.references.push((span, def));
+                    }
+                    ty
+                }
                 None => {
                     errors.push(TypeError::undefined(name, span));
                     Type::Unknown
@@ -35,6 +53,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
                 UnaryOp::Neg => {
                     if !t.is_numeric() && !matches!(t, Type::Unknown | Type::Any) {
                         errors.push(TypeError::error(
+                            "E0013",
                             format!("unary '-' cannot be applied to '{}'", t), span,
                         ));
                     }
@@ -44,6 +63,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
                 UnaryOp::BitNot => {
                     if !matches!(t, Type::Int | Type::Unknown | Type::Any) {
                         errors.push(TypeError::error(
+                            "E0013",
                             format!("unary '~' requires Int, got '{}'", t), span,
                         ));
                     }
@@ -55,6 +75,11 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
         // ── Operatori binari ──────────────────────────────────────────────
         ExprKind::Binary { op, left, right } => {
             let lt = infer_expr(left, env, errors);
+            // `x is Int`: a destra un nome di tipo built-in, non una variabile
+            if matches!(op, BinOp::Is | BinOp::IsNot) && matches!(&right.inner,
+                ExprKind::Ident(n) if BUILTIN_TYPE_NAMES.contains(&n.as_str()) && env.lookup(n).is_none()) {
+                return Type::Bool;
+            }
             let rt = infer_expr(right, env, errors);
             infer_binary(op, &lt, &rt, span, errors)
         }
@@ -72,6 +97,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
                     Some(u) => unified = u,
                     None => {
                         errors.push(TypeError::error(
+                            "E0014",
                             format!("array elements have inconsistent types: '{}' and '{}'", unified, t),
                             elem.span.clone(),
                         ));
@@ -81,6 +107,9 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
             Type::Array(Box::new(unified))
         }
 
+        // ── Tupla ─────────────────────────────────────────────────────────
+        ExprKind::Tuple(elems) => Type::Tuple(elems.iter().map(|e| infer_expr(e, env, errors)).collect()),
+
         ExprKind::Dict(_) => {
             // v0.2.5: Dict è tipizzato come Any per ora (type inference completa in fasi future)
             Type::Any
@@ -92,11 +121,13 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
             let et = infer_expr(end, env, errors);
             if !matches!(st, Type::Int | Type::Unknown | Type::Any) {
                 errors.push(TypeError::error(
+                    "E0015",
                     format!("range start must be Int, got '{}'", st), start.span.clone(),
                 ));
             }
             if !matches!(et, Type::Int | Type::Unknown | Type::Any) {
                 errors.push(TypeError::error(
+                    "E0015",
                     format!("range end must be Int, got '{}'", et), end.span.clone(),
                 ));
             }
@@ -104,9 +135,26 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
         }
 
         // ── Chiamata ──────────────────────────────────────────────────────
+        ExprKind::Pipe { value, func } =>
+            infer_expr(&Node::new(ExprKind::pipe_call(value, func), expr.span.clone()), env, errors),
         ExprKind::Call { callee, args, .. } => {
             let callee_ty = infer_expr(callee, env, errors);
-            for arg in args { infer_expr(arg, env, errors); }
+            let arg_tys: Vec<Type> = args.iter().map(|a| infer_expr(a, env, errors)).collect();
+            // Built-in: arietà e tipi degli argomenti dalla firma registrata
+            if let ExprKind::Ident(name) = &callee.inner {
+                if let Some(sig) = env.builtin_sig(name) {
+                    if args.len() < sig.min || sig.max.is_some_and(|m| args.len() > m) {
+                        errors.push(TypeError::arity_range(name, sig.min, sig.max, args.len(), span.clone()));
+                    }
+                    for (i, (arg, ty)) in args.iter().zip(&arg_tys).enumerate() {
+                        let expected = sig.param(i);
+                        if !expected.is_compatible(ty) {
+                            errors.push(TypeError::arg_type(name, i, expected, ty, arg.span.clone()));
+                        }
+                    }
+                    if let Type::Fn { ret, .. } = callee_ty { return *ret; }
+                }
+            }
             match &callee_ty {
                 Type::Fn { params, ret } => {
                     // Verifica arietà (tollerante: Any params = variadic)
@@ -132,7 +180,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
 
         // ── Accesso campo ─────────────────────────────────────────────────
         ExprKind::Field { object, field } => {
-            let obj_ty = infer_expr(object, env, errors);
+            let obj_ty = infer_expr(object, env, errors).char_as_str();
             match &obj_ty {
                 Type::Class(name) => {
                     if let Some(info) = env.lookup_class(name).cloned() {
@@ -152,6 +200,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
                 // Built-in su stringhe e array
                 Type::Str => match field.as_str() {
                     "len" | "upper" | "lower" | "trim" => Type::Int,
+                    "format" => Type::Unknown,
                     _ => {
                         errors.push(TypeError::unknown_field("Str", field, span));
                         Type::Unknown
@@ -167,6 +216,7 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
                 Type::Unknown | Type::Any => Type::Unknown,
                 other => {
                     errors.push(TypeError::error(
+                        "E0016",
                         format!("'{}' has no fields", other), span,
                     ));
                     Type::Unknown
@@ -176,10 +226,11 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
 
         // ── Indice ────────────────────────────────────────────────────────
         ExprKind::Index { object, index } => {
-            let obj_ty = infer_expr(object, env, errors);
+            let obj_ty = infer_expr(object, env, errors).char_as_str();
             let idx_ty = infer_expr(index, env, errors);
             if !matches!(idx_ty, Type::Int | Type::Unknown | Type::Any) {
                 errors.push(TypeError::error(
+                    "E0017",
                     format!("index must be Int, got '{}'", idx_ty),
                     index.span.clone(),
                 ));
@@ -187,9 +238,16 @@ pub fn infer_expr(expr: &Expr, env: &mut TypeEnv, errors: &mut Vec<TypeError>) -
             match &obj_ty {
                 Type::Array(inner) => *inner.clone(),
                 Type::Str          => Type::Str,
+                // Solo un indice letterale individua l'elemento; fuori range se ne accorge il runtime
+                Type::Tuple(elems) => match &index.inner {
+                    ExprKind::Int(i) => usize::try_from(*i).ok()
+                        .and_then(|i| elems.get(i)).cloned().unwrap_or(Type::Unknown),
+                    _ => Type::Unknown,
+                },
                 Type::Unknown | Type::Any => Type::Unknown,
                 other => {
                     errors.push(TypeError::error(
+                        "E0018",
                         format!("'{}' is not indexable", other), span,
                     ));
                     Type::Unknown
@@ -291,7 +349,7 @@ fn bind_pattern_vars(pat: &Pattern, env: &mut TypeEnv) {
         Pattern::Ident(name) => {
             env.define(name, Type::Unknown, true);
         }
-        Pattern::Constructor(_, inner) => {
+        Pattern::Constructor(_, inner) | Pattern::Tuple(inner) => {
             for p in inner { bind_pattern_vars(p, env); }
         }
         Pattern::Or(pats) => {
@@ -301,6 +359,9 @@ fn bind_pattern_vars(pat: &Pattern, env: &mut TypeEnv) {
             bind_pattern_vars(start, env);
             bind_pattern_vars(end, env);
         }
+        Pattern::StrAffix { rest, .. } => {
+            if let Pattern::Ident(name) = rest.as_ref() { env.define(name, Type::Str, true); }
+        }
         _ => {}
     }
 }
@@ -308,6 +369,7 @@ fn bind_pattern_vars(pat: &Pattern, env: &mut TypeEnv) {
 /// Tipo risultante di un'operazione binaria.
 fn infer_binary(op: &BinOp, lt: &Type, rt: &Type, span: Span, errors: &mut Vec<TypeError>) -> Type {
     let op_str = op_name(op);
+    let (lt, rt) = (&lt.char_as_str(), &rt.char_as_str());
     match op {
         // Aritmetica
         BinOp::Add => {
@@ -324,6 +386,10 @@ fn infer_binary(op: &BinOp, lt: &Type, rt: &Type, span: Span, errors: &mut Vec<T
             Type::Unknown
         }
         BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Pow => {
+            // Str * Int = Str (ripetizione)
+            if matches!(op, BinOp::Mul) && matches!((lt, rt), (Type::Str, Type::Int) | (Type::Int, Type::Str)) {
+                return Type::Str;
+            }
             if lt.is_numeric() && rt.is_numeric() {
                 return Type::unify(lt, rt).unwrap_or(Type::Float);
             }
@@ -366,7 +432,7 @@ fn infer_binary(op: &BinOp, lt: &Type, rt: &Type, span: Span, errors: &mut Vec<T
 
         // Membership
         BinOp::In | BinOp::NotIn => Type::Bool,
-        BinOp::Is => Type::Bool,
+        BinOp::Is | BinOp::IsNot => Type::Bool,
     }
 }
 
@@ -395,5 +461,6 @@ fn op_name(op: &BinOp) -> &'static str {
         BinOp::In     => "in",
         BinOp::NotIn  => "not in",
         BinOp::Is     => "is",
+        BinOp::IsNot  => "is not",
     }
 }
@@ -1,28 +1,35 @@
 pub mod check;
+pub mod diagnostic;
 pub mod env;
 pub mod error;
 pub mod infer;
+pub mod purity;
+pub mod query;
 pub mod types;
 
 pub use check::check_program;
-pub use env::TypeEnv;
+pub use diagnostic::{analyze_document, Diagnostic, Origin};
+pub use env::{Shadowing, TypeEnv};
 pub use error::{Severity, TypeError};
+pub use query::{definition_at, type_at};
 pub use types::Type;
 
 /// Analizza il sorgente e restituisce la lista di diagnostici.
 /// Non blocca a errori: restituisce sempre tutti i problemi trovati.
 pub fn analyse(source: &str) -> Vec<TypeError> {
-    let (program, lex_errors, parse_errors) = neba_parser::parse(source);
+    let (program, lex_errors, lex_warnings, parse_errors) = neba_parser::parse_with_warnings(source);
 
     let mut errors = Vec::new();
 
-    // Converti errori di lex/parse in TypeError con span dummy
-    let dummy_span = neba_lexer::Span::new(0, 0, 0, 0);
+    // Converti errori di lex/parse in TypeError, conservando la posizione
     for e in lex_errors {
-        errors.push(TypeError::error(format!("lex error: {}", e), dummy_span.clone()));
+        errors.push(TypeError::error(e.code(), format!("lex error: {}", e), e.span().clone()));
+    }
+    for e in lex_warnings {
+        errors.push(TypeError::warning(e.code(), format!("lex warning: {}", e), e.span().clone()));
     }
     for e in parse_errors {
-        errors.push(TypeError::error(format!("parse error: {}", e), dummy_span.clone()));
+        errors.push(TypeError::error(e.code(), format!("parse error: {}", e), e.span().clone()));
     }
 
     let mut env = TypeEnv::new();
@@ -30,6 +37,13 @@ pub fn analyse(source: &str) -> Vec<TypeError> {
     errors
 }
 
+/// Tutti i diagnostici di `analyse` come array JSON (un oggetto per diagnostico,
+/// vedi `Diagnostic::to_json`), per editor e strumenti esterni.
+pub fn diagnostics_json(source: &str) -> String {
+    let items: Vec<String> = analyze_document(source).iter().map(Diagnostic::to_json).collect();
+    format!("[{}]", items.join(","))
+}
+
 /// Analizza e restituisce solo gli errori (non i warning).
 pub fn check(source: &str) -> Vec<TypeError> {
     analyse(source)
@@ -54,6 +68,10 @@ mod tests {
         assert!(!errs.is_empty(), "expected errors, got none for: {}", src);
     }
 
+    fn warnings(src: &str) -> Vec<TypeError> {
+        analyse(src).into_iter().filter(|e| e.severity == Severity::Warning).collect()
+    }
+
     fn err_contains(src: &str, fragment: &str) {
         let errs = check(src);
         let found = errs.iter().any(|e| e.message.contains(fragment));
@@ -73,14 +91,54 @@ mod tests {
     #[test] fn t_undef()      { err("let x = undefined_var"); }
     #[test] fn t_immut()      { err("let x = 1\nx = 2"); }
     #[test] fn t_mut_ok()     { ok("var x = 1\nx = 2"); }
+    #[test] fn t_compound_immut()       { err_contains("let x = 1\nx += 1", "immutable variable 'x'"); }
+    #[test] fn t_compound_immut_local() { err_contains("fn f()\n    let n = 0\n    n *= 2", "immutable variable 'n'"); }
+    #[test] fn t_compound_mut_ok()      { ok("var x = 1\nx += 1\nx -= 2"); }
+    #[test] fn t_reassign_annot_ok()   { ok("var x: Int = 1\nx = 2"); }
+    #[test] fn t_reassign_annot_err()  { err_contains("var x: Int = 1\nx = \"s\"", "type mismatch"); }
+    #[test] fn t_reassign_local_err()  { err_contains("fn f()\n    var s: Str = \"a\"\n    s = 3", "type mismatch"); }
+    #[test] fn t_reassign_unannotated() { ok("var x = 1\nx = \"s\"\nlet y: Str = x"); }
+    #[test] fn t_reassign_unannotated_branch() {
+        ok("var x = 1\nlet c = true\nif c\n    x = \"s\"\nlet y = x + 1");
+        ok("fn f(c: Bool)\n    var x = 1\n    if c\n        x = \"s\"\n    return x + 1");
+        // Stesso tipo: resta Int e gli errori successivi sono ancora segnalati
+        err_contains("var x = 1\nif true\n    x = 2\nlet y: Str = x", "type mismatch");
+    }
+
+    // ── Shadowing ─────────────────────────────────────────────────────────
+    #[test] fn t_shadow_inner() {
+        let w = warnings("let x = 1\nfn f()\n    let x = 2");
+        assert_eq!(w.len(), 1, "{:?}", w);
+        assert!(w[0].message.contains("shadows outer binding 'x'"));
+        assert_eq!(w[0].span.line, 3);
+    }
+    #[test] fn t_shadow_same_scope() {
+        let w = warnings("var x = 1\nlet x = 2");
+        assert!(w.iter().any(|e| e.message.contains("redefined in the same scope")), "{:?}", w);
+    }
+    #[test] fn t_shadow_distinct()    { assert!(warnings("let x = 1\nfn f()\n    let y = 2").is_empty()); }
+    #[test] fn t_shadow_underscore()  { assert!(warnings("let _x = 1\nif true\n    let _x = 2").is_empty()); }
+    #[test] fn t_shadow_builtin_ok()  { assert!(warnings("let max = 3").is_empty()); }
 
     // ── Annotazioni di tipo ───────────────────────────────────────────────
     #[test] fn t_annot_ok()   { ok("let x: Int = 42"); }
     #[test] fn t_annot_err()  { err_contains("let x: Int = \"hello\"", "type mismatch"); }
     #[test] fn t_annot_float(){ ok("let x: Float = 1"); } // Int → Float ok
     #[test] fn t_annot_str()  { ok("let s: Str = \"world\""); }
+    #[test] fn t_char() {
+        ok("let c: Char = 'a'\nlet s: Str = c + \"b\"\nlet r: Str = '=' * 3\nlet b: Bool = c < \"z\"");
+        err_contains("let c: Char = 1", "type mismatch");
+    }
+    #[test] fn t_tuple_index() {
+        ok("let t = (1, \"a\")\nlet n: Int = t[0]\nlet s: Str = t[1]\nmatch t\n    (a, b) => a");
+        err_contains("let t = (1, \"a\")\nlet n: Int = t[1]", "type mismatch");
+    }
 
     // ── Operatori ─────────────────────────────────────────────────────────
+    #[test] fn t_is_type_name() {
+        ok("let x = 1\nlet b: Bool = x is Int or x is not Char or (1, 2) is Tuple");
+        err_contains("let b = 1 is Nope", "undefined variable");
+    }
     #[test] fn t_add_int()   { ok("let x = 1 + 2"); }
     #[test] fn t_add_float() { ok("let x = 1.0 + 2.0"); }
     #[test] fn t_add_str()   { ok("let s = \"a\" + \"b\""); }
@@ -154,4 +212,122 @@ mod tests {
 
     // ── F-string ─────────────────────────────────────────────────────────
     #[test] fn t_fstr() { ok("let name = \"world\"\nlet s = f\"hello {name}\""); }
+
+    #[test] fn t_special_floats() { ok("let a: Float = inf\nlet b: Float = -nan"); }
+
+    // ── pure fn ──────────────────────────────────────────────────────────
+    #[test] fn t_pure_ok()        { ok("pure fn sq(x: Int) -> Int\n    var acc = 0\n    acc += x * x\n    return acc"); }
+    #[test] fn t_pure_print()     { err_contains("pure fn f(x)\n    print(x)\n    return x", "pure function 'f' calls 'print'"); }
+    #[test] fn t_pure_io_module() { err_contains("pure fn f()\n    return io.read_file(\"a\")", "calls 'io.read_file'"); }
+    #[test] fn t_pure_global()    { err_contains("var n = 0\npure fn f()\n    n += 1", "mutates non-local 'n'"); }
+    #[test] fn t_pure_param_mut() { err_contains("pure fn f(xs)\n    xs[0] = 1", "mutates non-local 'xs'"); }
+    #[test] fn t_pure_push_param() { err_contains("pure fn f(xs)\n    push(xs, 1)", "mutates non-local 'xs'"); }
+    #[test] fn t_pure_sort_global() { err_contains("var g = [2, 1]\npure fn f()\n    sort(g)", "mutates non-local 'g'"); }
+    #[test] fn t_pure_push_local()  { ok("pure fn f(n: Int)\n    var xs = []\n    push(xs, n)\n    return xs"); }
+    #[test] fn t_pure_transitive()  { err_contains("fn log(x)\n    println(x)\nfn step(x)\n    log(x)\npure fn f(x)\n    step(x)", "calls impure function 'step'"); }
+    #[test] fn t_pure_calls_pure()  { ok("fn sq(x: Int) -> Int\n    return x * x\npure fn f(x: Int) -> Int\n    return sq(x)"); }
+    #[test] fn t_pure_fs_builtins() {
+        for f in ["sleep(1)", "exists(\"a\")", "list_dir(\".\")", "glob(\"*\")"] {
+            err_contains(&format!("pure fn f()\n    {}", f), "calls '");
+        }
+    }
+    #[test] fn t_pure_match_binding() { ok("pure fn f(o)\n    match o\n        Some(v) =>\n            var w = [v]\n            v = 1\n            return w\n        _ => return []"); }
+    #[test] fn t_infer_return_drives_error() { err_contains("fn f()\n    return 1\nlet s: Str = f()", "expected 'Str', got 'Int'"); }
+    #[test] fn t_infer_return_ok()           { ok("fn f(x: Int)\n    return x * 2\nlet n: Int = f(3)"); }
+    #[test] fn t_infer_return_none()         { err_contains("fn f()\n    println(1)\nlet n: Int = f()", "expected 'Int', got 'None'"); }
+    #[test] fn t_infer_return_branches()     { err_contains("fn f(b: Bool)\n    if b\n        return 1\n    else\n        return 2.5\nlet s: Str = f(true)", "got 'Float'"); }
+    #[test] fn t_infer_return_conflict()     { err_contains("fn f(b: Bool)\n    if b\n        return 1\n    return \"x\"", "conflicting return types: 'Int' and 'Str'"); }
+    #[test] fn t_infer_return_fallthrough()  { ok("fn f(b: Bool)\n    if b\n        return 1\nlet s: Str = f(false)"); }
+    #[test] fn t_infer_method_return()       { err_contains("class C\n    fn get(self)\n        return 1\nlet c = C()\nlet s: Str = c.get()", "got 'Int'"); }
+    #[test] fn t_mutual_recursion_ab() { ok("fn a(n: Int) -> Int\n    return b(n - 1)\nfn b(n: Int) -> Int\n    return a(n)"); }
+    #[test] fn t_mutual_recursion_ba() { ok("fn b(n: Int) -> Int\n    return a(n)\nfn a(n: Int) -> Int\n    return b(n - 1)"); }
+    #[test] fn t_mutual_recursion_type() { err_contains("fn a() -> Int\n    return b()\nfn b() -> Str\n    return \"x\"", "function declares 'Int', got 'Str'"); }
+    #[test] fn t_forward_ref_arity()   { err_contains("fn main()\n    g(1, 2)\nfn g(x: Int)\n    return x", "'g' expects 1 argument(s), got 2"); }
+    #[test] fn t_builtin_too_few()  { err_contains("len()", "'len' expects 1 argument(s), got 0"); }
+    #[test] fn t_builtin_arg_type() { err_contains("abs(\"x\")", "argument 1 of 'abs' expects 'Float', got 'Str'"); }
+    #[test] fn t_builtin_variadic() { ok("let m = min(1, 2, 3)\nprintln(m, \"a\", 1.5)"); }
+    #[test] fn t_builtin_range_arity() { err_contains("range(1, 2, 3, 4)", "'range' expects 1 to 3 argument(s), got 4"); }
+    #[test] fn t_builtin_shadowed()  { ok("fn len(a, b)\n    return a\nlen(1, 2)"); }
+    #[test] fn t_builtin_stdlib() {
+        ok("var a = [3, 1, 2]\nsort(a)\nlet d = {\"k\": 1}\nlet ks = keys(d)\nlet r = math.sqrt(2.0)\n\
+            let s = sum(a) + len(join(map(a, str), \",\"))\nprintln(hash(\"x\"), sha256_hex(\"x\"), upper(\"a\"))");
+    }
+    #[test] fn t_builtin_stdlib_arity() { err_contains("keys()", "'keys' expects 1 argument(s), got 0"); }
+    #[test] fn t_deprecation() {
+        let diags = analyse("use math::sin\nmod util");
+        assert_eq!(diags.len(), 2, "{:?}", diags);
+        assert!(diags.iter().all(|d| d.severity == Severity::Deprecation));
+        assert!(diags[0].message.contains("built-in modules"), "{}", diags[0]);
+        assert!(warnings("use math::sin").is_empty());
+        ok("use math::sin");
+    }
+    #[test] fn t_codes() {
+        let code = |src: &str| analyse(src)[0].code;
+        assert_eq!(code("let x: Int = \"a\""), "E0001");
+        assert_eq!(code("1 + \"a\""), "E0002");
+        assert_eq!(code("y"), "E0006");
+        assert_eq!(code("let x = 1\nx = 2"), "E0008");
+        assert_eq!(code("let x = 1\nfn f()\n    let x = 2"), "W0001");
+        assert_eq!(code("mod util"), "D0001");
+        assert_eq!(code("let = 1"), "P0001");
+        assert_eq!(code("let s = \"abc"), "L0002");
+        assert!(analyse("y")[0].to_string().starts_with("[error E0006] "));
+    }
+    #[test] fn t_diagnostics_json() {
+        assert_eq!(diagnostics_json("let x = 1"), "[]");
+        assert_eq!(diagnostics_json("var a = [2, 1]\nsort(a)\nprintln(keys({\"k\": a}), math.floor(1.5))"), "[]");
+        assert_eq!(
+            diagnostics_json("let a = 1\nlet b = 2 + \"s\""),
+            r#"[{"severity":"error","code":"E0002","message":"operator '+' cannot be applied to 'Int' and 'Str'","line":2,"column":9,"end_line":2,"end_column":10}]"#,
+        );
+        assert!(diagnostics_json("let s = \"a\tb\" + 1").contains("'Str'"));
+    }
+    #[test] fn t_analyze_clean() {
+        assert!(analyze_document("let x: Int = 1\nfn f(a: Int) -> Int\n    return a * x\nf(2)").is_empty());
+        assert!(analyze_document("").is_empty());
+    }
+    #[test] fn t_analyze_mixed() {
+        let diags = analyze_document("let a = 1 $ 2\nlet = 3\nlet b: Int = \"s\"");
+        let origins: Vec<Origin> = diags.iter().map(|d| d.origin).collect();
+        assert!(origins.contains(&Origin::Lexer) && origins.contains(&Origin::Parser) && origins.contains(&Origin::TypeChecker), "{:?}", diags);
+        assert!(diags.windows(2).all(|w| (w[0].line, w[0].column) <= (w[1].line, w[1].column)));
+        let ty = diags.iter().find(|d| d.origin == Origin::TypeChecker).unwrap();
+        assert_eq!((ty.severity.clone(), ty.code, ty.line, ty.column, ty.end_line, ty.end_column), (Severity::Error, "E0001", 3, 14, 3, 17));
+    }
+    #[test] fn t_analyze_malformed_no_panic() {
+        for src in ["(((", "fn", "class A\n    fn\n", "match x\n    =>\n", "f\"{x", "a[1:", "\"\"\"", "\t\tx", "x\n  y\n y"] {
+            assert!(!analyze_document(src).is_empty(), "{:?}", src);
+        }
+    }
+    #[test] fn t_type_at() {
+        let src = "let name = \"neba\"\nlet n = 41\nlet m = n + 1.5\nfn f(x: Int) -> Bool\n    return x > 0\nf(n)";
+        assert_eq!(type_at(src, 3, 9), Some(Type::Int));      // `n`
+        assert_eq!(type_at(src, 3, 13), Some(Type::Float));   // `1.5`
+        assert_eq!(type_at(src, 3, 15), Some(Type::Float));   // ultimo carattere di `1.5`
+        assert_eq!(type_at(src, 1, 12), Some(Type::Str));     // `"neba"`
+        assert_eq!(type_at(src, 5, 12), Some(Type::Int));     // parametro `x`
+        assert_eq!(type_at(src, 6, 1), Some(Type::Fn { params: vec![Type::Int], ret: Box::new(Type::Bool) }));
+        assert_eq!(type_at(src, 3, 11), None);                // `+`
+        assert_eq!(type_at(src, 9, 1), None);
+    }
+    #[test] fn t_definition_at() {
+        let src = "let base = 40\nfn add(x, y)\n    return x + y\nclass P\n    v: Int\nadd(base, 2)\nlet p = P()\nprint(p)";
+        let at = |l, c| definition_at(src, l, c).map(|s| (s.line, s.column));
+        assert_eq!(at(6, 5), Some((1, 1)));    // `base` → `let base`
+        assert_eq!(at(6, 1), Some((2, 1)));    // `add` → `fn add`
+        assert_eq!(at(3, 12), Some((2, 8)));   // `x` → parametro
+        assert_eq!(at(7, 9), Some((4, 1)));    // `P` → `class P`
+        assert_eq!(at(8, 1), None);            // built-in
+        assert_eq!(at(6, 4), None);            // `(`
+    }
+    #[test] fn t_parse_error_span() {
+        let errs = check("let x = 1\nlet = 42\n");
+        let e = errs.iter().find(|e| e.message.starts_with("parse error")).unwrap();
+        assert_eq!((e.span.line, e.span.column), (2, 5));
+    }
+    #[test] fn t_lex_error_span() {
+        let errs = check("let a = 1\nlet b = 2 $ 3\n");
+        let e = errs.iter().find(|e| e.message.starts_with("lex error")).unwrap();
+        assert_eq!((e.span.line, e.span.column), (2, 11));
+    }
 }
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use neba_parser::ast::*;
+use crate::error::TypeError;
+
+/// Builtin globali con effetti collaterali (I/O, filesystem, tempo, casualità).
+const IMPURE_BUILTINS: &[&str] = &[
+    "print", "println", "input", "time_ms", "clock", "sleep",
+    "exists", "list_dir", "glob",
+    "choice", "sample",
+];
+
+/// Builtin che modificano in-place il primo argomento: ammessi solo su valori
+/// creati nella funzione, come le assegnazioni per indice.
+const MUTATING_BUILTINS: &[&str] = &[
+    "push", "pop", "clear", "append", "insert", "remove", "sort", "reverse", "freeze", "shuffle",
+];
+
+/// Moduli stdlib interamente impuri: ogni chiamata `modulo.f(...)` è vietata.
+const IMPURE_MODULES: &[&str] = &["io", "random"];
+
+/// Verifica che il corpo di una `pure fn` non abbia effetti collaterali:
+/// niente I/O o casualità, nessuna scrittura su variabili non locali,
+/// nessuna mutazione tramite campi o indici di valori non creati nella funzione,
+/// nessuna chiamata a una funzione di `impure_fns` (vedi `impure_functions`).
+pub fn check_pure_fn(name: &str, params: &[Param], body: &[Stmt], impure_fns: &HashSet<String>, errors: &mut Vec<TypeError>) {
+    let params = params.iter().map(|p| p.name.clone()).collect();
+    let mut checker = PurityChecker { fn_name: name, locals: HashSet::new(), params, impure_fns, errors };
+    checker.block(body);
+}
+
+/// Funzioni top-level (non `pure`) con effetti collaterali, anche indiretti:
+/// quelle il cui corpo non passerebbe `check_pure_fn`, fino al punto fisso.
+pub fn impure_functions(stmts: &[Stmt]) -> HashSet<String> {
+    let fns: Vec<_> = stmts.iter().filter_map(|s| match &s.inner {
+        StmtKind::Fn { name, params, body, is_pure: false, .. } => Some((name, params, body)),
+        _ => None,
+    }).collect();
+    let mut impure = HashSet::new();
+    loop {
+        let mut changed = false;
+        for (name, params, body) in &fns {
+            if impure.contains(*name) { continue; }
+            let mut errors = Vec::new();
+            check_pure_fn(name, params, body, &impure, &mut errors);
+            if !errors.is_empty() {
+                impure.insert(name.to_string());
+                changed = true;
+            }
+        }
+        if !changed { return impure; }
+    }
+}
+
+struct PurityChecker<'a> {
+    fn_name: &'a str,
+    /// Binding creati dentro la funzione (i parametri sono esclusi: possono
+    /// essere riferimenti a dati del chiamante).
+    locals:  HashSet<String>,
+    /// Parametri: non sono locali, ma nascondono builtin e funzioni globali.
+    params:  HashSet<String>,
+    impure_fns: &'a HashSet<String>,
+    errors:  &'a mut Vec<TypeError>,
+}
+
+impl PurityChecker<'_> {
+    fn violation(&mut self, what: String, span: &neba_lexer::Span) {
+        self.errors.push(TypeError::impure(self.fn_name, &what, span.clone()));
+    }
+
+    fn shadowed(&self, name: &str) -> bool {
+        self.locals.contains(name) || self.params.contains(name)
+    }
+
+    /// Scrittura su `target` (assegnazione o builtin mutante).
+    fn mutation(&mut self, target: &Expr) {
+        match root_ident(target) {
+            Some(root) if self.locals.contains(root) => {}
+            Some(root) => {
+                let what = format!("mutates non-local '{}'", root);
+                self.violation(what, &target.span);
+            }
+            None => self.violation("mutates non-local state".to_string(), &target.span),
+        }
+    }
+
+    /// Aggiunge ai locali gli identificatori legati da un pattern di `match`.
+    fn bind_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Ident(name) => { self.locals.insert(name.clone()); }
+            Pattern::Constructor(_, items) | Pattern::Or(items) | Pattern::Tuple(items) =>
+                for p in items { self.bind_pattern(p); },
+            Pattern::StrAffix { rest, .. } => self.bind_pattern(rest),
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        for s in stmts { self.stmt(s); }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.inner {
+            StmtKind::Let { name, value, .. } | StmtKind::Var { name, value, .. } => {
+                self.expr(value);
+                self.locals.insert(name.clone());
+            }
+            StmtKind::Assign { target, value, .. } => {
+                self.expr(value);
+                self.mutation(target);
+                self.expr(target);
+            }
+            StmtKind::Fn { name, body, .. } => {
+                self.locals.insert(name.clone());
+                self.block(body);
+            }
+            StmtKind::While { condition, body } => { self.expr(condition); self.block(body); }
+            StmtKind::For { var, iterable, body } => {
+                self.expr(iterable);
+                self.locals.insert(var.clone());
+                self.block(body);
+            }
+            StmtKind::Return(Some(e)) | StmtKind::Expr(e) => self.expr(e),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.inner {
+            ExprKind::Call { callee, args, kwargs } => {
+                match &callee.inner {
+                    ExprKind::Ident(f) if self.shadowed(f) => {}
+                    ExprKind::Ident(f) if IMPURE_BUILTINS.contains(&f.as_str()) => {
+                        self.violation(format!("calls '{}'", f), &expr.span);
+                    }
+                    ExprKind::Ident(f) if self.impure_fns.contains(f) => {
+                        self.violation(format!("calls impure function '{}'", f), &expr.span);
+                    }
+                    ExprKind::Ident(f) if MUTATING_BUILTINS.contains(&f.as_str()) => {
+                        if let Some(target) = args.first() { self.mutation(target); }
+                    }
+                    ExprKind::Field { object, field } => {
+                        if let ExprKind::Ident(m) = &object.inner {
+                            if IMPURE_MODULES.contains(&m.as_str()) && !self.shadowed(m) {
+                                self.violation(format!("calls '{}.{}'", m, field), &expr.span);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                self.expr(callee);
+                for a in args { self.expr(a); }
+                for (_, v) in kwargs { self.expr(v); }
+            }
+            ExprKind::Binary { left, right, .. } => { self.expr(left); self.expr(right); }
+            ExprKind::Unary { operand, .. } => self.expr(operand),
+            ExprKind::Field { object, .. } => self.expr(object),
+            ExprKind::Index { object, index } => { self.expr(object); self.expr(index); }
+            ExprKind::Slice { object, start, end, step } => {
+                self.expr(object);
+                for e in [start, end, step].into_iter().flatten() { self.expr(e); }
+            }
+            ExprKind::Array(items) | ExprKind::Tuple(items) => for e in items { self.expr(e); },
+            ExprKind::Dict(pairs) => for (k, v) in pairs { self.expr(k); self.expr(v); },
+            ExprKind::Range { start, end, .. } => { self.expr(start); self.expr(end); }
+            ExprKind::If { condition, then_block, elif_branches, else_block } => {
+                self.expr(condition);
+                self.block(then_block);
+                for (c, b) in elif_branches { self.expr(c); self.block(b); }
+                if let Some(b) = else_block { self.block(b); }
+            }
+            ExprKind::Match { subject, arms } => {
+                self.expr(subject);
+                for arm in arms {
+                    self.bind_pattern(&arm.pattern);
+                    self.block(&arm.body);
+                }
+            }
+            ExprKind::Lambda { body, .. } => self.block(body),
+            ExprKind::Pipe { value, func } => self.expr(&Node::new(ExprKind::pipe_call(value, func), expr.span.clone())),
+            ExprKind::Spawn(e) | ExprKind::Await(e) | ExprKind::Some(e)
+            | ExprKind::Ok(e) | ExprKind::Err(e) | ExprKind::Try(e) => self.expr(e),
+            _ => {}
+        }
+    }
+}
+
+/// Risale `a.b[c].d` fino all'identificatore radice `a`.
+fn root_ident(expr: &Expr) -> Option<&str> {
+    match &expr.inner {
+        ExprKind::Ident(name) => Some(name),
+        ExprKind::Field { object, .. } | ExprKind::Index { object, .. } | ExprKind::Slice { object, .. } => root_ident(object),
+        _ => None,
+    }
+}
@@ -0,0 +1,40 @@
+//! Interrogazioni puntuali sul sorgente per gli editor (hover, go-to-definition).
+
+use crate::check::check_program;
+use crate::env::TypeEnv;
+use crate::types::Type;
+use neba_lexer::Span;
+
+/// Analizza `source` e restituisce l'ambiente con le annotazioni per l'editor.
+fn analysed_env(source: &str) -> TypeEnv {
+    let (program, _, _) = neba_parser::parse(source);
+    let mut env = TypeEnv::new();
+    check_program(&program, &mut env, &mut Vec::new());
+    env
+}
+
+/// True se `span` (su una sola riga) copre la posizione `line`/`column`.
+fn covers(span: &Span, line: usize, column: usize) -> bool {
+    let width = span.end.saturating_sub(span.start).max(1);
+    span.line == line && (span.column..span.column + width).contains(&column)
+}
+
+/// Tipo inferito dell'identificatore o letterale alla posizione `line`/`column`
+/// (da 1, colonne in caratteri). `None` se lì non c'è un'espressione annotata.
+pub fn type_at(source: &str, line: usize, column: usize) -> Option<Type> {
+    let env = analysed_env(source);
+    // L'ultima annotazione vince: un'espressione può essere rivisitata dopo che
+    // l'inferenza ha raffinato i tipi (es. ritorni di funzioni non annotate).
+    env.expr_types.iter().rev()
+        .find(|(span, _)| covers(span, line, column))
+        .map(|(_, ty)| ty.clone())
+}
+
+/// Span della definizione (`let`/`var`/`fn`/`class`/`for`, o del parametro) a cui
+/// si riferisce l'identificatore alla posizione `line`/`column`. `None` se lì non
+/// c'è un identificatore o se è un built-in.
+pub fn definition_at(source: &str, line: usize, column: usize) -> Option<Span> {
+    analysed_env(source).references.into_iter()
+        .find(|(used, _)| covers(used, line, column))
+        .map(|(_, def)| def)
+}
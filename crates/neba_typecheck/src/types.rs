@@ -9,12 +9,16 @@ pub enum Type {
     Float,
     Bool,
     Str,
+    // Un carattere: vale come Str fuori da `is`
+    Char,
     None,
 
     // Contenitori
     Array(Box<Type>),
     Option(Box<Type>),
     Result(Box<Type>, Box<Type>),
+    // Tupla: un tipo per posizione
+    Tuple(Vec<Type>),
 
     // Funzione: parametri + tipo di ritorno
     Fn { params: Vec<Type>, ret: Box<Type> },
@@ -38,6 +42,7 @@ impl Type {
                 "Float" => Type::Float,
                 "Bool"  => Type::Bool,
                 "Str"   => Type::Str,
+                "Char"  => Type::Char,
                 "None"  => Type::None,
                 "Any"   => Type::Any,
                 other   => Type::Class(other.to_string()),
@@ -85,12 +90,17 @@ impl Type {
         if matches!((self, other), (Type::Int, Type::Float) | (Type::Float, Type::Int)) {
             return true;
         }
+        if matches!((self, other), (Type::Str, Type::Char) | (Type::Char, Type::Str)) {
+            return true;
+        }
         // Compatibilità ricorsiva per i contenitori
         match (self, other) {
             (Type::Array(a),       Type::Array(b))       => a.is_compatible(b),
             (Type::Option(a),      Type::Option(b))      => a.is_compatible(b),
             (Type::Result(a1, a2), Type::Result(b1, b2)) =>
                 a1.is_compatible(b1) && a2.is_compatible(b2),
+            (Type::Tuple(a),       Type::Tuple(b))       =>
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.is_compatible(y)),
             _ => self == other,
         }
     }
@@ -106,6 +116,13 @@ impl Type {
         if matches!((a, b), (Type::Int, Type::Float) | (Type::Float, Type::Int)) {
             return Some(Type::Float);
         }
+        if matches!((a, b), (Type::Str, Type::Char) | (Type::Char, Type::Str)) {
+            return Some(Type::Str);
+        }
+        if let (Type::Tuple(xs), Type::Tuple(ys)) = (a, b) {
+            if xs.len() != ys.len() { return None; }
+            return xs.iter().zip(ys).map(|(x, y)| Type::unify(x, y)).collect::<Option<Vec<_>>>().map(Type::Tuple);
+        }
         if a == b { Some(a.clone()) } else { None }
     }
 
@@ -116,7 +133,7 @@ impl Type {
 
     /// True se il tipo supporta confronto d'ordine (<, <=, >, >=).
     pub fn is_ordered(&self) -> bool {
-        matches!(self, Type::Int | Type::Float | Type::Str | Type::Unknown | Type::Any)
+        matches!(self, Type::Int | Type::Float | Type::Str | Type::Char | Type::Unknown | Type::Any)
     }
 
     /// Tipo di un elemento di un iterabile.
@@ -124,11 +141,17 @@ impl Type {
         match self {
             Type::Array(inner) => Some(*inner.clone()),
             Type::Str          => Some(Type::Str),
+            Type::Char         => Some(Type::Char),
             Type::Unknown | Type::Any => Some(Type::Unknown),
             _ => None,
         }
     }
 
+    /// Char come Str (operatori, indici, campi); gli altri tipi invariati.
+    pub fn char_as_str(&self) -> Type {
+        if matches!(self, Type::Char) { Type::Str } else { self.clone() }
+    }
+
     /// Nome human-readable del tipo.
     pub fn name(&self) -> String {
         self.to_string()
@@ -142,10 +165,16 @@ impl fmt::Display for Type {
             Type::Float        => write!(f, "Float"),
             Type::Bool         => write!(f, "Bool"),
             Type::Str          => write!(f, "Str"),
+            Type::Char         => write!(f, "Char"),
             Type::None         => write!(f, "None"),
             Type::Array(t)     => write!(f, "Array[{}]", t),
             Type::Option(t)    => write!(f, "Option[{}]", t),
             Type::Result(t, e) => write!(f, "Result[{}, {}]", t, e),
+            Type::Tuple(ts) if ts.len() == 1 => write!(f, "({},)", ts[0]),
+            Type::Tuple(ts) => {
+                let ts: Vec<String> = ts.iter().map(|t| t.to_string()).collect();
+                write!(f, "({})", ts.join(", "))
+            }
             Type::Fn { params, ret } => {
                 let ps: Vec<String> = params.iter().map(|p| p.to_string()).collect();
                 write!(f, "Fn[{}] -> {}", ps.join(", "), ret)
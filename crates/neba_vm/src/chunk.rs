@@ -42,10 +42,14 @@ pub struct Chunk {
     pub names: Vec<String>,
     /// Prototipi di funzione definiti in questo chunk
     pub fn_protos: Vec<FnProto>,
-    /// Mappa offset→riga sorgente per debug/error reporting
-    lines: Vec<(usize, u32)>, // (offset_start, line)
+    /// Mappa offset→posizione sorgente per debug/error reporting
+    lines: Vec<(usize, u32, u32)>, // (offset_start, line, column)
+    /// Colonna attribuita alle prossime istruzioni emesse (impostata dal compilatore)
+    column: u32,
     /// Indice per deduplicazione O(1) in add_const (solo scalari: Int/Float/Bool/Str/None)
     const_index: std::collections::HashMap<ConstKey, u16>,
+    /// Indice per deduplicazione O(1) in add_name
+    name_index: std::collections::HashMap<String, u16>,
 }
 
 /// Chiave hashable per valori scalari nel pool costanti.
@@ -55,7 +59,8 @@ enum ConstKey {
     // Float serializzato come bits per Eq/Hash
     Float(u64),
     Bool(bool),
-    Str(String),
+    Str(Rc<String>),
+    Char(char),
     None,
 }
 
@@ -70,12 +75,17 @@ impl Chunk {
         let offset = self.code.len();
         self.code.push(op as u8);
         match self.lines.last() {
-            Some(&(_, l)) if l == line => {}
-            _ => self.lines.push((offset, line)),
+            Some(&(_, l, c)) if l == line && c == self.column => {}
+            _ => self.lines.push((offset, line, self.column)),
         }
         offset
     }
 
+    /// Imposta la colonna sorgente per le istruzioni successive; restituisce la precedente.
+    pub fn set_column(&mut self, column: u32) -> u32 {
+        std::mem::replace(&mut self.column, column)
+    }
+
     pub fn emit_u8(&mut self, b: u8) {
         self.code.push(b);
     }
@@ -175,14 +185,16 @@ impl Chunk {
         }
     }
     /// Aggiunge una costante al pool, restituisce l'indice.
-    /// Deduplicazione semplice per Int/Bool/None/Str.
+    /// Deduplicazione per Int/Float/Bool/None/Str. I Float sono confrontati bit a bit:
+    /// `0.0` e `-0.0` restano distinti, due `NaN` identici condividono lo slot.
     pub fn add_const(&mut self, v: Value) -> u16 {
         // Deduplicazione O(1) per scalari via HashMap
         let key = match &v {
             Value::Int(n)   => Some(ConstKey::Int(*n)),
             Value::Float(f) => Some(ConstKey::Float(f.to_bits())),
             Value::Bool(b)  => Some(ConstKey::Bool(*b)),
-            Value::Str(s)   => Some(ConstKey::Str(s.as_ref().clone())),
+            Value::Str(s)   => Some(ConstKey::Str(Rc::clone(s))),
+            Value::Char(c)  => Some(ConstKey::Char(*c)),
             Value::None     => Some(ConstKey::None),
             _               => None,
         };
@@ -201,10 +213,9 @@ impl Chunk {
 
     /// Aggiunge un nome al pool, restituisce l'indice.
     pub fn add_name(&mut self, name: &str) -> u16 {
-        if let Some(i) = self.names.iter().position(|n| n == name) {
-            return i as u16;
-        }
+        if let Some(&idx) = self.name_index.get(name) { return idx; }
         let idx = self.names.len() as u16;
+        self.name_index.insert(name.to_string(), idx);
         self.names.push(name.to_string());
         idx
     }
@@ -219,10 +230,15 @@ impl Chunk {
     // ── Debug ─────────────────────────────────────────────────────────────
 
     pub fn line_at(&self, offset: usize) -> u32 {
-        let mut current = 0u32;
-        for &(start, line) in &self.lines {
+        self.pos_at(offset).0
+    }
+
+    /// Posizione sorgente `(line, column)` dell'istruzione all'offset dato.
+    pub fn pos_at(&self, offset: usize) -> (u32, u32) {
+        let mut current = (0u32, 0u32);
+        for &(start, line, column) in &self.lines {
             if start > offset { break; }
-            current = line;
+            current = (line, column);
         }
         current
     }
@@ -242,13 +258,13 @@ impl Chunk {
                     out.push_str(&format!("  #{} {:?}", idx,
                         self.constants.get(idx as usize).map(|v| format!("{}", v)).unwrap_or("?".into())));
                 }
-                Op::LoadLocal | Op::StoreLocal | Op::LoadUpval | Op::StoreUpval | Op::Call | Op::PopN => {
+                Op::LoadLocal | Op::StoreLocal | Op::AddStoreLocal | Op::LoadUpval | Op::StoreUpval | Op::Call | Op::PopN | Op::Rot => {
                     out.push_str(&format!("  {}", self.code[i + 1]));
                 }
-                Op::LoadGlobal | Op::StoreGlobal | Op::GetField | Op::SetField
+                Op::LoadGlobal | Op::StoreGlobal | Op::AddStoreGlobal | Op::TagArray | Op::GetField | Op::SetField
                 | Op::MakeInstance | Op::MakeClosure | Op::MakeArray | Op::BuildStr => {
                     let idx = read_u16(&self.code, i + 1);
-                    let name = if matches!(op, Op::GetField | Op::SetField | Op::MakeInstance | Op::LoadGlobal | Op::StoreGlobal) {
+                    let name = if matches!(op, Op::GetField | Op::SetField | Op::MakeInstance | Op::LoadGlobal | Op::StoreGlobal | Op::AddStoreGlobal | Op::TagArray) {
                         self.names.get(idx as usize).cloned().unwrap_or_default()
                     } else if matches!(op, Op::MakeClosure) {
                         self.fn_protos.get(idx as usize).map(|p| p.name.clone()).unwrap_or_default()
@@ -261,7 +277,7 @@ impl Chunk {
                     let name = self.names.get(idx as usize).cloned().unwrap_or_default();
                     out.push_str(&format!("  #{} {} mut={}", idx, name, mut_));
                 }
-                Op::Jump | Op::JumpFalse | Op::JumpTrue | Op::JumpFalsePeek | Op::JumpTruePeek
+                Op::Jump | Op::JumpFalse | Op::JumpTrue | Op::JumpFalseOrPop | Op::JumpTrueOrPop
                 | Op::IsSome | Op::IsNone | Op::IsOk | Op::IsErr => {
                     let offset = read_i16(&self.code, i + 1);
                     let target = (i as isize + 3 + offset as isize) as usize;
@@ -281,6 +297,14 @@ impl Chunk {
                     let jmp  = read_i16(&self.code, i + 3);
                     out.push_str(&format!("  #{} {:+}", cidx, jmp));
                 }
+                Op::MatchAffix | Op::StripAffix => {
+                    let pre = read_u16(&self.code, i + 1);
+                    let suf = read_u16(&self.code, i + 3);
+                    out.push_str(&format!("  pre=#{} suf=#{}", pre, suf));
+                    if op == Op::MatchAffix {
+                        out.push_str(&format!(" {:+}", read_i16(&self.code, i + 5)));
+                    }
+                }
                 Op::MatchRange => {
                     let lo   = read_u16(&self.code, i + 1);
                     let hi   = read_u16(&self.code, i + 3);
@@ -312,15 +336,3 @@ pub fn read_u16(code: &[u8], offset: usize) -> u16 {
 pub fn read_i16(code: &[u8], offset: usize) -> i16 {
     read_u16(code, offset) as i16
 }
-
-fn values_equal(a: &Value, b: &Value) -> bool {
-    use Value::*;
-    match (a, b) {
-        (Int(x),  Int(y))  => x == y,
-        (Float(x),Float(y))=> x == y,
-        (Bool(x), Bool(y)) => x == y,
-        (Str(x),  Str(y))  => x == y,
-        (None,    None)    => true,
-        _ => false,
-    }
-}
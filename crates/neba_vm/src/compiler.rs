@@ -1,11 +1,13 @@
-use std::rc::Rc;
 
 use neba_parser::ast::*;
 
-use crate::chunk::{Chunk, FnProto, UpvalueDesc};
+use crate::chunk::{Chunk, FnProto};
 use crate::error::{VmError, VmResult};
 use crate::opcode::Op;
 use crate::value::Value;
+use rustc_hash::FxHashSet;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 // ── Scope tracking ────────────────────────────────────────────────────────
 
@@ -20,15 +22,11 @@ struct Local {
 /// Un upvalue visto dal compilatore.
 #[derive(Debug, Clone)]
 struct UpvalueDef {
+    #[allow(dead_code)]
     is_local: bool,
     index: u8,
 }
 
-/// Contesto di un singolo scope (blocco annidato).
-struct Scope {
-    depth: usize,
-}
-
 // ── ClassInfo (metadati di classe registrati durante la compilazione) ─────
 
 #[derive(Debug, Clone)]
@@ -84,7 +82,8 @@ fn try_fold_binary(op: &BinOp, lk: &ExprKind, rk: &ExprKind) -> Option<Value> {
             BinOp::Add => Some(Value::Float(a + b)),
             BinOp::Sub => Some(Value::Float(a - b)),
             BinOp::Mul => Some(Value::Float(a * b)),
-            BinOp::Div => Some(Value::Float(a / b)),
+            // x / 0.0 dipende dalla politica di divisione della VM: si valuta a runtime
+            BinOp::Div => if *b != 0.0 { Some(Value::Float(a / b)) } else { None },
             BinOp::Pow => Some(Value::Float(a.powf(*b))),
             BinOp::Eq  => Some(Value::Bool(a == b)),
             BinOp::Ne  => Some(Value::Bool(a != b)),
@@ -135,6 +134,17 @@ fn try_fold_unary(op: &UnaryOp, operand: &ExprKind) -> Option<Value> {
 }
 
 
+/// Stato del compilatore che sopravvive tra compilazioni successive (REPL):
+/// classi, trait e impl dichiarati negli snippet precedenti. Le globali vivono
+/// invece nella `Vm`, che le risolve per nome a runtime.
+#[derive(Debug, Clone, Default)]
+pub struct CompileState {
+    pub class_registry: std::collections::HashMap<String, ClassInfo>,
+    pub trait_registry: std::collections::HashMap<String, TraitInfo>,
+    pub impl_registry: std::collections::HashMap<String, Vec<Stmt>>,
+    pub impl_traits_registry: std::collections::HashMap<String, Vec<String>>,
+}
+
 pub struct Compiler {
     chunk: Chunk,
     locals: Vec<Local>,
@@ -153,7 +163,9 @@ pub struct Compiler {
     /// Mappa class_name → lista di trait implementati (per `is` a runtime)
     pub impl_traits_registry: std::collections::HashMap<String, Vec<String>>,
     /// Nome della funzione (per messaggi di errore)
+    #[allow(dead_code)]
     fn_name: String,
+    #[allow(dead_code)]
     is_function: bool,
     /// Profondità corrente dello stack (per cleanup di break/continue)
     stack_depth: usize,
@@ -162,8 +174,14 @@ pub struct Compiler {
     loop_local_counts: Vec<usize>,
     /// Locali del frame padre (per cattura upvalue)
     parent_locals: Vec<(String, u8)>,
+    /// Stringhe costanti già emesse, condivise con i sotto-compilatori delle funzioni
+    strings: StrPool,
 }
 
+/// Pool di interning delle stringhe costanti: letterali identici in chunk diversi
+/// (script, funzioni, metodi) puntano alla stessa allocazione.
+type StrPool = Rc<RefCell<FxHashSet<Rc<String>>>>;
+
 impl Compiler {
     // ── Costruttori ───────────────────────────────────────────────────────
 
@@ -185,10 +203,11 @@ impl Compiler {
             loop_local_counts: Vec::new(),
             parent_locals: Vec::new(),
             is_function: false,
+            strings: StrPool::default(),
         }
     }
 
-    fn new_function(name: &str) -> Self {
+    fn new_function(name: &str, strings: &StrPool) -> Self {
         Compiler {
             chunk: Chunk::new(),
             locals: Vec::new(),
@@ -206,18 +225,39 @@ impl Compiler {
             is_function: true,
             loop_local_counts: Vec::new(),
             parent_locals: Vec::new(),
+            strings: Rc::clone(strings),
         }
     }
 
     // ── Entry point ───────────────────────────────────────────────────────
 
     pub fn compile(program: &Program) -> VmResult<Chunk> {
+        Self::compile_incremental(program, &mut CompileState::default())
+    }
+
+    /// Come `compile`, ma parte dalle dichiarazioni in `state` e, se la compilazione
+    /// riesce, vi aggiunge quelle del programma (in caso di errore `state` non cambia).
+    pub fn compile_incremental(program: &Program, state: &mut CompileState) -> VmResult<Chunk> {
         let mut c = Compiler::new_script();
+        c.class_registry       = state.class_registry.clone();
+        c.trait_registry       = state.trait_registry.clone();
+        c.impl_registry        = state.impl_registry.clone();
+        c.impl_traits_registry = state.impl_traits_registry.clone();
+        let chunk = c.compile_program(program)?;
+        state.class_registry       = c.class_registry;
+        state.trait_registry       = c.trait_registry;
+        state.impl_registry        = c.impl_registry;
+        state.impl_traits_registry = c.impl_traits_registry;
+        Ok(chunk)
+    }
+
+    fn compile_program(&mut self, program: &Program) -> VmResult<Chunk> {
+        let c = self;
         let stmts = &program.stmts;
         if stmts.is_empty() {
             c.chunk.emit(Op::Nil, 0);
             c.chunk.emit(Op::Halt, 0);
-            return Ok(c.chunk);
+            return Ok(std::mem::take(&mut c.chunk));
         }
 
         // ── Pre-pass: raccoglie trait e impl prima di compilare ───────────
@@ -231,38 +271,36 @@ impl Compiler {
                         methods: methods.clone(),
                     });
                 }
-                StmtKind::Impl { trait_name, for_type, methods } => {
-                    if let Some(class_name) = for_type {
-                        // Registra il trait come implementato da questa classe
-                        c.impl_traits_registry
-                            .entry(class_name.clone())
-                            .or_insert_with(Vec::new)
-                            .push(trait_name.clone());
-                        // Aggiunge i metodi dell'impl alla mappa per quella classe
-                        let entry = c.impl_registry
-                            .entry(class_name.clone())
-                            .or_insert_with(Vec::new);
-
-                        // Aggiunge i metodi implementati dall'utente
-                        for m in methods {
-                            entry.push(m.clone());
-                        }
+                StmtKind::Impl { trait_name, for_type: Some(class_name), methods } => {
+                    // Registra il trait come implementato da questa classe
+                    c.impl_traits_registry
+                        .entry(class_name.clone())
+                        .or_default()
+                        .push(trait_name.clone());
+                    // Aggiunge i metodi dell'impl alla mappa per quella classe
+                    let entry = c.impl_registry
+                        .entry(class_name.clone())
+                        .or_default();
+
+                    // Aggiunge i metodi implementati dall'utente
+                    for m in methods {
+                        entry.push(m.clone());
+                    }
 
-                        // Aggiunge i metodi default del trait NON overridati dall'impl
-                        if let Some(trait_info) = c.trait_registry.get(trait_name).cloned() {
-                            let impl_names: std::collections::HashSet<String> = methods.iter()
-                                .filter_map(|s| if let StmtKind::Fn { name, .. } = &s.inner { Some(name.clone()) } else { None })
-                                .collect();
-                            for default_method in &trait_info.methods {
-                                if let StmtKind::Fn { name: mname, body, .. } = &default_method.inner {
-                                    // Metodo con body vuoto = firma astratta (non ha implementazione default)
-                                    // Metodo con solo `pass` = abstract marker
-                                    // Solo metodi con body reale (len > 0, non solo pass) sono default
-                                    let is_abstract = body.is_empty()
-                                        || (body.len() == 1 && matches!(body[0].inner, StmtKind::Pass));
-                                    if !is_abstract && !impl_names.contains(mname) {
-                                        entry.push(default_method.clone());
-                                    }
+                    // Aggiunge i metodi default del trait NON overridati dall'impl
+                    if let Some(trait_info) = c.trait_registry.get(trait_name).cloned() {
+                        let impl_names: std::collections::HashSet<String> = methods.iter()
+                            .filter_map(|s| if let StmtKind::Fn { name, .. } = &s.inner { Some(name.clone()) } else { None })
+                            .collect();
+                        for default_method in &trait_info.methods {
+                            if let StmtKind::Fn { name: mname, body, .. } = &default_method.inner {
+                                // Metodo con body vuoto = firma astratta (non ha implementazione default)
+                                // Metodo con solo `pass` = abstract marker
+                                // Solo metodi con body reale (len > 0, non solo pass) sono default
+                                let is_abstract = body.is_empty()
+                                    || (body.len() == 1 && matches!(body[0].inner, StmtKind::Pass));
+                                if !is_abstract && !impl_names.contains(mname) {
+                                    entry.push(default_method.clone());
                                 }
                             }
                         }
@@ -286,12 +324,34 @@ impl Compiler {
         }
         c.chunk.emit(Op::Halt, last_line);
         c.chunk.peephole_optimize();
-        Ok(c.chunk)
+        Ok(std::mem::take(&mut c.chunk))
+    }
+
+    /// Aggiunge una costante al chunk; le Str passano dal pool condiviso.
+    fn add_const(&mut self, v: Value) -> u16 {
+        let v = match v {
+            Value::Str(s) => {
+                let mut pool = self.strings.borrow_mut();
+                match pool.get(&s) {
+                    Some(shared) => Value::Str(Rc::clone(shared)),
+                    None => { pool.insert(Rc::clone(&s)); Value::Str(s) }
+                }
+            }
+            v => v,
+        };
+        self.chunk.add_const(v)
     }
 
     // ── Statement ─────────────────────────────────────────────────────────
 
     fn compile_stmt(&mut self, stmt: &Stmt) -> VmResult<()> {
+        let prev_col = self.chunk.set_column(stmt.span.column as u32);
+        let result = self.compile_stmt_inner(stmt);
+        self.chunk.set_column(prev_col);
+        result
+    }
+
+    fn compile_stmt_inner(&mut self, stmt: &Stmt) -> VmResult<()> {
         let line = stmt.span.line as u32;
         match &stmt.inner {
             StmtKind::Expr(e) => {
@@ -302,12 +362,14 @@ impl Compiler {
                     self.chunk.emit(Op::Pop, line);
                 }
             }
-            StmtKind::Let { name, value, .. } => {
+            StmtKind::Let { name, ty, value } => {
                 self.compile_expr(value)?;
+                self.emit_array_tag(ty.as_ref(), line);
                 self.define_var(name, false, line)?;
             }
-            StmtKind::Var { name, value, .. } => {
+            StmtKind::Var { name, ty, value } => {
                 self.compile_expr(value)?;
+                self.emit_array_tag(ty.as_ref(), line);
                 self.define_var(name, true, line)?;
             }
             StmtKind::Assign { target, op, value } => {
@@ -456,6 +518,42 @@ impl Compiler {
         Ok(())
     }
 
+    /// `let a: Array[T] = ...` → TagArray col nome di T; la VM lo ignora se i
+    /// checked arrays non sono attivi. Per `Array[Array[Int]]` si controlla solo `Array`.
+    fn emit_array_tag(&mut self, ty: Option<&TypeExpr>, line: u32) {
+        let Some(TypeKind::Generic(outer, args)) = ty.map(|t| &t.inner) else { return };
+        if outer != "Array" || args.len() != 1 { return; }
+        let elem = match &args[0].inner {
+            TypeKind::Named(n) | TypeKind::Generic(n, _) => n,
+            TypeKind::Error => return,
+        };
+        let idx = self.chunk.add_name(elem);
+        self.chunk.emit(Op::TagArray, line);
+        self.chunk.emit_u16(idx);
+    }
+
+    /// Somma i due valori in cima allo stack e memorizza in `name`.
+    /// Gli upvalue non hanno un AddStore dedicato: Add + StoreUpval.
+    fn emit_add_store(&mut self, name: &str, line: u32) -> VmResult<()> {
+        if let Some((idx, mutable)) = self.resolve_local(name) {
+            if !mutable {
+                return Err(VmError::CompileError(
+                    format!("cannot assign to immutable variable '{}'", name)
+                ));
+            }
+            self.chunk.emit(Op::AddStoreLocal, line);
+            self.chunk.emit_u8(idx);
+        } else if self.resolve_upvalue(name).is_some() {
+            self.chunk.emit(Op::Add, line);
+            self.emit_store(name, line)?;
+        } else {
+            let idx = self.chunk.add_name(name);
+            self.chunk.emit(Op::AddStoreGlobal, line);
+            self.chunk.emit_u16(idx);
+        }
+        Ok(())
+    }
+
     // ── Scope ─────────────────────────────────────────────────────────────
 
     fn push_scope(&mut self) { self.scope_depth += 1; }
@@ -476,11 +574,47 @@ impl Compiler {
         self.scope_depth -= 1;
     }
 
+    /// Come `pop_scope`, ma per scope che lasciano un valore in cima (block-expr,
+    /// arm di match): i locali stanno sotto il risultato → `Rot` porta il
+    /// risultato sotto di loro, poi `PopN` li scarta.
+    fn pop_scope_keep_top(&mut self, line: u32) {
+        let mut count = self.locals.iter().rev()
+            .take_while(|l| l.depth == self.scope_depth)
+            .count();
+        for _ in 0..count { self.locals.pop(); }
+        while count > 0 {
+            let n = count.min(u8::MAX as usize - 1);
+            if n == 1 {
+                self.chunk.emit(Op::Swap, line);
+                self.chunk.emit(Op::Pop,  line);
+            } else {
+                self.chunk.emit(Op::Rot, line);
+                self.chunk.emit_u8(n as u8 + 1);
+                self.chunk.emit(Op::PopN, line);
+                self.chunk.emit_u8(n as u8);
+            }
+            count -= n;
+        }
+        self.scope_depth -= 1;
+    }
+
     // ── Assegnazione ──────────────────────────────────────────────────────
 
     fn compile_assign(&mut self, target: &Expr, op: &AssignOp, value: &Expr, line: u32) -> VmResult<()> {
         match &target.inner {
             ExprKind::Ident(name) => {
+                // `x += r` e `x = x + r` → load x, r, AddStore (accodamento in place sulle Str)
+                let add_rhs = match (op, &value.inner) {
+                    (AssignOp::AddAssign, _) => Some(value),
+                    (AssignOp::Assign, ExprKind::Binary { op: BinOp::Add, left, right })
+                        if matches!(&left.inner, ExprKind::Ident(n) if n == name) => Some(&**right),
+                    _ => None,
+                };
+                if let Some(rhs) = add_rhs {
+                    self.emit_load(name, line)?;
+                    self.compile_expr(rhs)?;
+                    return self.emit_add_store(name, line);
+                }
                 if !matches!(op, AssignOp::Assign) {
                     // x += rhs → carica x, carica rhs, op, store
                     self.emit_load(name, line)?;
@@ -492,16 +626,28 @@ impl Compiler {
                 self.emit_store(name, line)?;
                 // Nessun valore residuo: l'assegnazione è uno statement puro
             }
-            ExprKind::Slice { .. } => {
-                return Err(VmError::CompileError("slice assignment not supported".into()));
+            ExprKind::Slice { object, start, end, step } => {
+                if !matches!(op, AssignOp::Assign) {
+                    return Err(VmError::CompileError("compound assignment to a slice is not supported".into()));
+                }
+                if step.is_some() {
+                    return Err(VmError::CompileError("slice assignment does not support a step".into()));
+                }
+                self.compile_expr(object)?;
+                let mut flags: u8 = 0;
+                if let Some(s) = start { self.compile_expr(s)?; flags |= 1; }
+                if let Some(e) = end   { self.compile_expr(e)?; flags |= 2; }
+                self.compile_expr(value)?;
+                self.chunk.emit(Op::SetSlice, line);
+                self.chunk.emit_u8(flags);
             }
             ExprKind::Index { object, index } => {
                 self.compile_expr(object)?;
                 self.compile_expr(index)?;
                 if !matches!(op, AssignOp::Assign) {
-                    self.chunk.emit(Op::Dup, line);
-                    self.compile_expr(object)?;
-                    self.compile_expr(index)?;
+                    // obj idx → obj idx obj idx: oggetto e indice valutati una volta sola
+                    self.chunk.emit(Op::Over, line);
+                    self.chunk.emit(Op::Over, line);
                     self.chunk.emit(Op::GetIndex, line);
                     self.compile_expr(value)?;
                     self.emit_compound_op(op, line);
@@ -548,15 +694,25 @@ impl Compiler {
 
     // ── Espressioni ───────────────────────────────────────────────────────
 
+    /// Compila un'espressione attribuendo le sue istruzioni alla sua colonna;
+    /// al ritorno ripristina quella dell'espressione padre.
     fn compile_expr(&mut self, expr: &Expr) -> VmResult<()> {
+        let prev_col = self.chunk.set_column(expr.span.column as u32);
+        let result = self.compile_expr_inner(expr);
+        self.chunk.set_column(prev_col);
+        result
+    }
+
+    fn compile_expr_inner(&mut self, expr: &Expr) -> VmResult<()> {
         let line = expr.span.line as u32;
         match &expr.inner {
-            ExprKind::Int(n)   => { let i = self.chunk.add_const(Value::Int(*n));   self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
-            ExprKind::Float(f) => { let i = self.chunk.add_const(Value::Float(*f)); self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
+            ExprKind::Int(n)   => { let i = self.add_const(Value::Int(*n));   self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
+            ExprKind::Float(f) => { let i = self.add_const(Value::Float(*f)); self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
             ExprKind::Bool(b)  => { self.chunk.emit(if *b { Op::True } else { Op::False }, line); }
             ExprKind::None     => { self.chunk.emit(Op::Nil, line); }
-            ExprKind::Str(s)   => { let i = self.chunk.add_const(Value::str(s.as_str())); self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
+            ExprKind::Str(s)   => { let i = self.add_const(Value::str(s.as_str())); self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
             ExprKind::FStr(t)  => { self.compile_fstring(t, line)?; }
+            ExprKind::Char(c)  => { let i = self.add_const(Value::Char(*c)); self.chunk.emit(Op::Const, line); self.chunk.emit_u16(i); }
 
             ExprKind::Ident(name) => { self.emit_load(name, line)?; }
 
@@ -586,11 +742,36 @@ impl Compiler {
                 self.compile_match(subject, arms, line)?;
             }
 
+            ExprKind::Pipe { value, func } => {
+                self.compile_expr(&Node::new(ExprKind::pipe_call(value, func), expr.span.clone()))?;
+            }
+
             ExprKind::Call { callee, args, kwargs } => {
+                // `s.format(..., nome=v)`: i kwargs diventano un Dict in coda agli
+                // argomenti e il metodo `format**` (nome non scrivibile da sorgente)
+                if let ExprKind::Field { object, field } = &callee.inner {
+                    if field == "format" && !kwargs.is_empty() {
+                        self.compile_expr(object)?;
+                        for a in args { self.compile_expr(a)?; }
+                        for (name, v) in kwargs {
+                            let idx = self.add_const(Value::str(name.clone()));
+                            self.chunk.emit(Op::Const, line);
+                            self.chunk.emit_u16(idx);
+                            self.compile_expr(v)?;
+                        }
+                        self.chunk.emit(Op::MakeDict, line);
+                        self.chunk.emit_u16(kwargs.len() as u16);
+                        let idx = self.chunk.add_name("format**");
+                        self.chunk.emit(Op::CallMethod, line);
+                        self.chunk.emit_u16(idx);
+                        self.chunk.emit_u8(args.len() as u8 + 1);
+                        return Ok(());
+                    }
+                }
                 // Named kwargs non ancora supportati — errore esplicito a compile-time
                 if !kwargs.is_empty() {
                     return Err(VmError::CompileError(
-                        format!("named arguments (kwargs) not yet supported — use positional arguments")
+                        "named arguments (kwargs) not yet supported — use positional arguments".to_string()
                     ));
                 }
                 // str(x) → compila x + Op::ToStr (chiama __str__ se Instance)
@@ -649,7 +830,7 @@ impl Compiler {
                 for (key, val) in pairs {
                     // Se la chiave è un Ident bareword (es. {x: 1}), la trattiamo come Str "x"
                     if let ExprKind::Ident(name) = &key.inner {
-                        let idx = self.chunk.add_const(Value::str(name.clone()));
+                        let idx = self.add_const(Value::str(name.clone()));
                         self.chunk.emit(Op::Const, key.span.line as u32);
                         self.chunk.emit_u16(idx);
                     } else {
@@ -698,6 +879,10 @@ impl Compiler {
                 self.compile_fn_def("<lambda>", params, body, false, line)?;
             }
 
+            ExprKind::Tuple(_) => {
+                return Err(VmError::CompileError("tuples not yet supported — use the interpreter".into()));
+            }
+
             ExprKind::Error => {
                 return Err(VmError::CompileError("AST error node".into()));
             }
@@ -714,7 +899,7 @@ impl Compiler {
             Value::Bool(false) => { self.chunk.emit(Op::False, line); }
             Value::None        => { self.chunk.emit(Op::Nil, line); }
             _ => {
-                let ci = self.chunk.add_const(val);
+                let ci = self.add_const(val);
                 self.chunk.emit(Op::Const, line);
                 self.chunk.emit_u16(ci);
             }
@@ -725,28 +910,31 @@ impl Compiler {
         match op {
             BinOp::And => {
                 self.compile_expr(left)?;
-                let patch = self.chunk.emit_jump(Op::JumpFalsePeek, line);
-                self.chunk.emit(Op::Pop, line);
+                let patch = self.chunk.emit_jump(Op::JumpFalseOrPop, line);
                 self.compile_expr(right)?;
                 self.chunk.patch_jump(patch);
             }
             BinOp::Or => {
                 self.compile_expr(left)?;
-                let patch = self.chunk.emit_jump(Op::JumpTruePeek, line);
-                self.chunk.emit(Op::Pop, line);
+                let patch = self.chunk.emit_jump(Op::JumpTrueOrPop, line);
                 self.compile_expr(right)?;
                 self.chunk.patch_jump(patch);
             }
-            BinOp::Is => {
-                // Emette il nome classe/trait come stringa invece di caricare la variabile
+            BinOp::Is | BinOp::IsNot => {
+                // Emette il nome classe/trait/tipo come stringa invece di caricare la variabile
                 self.compile_expr(left)?;
                 if let ExprKind::Ident(name) = &right.inner {
-                    if self.class_registry.contains_key(name.as_str()) {
-                        let ci = self.chunk.add_const(Value::str(name.clone()));
+                    if BUILTIN_TYPE_NAMES.contains(&name.as_str()) && !self.class_registry.contains_key(name.as_str())
+                        && self.resolve_local(name).is_none() && self.resolve_upvalue(name).is_none() {
+                        let ci = self.add_const(Value::str(format!("type:{}", name)));
+                        self.chunk.emit(Op::Const, line);
+                        self.chunk.emit_u16(ci);
+                    } else if self.class_registry.contains_key(name.as_str()) {
+                        let ci = self.add_const(Value::str(name.clone()));
                         self.chunk.emit(Op::Const, line);
                         self.chunk.emit_u16(ci);
                     } else if self.trait_registry.contains_key(name.as_str()) {
-                        let ci = self.chunk.add_const(Value::str(format!("trait:{}", name)));
+                        let ci = self.add_const(Value::str(format!("trait:{}", name)));
                         self.chunk.emit(Op::Const, line);
                         self.chunk.emit_u16(ci);
                     } else {
@@ -756,6 +944,7 @@ impl Compiler {
                     self.compile_expr(right)?;
                 }
                 self.chunk.emit(Op::Is, line);
+                if matches!(op, BinOp::IsNot) { self.chunk.emit(Op::Not, line); }
             }
             _ => {
                 // Constant folding: se entrambi i lati sono literali, calcola a compile-time
@@ -786,7 +975,7 @@ impl Compiler {
                     BinOp::Shr    => Op::Shr,
                     BinOp::In     => Op::In,
                     BinOp::NotIn  => Op::NotIn,
-                    BinOp::And | BinOp::Or | BinOp::Is => unreachable!(),
+                    BinOp::And | BinOp::Or | BinOp::Is | BinOp::IsNot => unreachable!(),
                 };
                 self.chunk.emit(instr, line);
             }
@@ -850,39 +1039,8 @@ impl Compiler {
             self.compile_stmt(last)?;
             self.chunk.emit(Op::Nil, line);
         }
-        // Pop scope ma senza poppare il valore risultato
-        // → pop i locali manualmente
-        let count = self.locals.iter().rev()
-            .take_while(|l| l.depth == self.scope_depth)
-            .count();
-        // Sposta il valore risultato sopra i locali da poppare
-        // usando una tecnica: salviamo in un local temporaneo implicito
-        // ma per semplicità usiamo un approccio diverso:
-        // pop_scope fa il cleanup dei locals PRIMA del valore sul top.
-        // Dobbiamo fare "rot(count+1)" ma non abbiamo quell'istruzione.
-        //
-        // Soluzione pragmatica: i locali vengono poppati dai lato della vm
-        // tramite il meccanismo del call-frame. In realtà in un block-expr
-        // i locali sono su stack sotto il valore → serve riordino.
-        //
-        // Soluzione semplice: riserviamo lo slot 0 del frame per il "block result"
-        // tramite una variabile locale temporanea (non esposta).
-        // Oppure: non usiamo scope-locali per block-expr, usiamo solo globali.
-        //
-        // Per v0.2.0 accettiamo che i block-expr abbiano locali che vengono
-        // gestiti correttamente grazie al fatto che pop_scope emette Pop/PopN
-        // DOPO il valore → la VM deve gestire questo.
-        //
-        // Alternativa più semplice: compila i block come statement normali
-        // e lascia l'ultimo valore sullo stack; i locali del blocco sono
-        // sempre sotto il valore finale → il chiamante sa che deve "rot".
-        //
-        // DECISIONE: in v0.2.0 i block-expr NON creano scope locale.
-        // Le variabili definite nel blocco vanno nello scope esterno.
-        // Questo è il comportamento corretto per if/match/while come expression.
-        for l in self.locals.iter().rev().take_while(|l| l.depth == self.scope_depth) {}
-        for _ in 0..count { self.locals.pop(); }
-        self.scope_depth -= 1;
+        // Chiudi lo scope senza perdere il risultato
+        self.pop_scope_keep_top(line);
         Ok(())
     }
 
@@ -911,19 +1069,8 @@ impl Compiler {
             self.compile_pattern_bind(&arm.pattern, arm_line)?;
             self.compile_block_expr(&arm.body, arm_line)?;
 
-            // Stack: [subject, ...bindings..., result]
-            // Conta i binding locali nello scope dell'arm (esclude __match_subject_)
-            let bind_count = self.locals.iter().rev()
-                .take_while(|l| l.depth == self.scope_depth)
-                .count();
-            // FIX: emetti Pop per ciascun binding, mantenendo il result in cima.
-            // Strategia: Swap result sopra ogni binding, poi Pop il binding.
-            for _ in 0..bind_count {
-                self.chunk.emit(Op::Swap, arm_line);  // [... binding, result] → [... result, binding]
-                self.chunk.emit(Op::Pop,  arm_line);  // pop binding
-            }
-            for _ in 0..bind_count { self.locals.pop(); }
-            self.scope_depth -= 1;
+            // Stack: [subject, ...bindings..., result] → scarta i binding dell'arm
+            self.pop_scope_keep_top(arm_line);
 
             // Ora: [subject, result]
             // Salta alla fine
@@ -940,18 +1087,24 @@ impl Compiler {
         // Stack: [subject, result]
         for p in end_patches { self.chunk.patch_jump(p); }
 
-        // FIX: rimuovi il subject da sotto il result
-        // Swap -> [result, subject],  Pop -> [result]
-        self.chunk.emit(Op::Swap, line);
-        self.chunk.emit(Op::Pop, line);
-
-        // Chiudi lo scope del subject (il local implicito è già rimosso da Swap+Pop)
-        self.locals.pop(); // rimuovi __match_subject dal tracking
-        self.scope_depth -= 1;
+        // Rimuovi il subject da sotto il result chiudendo il suo scope
+        self.pop_scope_keep_top(line);
 
         Ok(())
     }
 
+    /// Costanti Str di prefisso e suffisso di un pattern `StrAffix`.
+    fn affix_consts(&mut self, prefix: &str, suffix: &str) -> (u16, u16) {
+        (self.add_const(Value::str(prefix)), self.add_const(Value::str(suffix)))
+    }
+
+    fn emit_strip_affix(&mut self, prefix: &str, suffix: &str, line: u32) {
+        let (pre, suf) = self.affix_consts(prefix, suffix);
+        self.chunk.emit(Op::StripAffix, line);
+        self.chunk.emit_u16(pre);
+        self.chunk.emit_u16(suf);
+    }
+
     fn compile_pattern_check(&mut self, pat: &Pattern, fail_patches: &mut Vec<usize>, line: u32) -> VmResult<()> {
         match pat {
             Pattern::Wildcard | Pattern::Ident(_) => {}
@@ -961,10 +1114,11 @@ impl Compiler {
                     ExprKind::Float(f) => Value::Float(*f),
                     ExprKind::Bool(b)  => Value::Bool(*b),
                     ExprKind::Str(s)   => Value::str(s.as_str()),
+                    ExprKind::Char(c)  => Value::Char(*c),
                     ExprKind::None     => Value::None,
                     _ => return Err(VmError::CompileError("invalid literal in pattern".into())),
                 };
-                let cidx = self.chunk.add_const(v);
+                let cidx = self.add_const(v);
                 let patch = self.chunk.code.len();
                 self.chunk.emit(Op::MatchLit, line);
                 self.chunk.emit_u16(cidx);
@@ -990,25 +1144,30 @@ impl Compiler {
                     // il bind farà Dup+Unwrap sul Some/Ok/Err ancora intatto sullo stack.
                     let needs_inner_check = inner.iter().any(|p| !matches!(p, Pattern::Ident(_) | Pattern::Wildcard));
                     if needs_inner_check {
+                        // I sub-pattern lavorano su una copia unwrappata: il subject resta
+                        // intatto per gli arm successivi e per compile_pattern_bind.
+                        // La copia va tolta sia in caso di successo sia di fallimento.
+                        self.chunk.emit(Op::Dup, line);
                         self.chunk.emit(Op::Unwrap, line);
+                        let mut inner_fail = Vec::new();
                         for p in inner {
-                            self.compile_pattern_check(p, fail_patches, line)?;
+                            self.compile_pattern_check(p, &mut inner_fail, line)?;
                         }
-                        // NOTA: dopo aver verificato i sub-pattern, lo stack ha il valore
-                        // unwrappato. Il bind si aspetta il costruttore originale, quindi
-                        // qui non serve ri-wrappare: compile_pattern_bind usa Dup+Unwrap
-                        // sul soggetto originale che è ancora sotto nella stack (non modificato
-                        // da peek operations). In questa versione il check con inner usa
-                        // una copia duplicata per sicurezza — TODO: gestione più robusta.
+                        self.chunk.emit(Op::Pop, line);
+                        let matched = self.chunk.emit_jump(Op::Jump, line);
+                        for p in inner_fail { self.chunk.patch_jump(p); }
+                        self.chunk.emit(Op::Pop, line);
+                        fail_patches.push(self.chunk.emit_jump(Op::Jump, line));
+                        self.chunk.patch_jump(matched);
                     }
                 }
             }
             Pattern::Range { start, end, inclusive } => {
                 let lo = if let Pattern::Literal(ExprKind::Int(n)) = start.as_ref() {
-                    self.chunk.add_const(Value::Int(*n))
+                    self.add_const(Value::Int(*n))
                 } else { return Err(VmError::CompileError("range pattern requires Int".into())); };
                 let hi = if let Pattern::Literal(ExprKind::Int(n)) = end.as_ref() {
-                    self.chunk.add_const(Value::Int(*n))
+                    self.add_const(Value::Int(*n))
                 } else { return Err(VmError::CompileError("range pattern requires Int".into())); };
                 let patch = self.chunk.code.len();
                 self.chunk.emit(Op::MatchRange, line);
@@ -1019,6 +1178,15 @@ impl Compiler {
                 // FIX: offset field is at patch+6 (after opcode + 2*u16 + u8)
                 fail_patches.push(patch + 6);
             }
+            Pattern::StrAffix { prefix, suffix, .. } => {
+                let (pre, suf) = self.affix_consts(prefix, suffix);
+                let patch = self.chunk.code.len();
+                self.chunk.emit(Op::MatchAffix, line);
+                self.chunk.emit_u16(pre);
+                self.chunk.emit_u16(suf);
+                self.chunk.emit_i16(0);
+                fail_patches.push(patch + 5);
+            }
             Pattern::Or(pats) => {
                 // Se almeno uno matcha, ok. Compile as chain of checks with
                 // success jumps past all fail checks.
@@ -1036,6 +1204,9 @@ impl Compiler {
                 // success patches: atterrano tutti subito dopo
                 for sp in success_patches { self.chunk.patch_jump(sp); }
             }
+            Pattern::Tuple(_) => {
+                return Err(VmError::CompileError("tuple patterns not yet supported — use the interpreter".into()));
+            }
             Pattern::Error => {}
         }
         Ok(())
@@ -1048,6 +1219,13 @@ impl Compiler {
                 self.chunk.emit(Op::Dup, line);
                 self.locals.push(Local { name: name.clone(), depth: self.scope_depth, mutable: true });
             }
+            Pattern::StrAffix { prefix, rest, suffix } => {
+                if let Pattern::Ident(name) = rest.as_ref() {
+                    self.chunk.emit(Op::Dup, line);
+                    self.emit_strip_affix(prefix, suffix, line);
+                    self.locals.push(Local { name: name.clone(), depth: self.scope_depth, mutable: true });
+                }
+            }
             Pattern::Constructor(name, inner) if !inner.is_empty() => {
                 if matches!(name.as_str(), "Some" | "Ok" | "Err") {
                     // Dup il subject, poi Unwrap → il valore interno è ora in TOS.
@@ -1063,6 +1241,13 @@ impl Compiler {
                                 self.locals.push(Local { name: iname.clone(), depth: self.scope_depth, mutable: true });
                             }
                             Pattern::Wildcard => { /* valore già sullo stack, va rimosso */ }
+                            Pattern::StrAffix { prefix, rest, suffix } => {
+                                // Come Ident: inner_value diventa la parte centrale, senza Dup
+                                if let Pattern::Ident(iname) = rest.as_ref() {
+                                    self.emit_strip_affix(prefix, suffix, line);
+                                    self.locals.push(Local { name: iname.clone(), depth: self.scope_depth, mutable: true });
+                                }
+                            }
                             _ => { self.compile_pattern_bind(p, line)?; }
                         }
                     }
@@ -1129,14 +1314,14 @@ impl Compiler {
         let pos_local = self.locals.len() as u8;
         self.locals.push(Local { name: format!("__pos_{}", pos_local), depth: self.scope_depth, mutable: true });
         // Emetti Int(0) per la posizione iniziale
-        let zero_idx = self.chunk.add_const(Value::Int(0));
+        let zero_idx = self.add_const(Value::Int(0));
         self.chunk.emit(Op::Const, line);
         self.chunk.emit_u16(zero_idx);
 
         // Il var di iterazione
         let var_local = self.locals.len() as u8;
         self.locals.push(Local { name: var.to_string(), depth: self.scope_depth, mutable: true });
-        self.loop_local_counts.last_mut().map(|c| *c = self.locals.len() - locals_before); // ← aggiungi qui
+        if let Some(c) = self.loop_local_counts.last_mut() { *c = self.locals.len() - locals_before; }
         self.chunk.emit(Op::Nil, line); // placeholder
 
         let loop_start = self.chunk.code.len();
@@ -1192,7 +1377,7 @@ impl Compiler {
         is_async: bool,
         line: u32,
     ) -> VmResult<()> {
-        let mut fn_compiler = Compiler::new_function(name);
+        let mut fn_compiler = Compiler::new_function(name, &self.strings);
         // Copia i registry nel sotto-compiler (class, trait, impl)
         fn_compiler.class_registry = self.class_registry.clone();
         fn_compiler.trait_registry  = self.trait_registry.clone();
@@ -1302,7 +1487,7 @@ impl Compiler {
         //   3. restituisce l'istanza
         let ctor_name = name.to_string();
         let ctor_line = line;
-        let mut ctor = Compiler::new_function(name);
+        let mut ctor = Compiler::new_function(name, &self.strings);
         ctor.class_registry = self.class_registry.clone();
         ctor.trait_registry  = self.trait_registry.clone();
         ctor.impl_registry   = self.impl_registry.clone();
@@ -1431,7 +1616,7 @@ impl Compiler {
             proto:    std::rc::Rc::new(proto),
             upvalues: std::rc::Rc::new(Vec::new()),
         }));
-        let idx = self.chunk.add_const(closure);
+        let idx = self.add_const(closure);
         self.chunk.emit(Op::Const, line);
         self.chunk.emit_u16(idx);
         self.define_var(name, false, line)?;
@@ -1445,11 +1630,12 @@ impl Compiler {
         let mut segments = 0usize;
         let mut i = 0;
         let mut literal = String::new();
+        let mut interpolated = false;
 
         while i < chars.len() {
             if chars[i] == '{' && chars.get(i + 1) != Some(&'{') {
                 if !literal.is_empty() {
-                    let cidx = self.chunk.add_const(Value::str(literal.as_str()));
+                    let cidx = self.add_const(Value::str(literal.as_str()));
                     self.chunk.emit(Op::Const, line);
                     self.chunk.emit_u16(cidx);
                     literal.clear();
@@ -1462,6 +1648,7 @@ impl Compiler {
                     match chars[j] { '{' => depth += 1, '}' => depth -= 1, _ => {} }
                     j += 1;
                 }
+                interpolated = true;
                 let expr_src: String = chars[start..j - 1].iter().collect();
                 let (prog, _, _) = neba_parser::parse(&expr_src);
                 if let Some(stmt) = prog.stmts.first() {
@@ -1480,8 +1667,15 @@ impl Compiler {
                 literal.push(chars[i]); i += 1;
             }
         }
+        if !interpolated {
+            // Nessuna interpolazione: è una costante, niente BuildStr a runtime
+            let cidx = self.add_const(Value::str(literal));
+            self.chunk.emit(Op::Const, line);
+            self.chunk.emit_u16(cidx);
+            return Ok(());
+        }
         if !literal.is_empty() {
-            let cidx = self.chunk.add_const(Value::str(literal.as_str()));
+            let cidx = self.add_const(Value::str(literal.as_str()));
             self.chunk.emit(Op::Const, line);
             self.chunk.emit_u16(cidx);
             segments += 1;
@@ -1550,6 +1744,7 @@ fn const_eval(expr: &Expr) -> Option<Value> {
         ExprKind::Float(f) => Some(Value::Float(*f)),
         ExprKind::Bool(b)  => Some(Value::Bool(*b)),
         ExprKind::Str(s)   => Some(Value::str(s.as_str())),
+        ExprKind::Char(c)  => Some(Value::Char(*c)),
         ExprKind::None     => Some(Value::None),
         _ => None,
     }
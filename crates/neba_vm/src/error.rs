@@ -10,11 +10,16 @@ pub enum VmError {
     AssignImmutable(String),
     TypeError(String),
     DivisionByZero,
-    IndexOutOfBounds { index: i64, len: usize },
+    /// `pos` è la posizione `(line, column)` dell'espressione `a[i]`, impostata da `run_chunk`
+    IndexOutOfBounds { index: i64, len: usize, pos: Option<(u32, u32)> },
     NotCallable(String),
     ArityMismatch { name: String, expected: usize, got: usize },
     UnknownField { type_name: String, field: String },
     StackOverflow,
+    /// Superato il budget di istruzioni impostato con `Vm::set_step_limit`
+    StepLimitExceeded(u64),
+    /// Superato un limite di risorse (es. il budget di memoria di `Vm::set_alloc_limit`)
+    ResourceLimit(String),
     Generic(String),
 }
 
@@ -26,14 +31,19 @@ impl fmt::Display for VmError {
             VmError::AssignImmutable(n)    => write!(f, "[RuntimeError] Cannot assign to immutable variable '{}'", n),
             VmError::TypeError(m)          => write!(f, "[RuntimeError] Type error: {}", m),
             VmError::DivisionByZero        => write!(f, "[RuntimeError] Division by zero"),
-            VmError::IndexOutOfBounds { index, len }
-                => write!(f, "[RuntimeError] Index {} out of bounds for length {}", index, len),
+            VmError::IndexOutOfBounds { index, len, pos } => {
+                write!(f, "[RuntimeError] Index {} out of bounds for length {}", index, len)?;
+                if let Some((line, col)) = pos { write!(f, " at line {}, column {}", line, col)?; }
+                Ok(())
+            }
             VmError::NotCallable(t)        => write!(f, "[RuntimeError] '{}' is not callable", t),
             VmError::ArityMismatch { name, expected, got }
                 => write!(f, "[RuntimeError] '{}' expects {} arg(s), got {}", name, expected, got),
             VmError::UnknownField { type_name, field }
                 => write!(f, "[RuntimeError] '{}' has no field '{}'", type_name, field),
             VmError::StackOverflow         => write!(f, "[RuntimeError] Stack overflow"),
+            VmError::StepLimitExceeded(n)  => write!(f, "[RuntimeError] Step limit {} exceeded (infinite loop?)", n),
+            VmError::ResourceLimit(m)      => write!(f, "[RuntimeError] Resource limit: {}", m),
             VmError::Generic(m)            => write!(f, "[RuntimeError] {}", m),
         }
     }
@@ -0,0 +1,196 @@
+//! `serde::Serialize`/`Deserialize` per Value, e i builtin JSON che li usano.
+//!
+//! Copre il sottoinsieme "dati" di Value: Int, Float, Str, Bool, None, Array,
+//! Dict (chiavi Str) e TypedArray (serializzato come array). Option/Result
+//! diventano oggetti con un'unica chiave tag:
+//!
+//! | Neba        | JSON                                  |
+//! |-------------|---------------------------------------|
+//! | `Some(v)`   | `{"$some": v}`                        |
+//! | `Ok(v)`     | `{"$ok": v}`                          |
+//! | `Err(e)`    | `{"$err": e}`                         |
+//! | istanza `P` | `{"$class": "P", "fields": {...}}`    |
+//!
+//! Le istanze sono solo serializzabili: in lettura l'oggetto taggato resta un
+//! Dict, perché ricostruire un'istanza richiede la classe a runtime.
+//! Closure, funzioni native e Range non hanno rappresentazione → errore.
+//! Il formato è quello di qualunque serializer serde (JSON, MessagePack, ...).
+
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::value::Value;
+
+/// Profondità massima in scrittura (un Array che contiene sé stesso non
+/// termina); in lettura vale il limite del deserializer (128 per serde_json).
+const MAX_DEPTH: usize = 128;
+
+// ── Serializzazione ───────────────────────────────────────────────────────
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        Nested(self, 0).serialize(s)
+    }
+}
+
+/// Un Value con la sua profondità di annidamento.
+struct Nested<'a>(&'a Value, usize);
+
+impl Nested<'_> {
+    fn child<'b>(&self, v: &'b Value) -> Nested<'b> { Nested(v, self.1 + 1) }
+}
+
+impl Serialize for Nested<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if self.1 > MAX_DEPTH {
+            return Err(ser::Error::custom("value nested too deeply (cyclic?)"));
+        }
+        match self.0 {
+            Value::Int(n)   => s.serialize_i64(*n),
+            Value::Float(f) if !f.is_finite() =>
+                Err(ser::Error::custom(format!("cannot serialize non-finite float {}", f))),
+            Value::Float(f) => s.serialize_f64(*f),
+            Value::Bool(b)  => s.serialize_bool(*b),
+            Value::None     => s.serialize_unit(),
+            Value::Str(v)   => s.serialize_str(v),
+            Value::Char(c)  => s.serialize_char(*c),
+            Value::Array(a) => {
+                let a = a.borrow();
+                let mut seq = s.serialize_seq(Some(a.len()))?;
+                for item in a.iter() { seq.serialize_element(&self.child(item))?; }
+                seq.end()
+            }
+            Value::TypedArray(t) => {
+                let t = t.borrow();
+                let mut seq = s.serialize_seq(Some(t.len()))?;
+                for item in (0..t.len()).filter_map(|i| t.get(i)) {
+                    seq.serialize_element(&item)?;
+                }
+                seq.end()
+            }
+            Value::Dict(d) => {
+                let d = d.borrow();
+                let mut map = s.serialize_map(Some(d.len()))?;
+                for (k, v) in d.iter() {
+                    let Value::Str(k) = k else {
+                        return Err(ser::Error::custom(format!("dict keys must be Str, got {}", k.type_name())));
+                    };
+                    map.serialize_entry(k.as_str(), &self.child(v))?;
+                }
+                map.end()
+            }
+            Value::Some_(inner) => self.tagged(s, "$some", inner),
+            Value::Ok_(inner)   => self.tagged(s, "$ok", inner),
+            Value::Err_(inner)  => self.tagged(s, "$err", inner),
+            Value::Instance(inst) => {
+                let inst = inst.borrow();
+                // HashMap non ha ordine stabile: ordiniamo per nome campo
+                let mut fields: Vec<_> = inst.fields.iter().collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                let fields: Vec<_> = fields.into_iter().map(|(k, v)| (k, self.child(v))).collect();
+                let mut map = s.serialize_map(Some(2))?;
+                map.serialize_entry("$class", &inst.class_name)?;
+                map.serialize_entry("fields", &Fields(&fields))?;
+                map.end()
+            }
+            other => Err(ser::Error::custom(format!("cannot serialize {}", other.type_name()))),
+        }
+    }
+}
+
+impl Nested<'_> {
+    fn tagged<S: Serializer>(&self, s: S, tag: &str, inner: &Value) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(Some(1))?;
+        map.serialize_entry(tag, &self.child(inner))?;
+        map.end()
+    }
+}
+
+/// Campi di un'istanza, già ordinati.
+struct Fields<'a, 'b>(&'a [(&'a String, Nested<'b>)]);
+
+impl Serialize for Fields<'_, '_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(Some(self.0.len()))?;
+        for (k, v) in self.0 { map.serialize_entry(k.as_str(), v)?; }
+        map.end()
+    }
+}
+
+// ── Deserializzazione ─────────────────────────────────────────────────────
+
+/// Gli interi che stanno in un i64 diventano Int, gli altri numeri Float.
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Neba data value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> { Ok(Value::Bool(b)) }
+    fn visit_i64<E>(self, n: i64) -> Result<Value, E> { Ok(Value::Int(n)) }
+    fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+        Ok(i64::try_from(n).map_or(Value::Float(n as f64), Value::Int))
+    }
+    fn visit_f64<E>(self, f: f64) -> Result<Value, E> { Ok(Value::Float(f)) }
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> { Ok(Value::str(s)) }
+    fn visit_string<E>(self, s: String) -> Result<Value, E> { Ok(Value::str(s)) }
+    fn visit_unit<E>(self) -> Result<Value, E> { Ok(Value::None) }
+    fn visit_none<E>(self) -> Result<Value, E> { Ok(Value::None) }
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Value, D::Error> {
+        Value::deserialize(d)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(item) = seq.next_element()? { items.push(item); }
+        Ok(Value::array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut map = IndexMap::new();
+        while let Some((k, v)) = access.next_entry::<String, Value>()? {
+            map.insert(Value::str(k), v);
+        }
+        // Oggetti taggati a chiave singola → Option/Result
+        if map.len() == 1 {
+            let (k, _) = map.get_index(0).unwrap();
+            let tag = match k { Value::Str(s) => s.as_str(), _ => "" };
+            let wrap: Option<fn(Box<Value>) -> Value> = match tag {
+                "$some" => Some(Value::Some_),
+                "$ok"   => Some(Value::Ok_),
+                "$err"  => Some(Value::Err_),
+                _ => None,
+            };
+            if let Some(wrap) = wrap {
+                let (_, inner) = map.pop().unwrap();
+                return Ok(wrap(Box::new(inner)));
+            }
+        }
+        Ok(Value::dict_from_map(map))
+    }
+}
+
+// ── JSON ──────────────────────────────────────────────────────────────────
+
+/// Serializza `v` in JSON compatto.
+pub fn to_json(v: &Value) -> Result<String, String> {
+    serde_json::to_string(v).map_err(|e| format!("to_json: {}", e))
+}
+
+/// Legge un documento JSON (RFC 8259: niente zeri iniziali, virgole finali, ...).
+pub fn from_json(src: &str) -> Result<Value, String> {
+    serde_json::from_str(src).map_err(|e| format!("from_json: {}", e))
+}
@@ -1,7 +1,9 @@
 pub mod chunk;
 pub mod compiler;
 pub mod error;
+pub mod json;
 pub mod opcode;
+pub mod regex;
 pub mod stdlib;
 pub mod value;
 pub mod vm;
@@ -73,6 +75,72 @@ mod tests {
     #[test] fn t_strcat()   { assert_eq!(r(r#""ab" + "cd""#), Value::str("abcd")); }
     #[test] fn t_repeat()   { assert_eq!(r(r#""ha" * 3"#),    Value::str("hahaha")); }
     #[test] fn t_dbz()      { assert!(matches!(r_err("1 / 0"), VmError::DivisionByZero)); }
+    #[test] fn t_byte_len() {
+        assert_eq!(r("let s = \"héllo\"\n[len(s), s.byte_len(), byte_len(s), string.byte_len(s), s.len]"),
+                   Value::array(vec![Value::Int(5), Value::Int(6), Value::Int(6), Value::Int(6), Value::Int(5)]));
+    }
+    #[test] fn t_string_append_in_place() {
+        // 100k accodamenti: con AddStore il buffer cresce in place (O(n) ammortizzato),
+        // prima ogni iterazione ricopiava l'intera stringa (O(n²))
+        let src = "var s = \"\"\nfor i in 0..100000\n    s += \"x\"\nfn build(n)\n    var t = \"\"\n    for i in 0..n\n        t = t + \"ab\"\n    return t\nlet u = build(50000)\n[len(s), len(u), u[0..4]]";
+        assert_eq!(r(src), Value::array(vec![Value::Int(100000), Value::Int(100000), Value::str("abab")]));
+        // Una copia presa prima dell'accodamento non deve cambiare
+        assert_eq!(r("var s = \"ab\"\nlet t = s\ns += \"c\"\n[s, t]"), Value::array(vec![Value::str("abc"), Value::str("ab")]));
+        assert_eq!(r("var n = 1\nn = n + 2\nn += 3\nn"), Value::Int(6));
+        let (program, _, _) = neba_parser::parse("fn f()\n    let s = \"a\"\n    s += \"b\"");
+        assert!(Compiler::compile(&program).is_err());
+    }
+    #[test] fn t_add_store_int_wraps() {
+        // Come `x = x + 1`: overflow Int con wrapping, anche in build debug
+        let max = "9223372036854775807";
+        assert_eq!(r(&format!("var x = {}\nx += 1\nx", max)), Value::Int(i64::MIN));
+        assert_eq!(r(&format!("fn f()\n    var y = {}\n    y += 1\n    return y\nf()", max)), Value::Int(i64::MIN));
+        assert_eq!(r(&format!("var z = {}\nz = z + 1\nz", max)), Value::Int(i64::MIN));
+    }
+    #[test] fn t_is_not() {
+        assert_eq!(r("let x = 42\n[x is Int, x is not Int, x is not Str, x is not None, None is not None]"),
+                   Value::array(vec![Value::Bool(true), Value::Bool(false), Value::Bool(true), Value::Bool(true), Value::Bool(false)]));
+        assert_eq!(r("class P\n    a: Int\n    fn __init__(self, a)\n        self.a = a\nlet p = P(1)\n[p is not P, p is not Int, 1.5 is not Float]"),
+                   Value::array(vec![Value::Bool(false), Value::Bool(true), Value::Bool(false)]));
+        assert_eq!(r("fn f(Int)\n    return 5 is not Int\nf(2.0)"), Value::Bool(true));
+        // Un range è un Array per `is`, come nell'interprete
+        assert_eq!(r("let r = 0..3\n[r is Array, r is not Array, r is Int]").to_string(), "[true, false, false]");
+    }
+    #[test] fn t_trivia_only_program() {
+        for src in ["", "# solo commenti\n# altro", "\n\n   \n", "    # indentato\n\n"] {
+            assert_eq!(r(src), Value::None, "{:?}", src);
+        }
+    }
+    #[cfg(feature = "graphemes")]
+    #[test] fn t_grapheme_strings() {
+        // "cafe" + U+0301 (accento combinante) + "!": 6 code point, 5 grafemi
+        let src = "let s = \"cafe\u{301}!\"\nvar n = 0\nfor c in s\n    n += 1\n[s[3], s[4], s[3..5], s[-2:], n, len(s)]";
+        let eval = |graphemes: bool| {
+            let (program, _, _) = neba_parser::parse(src);
+            let mut vm = Vm::new();
+            vm.set_grapheme_strings(graphemes);
+            vm.run_chunk(Compiler::compile(&program).unwrap()).unwrap()
+        };
+        let arr = |items: [Value; 6]| Value::array(items.to_vec());
+        assert_eq!(eval(false), arr([Value::str("e"), Value::str("\u{301}"), Value::str("e\u{301}"), Value::str("\u{301}!"), Value::Int(6), Value::Int(6)]));
+        assert_eq!(eval(true), arr([Value::str("e\u{301}"), Value::str("!"), Value::str("e\u{301}!"), Value::str("e\u{301}!"), Value::Int(5), Value::Int(6)]));
+    }
+    #[test] fn t_ieee_division() {
+        let div = |ieee: bool, src: &str| {
+            let (program, _, _) = neba_parser::parse(src);
+            let mut vm = Vm::new();
+            vm.set_ieee_division(ieee);
+            vm.run_chunk(Compiler::compile(&program).unwrap())
+        };
+        assert_eq!(div(true, "[1.0 / 0.0, -1.0 / 0, 1 / 0.0]"), Ok(Value::array(vec![Value::Float(f64::INFINITY), Value::Float(f64::NEG_INFINITY), Value::Float(f64::INFINITY)])));
+        assert!(matches!(div(true, "0.0 / 0.0"), Ok(Value::Float(f)) if f.is_nan()));
+        assert_eq!(div(true, "1 / 0"), Err(VmError::DivisionByZero));
+        for src in ["1.0 / 0.0", "let z = 0.0\n1 / z", "1 / 0"] {
+            assert_eq!(div(false, src), Err(VmError::DivisionByZero), "{}", src);
+        }
+        // Default stretto, come l'interprete
+        assert!(r_err("1.0 / 0.0").to_string().contains("zero"));
+    }
 
     // ── Confronto / logica ────────────────────────────────────────────────
     #[test] fn t_eq()  { assert_eq!(r("1 == 1"),         Value::Bool(true)); }
@@ -88,10 +156,399 @@ mod tests {
     #[test] fn t_var()   { assert_eq!(r("var x = 1\nx = 2\nx"), Value::Int(2)); }
     #[test] fn t_compound() { assert_eq!(r("var x = 10\nx += 5\nx"), Value::Int(15)); }
     #[test] fn t_undef() { assert!(matches!(r_err("foo"), VmError::UndefinedVariable(_))); }
+    #[test] fn t_tuple_unsupported() {
+        assert!(matches!(r_err("let t = (1, 2)"), VmError::CompileError(m) if m.contains("tuples not yet supported")));
+        assert!(matches!(r_err("match 1\n    (a, b) => a\n    _ => 0\n"), VmError::CompileError(_)));
+    }
 
     // ── Controllo di flusso ───────────────────────────────────────────────
     #[test] fn t_if_true()  { assert_eq!(r("var x = 0\nif true\n    x = 1\nx"), Value::Int(1)); }
     #[test] fn t_if_false() { assert_eq!(r("var x = 0\nif false\n    x = 1\nx"), Value::Int(0)); }
+    #[test] fn t_inf_gt_max()     { assert_eq!(r("inf > 1e308"), Value::Bool(true)); }
+    #[test] fn t_nan_ne_nan()     { assert_eq!(r("let x = nan\n[x != x, x == x, nan < 1.0]"), Value::array(vec![Value::Bool(true), Value::Bool(false), Value::Bool(false)])); }
+    #[test] fn t_div_by_inf()     { assert_eq!(r("1.0 / inf == 0.0"), Value::Bool(true)); }
+    #[test] fn t_neg_inf()        { assert_eq!(r("-inf < -1e308"), Value::Bool(true)); }
+    #[test] fn t_math_inf_field() { assert_eq!(r("math.inf == inf"), Value::Bool(true)); }
+    #[test] fn t_instance_str_hook() {
+        assert_eq!(r("class P\n    x: Int = 0\n    fn __str__(self)\n        return \"P<\" + str(self.x) + \">\"\nlet p = P()\nstr(p) + f\" {p}\""), Value::str("P<0> P<0>"));
+    }
+    #[test] fn t_instance_str_nested() {
+        let cls = "class P\n    x: Int = 0\n    fn __str__(self)\n        return \"P<\" + str(self.x) + \">\"\n";
+        assert_eq!(r(&format!("{}let p = P()\nstr([p, [p]])", cls)), Value::str("[P<0>, [P<0>]]"));
+        assert_eq!(r(&format!("{}let d = {{\"k\": Some(P())}}\nf\"{{d}}\"", cls)), Value::str("{k: Some(P<0>)}"));
+        assert_eq!(r(&format!("{}var a = [P()]\npush(a, a)\nstr(a)", cls)), Value::str("[P<0>, [...]]"));
+    }
+    #[test] fn t_instance_str_fallback() {
+        assert_eq!(r("class Q\n    y: Int = 1\n    x: Int = 2\n    fn get(self)\n        return self.x\nstr(Q())"), Value::str("Q(x=2, y=1)"));
+    }
+    #[test] fn t_instance_dict_key() {
+        let src = "class K\n    id: Int = 0\n    fn __init__(self, id)\n        self.id = id\n    fn __hash__(self)\n        return self.id\n    fn __eq__(self, o)\n        return self.id == o.id\nlet d = {K(1): \"a\"}\nd[K(2)] = \"b\"\nd[K(1)] = \"c\"\nd[K(1)] + d[K(2)] + str(len(d)) + str(K(2) in d)";
+        assert_eq!(r(src), Value::str("cb2true"));
+    }
+    #[test] fn t_instance_dict_key_buckets() {
+        // Una lettura: un __hash__ e un __eq__ (solo sulle chiavi dello stesso bucket); hash uguali ma istanze diverse restano distinte
+        let cls = "var calls = 0\nclass K\n    id: Int = 0\n    h: Int = 0\n    fn __init__(self, id, h)\n        self.id = id\n        self.h = h\n    fn __hash__(self)\n        calls += 1\n        return self.h\n    fn __eq__(self, o)\n        calls += 1\n        return self.id == o.id\n";
+        let src = format!("{}var d = {{}}\nvar i = 0\nwhile i < 50\n    d[K(i, i)] = i\n    i += 1\ncalls = 0\nlet v = d[K(25, 25)]\nstr(v) + \" \" + str(calls)", cls);
+        assert_eq!(r(&src), Value::str("25 2"));
+        let src = format!("{}var d = {{}}\nd[K(1, 0)] = \"a\"\nd[K(2, 0)] = \"b\"\nd[K(1, 0)] = \"c\"\nd[K(1, 0)] + d[K(2, 0)] + str(len(d))", cls);
+        assert_eq!(r(&src), Value::str("cb2"));
+    }
+    #[test] fn t_instance_dict_key_mutated() {
+        // L'hash non resta legato all'istanza: dopo `k.id = 2` un nuovo Dict usa il nuovo hash
+        let src = "class K\n    id: Int = 0\n    fn __init__(self, id)\n        self.id = id\n    fn __hash__(self)\n        return self.id\n    fn __eq__(self, o)\n        return self.id == o.id\n\
+                   let k = K(1)\nlet d = {}\nd[k] = \"a\"\nk.id = 2\nlet e = {}\ne[k] = \"x\"\n\
+                   e[K(2)] + str(K(1) in e) + str(len(e)) + d[k]";
+        assert_eq!(r(src), Value::str("xfalse1a"));
+    }
+    #[test] fn t_instance_dict_key_unhashable() {
+        assert!(r_err("class K\n    id: Int = 0\nlet d = {}\nd[K()] = 1").to_string().contains("unhashable"));
+    }
+    #[test] fn t_deep_eq_nested() {
+        assert_eq!(r("deep_eq([1, {\"a\": [2, 3]}], [1, {\"a\": [2, 3]}])"), Value::Bool(true));
+        assert_eq!(r("deep_eq([1, {\"a\": [2, 3]}], [1, {\"a\": [2, 4]}])"), Value::Bool(false));
+        assert_eq!(r("class P\n    x: Int = 0\ndeep_eq({\"a\": P()}, {\"a\": P()})"), Value::Bool(true));
+    }
+    #[test] fn t_deep_eq_ignores_custom_eq() {
+        let src = "class P\n    x: Int = 0\n    fn __init__(self, x)\n        self.x = x\n    fn __eq__(self, o)\n        return true\nstr(deep_eq(P(1), P(1))) + str(deep_eq(P(1), P(2)))";
+        assert_eq!(r(src), Value::str("truefalse"));
+    }
+    #[test] fn t_freeze_read()    { assert_eq!(r("let a = freeze([1, 2, 3])\na[1] + len(a) + a[-1]"), Value::Int(8)); }
+    #[test] fn t_freeze_mutation_errors() {
+        assert!(r_err("let a = freeze([1])\na[0] = 2").to_string().contains("frozen"));
+        assert!(r_err("let a = freeze([1])\npush(a, 2)").to_string().contains("frozen"));
+        assert!(r_err("let a = freeze([1])\npop(a)").to_string().contains("frozen"));
+    }
+    #[test] fn t_cyclic_array_str() {
+        assert_eq!(r("var a = [1]\npush(a, a)\nstr(a)"), Value::str("[1, [...]]"));
+        assert_eq!(r("var d = {\"k\": 1}\nd[\"self\"] = d\nstr(d)"), Value::str("{k: 1, self: {...}}"));
+    }
+    #[test] fn t_clear_array_alias() { assert_eq!(r("var a = [1, 2, 3]\nlet b = a\nclear(a)\npush(b, 9)\nlen(a) * 10 + a[0]"), Value::Int(19)); }
+    #[test] fn t_clear_dict()        { assert_eq!(r("var d = {\"a\": 1}\nlet e = d\nclear(d)\nlen(e)"), Value::Int(0)); }
+    #[test] fn t_slice_assign_same_len() { assert_eq!(r("var a = [1, 2, 3, 4]\na[1:3] = [9, 9]\nstr(a)"), Value::str("[1, 9, 9, 4]")); }
+    #[test] fn t_slice_assign_grow()     { assert_eq!(r("var a = [1, 2, 3]\na[1:2] = [7, 8, 9]\nstr(a)"), Value::str("[1, 7, 8, 9, 3]")); }
+    #[test] fn t_slice_assign_shrink()   { assert_eq!(r("var a = [1, 2, 3, 4]\na[0:3] = []\nstr(a)"), Value::str("[4]")); }
+    #[test] fn t_slice_assign_insert()   { assert_eq!(r("var a = [1, 2]\na[1:1] = [5]\nstr(a)"), Value::str("[1, 5, 2]")); }
+    #[test] fn t_slice_assign_clamped()  { assert_eq!(r("var a = [1, 2]\na[1:100] = [3]\na[-50:0] = [0]\nstr(a)"), Value::str("[0, 1, 3]")); }
+    #[test] fn t_range_start_gt_end_empty() {
+        // `..` con start > end è vuoto; si scende solo con uno step esplicito
+        assert_eq!(r("var s = \"\"\nfor i in 5..0\n    s = s + str(i)\nlet n = 0\nfor i in 1..n\n    s = s + str(i)\nfor i in 3..=0\n    s = s + str(i)\ns"), Value::str(""));
+        assert_eq!(r("var s = \"\"\nfor i in range(3, -1, -1)\n    s = s + str(i)\ns"), Value::str("3210"));
+    }
+    #[test] fn t_range_start_gt_end_builtins() {
+        assert_eq!(r("str(range(3, 0)) + str(range(3, 0, -1)) + str(len(5..0)) + str(sum(3..=1)) + str(1 in 3..0) + str(len(1..=3))"), Value::str("[][3, 2, 1]00false3"));
+    }
+    #[test] fn t_register_native() {
+        fn double(args: &[Value]) -> Result<Value, String> {
+            match args { [Value::Int(n)] => Ok(Value::Int(n * 2)), _ => Err("double(Int)".into()) }
+        }
+        let (program, _, _) = neba_parser::parse("double(21)");
+        let mut vm = Vm::new();
+        vm.register_native("double", double);
+        assert_eq!(vm.run_chunk(Compiler::compile(&program).unwrap()).unwrap(), Value::Int(42));
+    }
+    #[test] fn t_call_function() {
+        let (program, _, _) = neba_parser::parse("fn add(a, b = 10)\n    return a + b\nfn boom(a)\n    return a[5]\nlet k = 3");
+        let mut vm = Vm::new();
+        vm.run_chunk(Compiler::compile(&program).unwrap()).unwrap();
+        assert_eq!(vm.call_function("add", vec![Value::Int(2), Value::Int(3)]), Ok(Value::Int(5)));
+        assert_eq!(vm.call_function("add", vec![Value::Int(2)]), Ok(Value::Int(12)));
+        assert!(matches!(vm.call_function("add", vec![]), Err(VmError::ArityMismatch { .. })));
+        assert!(matches!(vm.call_function("nope", vec![]), Err(VmError::UndefinedVariable(_))));
+        assert!(matches!(vm.call_function("k", vec![]), Err(VmError::NotCallable(_))));
+        // Dopo un errore la VM resta utilizzabile
+        assert!(vm.call_function("boom", vec![Value::array(vec![])]).is_err());
+        assert_eq!(vm.call_function("add", vec![Value::Int(1), Value::Int(1)]), Ok(Value::Int(2)));
+    }
+    #[test] fn t_value_conversions() {
+        assert_eq!(i64::try_from(Value::from(42i64)), Ok(42));
+        assert_eq!(f64::try_from(Value::from(2.5)), Ok(2.5));
+        assert_eq!(f64::try_from(Value::from(2i64)), Ok(2.0));
+        assert_eq!(bool::try_from(Value::from(true)), Ok(true));
+        assert_eq!(String::try_from(Value::from("ciao")), Ok("ciao".to_string()));
+        assert_eq!(String::try_from(Value::from("ciao".to_string())), Ok("ciao".to_string()));
+        let v = vec![Value::from(1i64), Value::from("a"), Value::from(false)];
+        assert_eq!(Vec::<Value>::try_from(Value::from(v.clone())), Ok(v));
+        assert_eq!(i64::try_from(Value::from("1")), Err("expected Int, got Str".to_string()));
+        assert!(Vec::<Value>::try_from(Value::None).is_err());
+    }
+    #[test] fn t_json_roundtrip() {
+        use crate::json::{from_json, to_json};
+        let v = r("{\"nome\": \"Neba \\\"β\\\"\\n\", \"n\": [1, 2.5, -3, true, none], \"opt\": Some(Ok(Err(\"x\")))}");
+        let text = to_json(&v).unwrap();
+        assert_eq!(text, r#"{"nome":"Neba \"β\"\n","n":[1,2.5,-3,true,null],"opt":{"$some":{"$ok":{"$err":"x"}}}}"#);
+        assert_eq!(from_json(&text).unwrap(), v);
+        assert_eq!(from_json(" [1.0, 1e2, \"\\u00e8\\ud83d\\ude00\"] ").unwrap(),
+                   Value::array(vec![Value::Float(1.0), Value::Float(100.0), Value::str("è😀")]));
+        assert!(to_json(&r("{1: 2}")).is_err());
+        assert!(to_json(&Value::Float(f64::NAN)).is_err());
+        assert!(from_json("[1,]").is_err());
+        assert!(from_json("{\"a\": 1} x").is_err());
+        assert!(from_json("01").is_err());
+        assert!(from_json("[-01]").is_err());
+        assert_eq!(from_json("0").unwrap(), Value::Int(0));
+        let deep = r("var a = [1]\npush(a, a)\na");
+        assert!(to_json(&deep).unwrap_err().contains("nested too deeply"));
+    }
+    #[test] fn t_json_builtins() {
+        assert_eq!(r("to_json({\"a\": [1, Some(2.5)]})"), Value::str(r#"{"a":[1,{"$some":2.5}]}"#));
+        assert_eq!(r("from_json(\"[1, {\\\"b\\\": null}]\")"), r("Ok([1, {\"b\": none}])"));
+        assert_eq!(r("match from_json(\"01\")\n    Ok(_) => \"ok\"\n    Err(e) => \"err\""), Value::str("err"));
+        assert!(r_err("to_json(print)").to_string().contains("cannot serialize"));
+    }
+    #[test] fn t_checked_arrays() {
+        let checked = |src: &str| {
+            let mut vm = Vm::new();
+            vm.set_checked_arrays(true);
+            vm.eval(src)
+        };
+        let ok = "let a: Array[Float] = [1.5]\npush(a, 2)\nappend(a, 3.0)\ninsert(a, 0, 0.5)\na[1] = 4\na[0:1] = [9.0]\nlen(a)";
+        assert_eq!(checked(ok).unwrap(), Value::Int(4));
+        let bad = [
+            "let a: Array[Int] = []\npush(a, \"x\")",
+            "var a: Array[Str] = [\"a\"]\na[0] = 1",
+            "let a: Array[Int] = [1]\na[0:1] = [1, none]",
+            "fn f()\n    let a: Array[Bool] = [true]\n    insert(a, 0, 1)\nf()",
+            "let a: Array[Int] = [1, 2.5]",
+        ];
+        for src in bad {
+            let err = checked(src).unwrap_err().to_string();
+            assert!(err.contains("cannot hold"), "{}: {}", src, err);
+        }
+        let err = checked("let a: Array[Int] = []\npush(a, \"x\")").unwrap_err();
+        assert_eq!(err.to_string(), VmError::TypeError("push: Array[Int] cannot hold Str".into()).to_string());
+        // Classi: l'istanza deve essere della classe (o implementarne il trait)
+        let cls = "class P\n    x: Int\nlet ps: Array[P] = [P()]\npush(ps, P())\n";
+        assert!(checked(cls).is_ok());
+        assert!(checked(&format!("{}push(ps, 3)", cls)).is_err());
+        // Un array non annotato non eredita il tipo di uno tipizzato già liberato
+        let stale = "fn f()\n    let a: Array[Int] = [1]\nfor i in range(100)\n    f()\n    let b = []\n    push(b, \"x\")";
+        assert!(checked(stale).is_ok());
+        // Char: vale come Str, ma Array[Char] non accetta una Str
+        assert!(checked("let a: Array[Str] = ['x']\npush(a, 'y')\na[0] = \"z\"").is_ok());
+        assert!(checked("let a: Array[Char] = ['x']\npush(a, \"yz\")").is_err());
+        // Opzione spenta (default): nessun controllo
+        assert_eq!(r("let a: Array[Int] = []\npush(a, \"x\")\nlen(a)"), Value::Int(1));
+    }
+    #[test] fn t_assert_eq_raises() {
+        assert_eq!(r("assert_eq([1, 2], [1, 2])\nassert_raises(fn() => 1 / 0)\n1"), Value::Int(1));
+        assert_eq!(r_err("assert_eq(\"a\", 1)").to_string(), VmError::Generic("assertion failed: \"a\" != 1".into()).to_string());
+        assert!(r_err("assert_raises(fn() => 1)").to_string().contains("did not raise"));
+        // Anche dentro una funzione chiamata dall'host (loop di call_value_sync)
+        let mut vm = Vm::new();
+        vm.eval("fn t()\n    assert_raises(fn() => [][0])\n    return 7").unwrap();
+        assert_eq!(vm.call_function("t", vec![]), Ok(Value::Int(7)));
+    }
+    #[test] fn t_option_result_combinators() {
+        let src = "fn meta(x)\n    if x % 2 == 0\n        return Some(int(x / 2))\n    return None\n";
+        assert_eq!(r(&format!("{}and_then(and_then(Some(8), meta), meta)", src)).to_string(), "Some(2)");
+        assert_eq!(r(&format!("{}and_then(and_then(Some(6), meta), meta)", src)), Value::None);
+        assert_eq!(r(&format!("{}and_then(None, meta)", src)), Value::None);
+        assert_eq!(r("or_else(None, fn() => Some(0))").to_string(), "Some(0)");
+        assert_eq!(r("or_else(Err(1), fn(e) => Ok(e + 1))").to_string(), "Ok(2)");
+        assert_eq!(r("map_err(Err(\"io\"), fn(e) => \"errore: \" + e)").to_string(), "Err(errore: io)");
+        assert_eq!(r("map_err(Ok(1), fn(e) => 0)").to_string(), "Ok(1)");
+        assert_eq!(r("ok_or(Some(1), \"vuoto\")").to_string(), "Ok(1)");
+        assert_eq!(r("ok_or(None, \"vuoto\")").to_string(), "Err(vuoto)");
+        assert!(matches!(r_err("map_err(Some(1), fn(e) => e)"), VmError::TypeError(_)));
+    }
+    #[test] fn t_regex() {
+        assert_eq!(r(r#"regex_match(r"(\w+)@(\w+)\.com", "scrivi a neba@esempio.com")"#).to_string(),
+                   "Some([neba@esempio.com, neba, esempio])");
+        assert_eq!(r(r#"regex_match(r"^\d+$", "12a")"#), Value::None);
+        assert_eq!(r(r#"regex_find_all(r"\d+", "a1 b22 c333")"#).to_string(), "[1, 22, 333]");
+        assert_eq!(r(r#"regex_find_all(r"(\w)=(\d)", "a=1, b=2")"#).to_string(), "[[a, 1], [b, 2]]");
+        assert_eq!(r(r#"regex_replace(r"(\w+) (\w+)", "ciao mondo", "$2 $1 $$")"#), Value::str("mondo ciao $"));
+        assert_eq!(r(r#"regex_replace("a|b", "abc", "")"#), Value::str("c"));
+        assert!(matches!(r(r#"regex_match("(a", "a")"#), Value::Err_(_)));
+        assert!(matches!(r(r#"regex_find_all("[z-a]", "a")"#), Value::Err_(_)));
+    }
+    #[test] fn t_char() {
+        assert_eq!(r("'x'"), Value::Char('x'));
+        assert_eq!(r("\"xyz\"[0] == 'x'"), Value::Bool(true));
+        assert_eq!(r("'x' == \"xy\""), Value::Bool(false));
+        assert_eq!(r("\"ab\" + 'c'"), Value::str("abc"));
+        assert_eq!(r("'é' in \"caffé\""), Value::Bool(true));
+        assert_eq!(r("match \"q\"[0]\n    'p' => 1\n    'q' => 2\n    _ => 3"), Value::Int(2));
+        // Chiave Dict: 'a' e "a" sono la stessa chiave
+        assert_eq!(r("let d = {\"a\": 1}\nd['a']"), Value::Int(1));
+        // Fuori da `is`/`typeof` un Char vale come la Str del suo carattere
+        assert_eq!(r("f\"{'='*3}\" + 'a' + 'b'"), Value::str("===ab"));
+        assert_eq!(r("\"abc\"[0] < 'b' and 'b' > \"a\""), Value::Bool(true));
+        assert_eq!(r("len('x') + ord('a') + len(\"it's\")"), Value::Int(102));
+        assert_eq!(r("upper('q') + join(split(\"a,b\", ','), '-') + replace(\"aXa\", 'X', 'b')"), Value::str("Qa-baba"));
+        assert_eq!(r("'a' is Char and not (\"a\" is Char)"), Value::Bool(true));
+    }
+    #[test] fn t_hash() {
+        use std::hash::{Hash, Hasher};
+        let h = r("hash(\"neba\")");
+        assert_eq!(r("hash(\"ne\" + \"ba\")"), h);
+        // Stesso `Hash` delle chiavi Dict, senza seme casuale
+        let mut state = neba_runtime::StableHasher::default();
+        Value::str("neba").hash(&mut state);
+        assert_eq!(h, Value::Int(state.finish() as i64));
+        assert_ne!(r("hash(1)"), r("hash(\"1\")"));
+        assert_eq!(r("hash(Some(1)) == hash(Some(1))"), Value::Bool(true));
+        // -0.0 == 0.0: stesso hash e stessa chiave
+        assert_eq!(r("hash(-0.0) == hash(0.0)"), Value::Bool(true));
+        assert_eq!(r("let d = {0.0: \"zero\"}\nd[-0.0]"), Value::str("zero"));
+        assert!(r_err("hash([1])").to_string().contains("unhashable type"));
+        assert_eq!(r("sha256_hex(\"abc\")"), Value::str("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"));
+    }
+    #[test] fn t_regex_engine() {
+        use crate::regex::Regex;
+        let find = |p: &str, t: &str| {
+            let text: Vec<char> = t.chars().collect();
+            Regex::new(p).unwrap().captures_from(&text, 0).unwrap()
+                .and_then(|c| c[0]).map(|(a, b)| text[a..b].iter().collect::<String>())
+        };
+        assert_eq!(find("a.c", "xxabc").as_deref(), Some("abc"));
+        assert_eq!(find("<.+>", "<a><b>").as_deref(), Some("<a><b>"));
+        assert_eq!(find("<.+?>", "<a><b>").as_deref(), Some("<a>"));
+        assert_eq!(find("(?:ab)+c", "ababc").as_deref(), Some("ababc"));
+        assert_eq!(find("x{2,3}", "xxxx").as_deref(), Some("xxx"));
+        assert_eq!(find("[^0-9 ]+", "12 ab3").as_deref(), Some("ab"));
+        assert_eq!(find(r"\bcat\b", "concat cat").as_deref(), Some("cat"));
+        assert_eq!(find("è+", "caffèè").as_deref(), Some("èè"));
+        assert_eq!(find("(a|ab)(c|bcd)$", "abcd").as_deref(), Some("abcd"));
+        assert_eq!(find("a{x}", "a{x}").as_deref(), Some("a{x}"));
+        assert_eq!(find("(a*)*b", "aaab").as_deref(), Some("aaab"));
+        // Ripetizioni di un solo carattere non ricorrono per iterazione
+        let long = format!("a{}z", "x".repeat(100_000));
+        assert_eq!(find("a.*z", &long).map(|m| m.len()), Some(long.len()));
+        // Né le ripetizioni di gruppi: input lunghi non esauriscono lo stack
+        let ab = "ab".repeat(9000);
+        assert_eq!(find("(?:ab)+", &ab).map(|m| m.len()), Some(ab.len()));
+        assert_eq!(find(r"(\w\w)+$", &ab[..10_000]).map(|m| m.len()), Some(10_000));
+        assert_eq!(find("(?:ab)+?", &ab).as_deref(), Some("ab"));
+        let text: Vec<char> = "abab".chars().collect();
+        assert_eq!(Regex::new("(ab)+").unwrap().captures_from(&text, 0).unwrap(), Some(vec![Some((0, 4)), Some((2, 4))]));
+        // Backtracking esponenziale → errore, non un match troncato
+        let text: Vec<char> = "a".repeat(40).chars().collect();
+        assert!(Regex::new("(a|a)*c").unwrap().captures_from(&text, 0).is_err());
+        assert!(r_err(r#"regex_match("(a|a)*c", "a" * 40)"#).to_string().contains("too complex"));
+        for bad in ["*a", "a)", "[abc", r"\q", "a{3,1}", "^*"] {
+            assert!(Regex::new(bad).is_err(), "{}", bad);
+        }
+    }
+    #[test] fn t_str_format() {
+        assert_eq!(r("\"{0} e {1}, {0}\".format(\"a\", 2)"), Value::str("a e 2, a"));
+        assert_eq!(r("let t = \"{saluto}, {0}! {{ok}}\"\nt.format(\"Neba\", saluto=\"Ciao\")"), Value::str("Ciao, Neba! {ok}"));
+        // Dentro una closure chiamata da una HOF (loop di call_value_sync)
+        assert_eq!(r("map([1, 2], fn(x) => \"<{0}|{k}>\".format(x, k=x * 10))").to_string(), "[<1|10>, <2|20>]");
+        // Le Instance passano da __str__, come in str()
+        let cls = "class P\n    x: Int = 0\n    fn __str__(self)\n        return \"P\"\n";
+        assert_eq!(r(&format!("{cls}\"<{{0}}>\".format(P())")), Value::str("<P>"));
+        assert_eq!(r(&format!("{cls}map([1], fn(x) => \"<{{0}}|{{k}}>\".format(P(), k=P()))")).to_string(), "[<P|P>]");
+        assert!(r_err("\"{0} {1}\".format(1)").to_string().contains("no argument for placeholder {1}"));
+        assert!(r_err("\"{x}\".format(y=1)").to_string().contains("no argument named 'x'"));
+    }
+    #[test] fn t_pipe() {
+        assert_eq!(r("[1, 2, 3] |> sum"), Value::Int(6));
+        assert_eq!(r("fn add(x, n)\n    return x + n\n5 |> add(1) |> add(10) |> str"), Value::str("16"));
+        assert_eq!(r("[1, 2, 3] |> map(fn(x) => x * x) |> sum"), Value::Int(14));
+        assert_eq!(r("let k = 3\n2 |> (fn(x) => x * k)"), Value::Int(6));
+    }
+    #[test] fn t_set_get_global() {
+        let (program, _, _) = neba_parser::parse("let result = limit * 2\nlimit = 0");
+        let mut vm = Vm::new();
+        vm.set_global("limit", Value::Int(21));
+        vm.run_chunk(Compiler::compile(&program).unwrap()).unwrap();
+        assert_eq!(vm.get_global("result"), Some(Value::Int(42)));
+        assert_eq!(vm.get_global("limit"), Some(Value::Int(0)));
+        assert_eq!(vm.get_global("missing"), None);
+    }
+    #[test] fn t_sandboxed_no_io() {
+        // La sandbox toglie le globali: l'errore è sul nome rimosso
+        let calls = [
+            ("io", "io.read_file(\"/etc/passwd\")"), ("io", "io.file_exists(\"/\")"),
+            ("print", "print(1)"), ("input", "input()"), ("sleep", "sleep(1)"),
+        ];
+        for (name, src) in calls {
+            let (program, _, _) = neba_parser::parse(src);
+            let err = Vm::sandboxed().run_chunk(Compiler::compile(&program).unwrap()).unwrap_err();
+            assert!(matches!(&err, VmError::UndefinedVariable(n) if n == name), "{}: {}", src, err);
+        }
+        assert_eq!(r("io.file_exists(\"/\")"), Value::Bool(true));
+    }
+    #[test] fn t_sandboxed_step_limit() {
+        let (program, _, _) = neba_parser::parse("var i = 0\nwhile true\n    i += 1");
+        let mut vm = Vm::sandboxed();
+        vm.set_step_limit(10_000);
+        let err = vm.run_chunk(Compiler::compile(&program).unwrap()).unwrap_err();
+        assert!(matches!(err, VmError::StepLimitExceeded(_)), "{}", err);
+    }
+    #[test] fn t_step_limit_infinite_loop() {
+        assert_eq!(run_limited("while true\n    pass", 1000).unwrap_err(), VmError::StepLimitExceeded(1000));
+    }
+    #[test] fn t_step_limit_finite_ok() {
+        assert_eq!(run_limited("var s = 0\nfor i in range(10)\n    s += i\ns", 1000).unwrap(), Value::Int(45));
+    }
+    #[test] fn t_alloc_limit() {
+        let run_capped = |src: &str| {
+            let (program, _, _) = neba_parser::parse(src);
+            let mut vm = Vm::new();
+            vm.set_alloc_limit(1 << 20);
+            vm.run_chunk(Compiler::compile(&program).unwrap())
+        };
+        assert!(matches!(run_capped("var a = []\nwhile true\n    push(a, [0])"), Err(VmError::ResourceLimit(_))));
+        assert!(matches!(run_capped("\"x\" * 1000000000"), Err(VmError::ResourceLimit(_))));
+        assert_eq!(run_capped("var a = []\nfor i in range(100)\n    push(a, i)\nlen(a)").unwrap(), Value::Int(100));
+        // Anche fuori dal fast path di push: chiamata indiretta e altri builtin
+        for src in ["var a = []\nlet p = push\nwhile true\n    p(a, 0)",
+                    "var a = []\nwhile true\n    insert(a, 0, 0)",
+                    "var s = \"x\"\nwhile true\n    s = join([s, s], \"\")"] {
+            assert!(matches!(run_capped(src), Err(VmError::ResourceLimit(_))), "{}", src);
+        }
+        // `d[k] = v` addebita solo le chiavi nuove
+        assert!(matches!(run_capped("var d = {}\nvar i = 0\nwhile true\n    d[i] = i\n    i += 1"), Err(VmError::ResourceLimit(_))));
+        assert!(run_capped("var d = {}\nfor i in 0..100000\n    d[0] = i").is_ok());
+    }
+    #[test] fn t_sandboxed_map_callback_counts() {
+        let (program, _, _) = neba_parser::parse("fn spin(x)\n    while true\n        x += 1\nmap([1], spin)");
+        let mut vm = Vm::sandboxed();
+        vm.set_step_limit(10_000);
+        assert!(matches!(vm.run_chunk(Compiler::compile(&program).unwrap()), Err(VmError::StepLimitExceeded(_))));
+    }
+    #[test] fn t_eval_incremental() {
+        let mut vm = Vm::new();
+        assert_eq!(vm.eval("let base = 40\nfn add2(x)\n    return x + 2").unwrap(), Value::None);
+        assert_eq!(vm.eval("add2(base)").unwrap(), Value::Int(42));
+    }
+    #[test] fn t_eval_incremental_class() {
+        let mut vm = Vm::new();
+        vm.eval("trait Named\n    fn name(self)\nclass P\n    x: Int\n    fn __init__(self, x)\n        self.x = x\nimpl Named for P\n    fn name(self)\n        return \"p\"").unwrap();
+        assert_eq!(vm.eval("let p = P(3)\np.x").unwrap(), Value::Int(3));
+        assert_eq!(vm.eval("p is Named").unwrap(), Value::Bool(true));
+    }
+    #[test] fn t_eval_after_error() {
+        let mut vm = Vm::new();
+        vm.eval("var n = 1").unwrap();
+        assert!(vm.eval("n = n / 0").is_err());
+        assert_eq!(vm.eval("n + 1").unwrap(), Value::Int(2));
+    }
+    #[test] fn t_approx_eq()      { assert_eq!(r("approx_eq(0.1 + 0.2, 0.3)"), Value::Bool(true)); }
+    #[test] fn t_approx_eq_diff() { assert_eq!(r("approx_eq(1.0, 1.001) or approx_eq(1e9, 1e9 + 10)"), Value::Bool(false)); }
+    #[test] fn t_approx_eq_eps()  { assert_eq!(r("math.approx_eq(1.0, 1.001, 0.01)"), Value::Bool(true)); }
+    #[test] fn t_approx_eq_special() {
+        assert_eq!(r("[approx_eq(nan, nan), approx_eq(inf, inf), approx_eq(inf, 1e308), approx_eq(-inf, inf)]"),
+                   Value::array(vec![Value::Bool(false), Value::Bool(true), Value::Bool(false), Value::Bool(false)]));
+    }
+    #[test] fn t_and_returns_right()  { assert_eq!(r("let a = 1\na and \"b\""), Value::str("b")); }
+    #[test] fn t_and_returns_left()   { assert_eq!(r("let a = 0\na and \"b\""), Value::Int(0)); }
+    #[test] fn t_or_returns_left()    { assert_eq!(r("let a = 3\na or \"b\""), Value::Int(3)); }
+    #[test] fn t_or_returns_right()   { assert_eq!(r("let a = None\na or \"b\""), Value::str("b")); }
+    #[test] fn t_and_or_stack_clean() {
+        // Un valore spurio sullo stack sposterebbe gli slot dei locali successivi
+        let src = "fn f(a, b)\n    let x = a and b\n    let y = a or b\n    let z = 7\n    return x + 10 * y + 100 * z\nf(0, 2) * 1000 + f(1, 2)";
+        assert_eq!(r(src), Value::Int(720_712));
+    }
+    #[test] fn t_if_block_scope() {
+        assert!(matches!(r_err("let y = if true\n    let tmp = 1\n    tmp\nelse\n    2\ntmp"), VmError::UndefinedVariable(_)));
+    }
+    #[test] fn t_if_block_locals_popped() {
+        // I locali del blocco non devono restare sullo stack sotto il risultato
+        assert_eq!(r("fn f()\n    let y = if true\n        let tmp = 10\n        tmp + 1\n    else\n        2\n    let z = 5\n    return y + z\nf()"), Value::Int(16));
+    }
     #[test] fn t_while() {
         assert_eq!(r("var i = 0\nvar s = 0\nwhile i < 5\n    s += i\n    i += 1\ns"), Value::Int(10));
     }
@@ -127,6 +584,10 @@ mod tests {
     #[test] fn t_arr_idx()  { assert_eq!(r("let a = [10,20,30]\na[1]"),        Value::Int(20)); }
     #[test] fn t_arr_neg()  { assert_eq!(r("let a = [1,2,3]\na[-1]"),          Value::Int(3)); }
     #[test] fn t_arr_set()  { assert_eq!(r("var a = [1,2,3]\na[0] = 99\na[0]"), Value::Int(99)); }
+    #[test] fn t_arr_compound_set() {
+        // Oggetto e indice valutati una volta (Over, Over)
+        assert_eq!(r("var a = [1, 2]\nvar n = 0\nfn i()\n    n += 1\n    return 1\na[i()] += 10\nlet d = {\"k\": 2}\nd[\"k\"] *= 5\n[a, n, d[\"k\"]]").to_string(), "[[1, 12], 1, 10]");
+    }
     #[test] fn t_arr_in()   { assert_eq!(r("2 in [1,2,3]"),                    Value::Bool(true)); }
     #[test] fn t_arr_notin(){ assert_eq!(r("5 not in [1,2,3]"),                Value::Bool(true)); }
 
@@ -249,6 +710,15 @@ mod dict_tests {
 d["x"]"#), Value::Int(10));
     }
 
+    #[test]
+    fn t_dict_nested_multiline() {
+        assert_eq!(r(r#"let d = {
+    "a": {"b": [1, 2]},
+    "c": 3,
+}
+d["a"]["b"][1] + d["c"]"#), Value::Int(5));
+    }
+
     #[test]
     fn t_dict_int_key() {
         assert_eq!(r("let d = {1: 100, 2: 200}\nd[2]"), Value::Int(200));
@@ -364,11 +834,12 @@ len(d)"#), Value::Int(1));
 #[cfg(test)]
 mod typed_array_tests {
     use super::*;
-    use crate::value::{TypedArrayData};
+    
 
     fn r(src: &str) -> Value {
         match run(src) { Ok(v) => v, Err(e) => panic!("VmError: {}", e) }
     }
+    #[allow(dead_code)]
     fn rerr(src: &str) -> String {
         match run(src) { Ok(v) => panic!("expected error, got {:?}", v), Err(e) => e.to_string() }
     }
@@ -554,12 +1025,38 @@ typeof(z)"#), Value::str("Int64Array"));
     #[test] fn t_match_binding()    { assert_eq!(run_limited("match 42\n    n => n", 500).unwrap(), Value::Int(42)); }
     #[test] fn t_match_range_hit()  { assert_eq!(run_limited("match 5\n    1..=9 => 1\n    _ => 0", 500).unwrap(), Value::Int(1)); }
     #[test] fn t_match_range_miss() { assert_eq!(run_limited("match 10\n    1..=9 => 1\n    _ => 0", 500).unwrap(), Value::Int(0)); }
+    #[test] fn t_match_subject_once() {
+        // Il subject va valutato una sola volta, sia senza arm corrispondente sia con più arm provati
+        assert_eq!(run_limited("var calls = 0\nfn next(v)\n    calls += 1\n    return v\nmatch next(7)\n    1 => 0\n    2 | 3 => 0\n    Some(x) => 0\n    0..=5 => 0\nmatch next(Ok(3))\n    Err(e) => 0\n    Ok(4) => 1\n    Ok(v) => v\ncalls", 2000).unwrap(), Value::Int(2));
+    }
+    #[test] fn t_match_nested_literal_keeps_subject() {
+        // Un sub-pattern non di solo binding non deve consumare il subject degli arm successivi
+        assert_eq!(r("match Ok(3)\n    Ok(4) => 1\n    Ok(v) => v"), Value::Int(3));
+        assert_eq!(r("match Some(1)\n    Some(1) => 10\n    _ => 0"), Value::Int(10));
+    }
+    #[test] fn t_match_str_affix() {
+        let src = "fn host(url)\n    return match url\n        \"http://\" + rest => rest\n        nome + \".txt\" => nome\n        Some(\"k=\" + v) => v\n        _ => \"altro\"\n";
+        for (arg, expected) in [("\"http://neba.dev\"", "neba.dev"), ("\"ftp://neba.dev\"", "altro"),
+                                ("\"note.txt\"", "note"), ("Some(\"k=1\")", "1"), ("42", "altro")] {
+            assert_eq!(r(&format!("{}host({})", src, arg)), Value::str(expected), "{}", arg);
+        }
+    }
     #[test] fn t_match_let_result() {
         assert_eq!(
             run_limited("let x = 2\nlet r = match x\n    1 => \"uno\"\n    2 => \"due\"\n    _ => \"altro\"\nr", 500).unwrap(),
             Value::str("due")
         );
     }
+    #[test] fn t_hot_loop_arith() {
+        // Loop stretto: il dispatch con stato del frame in cache non deve cambiare i risultati
+        let src = "var s = 0\nvar i = 0\nwhile i < 200000\n    s += i * 2 - i % 7\n    i += 1\ns";
+        let expected: i64 = (0..200000i64).map(|i| i * 2 - i % 7).sum();
+        assert_eq!(r(src), Value::Int(expected));
+    }
+    #[test] fn t_hot_loop_upvalues() {
+        let src = "fn counter()\n    var n = 0\n    fn tick()\n        n = n + 1\n        return n\n    return tick\nlet t = counter()\nvar last = 0\nfor i in 0..50000\n    last = t()\nlast";
+        assert_eq!(r(src), Value::Int(50000));
+    }
     #[test] fn t_match_in_loop() {
         // Questo è il caso originale che causava OOM — ora deve completare in pochi step
         assert_eq!(
@@ -1171,3 +1668,108 @@ match r1
         assert_eq!(run(src).unwrap(), Value::Int(55));
     }
 }
+
+#[cfg(test)]
+mod stack_op_tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::opcode::Op;
+
+    /// Push di 1, 2, 3, poi `ops`, poi impacchetta tutto lo stack in un array.
+    fn stack_after(ops: &[(Op, Option<u8>)], depth: u16) -> Value {
+        let mut c = Chunk::new();
+        for n in 1..=3 {
+            let idx = c.add_const(Value::Int(n));
+            c.emit(Op::Const, 1);
+            c.emit_u16(idx);
+        }
+        for (op, arg) in ops {
+            c.emit(*op, 1);
+            if let Some(a) = arg { c.emit_u8(*a); }
+        }
+        c.emit(Op::MakeArray, 1);
+        c.emit_u16(depth);
+        c.emit(Op::Halt, 1);
+        Vm::new().run_chunk(c).unwrap()
+    }
+    fn ints(v: &[i64]) -> Value { Value::array(v.iter().map(|&n| Value::Int(n)).collect()) }
+
+    #[test] fn t_swap()  { assert_eq!(stack_after(&[(Op::Swap, None)], 3),     ints(&[1, 3, 2])); }
+    #[test] fn t_over()  { assert_eq!(stack_after(&[(Op::Over, None)], 4),     ints(&[1, 2, 3, 2])); }
+    #[test] fn t_rot3()  { assert_eq!(stack_after(&[(Op::Rot, Some(3))], 3),   ints(&[3, 1, 2])); }
+    #[test] fn t_rot2()  { assert_eq!(stack_after(&[(Op::Rot, Some(2))], 3),   ints(&[1, 3, 2])); }
+    #[test] fn t_rot_popn() {
+        // Il pattern usato da block-expr/match: risultato sotto, poi scarta i locali
+        assert_eq!(stack_after(&[(Op::Rot, Some(3)), (Op::PopN, Some(2))], 1), ints(&[3]));
+    }
+    #[test] fn t_match_many_bindings() {
+        assert_eq!(run("fn f(r)\n    return match r\n        Ok(v) => v * 2\n        Err(e) => 0\nlet a = 1\nlet b = if true\n    let x = 1\n    let y = 2\n    let z = 3\n    x + y + z\nelse\n    0\nf(Ok(b)) + a").unwrap(), Value::Int(13));
+    }
+}
+
+#[cfg(test)]
+mod chunk_tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    #[test]
+    fn t_const_pool_no_duplicates() {
+        let src = "var s = 0\nfor i in 0..10\n    s = s + 0 + 1\n    s = s + 1 + 0\nlet t = \"a\" + \"a\"\ns";
+        let (program, _, _) = neba_parser::parse(src);
+        let chunk = Compiler::compile(&program).unwrap();
+        let reprs: Vec<String> = chunk.constants.iter().map(|v| format!("{:?}", v)).collect();
+        let mut uniq = reprs.clone();
+        uniq.sort();
+        uniq.dedup();
+        assert_eq!(reprs.len(), uniq.len(), "duplicati nel pool: {:?}", reprs);
+        let mut names = chunk.names.clone();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), chunk.names.len());
+    }
+
+    #[test]
+    fn t_string_consts_interned() {
+        let src = format!("{}let f = f\"ciao\"\nfn g()\n    return \"ciao\"\n[g(), f]", "let a = \"ciao\"\n".repeat(50));
+        let (program, _, _) = neba_parser::parse(&src);
+        let chunk = Compiler::compile(&program).unwrap();
+        let strs = |c: &Chunk| -> Vec<std::rc::Rc<String>> {
+            c.constants.iter().filter_map(|v| if let Value::Str(s) = v { Some(s.clone()) } else { None }).collect()
+        };
+        // 50 letterali + f-string senza interpolazioni → un solo slot, nessun BuildStr
+        let top = strs(&chunk);
+        assert_eq!(top.len(), 1);
+        assert!(!chunk.disassemble("script").contains("BuildStr"));
+        // Il letterale nella funzione condivide l'allocazione di quello dello script
+        let inner = strs(&chunk.fn_protos[0].chunk);
+        assert!(std::rc::Rc::ptr_eq(&top[0], &inner[0]));
+        assert_eq!(Vm::new().run_chunk(chunk).unwrap(), Value::array(vec![Value::str("ciao"), Value::str("ciao")]));
+    }
+
+    fn error_pos(src: &str) -> Option<(u32, u32)> {
+        let (program, _, _) = neba_parser::parse(src);
+        let mut vm = Vm::new();
+        assert!(vm.run_chunk(Compiler::compile(&program).unwrap()).is_err());
+        vm.error_position()
+    }
+
+    #[test] fn t_error_column()       { assert_eq!(error_pos("let a = 10\nlet b = a + a / 0"), Some((2, 13))); }
+    #[test] fn t_error_column_in_fn() { assert_eq!(error_pos("fn f(x)\n    return x.foo\nf(1)"), Some((2, 12))); }
+    #[test] fn t_index_error_pos() {
+        let (program, _, _) = neba_parser::parse("let a = [1, 2, 3]\nlet i = 1\nlet x = a[i] + a[i + 5]");
+        let err = Vm::new().run_chunk(Compiler::compile(&program).unwrap()).unwrap_err();
+        assert_eq!(err, VmError::IndexOutOfBounds { index: 6, len: 3, pos: Some((3, 16)) });
+        assert!(err.to_string().ends_with("at line 3, column 16"));
+    }
+
+    #[test]
+    fn t_const_dedup_float_edge_cases() {
+        let mut c = Chunk::new();
+        let nan = c.add_const(Value::Float(f64::NAN));
+        assert_eq!(c.add_const(Value::Float(f64::NAN)), nan);
+        let zero = c.add_const(Value::Float(0.0));
+        assert_ne!(c.add_const(Value::Float(-0.0)), zero);
+        assert_ne!(c.add_const(Value::Int(0)), zero);
+        assert_eq!(c.add_name("x"), c.add_name("x"));
+    }
+}
@@ -27,6 +27,11 @@ pub enum Op {
     Swap,
     /// Scarta N elementi dal top (per cleanup di blocchi)  [u8]
     PopN,
+    /// `Rot [u8:n]` — sposta il top sotto gli n-1 elementi che lo precedono:
+    /// `[a, b, c]` con n=3 → `[c, a, b]`
+    Rot,
+    /// Copia il secondo elemento in cima (TOS-1 → push)
+    Over,
 
     // ── Variabili locali ──────────────────────────────────────────────────
     /// `LoadLocal [u8]` — push frame.locals[idx]
@@ -70,10 +75,11 @@ pub enum Op {
     JumpFalse,
     /// `JumpTrue [i16]` — salto se top è truthy, POP
     JumpTrue,
-    /// `JumpFalsePeek [i16]` — salto se top è falsy, NON POP (and/or short-circuit)
-    JumpFalsePeek,
-    /// `JumpTruePeek [i16]` — salto se top è truthy, NON POP
-    JumpTruePeek,
+    /// `JumpFalseOrPop [i16]` — `and`: se top è falsy salta lasciandolo come
+    /// risultato, altrimenti lo scarta e prosegue con l'operando destro
+    JumpFalseOrPop,
+    /// `JumpTrueOrPop [i16]` — `or`: se top è truthy salta lasciandolo, altrimenti pop
+    JumpTrueOrPop,
 
     // ── Funzioni ──────────────────────────────────────────────────────────
     /// `MakeClosure [u16]` — crea Closure da fn_protos[idx]
@@ -105,6 +111,9 @@ pub enum Op {
     /// `GetSlice [u8:flags]` — flags: bit0=has_start, bit1=has_end, bit2=has_step
     /// pop step? end? start? obj, push obj[start:end:step]
     GetSlice,
+    /// `SetSlice [u8:flags]` — flags: bit0=has_start, bit1=has_end
+    /// pop val, end?, start?, obj; sostituisce obj[start:end] con val (lunghezza libera)
+    SetSlice,
     /// `MakeRange [u8:inclusive]` — pop end, pop start, push Range/Array
     MakeRange,
 
@@ -174,6 +183,26 @@ pub enum Op {
     StoreLocal2,
     StoreLocal3,
 
+    // ── Concatenazione in place ──────────────────────────────────────────
+    /// `AddStoreLocal [u8:idx]` — pop r, pop l (copia appena caricata del local),
+    /// local = l + r. Se il local è una Str accoda r nel suo buffer senza riallocare:
+    /// `s += x` in un loop diventa O(n) ammortizzato invece di O(n²).
+    AddStoreLocal,
+    /// `AddStoreGlobal [u16:name]` — come AddStoreLocal, su una globale mutabile
+    AddStoreGlobal,
+
+    // ── Array tipizzati ──────────────────────────────────────────────────
+    /// `TagArray [u16:elem_type_name]` — `let a: Array[T]`: se la VM ha i checked
+    /// arrays attivi e top è un Array, lo registra come Array[T] (peek, non poppa)
+    TagArray,
+
+    // ── Pattern su stringhe ──────────────────────────────────────────────
+    /// `MatchAffix [u16:prefix] [u16:suffix] [i16]` — se top non è una Str con
+    /// quel prefisso e suffisso, jump (peek)
+    MatchAffix,
+    /// `StripAffix [u16:prefix] [u16:suffix]` — pop Str, push la parte centrale
+    StripAffix,
+
     // ── Misc ──────────────────────────────────────────────────────────────
     Nop,
     Halt,
@@ -186,24 +215,32 @@ impl Op {
             Op::Const       => 2,
             Op::Swap        => 0,
             Op::PopN        => 1,
+            Op::Rot         => 1,
             Op::LoadLocal   => 1,
             Op::StoreLocal  => 1,
             Op::LoadUpval   => 1,
             Op::StoreUpval  => 1,
             Op::LoadGlobal  => 2,
             Op::StoreGlobal => 2,
+            Op::AddStoreLocal  => 1,
+            Op::AddStoreGlobal => 2,
+            Op::TagArray       => 2,
+            Op::MatchAffix     => 6,   // [u16 prefix] [u16 suffix] [i16 offset]
+            Op::StripAffix     => 4,   // [u16 prefix] [u16 suffix]
             Op::DefGlobal   => 3,   // [u16 name] [u8 mutable]
             Op::Jump        => 2,
             Op::JumpFalse   => 2,
             Op::JumpTrue    => 2,
-            Op::JumpFalsePeek => 2,
-            Op::JumpTruePeek  => 2,
+            Op::JumpFalseOrPop => 2,
+            Op::JumpTrueOrPop  => 2,
             Op::MakeClosure => 3,   // [u16 proto_idx] [u8 n_upvalues]
             Op::Call        => 1,
             Op::CallMethod  => 3,   // [u16 name] [u8 argc]
             Op::MakeArray   => 2,
             Op::MakeDict    => 2,
             Op::MakeRange   => 1,
+            Op::GetSlice    => 1,
+            Op::SetSlice    => 1,
             Op::GetField    => 2,
             Op::SetField    => 2,
             Op::MakeInstance => 2,
@@ -226,7 +263,7 @@ impl Op {
     pub fn from_u8(b: u8) -> Option<Self> {
         // SAFETY: tutti i valori 0..=N sono varianti valide
         if b <= Op::Halt as u8 {
-            Some(unsafe { std::mem::transmute(b) })
+            Some(unsafe { std::mem::transmute::<u8, Op>(b) })
         } else {
             None
         }
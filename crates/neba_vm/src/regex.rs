@@ -0,0 +1,504 @@
+//! Espressioni regolari per `regex_match`, `regex_find_all` e `regex_replace`.
+//!
+//! Motore a backtracking scritto a mano, su code point (non byte). Sottoinsieme
+//! supportato:
+//!
+//! | Sintassi               | Significato                                     |
+//! |------------------------|-------------------------------------------------|
+//! | `.`                    | qualsiasi carattere tranne `\n`                 |
+//! | `[abc]` `[a-z]` `[^x]` | classe di caratteri (anche negata)              |
+//! | `\d \w \s` `\D \W \S`  | cifra, parola (`[A-Za-z0-9_]`), spazio e negate |
+//! | `\b` `\B`              | confine di parola e non-confine                 |
+//! | `^` `$`                | inizio e fine del testo                         |
+//! | `* + ? {n} {n,} {n,m}` | quantificatori greedy; con `?` finale lazy      |
+//! | `(...)` `(?:...)`      | gruppo con e senza cattura                      |
+//! | `a|b`                  | alternativa                                     |
+//! | `\n \t \r` `\.` ...    | escape di controllo e di metacaratteri          |
+//!
+//! Niente lookaround né backreference. Pattern patologici (`(a|a)*b`) possono
+//! essere esponenziali: i passi di backtracking sono limitati e oltre il
+//! limite la ricerca fallisce con un errore.
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    /// `\d`, `\w`, `\s` dentro una classe (negata con `\D` ecc.)
+    Builtin(char),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    Start,
+    End,
+    WordBoundary(bool),
+    /// Alternative di un gruppo; `Some(i)` se cattura nello slot `i`
+    Group(Vec<Vec<Node>>, Option<usize>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+/// Span (in indici di carattere) dell'intero match e di ogni gruppo.
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+/// Pattern compilato.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Vec<Inst>,
+    /// Registri per le ripetizioni non limitate (controllo iterazioni vuote)
+    regs: usize,
+    /// Gruppi di cattura, escluso il match intero
+    groups: usize,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut p = Parser { chars: pattern.chars().collect(), pos: 0, groups: 0 };
+        let alts = p.alternation()?;
+        if p.pos < p.chars.len() {
+            return Err(format!("regex: unbalanced ')' at {}", p.pos));
+        }
+        let mut c = Compiler::default();
+        c.node(&Node::Group(alts, Some(0)))?;
+        c.emit(Inst::Match)?;
+        Ok(Regex { prog: c.prog, regs: c.regs, groups: p.groups })
+    }
+
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// Primo match che inizia a `from` o dopo.
+    pub fn captures_from(&self, text: &[char], from: usize) -> Result<Option<Captures>, String> {
+        let m = Matcher { prog: &self.prog, text };
+        for start in from..=text.len() {
+            if let Some(slots) = m.run(start, 2 * (self.groups + 1), self.regs)? {
+                return Ok(Some(slots.chunks(2).map(|s| s[0].zip(s[1])).collect()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Tutti i match non sovrapposti, da sinistra a destra.
+    pub fn captures_all(&self, text: &[char]) -> Result<Vec<Captures>, String> {
+        let mut out = Vec::new();
+        let mut from = 0;
+        while from <= text.len() {
+            let Some(caps) = self.captures_from(text, from)? else { break };
+            let (start, end) = caps[0].unwrap_or((from, from));
+            // Un match vuoto non deve bloccare l'avanzamento
+            from = if end > start { end } else { end + 1 };
+            out.push(caps);
+        }
+        Ok(out)
+    }
+}
+
+// ── Parser ────────────────────────────────────────────────────────────────
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn alternation(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alts = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alts.push(self.sequence()?);
+        }
+        Ok(alts)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut seq = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' { break; }
+            let atom = self.atom()?;
+            seq.push(self.quantified(atom)?);
+        }
+        Ok(seq)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let at = self.pos;
+        let c = self.chars[self.pos];
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '[' => self.class()?,
+            '(' => {
+                let slot = if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let alts = self.alternation()?;
+                if self.peek() != Some(')') {
+                    return Err(format!("regex: unclosed '(' at {}", at));
+                }
+                self.pos += 1;
+                Node::Group(alts, slot)
+            }
+            '*' | '+' | '?' => return Err(format!("regex: nothing to repeat at {}", at)),
+            '\\' => match self.escape()? {
+                Escape::Char(c) => Node::Char(c),
+                Escape::Builtin(b) => Node::Class { items: vec![ClassItem::Builtin(b)], negated: false },
+                Escape::Boundary(on) => Node::WordBoundary(on),
+            },
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Escape, String> {
+        let Some(c) = self.peek() else {
+            return Err("regex: trailing '\\'".into());
+        };
+        self.pos += 1;
+        Ok(match c {
+            'd' | 'w' | 's' | 'D' | 'W' | 'S' => Escape::Builtin(c),
+            'b' => Escape::Boundary(true),
+            'B' => Escape::Boundary(false),
+            'n' => Escape::Char('\n'),
+            't' => Escape::Char('\t'),
+            'r' => Escape::Char('\r'),
+            c if c.is_ascii_alphanumeric() => return Err(format!("regex: unknown escape '\\{}'", c)),
+            c => Escape::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let start = self.pos - 1;
+        let negated = self.peek() == Some('^');
+        if negated { self.pos += 1; }
+        let mut items = Vec::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(format!("regex: unclosed '[' at {}", start));
+            };
+            self.pos += 1;
+            // `]` subito dopo `[` o `[^` è un carattere letterale
+            if c == ']' && !items.is_empty() { break; }
+            let lo = match c {
+                '\\' => match self.escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Builtin(b) => { items.push(ClassItem::Builtin(b)); continue; }
+                    Escape::Boundary(_) => return Err("regex: '\\b' inside a class".into()),
+                },
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') {
+                self.pos += 1;
+                let mut hi = self.chars[self.pos];
+                self.pos += 1;
+                if hi == '\\' {
+                    match self.escape()? {
+                        Escape::Char(c) => hi = c,
+                        _ => return Err("regex: invalid class range".into()),
+                    }
+                }
+                if hi < lo {
+                    return Err(format!("regex: invalid class range '{}-{}'", lo, hi));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Char(lo));
+            }
+        }
+        Ok(Node::Class { items, negated })
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => { self.pos += 1; (0, None) }
+            Some('+') => { self.pos += 1; (1, None) }
+            Some('?') => { self.pos += 1; (0, Some(1)) }
+            Some('{') => match self.braces() {
+                Some(bounds) => bounds,
+                // `{` che non è un quantificatore resta letterale
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        if matches!(atom, Node::Start | Node::End | Node::WordBoundary(_)) {
+            return Err(format!("regex: nothing to repeat at {}", self.pos - 1));
+        }
+        if max.is_some_and(|m| m < min) {
+            return Err(format!("regex: invalid repetition {{{},{}}}", min, max.unwrap_or(0)));
+        }
+        let greedy = self.peek() != Some('?');
+        if !greedy { self.pos += 1; }
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    /// `{n}`, `{n,}`, `{n,m}`; `None` (senza consumare) se il testo non è un quantificatore.
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.pos + 1..].iter().take_while(|&&c| c != '}').collect();
+        if self.pos + 1 + rest.len() >= self.chars.len() { return None; }
+        let bounds = match rest.split_once(',') {
+            None => { let n = rest.parse().ok()?; (n, Some(n)) }
+            Some((lo, "")) => (lo.parse().ok()?, None),
+            Some((lo, hi)) => (lo.parse().ok()?, Some(hi.parse().ok()?)),
+        };
+        self.pos += rest.len() + 2;
+        Some(bounds)
+    }
+}
+
+enum Escape {
+    Char(char),
+    Builtin(char),
+    Boundary(bool),
+}
+
+// ── Compilazione ──────────────────────────────────────────────────────────
+
+/// Istruzione del matcher: il pattern diventa un programma lineare eseguito
+/// con uno stack di backtracking esplicito, così la lunghezza dell'input non
+/// consuma stack nativo.
+#[derive(Debug, Clone)]
+enum Inst {
+    /// Un solo carattere (`Node::Char`, `Node::Any`, `Node::Class`)
+    One(Node),
+    Start,
+    End,
+    WordBoundary(bool),
+    /// Salva la posizione nello slot di cattura (`2*i` inizio, `2*i+1` fine)
+    Save(usize),
+    /// Prova `0`, in backtracking `1`
+    Split(usize, usize),
+    Jmp(usize),
+    /// Ripetizione di un atomo da un carattere, senza un'istruzione per iterazione
+    RepeatOne { node: Node, min: usize, max: Option<usize>, greedy: bool },
+    /// Ricorda la posizione di inizio iterazione nel registro
+    Mark(usize),
+    /// Fallisce se l'iterazione iniziata con `Mark` non ha consumato nulla
+    Progress(usize),
+    Match,
+}
+
+/// Dimensione massima del programma (i quantificatori `{n,m}` lo espandono).
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Default)]
+struct Compiler {
+    prog: Vec<Inst>,
+    regs: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.prog.len() >= MAX_PROGRAM {
+            return Err("regex: pattern too large".into());
+        }
+        self.prog.push(inst);
+        Ok(self.prog.len() - 1)
+    }
+
+    fn seq(&mut self, nodes: &[Node]) -> Result<(), String> {
+        nodes.iter().try_for_each(|n| self.node(n))
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(_) | Node::Any | Node::Class { .. } => { self.emit(Inst::One(node.clone()))?; }
+            Node::Start => { self.emit(Inst::Start)?; }
+            Node::End => { self.emit(Inst::End)?; }
+            Node::WordBoundary(on) => { self.emit(Inst::WordBoundary(*on))?; }
+            Node::Group(alts, slot) => {
+                if let Some(i) = slot { self.emit(Inst::Save(2 * i))?; }
+                let mut jumps = Vec::new();
+                for (n, alt) in alts.iter().enumerate() {
+                    let split = (n + 1 < alts.len()).then(|| self.emit(Inst::Split(0, 0))).transpose()?;
+                    self.seq(alt)?;
+                    if let Some(split) = split {
+                        jumps.push(self.emit(Inst::Jmp(0))?);
+                        self.prog[split] = Inst::Split(split + 1, self.prog.len());
+                    }
+                }
+                let end = self.prog.len();
+                for j in jumps { self.prog[j] = Inst::Jmp(end); }
+                if let Some(i) = slot { self.emit(Inst::Save(2 * i + 1))?; }
+            }
+            Node::Repeat { node: inner, min, max, greedy } => {
+                if matches!(**inner, Node::Char(_) | Node::Any | Node::Class { .. }) {
+                    self.emit(Inst::RepeatOne { node: (**inner).clone(), min: *min, max: *max, greedy: *greedy })?;
+                    return Ok(());
+                }
+                for _ in 0..*min { self.node(inner)?; }
+                let split = |greedy: bool, body: usize, exit: usize| {
+                    if greedy { Inst::Split(body, exit) } else { Inst::Split(exit, body) }
+                };
+                match max {
+                    None => {
+                        // L: split(body, exit); body: mark; inner; progress; jmp L
+                        let reg = self.regs;
+                        self.regs += 1;
+                        let top = self.emit(Inst::Split(0, 0))?;
+                        self.emit(Inst::Mark(reg))?;
+                        self.node(inner)?;
+                        self.emit(Inst::Progress(reg))?;
+                        self.emit(Inst::Jmp(top))?;
+                        self.prog[top] = split(*greedy, top + 1, self.prog.len());
+                    }
+                    Some(max) => {
+                        // Iterazioni opzionali annidate: ognuna può uscire fino in fondo
+                        let splits = (*min..*max).map(|_| {
+                            let at = self.emit(Inst::Split(0, 0))?;
+                            self.node(inner)?;
+                            Ok(at)
+                        }).collect::<Result<Vec<_>, String>>()?;
+                        let exit = self.prog.len();
+                        for at in splits { self.prog[at] = split(*greedy, at + 1, exit); }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// ── Matcher ───────────────────────────────────────────────────────────────
+
+/// Passi massimi per ogni posizione di partenza: oltre, il pattern è
+/// considerato patologico e la ricerca fallisce con un errore.
+const MAX_STEPS: usize = 1_000_000;
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn builtin_matches(b: char, c: char) -> bool {
+    match b {
+        'd' => c.is_ascii_digit(),
+        'w' => is_word(c),
+        's' => c.is_whitespace(),
+        'D' => !c.is_ascii_digit(),
+        'W' => !is_word(c),
+        _   => !c.is_whitespace(),
+    }
+}
+
+/// Punto di ripresa sullo stack di backtracking.
+enum Frame {
+    /// Riprende da `pc` alla posizione `pos`
+    Alt(usize, usize),
+    /// Annulla una `Save`
+    Slot(usize, Option<usize>),
+    /// Annulla una `Mark`
+    Reg(usize, usize),
+    /// Ripetizione di un carattere: `n` iterazioni da `pos`, prossima da provare
+    Repeat { pc: usize, pos: usize, n: usize, last: usize, greedy: bool },
+}
+
+struct Matcher<'t> {
+    prog: &'t [Inst],
+    text: &'t [char],
+}
+
+impl Matcher<'_> {
+    /// Vero se `node` (un solo carattere) accetta il carattere in `pos`.
+    fn single(&self, node: &Node, pos: usize) -> bool {
+        let Some(&c) = self.text.get(pos) else { return false };
+        match node {
+            Node::Char(x) => c == *x,
+            Node::Any => c != '\n',
+            Node::Class { items, negated } => {
+                let hit = items.iter().any(|item| match item {
+                    ClassItem::Char(x) => c == *x,
+                    ClassItem::Range(lo, hi) => (*lo..=*hi).contains(&c),
+                    ClassItem::Builtin(b) => builtin_matches(*b, c),
+                });
+                hit != *negated
+            }
+            _ => false,
+        }
+    }
+
+    fn at_boundary(&self, pos: usize) -> bool {
+        let before = pos > 0 && is_word(self.text[pos - 1]);
+        let after = self.text.get(pos).is_some_and(|&c| is_word(c));
+        before != after
+    }
+
+    /// Esegue il programma da `start`; `Ok(Some(slot))` al primo match.
+    fn run(&self, start: usize, slots: usize, regs: usize) -> Result<Option<Vec<Option<usize>>>, String> {
+        let mut caps = vec![None; slots];
+        let mut marks = vec![0; regs];
+        let mut stack = vec![Frame::Alt(0, start)];
+        let mut steps = 0;
+        'backtrack: while let Some(frame) = stack.pop() {
+            let (mut pc, mut pos) = match frame {
+                Frame::Alt(pc, pos) => (pc, pos),
+                Frame::Slot(i, old) => { caps[i] = old; continue }
+                Frame::Reg(i, old) => { marks[i] = old; continue }
+                Frame::Repeat { pc, pos, n, last, greedy } => {
+                    // Greedy scende verso il minimo, lazy sale verso il massimo
+                    let next = if greedy { n.checked_sub(1) } else { Some(n + 1) };
+                    if let Some(next) = next.filter(|&m| if greedy { m >= last } else { m <= last }) {
+                        stack.push(Frame::Repeat { pc, pos, n: next, last, greedy });
+                    }
+                    (pc, pos + n)
+                }
+            };
+            loop {
+                steps += 1;
+                if steps > MAX_STEPS {
+                    return Err("regex: pattern too complex for this input (backtracking limit)".into());
+                }
+                match &self.prog[pc] {
+                    Inst::One(node) => {
+                        if !self.single(node, pos) { continue 'backtrack; }
+                        pos += 1;
+                    }
+                    Inst::Start => if pos != 0 { continue 'backtrack },
+                    Inst::End => if pos != self.text.len() { continue 'backtrack },
+                    Inst::WordBoundary(on) => if self.at_boundary(pos) != *on { continue 'backtrack },
+                    Inst::Save(i) => {
+                        stack.push(Frame::Slot(*i, caps[*i]));
+                        caps[*i] = Some(pos);
+                    }
+                    Inst::Split(first, second) => {
+                        stack.push(Frame::Alt(*second, pos));
+                        pc = *first;
+                        continue;
+                    }
+                    Inst::Jmp(to) => { pc = *to; continue; }
+                    Inst::RepeatOne { node, min, max, greedy } => {
+                        let limit = max.unwrap_or(usize::MAX);
+                        let mut n = 0;
+                        while n < limit && self.single(node, pos + n) { n += 1; }
+                        if n < *min { continue 'backtrack; }
+                        let (first, last) = if *greedy { (n, *min) } else { (*min, n) };
+                        if first != last {
+                            let next = if *greedy { first - 1 } else { first + 1 };
+                            stack.push(Frame::Repeat { pc: pc + 1, pos, n: next, last, greedy: *greedy });
+                        }
+                        pos += first;
+                    }
+                    Inst::Mark(i) => {
+                        stack.push(Frame::Reg(*i, marks[*i]));
+                        marks[*i] = pos;
+                    }
+                    // Un'iterazione vuota non fa progressi: si ferma
+                    Inst::Progress(i) => if marks[*i] == pos { continue 'backtrack },
+                    Inst::Match => return Ok(Some(caps)),
+                }
+                pc += 1;
+            }
+        }
+        Ok(None)
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
 use rustc_hash::FxHashMap;
-use crate::value::{Value, TypedArrayData, Dtype, NdArray};
+use crate::regex::Regex;
+use crate::value::{Value, TypedArrayData, Dtype, check_mutable, check_elem};
 pub fn register_globals(globals: &mut FxHashMap<String, (Value, bool)>) {
     macro_rules! reg {
         ($name:expr, $fn:expr) => {
@@ -24,8 +24,28 @@ pub fn register_globals(globals: &mut FxHashMap<String, (Value, bool)>) {
     reg!("push",     neba_push);
     reg!("pop",      neba_pop);
     reg!("assert",   neba_assert);
+    reg!("assert_eq", neba_assert_eq);
+    reg!("assert_raises", assert_raises_stub);
+    // Combinatori con callback: intercettati in Op::Call come map/filter/reduce
+    reg!("and_then", combinator_stub);
+    reg!("or_else",  combinator_stub);
+    reg!("map_err",  combinator_stub);
+    reg!("ok_or",    neba_ok_or);
+    // Regex (sottoinsieme documentato in regex.rs); pattern non valido → Err(msg)
+    reg!("regex_match",    neba_regex_match);
+    reg!("regex_find_all", neba_regex_find_all);
+    reg!("regex_replace",  neba_regex_replace);
+    reg!("hash",       neba_hash);
+    reg!("sha256_hex", neba_sha256_hex);
+    // JSON (vedi json.rs); documento non valido → Err(msg)
+    reg!("to_json",    neba_to_json);
+    reg!("from_json",  neba_from_json);
+    reg!("approx_eq", math_approx_eq);
+    reg!("deep_eq",  neba_deep_eq);
+    reg!("freeze",   neba_freeze);
     reg!("clock",    neba_clock);
     reg!("time_ms",  neba_time_ms);
+    reg!("sleep",    neba_sleep);
     // ── Globali aggiuntivi (v0.2.15) ──────────────────────────────────────
     reg!("sum",       neba_sum);
     reg!("zip",       neba_zip);
@@ -48,6 +68,7 @@ pub fn register_globals(globals: &mut FxHashMap<String, (Value, bool)>) {
     // ── List (Array) ──────────────────────────────────────────────────────
     reg!("append",   neba_append);
     reg!("remove",   neba_remove);
+    reg!("clear",    neba_clear);
     reg!("contains", neba_contains);
     reg!("insert",   neba_insert);
     reg!("sort",     neba_sort);
@@ -104,9 +125,13 @@ pub fn register_globals(globals: &mut FxHashMap<String, (Value, bool)>) {
     reg!("pad_right",  str_pad_right);
     reg!("is_digit",   str_is_digit);
     reg!("is_alpha",   str_is_alpha);
+    reg!("byte_len",   str_byte_len);
     // ── TypedArray (v0.2.6) ───────────────────────────────────────────────
     register_typed_array_globals(globals);
     register_nd_module(globals);
+    // ── Float speciali IEEE (`-inf` è la negazione unaria di `inf`) ──────
+    globals.insert("inf".to_string(), (Value::Float(f64::INFINITY), false));
+    globals.insert("nan".to_string(), (Value::Float(f64::NAN),      false));
     // ── Stdlib modules (v0.2.11) ─────────────────────────────────────────
     globals.insert("math".to_string(),        (make_math_module(),        false));
     globals.insert("string".to_string(),      (make_string_module(),      false));
@@ -121,6 +146,25 @@ pub fn register_globals(globals: &mut FxHashMap<String, (Value, bool)>) {
     reg!("reduce", hof_reduce_stub);
 }
 
+/// Builtin di testo: un argomento Char vi arriva come la Str del suo carattere
+/// (vedi `Vm::call_native`). Gli altri ricevono il Char così com'è.
+pub const STR_BUILTINS: &[&str] = &[
+    "len", "int", "float", "ord", "join", "contains", "count", "sha256_hex", "from_json",
+    "regex_match", "regex_find_all", "regex_replace",
+    "upper", "lower", "strip", "lstrip", "rstrip", "split", "replace", "find",
+    "startswith", "starts_with", "endswith", "ends_with", "capitalize", "title",
+    "format", "zfill", "index_of", "char_at", "pad_left", "pad_right",
+    "is_digit", "is_alpha", "byte_len",
+];
+
+/// Globali rimosse da `Vm::sandboxed`: tutto ciò che tocca stdin/stdout o il
+/// filesystem. Builtin futuri di rete o ambiente vanno aggiunti qui.
+pub const SANDBOX_DISABLED: &[&str] = &["print", "println", "input", "io", "sleep"];
+
+pub fn remove_sandboxed(globals: &mut FxHashMap<String, (Value, bool)>) {
+    for name in SANDBOX_DISABLED { globals.remove(*name); }
+}
+
 fn neba_print(args: &[Value]) -> Result<Value, String> {
     print!("{}", args.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "));
     Ok(Value::None)
@@ -142,10 +186,7 @@ fn neba_len(args: &[Value]) -> Result<Value, String> {
         Some(Value::Str(s))        => Ok(Value::Int(s.chars().count() as i64)),
         Some(Value::Dict(d))       => Ok(Value::Int(d.borrow().len() as i64)),
         Some(Value::TypedArray(t)) => Ok(Value::Int(t.borrow().len() as i64)),
-        Some(Value::IntRange(s, e, inc)) => {
-            let len = if *inc { (e - s + 1).max(0) } else { (e - s).max(0) };
-            Ok(Value::Int(len))
-        }
+        Some(Value::IntRange(s, e, inc)) => Ok(Value::Int(crate::value::range_len(*s, *e, *inc))),
         Some(v) => Err(format!("len() not supported for {}", v.type_name())),
         None    => Err("len() requires 1 argument".into()),
     }
@@ -174,7 +215,7 @@ fn neba_float(args: &[Value]) -> Result<Value, String> {
     }
 }
 fn neba_bool(args: &[Value]) -> Result<Value, String> {
-    Ok(Value::Bool(args.first().map_or(false, |v| v.is_truthy())))
+    Ok(Value::Bool(args.first().is_some_and(|v| v.is_truthy())))
 }
 fn neba_type(args: &[Value]) -> Result<Value, String> {
     Ok(Value::str(args.first().map_or("None", |v| v.type_name())))
@@ -248,16 +289,24 @@ fn neba_range(args: &[Value]) -> Result<Value, String> {
 }
 fn neba_push(args: &[Value]) -> Result<Value, String> {
     match args {
-        [Value::Array(arr), val] => { arr.borrow_mut().push(val.clone()); Ok(Value::None) }
+        [Value::Array(arr), val] => { check_mutable(arr, "push")?; check_elem(arr, val, "push")?; arr.borrow_mut().push(val.clone()); Ok(Value::None) }
         _ => Err("push(array, value) requires Array and value".into()),
     }
 }
 fn neba_pop(args: &[Value]) -> Result<Value, String> {
     match args.first() {
-        Some(Value::Array(arr)) => arr.borrow_mut().pop().ok_or_else(|| "pop() on empty array".into()),
+        Some(Value::Array(arr)) => { check_mutable(arr, "pop")?; arr.borrow_mut().pop().ok_or_else(|| "pop() on empty array".into()) }
         _ => Err("pop(array) requires an Array".into()),
     }
 }
+/// freeze(array) → Array  (congela l'array in-place e lo restituisce: le letture
+/// funzionano, push/pop/assegnazione per indice falliscono)
+fn neba_freeze(args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Array(arr)] => { crate::value::freeze_array(arr); Ok(Value::Array(arr.clone())) }
+        _ => Err("freeze(array) requires an Array".into()),
+    }
+}
 fn neba_assert(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(v) if v.is_truthy() => Ok(Value::None),
@@ -268,6 +317,21 @@ fn neba_assert(args: &[Value]) -> Result<Value, String> {
         None => Err("assert() requires 1 argument".into()),
     }
 }
+/// assert_eq(a, b, msg?) — come assert(a == b), con i due valori nel messaggio
+fn neba_assert_eq(args: &[Value]) -> Result<Value, String> {
+    match args {
+        [a, b] | [a, b, _] if a == b => Ok(Value::None),
+        [a, b] => Err(format!("assertion failed: {} != {}", repr_str(a), repr_str(b))),
+        [a, b, msg] => Err(format!("{}: {} != {}", msg, repr_str(a), repr_str(b))),
+        _ => Err("assert_eq(a, b, msg?) requires 2 or 3 arguments".into()),
+    }
+}
+fn repr_str(v: &Value) -> String {
+    match v {
+        Value::Str(s) => format!("\"{}\"", s),
+        v => v.to_string(),
+    }
+}
 fn neba_clock(_args: &[Value]) -> Result<Value, String> {
     use std::time::{SystemTime, UNIX_EPOCH};
     let secs = SystemTime::now()
@@ -277,6 +341,17 @@ fn neba_clock(_args: &[Value]) -> Result<Value, String> {
     Ok(Value::Float(secs))
 }
 
+/// sleep(ms) — blocca il thread: nella VM `spawn` è sincrono, non c'è altro da eseguire
+fn neba_sleep(args: &[Value]) -> Result<Value, String> {
+    let ms = match args {
+        [Value::Int(n)] if *n >= 0 => *n as f64,
+        [Value::Float(f)] if *f >= 0.0 && f.is_finite() => *f,
+        _ => return Err("sleep(ms) requires a non-negative number".into()),
+    };
+    std::thread::sleep(std::time::Duration::from_secs_f64(ms / 1000.0));
+    Ok(Value::None)
+}
+
 fn neba_time_ms(_args: &[Value]) -> Result<Value, String> {
     use std::time::{SystemTime, UNIX_EPOCH};
     let ms = SystemTime::now()
@@ -293,23 +368,18 @@ fn neba_sum(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(Value::TypedArray(t)) => {
             let d = t.borrow();
-            return Ok(match &*d {
+            Ok(match &*d {
                 TypedArrayData::Float64(v) => Value::Float(v.iter().sum()),
                 TypedArrayData::Float32(v) => Value::Float(v.iter().map(|&x| x as f64).sum()),
                 TypedArrayData::Int64(v)   => Value::Int(v.iter().sum()),
                 TypedArrayData::Int32(v)   => Value::Int(v.iter().map(|&x| x as i64).sum()),
-            });
+            })
         }
         Some(Value::IntRange(s, e, inc)) => {
             // Formula di Gauss — O(1), zero allocazioni
-            let (s, e, inc) = (*s, *e, *inc);
-            return Ok(if inc {
-                let n = (e - s + 1).max(0);
-                Value::Int(n * (s + e) / 2)
-            } else {
-                let n = (e - s).max(0);
-                if n == 0 { Value::Int(0) } else { Value::Int(n * (s + (e - 1)) / 2) }
-            });
+            let n = crate::value::range_len(*s, *e, *inc);
+            if n == 0 { return Ok(Value::Int(0)); }
+            Ok(Value::Int(n * (*s + crate::value::range_nth(*s, n - 1)) / 2))
         }
         Some(Value::Array(a)) => {
             let arr = a.borrow();
@@ -380,8 +450,7 @@ fn neba_sorted(args: &[Value]) -> Result<Value, String> {
 
 fn neba_repr(args: &[Value]) -> Result<Value, String> {
     match args.first() {
-        Some(Value::Str(s))  => Ok(Value::str(format!("\"{}\"", s))),
-        Some(v)              => Ok(Value::str(v.to_string())),
+        Some(v)              => Ok(Value::str(repr_str(v))),
         None                 => Err("repr() requires 1 argument".into()),
     }
 }
@@ -389,13 +458,11 @@ fn neba_repr(args: &[Value]) -> Result<Value, String> {
 fn neba_find_arr(args: &[Value]) -> Result<Value, String> {
     match args {
         [Value::Array(a), Value::Closure(f)] => {
-            for item in a.borrow().iter() {
-                // call f(item) — we need the VM for this, so we use a workaround:
-                // store the closure call result by matching the closure's chunk
-                let _ = f; let _ = item;
-                return Err("find() with closure requires VM call — use filter()[0] instead".into());
-            }
-            Ok(Value::None)
+            // call f(item) richiede la VM: find è intercettato in Op::Call,
+            // qui si arriva solo se chiamato fuori dalla VM.
+            let _ = f;
+            if a.borrow().is_empty() { return Ok(Value::None); }
+            Err("find() with closure requires VM call — use filter()[0] instead".into())
         }
         _ => Err("find(array, fn) requires Array and Fn".into()),
     }
@@ -423,7 +490,7 @@ fn neba_chr(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(Value::Int(n)) => {
             let n = *n;
-            if n < 0 || n > 0x10FFFF {
+            if !(0..=0x10FFFF).contains(&n) {
                 return Err(format!("chr(): codepoint {} out of range 0–1114111", n));
             }
             match char::from_u32(n as u32) {
@@ -452,7 +519,7 @@ fn neba_ord(args: &[Value]) -> Result<Value, String> {
 fn neba_copy(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(Value::Array(a))      => Ok(Value::array(a.borrow().clone())),
-        Some(Value::Dict(d))       => Ok(Value::dict_from_map(d.borrow().clone())),
+        Some(Value::Dict(d))       => Ok(Value::Dict(std::rc::Rc::new(std::cell::RefCell::new(d.borrow().clone())))),
         Some(Value::TypedArray(t)) => Ok(Value::typed_array(t.borrow().clone())),
         Some(v) => Err(format!("copy() not supported for {}", v.type_name())),
         None    => Err("copy() requires 1 argument".into()),
@@ -540,7 +607,7 @@ fn neba_del_key(args: &[Value]) -> Result<Value, String> {
 /// append(array, value) → None  (alias di push, nome più comune)
 fn neba_append(args: &[Value]) -> Result<Value, String> {
     match args {
-        [Value::Array(arr), val] => { arr.borrow_mut().push(val.clone()); Ok(Value::None) }
+        [Value::Array(arr), val] => { check_mutable(arr, "append")?; check_elem(arr, val, "append")?; arr.borrow_mut().push(val.clone()); Ok(Value::None) }
         _ => Err("append(array, value) requires Array and value".into()),
     }
 }
@@ -549,6 +616,7 @@ fn neba_append(args: &[Value]) -> Result<Value, String> {
 fn neba_remove(args: &[Value]) -> Result<Value, String> {
     match args {
         [Value::Array(arr), val] => {
+            check_mutable(arr, "remove")?;
             let mut a = arr.borrow_mut();
             if let Some(pos) = a.iter().position(|v| v == val) {
                 a.remove(pos);
@@ -561,6 +629,15 @@ fn neba_remove(args: &[Value]) -> Result<Value, String> {
     }
 }
 
+/// clear(array | dict) → None  (svuota in-place: gli alias vedono il contenitore vuoto)
+fn neba_clear(args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Array(arr)] => { check_mutable(arr, "clear")?; arr.borrow_mut().clear(); Ok(Value::None) }
+        [Value::Dict(d)]    => { d.borrow_mut().clear(); Ok(Value::None) }
+        _ => Err("clear(container) requires an Array or Dict".into()),
+    }
+}
+
 /// contains(array, value) → Bool
 fn neba_contains(args: &[Value]) -> Result<Value, String> {
     match args {
@@ -574,6 +651,8 @@ fn neba_contains(args: &[Value]) -> Result<Value, String> {
 fn neba_insert(args: &[Value]) -> Result<Value, String> {
     match args {
         [Value::Array(arr), Value::Int(idx), val] => {
+            check_mutable(arr, "insert")?;
+            check_elem(arr, val, "insert")?;
             let mut a = arr.borrow_mut();
             let len = a.len() as i64;
             let i = if *idx < 0 { (len + idx).max(0) as usize } else { (*idx as usize).min(a.len()) };
@@ -588,6 +667,7 @@ fn neba_insert(args: &[Value]) -> Result<Value, String> {
 fn neba_sort(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(Value::Array(arr)) => {
+            check_mutable(arr, "sort")?;
             let mut a = arr.borrow_mut();
             a.sort_by(|x, y| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal));
             Ok(Value::None)
@@ -600,6 +680,7 @@ fn neba_sort(args: &[Value]) -> Result<Value, String> {
 fn neba_reverse(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(Value::Array(arr)) => {
+            check_mutable(arr, "reverse")?;
             arr.borrow_mut().reverse();
             Ok(Value::None)
         }
@@ -662,7 +743,7 @@ fn neba_merge_dict(args: &[Value]) -> Result<Value, String> {
     match args {
         [Value::Dict(a), Value::Dict(b)] => {
             let mut result = a.borrow().clone();
-            for (k, v) in b.borrow().iter() { result.insert(k.clone(), v.clone()); }
+            result.merge(&b.borrow());
             Ok(Value::Dict(std::rc::Rc::new(std::cell::RefCell::new(result))))
         }
         _ => Err("merge(dict1, dict2) requires two Dicts".into()),
@@ -919,7 +1000,7 @@ fn ta_ones(args: &[Value]) -> Result<Value, String> {
     let all_ints = !args.is_empty() && args.iter().all(|v| matches!(v, Value::Int(_)));
     if args.len() >= 2 && all_ints {
         let shape: Vec<usize> = args.iter().map(|v| if let Value::Int(n) = v { *n as usize } else { 1 }).collect();
-        let mut nd = crate::value::NdArray::zeros(shape, crate::value::Dtype::Float64);
+        let nd = crate::value::NdArray::zeros(shape, crate::value::Dtype::Float64);
         for i in 0..nd.size() { nd.set_flat(i, Value::Float(1.0)).unwrap(); }
         return Ok(Value::nd_array(nd));
     }
@@ -966,30 +1047,6 @@ fn ta_linspace(args: &[Value]) -> Result<Value, String> {
 
 // ── Operazioni ────────────────────────────────────────────────────────────
 
-/// sum(ta) → valore scalare (somma di tutti gli elementi)
-fn ta_sum(args: &[Value]) -> Result<Value, String> {
-    match args.first() {
-        Some(Value::TypedArray(t)) => {
-            let d = t.borrow();
-            Ok(match &*d {
-                TypedArrayData::Float64(v) => Value::Float(v.iter().sum()),
-                TypedArrayData::Float32(v) => Value::Float(v.iter().map(|&x| x as f64).sum()),
-                TypedArrayData::Int64(v)   => Value::Int(v.iter().sum()),
-                TypedArrayData::Int32(v)   => Value::Int(v.iter().map(|&x| x as i64).sum()),
-            })
-        }
-        Some(Value::Array(a)) => {
-            // Fallback per Array normale
-            let mut total = 0.0f64;
-            for v in a.borrow().iter() {
-                total += v.as_float().ok_or_else(|| format!("sum: non-numeric element {}", v.type_name()))?;
-            }
-            Ok(Value::Float(total))
-        }
-        _ => Err("sum() requires TypedArray or Array".into()),
-    }
-}
-
 /// mean(ta) → Float64 (media)
 fn ta_mean(args: &[Value]) -> Result<Value, String> {
     match args.first() {
@@ -1074,7 +1131,7 @@ fn parse_size_dtype(args: &[Value], default_dtype: &str) -> Result<(usize, Strin
     match args {
         [Value::Int(n)] => Ok((*n as usize, default_dtype.to_string())),
         [Value::Int(n), Value::Str(dtype)] => Ok((*n as usize, dtype.as_ref().clone())),
-        _ => Err(format!("expected (n: Int) or (n: Int, dtype: Str)")),
+        _ => Err("expected (n: Int) or (n: Int, dtype: Str)".to_string()),
     }
 }
 
@@ -1119,6 +1176,7 @@ pub fn make_math_module() -> Value {
         entry("is_nan", math_isnan),    // alias v0.2.30
         entry("is_inf", math_isinf),    // alias v0.2.30
         entry("is_finite", math_isfinite), // v0.2.30
+        entry("approx_eq", math_approx_eq),
         entry("degrees",math_degrees),
         entry("radians",math_radians),
         entry("hypot",  math_hypot),
@@ -1202,12 +1260,12 @@ fn math_tan(args: &[Value]) -> Result<Value, String> {
 }
 fn math_asin(args: &[Value]) -> Result<Value, String> {
     let x = as_f64(args.first().ok_or("asin() requires 1 argument")?, "asin")?;
-    if x < -1.0 || x > 1.0 { return Err(format!("asin() domain error: {}", x)); }
+    if !(-1.0..=1.0).contains(&x) { return Err(format!("asin() domain error: {}", x)); }
     Ok(Value::Float(x.asin()))
 }
 fn math_acos(args: &[Value]) -> Result<Value, String> {
     let x = as_f64(args.first().ok_or("acos() requires 1 argument")?, "acos")?;
-    if x < -1.0 || x > 1.0 { return Err(format!("acos() domain error: {}", x)); }
+    if !(-1.0..=1.0).contains(&x) { return Err(format!("acos() domain error: {}", x)); }
     Ok(Value::Float(x.acos()))
 }
 fn math_atan(args: &[Value]) -> Result<Value, String> {
@@ -1285,6 +1343,64 @@ fn math_lcm(args: &[Value]) -> Result<Value, String> {
         _ => Err("lcm(a, b) requires 2 Int arguments".into()),
     }
 }
+/// Tolleranza relativa di default di `approx_eq`.
+const APPROX_EQ_EPS: f64 = 1e-9;
+
+/// `approx_eq(a, b, eps = 1e-9)` — uguaglianza con tolleranza relativa:
+/// `|a - b| <= eps * max(1, |a|, |b|)`. NaN non è mai uguale a nulla;
+/// un infinito è uguale solo a sé stesso.
+fn math_approx_eq(args: &[Value]) -> Result<Value, String> {
+    let (a, b, eps) = match args {
+        [a, b]      => (as_f64(a, "approx_eq")?, as_f64(b, "approx_eq")?, APPROX_EQ_EPS),
+        [a, b, eps] => (as_f64(a, "approx_eq")?, as_f64(b, "approx_eq")?, as_f64(eps, "approx_eq")?),
+        _ => return Err("approx_eq(a, b, eps?) requires 2 or 3 arguments".into()),
+    };
+    if a == b { return Ok(Value::Bool(true)); }
+    if !a.is_finite() || !b.is_finite() { return Ok(Value::Bool(false)); }
+    Ok(Value::Bool((a - b).abs() <= eps * 1f64.max(a.abs()).max(b.abs())))
+}
+/// `deep_eq(a, b)` — uguaglianza strutturale che ignora `__eq__`: confronta
+/// Array, Dict, TypedArray/NdArray per contenuto e le Instance campo per campo.
+fn neba_deep_eq(args: &[Value]) -> Result<Value, String> {
+    match args {
+        [a, b] => Ok(Value::Bool(deep_eq(a, b))),
+        _ => Err("deep_eq(a, b) requires 2 arguments".into()),
+    }
+}
+
+fn deep_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Array(x), Value::Array(y)) => {
+            if std::rc::Rc::ptr_eq(x, y) { return true; }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| deep_eq(a, b))
+        }
+        (Value::Dict(x), Value::Dict(y)) => {
+            if std::rc::Rc::ptr_eq(x, y) { return true; }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && x.iter().all(|(k, v)| y.get(k).is_some_and(|w| deep_eq(v, w)))
+        }
+        (Value::TypedArray(x), Value::TypedArray(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            x.len() == y.len() && (0..x.len()).all(|i| x.get(i) == y.get(i))
+        }
+        (Value::NdArray(x), Value::NdArray(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            x.shape == y.shape && (0..x.size()).all(|i| x.get_flat(i) == y.get_flat(i))
+        }
+        (Value::Instance(x), Value::Instance(y)) => {
+            if std::rc::Rc::ptr_eq(x, y) { return true; }
+            let (x, y) = (x.borrow(), y.borrow());
+            x.class_name == y.class_name && x.fields.len() == y.fields.len()
+                && x.fields.iter().all(|(k, v)| y.fields.get(k).is_some_and(|w| deep_eq(v, w)))
+        }
+        // i metodi sono closure create per istanza: conta la funzione, non la cattura
+        (Value::Closure(x), Value::Closure(y)) => std::rc::Rc::ptr_eq(&x.proto.chunk, &y.proto.chunk),
+        (Value::NativeFn(x, _), Value::NativeFn(y, _)) => x == y,
+        (Value::Some_(x), Value::Some_(y)) | (Value::Ok_(x), Value::Ok_(y)) | (Value::Err_(x), Value::Err_(y)) => deep_eq(x, y),
+        _ => a == b,
+    }
+}
 fn math_isnan(args: &[Value]) -> Result<Value, String> {
     match args.first() {
         Some(Value::Float(f)) => Ok(Value::Bool(f.is_nan())),
@@ -1363,6 +1479,7 @@ pub fn make_string_module() -> Value {
         entry("contains",   str_contains_fn),
         entry("join",       str_join),
         entry("is_empty",   str_is_empty),
+        entry("byte_len",   str_byte_len),
         entry("index",      str_find),    // alias
         entry("format",     str_format),
         // v0.2.18
@@ -1405,15 +1522,15 @@ fn str_split(args: &[Value]) -> Result<Value, String> {
         }
         [Value::Str(s), Value::None] => {
             // explicit None separator → split by whitespace
-            let parts: Vec<Value> = s.split_whitespace().map(|p| Value::str(p)).collect();
+            let parts: Vec<Value> = s.split_whitespace().map(Value::str).collect();
             Ok(Value::array(parts))
         }
         [Value::Str(s), Value::Str(sep)] => {
-            let parts: Vec<Value> = s.split(sep.as_str()).map(|p| Value::str(p)).collect();
+            let parts: Vec<Value> = s.split(sep.as_str()).map(Value::str).collect();
             Ok(Value::array(parts))
         }
         [Value::Str(s), Value::Str(sep), Value::Int(n)] => {
-            let parts: Vec<Value> = s.splitn(*n as usize + 1, sep.as_str()).map(|p| Value::str(p)).collect();
+            let parts: Vec<Value> = s.splitn(*n as usize + 1, sep.as_str()).map(Value::str).collect();
             Ok(Value::array(parts))
         }
         _ => Err("string.split(s, sep?) requires Str".into()),
@@ -1521,7 +1638,7 @@ fn str_pad_left(args: &[Value]) -> Result<Value, String> {
             let pad_char = ch.chars().next().unwrap_or(' ');
             let len = s.chars().count();
             if len >= w { return Ok(Value::Str(s.clone())); }
-            let padding: String = std::iter::repeat(pad_char).take(w - len).collect();
+            let padding: String = std::iter::repeat_n(pad_char, w - len).collect();
             Ok(Value::str(format!("{}{}", padding, s.as_str())))
         }
         _ => Err("string.pad_left(s, width, ch?) requires Str, Int and optional Str".into()),
@@ -1538,7 +1655,7 @@ fn str_pad_right(args: &[Value]) -> Result<Value, String> {
             let pad_char = ch.chars().next().unwrap_or(' ');
             let len = s.chars().count();
             if len >= w { return Ok(Value::Str(s.clone())); }
-            let padding: String = std::iter::repeat(pad_char).take(w - len).collect();
+            let padding: String = std::iter::repeat_n(pad_char, w - len).collect();
             Ok(Value::str(format!("{}{}", s.as_str(), padding)))
         }
         _ => Err("string.pad_right(s, width, ch?) requires Str, Int and optional Str".into()),
@@ -1550,7 +1667,7 @@ fn str_chars(args: &[Value]) -> Result<Value, String> {
 }
 fn str_lines(args: &[Value]) -> Result<Value, String> {
     let s = get_str(args.first().ok_or("lines() requires 1 argument")?, "lines")?;
-    Ok(Value::array(s.lines().map(|l| Value::str(l)).collect()))
+    Ok(Value::array(s.lines().map(Value::str).collect()))
 }
 fn str_contains_fn(args: &[Value]) -> Result<Value, String> {
     match args {
@@ -1558,6 +1675,11 @@ fn str_contains_fn(args: &[Value]) -> Result<Value, String> {
         _ => Err("string.contains(s, sub) requires 2 Str arguments".into()),
     }
 }
+/// Lunghezza in byte UTF-8; `len(s)` conta invece i code point.
+fn str_byte_len(args: &[Value]) -> Result<Value, String> {
+    let s = get_str(args.first().ok_or("byte_len() requires 1 argument")?, "byte_len")?;
+    Ok(Value::Int(s.len() as i64))
+}
 fn str_is_empty(args: &[Value]) -> Result<Value, String> {
     let s = get_str(args.first().ok_or("is_empty() requires 1 argument")?, "is_empty")?;
     Ok(Value::Bool(s.is_empty()))
@@ -1665,7 +1787,7 @@ pub fn make_io_module() -> Value {
 fn io_read_file(args: &[Value]) -> Result<Value, String> {
     let path = get_str(args.first().ok_or("io.read_file() requires 1 argument")?, "io.read_file")?;
     std::fs::read_to_string(path)
-        .map(|s| Value::str(s))
+        .map(Value::str)
         .map_err(|e| format!("io.read_file('{}'): {}", path, e))
 }
 fn io_write_file(args: &[Value]) -> Result<Value, String> {
@@ -1699,7 +1821,7 @@ fn io_read_lines(args: &[Value]) -> Result<Value, String> {
     let path = get_str(args.first().ok_or("io.read_lines() requires 1 argument")?, "io.read_lines")?;
     let content = std::fs::read_to_string(path)
         .map_err(|e| format!("io.read_lines('{}'): {}", path, e))?;
-    Ok(Value::array(content.lines().map(|l| Value::str(l)).collect()))
+    Ok(Value::array(content.lines().map(Value::str).collect()))
 }
 fn io_delete_file(args: &[Value]) -> Result<Value, String> {
     let path = get_str(args.first().ok_or("io.delete_file() requires 1 argument")?, "io.delete_file")?;
@@ -1978,6 +2100,124 @@ fn hof_filter_stub(_: &[Value]) -> Result<Value, String> {
 fn hof_reduce_stub(_: &[Value]) -> Result<Value, String> {
     Err("reduce: should have been intercepted by VM HOF dispatch".into())
 }
+fn assert_raises_stub(_: &[Value]) -> Result<Value, String> {
+    Err("assert_raises: should have been intercepted by VM HOF dispatch".into())
+}
+fn combinator_stub(_: &[Value]) -> Result<Value, String> {
+    Err("and_then/or_else/map_err: should have been intercepted by VM HOF dispatch".into())
+}
+/// ok_or(opt, err) — `Some(x)` → `Ok(x)`, `None` → `Err(err)`
+fn neba_ok_or(args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Some_(x), _] => Ok(Value::Ok_(x.clone())),
+        [Value::None, err]   => Ok(Value::Err_(Box::new(err.clone()))),
+        [v, _] => Err(format!("ok_or: expected Option, got {}", v.type_name())),
+        _ => Err("ok_or(opt, err) requires 2 arguments".into()),
+    }
+}
+
+// ── Regex ─────────────────────────────────────────────────────────────────
+
+/// Pattern e testo di una funzione regex; il pattern non valido è un `Err` Neba, non un errore.
+fn regex_args(args: &[Value], ctx: &str) -> Result<Result<(Regex, Vec<char>), Value>, String> {
+    let (Some(pattern), Some(text)) = (args.first(), args.get(1)) else {
+        return Err(format!("{}(pattern, text) requires 2 arguments", ctx));
+    };
+    let text = get_str(text, ctx)?.chars().collect();
+    Ok(Regex::new(get_str(pattern, ctx)?).map(|re| (re, text)).map_err(|e| Value::Err_(Box::new(Value::str(e)))))
+}
+
+fn capture_value(text: &[char], span: Option<(usize, usize)>) -> Value {
+    span.map_or(Value::None, |(a, b)| Value::str(text[a..b].iter().collect::<String>()))
+}
+
+/// `regex_match(p, s)`: `Some([match, gruppo1, ...])` per il primo match, altrimenti `None`.
+fn neba_regex_match(args: &[Value]) -> Result<Value, String> {
+    let (re, text) = match regex_args(args, "regex_match")? { Ok(r) => r, Err(e) => return Ok(e) };
+    Ok(match re.captures_from(&text, 0)? {
+        Some(caps) => Value::Some_(Box::new(Value::array(caps.iter().map(|&c| capture_value(&text, c)).collect()))),
+        None => Value::None,
+    })
+}
+
+/// `regex_find_all(p, s)`: i match come Str, o senza gruppi o come array dei gruppi.
+fn neba_regex_find_all(args: &[Value]) -> Result<Value, String> {
+    let (re, text) = match regex_args(args, "regex_find_all")? { Ok(r) => r, Err(e) => return Ok(e) };
+    let found = re.captures_all(&text)?.into_iter().map(|caps| {
+        if re.groups() == 0 { capture_value(&text, caps[0]) }
+        else { Value::array(caps[1..].iter().map(|&c| capture_value(&text, c)).collect()) }
+    }).collect();
+    Ok(Value::array(found))
+}
+
+/// `regex_replace(p, s, repl)`: sostituisce tutti i match; in `repl` `$0`..`$9` sono i gruppi, `$$` un `$`.
+fn neba_regex_replace(args: &[Value]) -> Result<Value, String> {
+    let repl = get_str(args.get(2).ok_or("regex_replace(pattern, text, repl) requires 3 arguments")?, "regex_replace")?;
+    let (re, text) = match regex_args(args, "regex_replace")? { Ok(r) => r, Err(e) => return Ok(e) };
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in re.captures_all(&text)? {
+        let Some((a, b)) = caps[0] else { continue };
+        out.extend(&text[last..a]);
+        let mut r = repl.chars().peekable();
+        while let Some(c) = r.next() {
+            match (c, r.peek().copied()) {
+                ('$', Some('$')) => { r.next(); out.push('$'); }
+                ('$', Some(d @ '0'..='9')) => {
+                    r.next();
+                    let g = d as usize - '0' as usize;
+                    if let Some(Some((ga, gb))) = caps.get(g) { out.extend(&text[*ga..*gb]); }
+                }
+                (c, _) => out.push(c),
+            }
+        }
+        last = b;
+    }
+    out.extend(&text[last..]);
+    Ok(Value::str(out))
+}
+
+/// Valori con hash dato dal contenuto: quelli che `hash` accetta.
+fn content_hashable(v: &Value) -> bool {
+    match v {
+        Value::Str(_) | Value::Char(_) | Value::Int(_) | Value::Float(_) | Value::Bool(_) | Value::None => true,
+        Value::Some_(x) | Value::Ok_(x) | Value::Err_(x) => content_hashable(x),
+        _ => false,
+    }
+}
+
+/// `hash(v)` → Int stabile tra esecuzioni, coerente con le chiavi Dict (stesso `Hash`).
+fn neba_hash(args: &[Value]) -> Result<Value, String> {
+    use std::hash::{Hash, Hasher};
+    match args {
+        [v] if content_hashable(v) => {
+            let mut state = neba_runtime::StableHasher::default();
+            v.hash(&mut state);
+            Ok(Value::Int(state.finish() as i64))
+        }
+        [v] => Err(format!("hash: unhashable type {}", v.type_name())),
+        _ => Err("hash(value) requires 1 argument".into()),
+    }
+}
+
+/// `sha256_hex(s)` → digest SHA-256 dei byte UTF-8 di `s`, in esadecimale.
+fn neba_sha256_hex(args: &[Value]) -> Result<Value, String> {
+    let s = get_str(args.first().ok_or("sha256_hex(text) requires 1 argument")?, "sha256_hex")?;
+    Ok(Value::str(neba_runtime::sha256_hex(s.as_bytes())))
+}
+
+fn neba_to_json(args: &[Value]) -> Result<Value, String> {
+    let v = args.first().ok_or("to_json(v) requires 1 argument")?;
+    crate::json::to_json(v).map(Value::str)
+}
+
+fn neba_from_json(args: &[Value]) -> Result<Value, String> {
+    let s = get_str(args.first().ok_or("from_json(text) requires 1 argument")?, "from_json")?;
+    Ok(match crate::json::from_json(s) {
+        Ok(v)  => Value::Ok_(Box::new(v)),
+        Err(e) => Value::Err_(Box::new(Value::str(e))),
+    })
+}
 
 // ═══════════════════════════════════════════════════════════════════════════
 // v0.2.18 — math extensions, string extensions, random module, io.path
@@ -2015,7 +2255,7 @@ fn math_abs(args: &[Value]) -> Result<Value, String> {
 // Stato condiviso tramite thread_local — sufficiente per uso single-thread
 use std::cell::Cell;
 thread_local! {
-    static RNG_STATE: Cell<u64> = Cell::new(12345678901234567u64);
+    static RNG_STATE: Cell<u64> = const { Cell::new(12345678901234567u64) };
 }
 fn lcg_next() -> u64 {
     RNG_STATE.with(|s| {
@@ -2055,6 +2295,7 @@ fn math_choice(args: &[Value]) -> Result<Value, String> {
 fn math_shuffle(args: &[Value]) -> Result<Value, String> {
     match args.first().ok_or("math.shuffle() requires 1 argument")? {
         Value::Array(a) => {
+            check_mutable(a, "shuffle")?;
             let mut arr = a.borrow_mut();
             let n = arr.len();
             // Fisher-Yates
@@ -2103,7 +2344,7 @@ fn str_zfill(args: &[Value]) -> Result<Value, String> {
             let pad = w - s.len();
             // Gestisce il segno: "-42".zfill(5) → "-0042"
             if s.starts_with('-') {
-                Ok(Value::str(format!("-{}{}", "0".repeat(pad), &s[1..])))
+                Ok(Value::str(format!("-{}{}", "0".repeat(pad), s.strip_prefix('-').unwrap_or(s))))
             } else {
                 Ok(Value::str(format!("{}{}", "0".repeat(pad), s)))
             }
@@ -2442,7 +2683,7 @@ fn nd_ones(args: &[Value]) -> Result<Value, String> {
     if args.is_empty() { return Err("nd.ones(shape, dtype?) requires shape".into()); }
     let shape = parse_shape(&args[0])?;
     let dtype = parse_dtype_opt(args, 1);
-    let mut nd = Nd::zeros(shape, dtype);
+    let nd = Nd::zeros(shape, dtype);
     for i in 0..nd.size() { nd.set_flat(i, Value::Float(1.0)).unwrap(); }
     Ok(Value::nd_array(nd))
 }
@@ -2453,7 +2694,7 @@ fn nd_full(args: &[Value]) -> Result<Value, String> {
     let shape = parse_shape(&args[0])?;
     let dtype = parse_dtype_opt(args, 2);
     let fill = args[1].clone();
-    let mut nd = Nd::zeros(shape, dtype);
+    let nd = Nd::zeros(shape, dtype);
     for i in 0..nd.size() { nd.set_flat(i, fill.clone()).unwrap(); }
     Ok(Value::nd_array(nd))
 }
@@ -2470,7 +2711,7 @@ fn nd_eye(args: &[Value]) -> Result<Value, String> {
         _ => return Err("nd.eye(n) or nd.eye([n]) requires Int".into()),
     };
     let dtype = parse_dtype_opt(args, 1);
-    let mut nd = Nd::zeros(vec![n, n], dtype);
+    let nd = Nd::zeros(vec![n, n], dtype);
     for i in 0..n { nd.set_flat(i*n + i, Value::Float(1.0)).unwrap(); }
     Ok(Value::nd_array(nd))
 }
@@ -2646,7 +2887,7 @@ fn nd_slice_fn(args: &[Value]) -> Result<Value, String> {
     let row_range = match args.get(1) {
         Some(Value::Array(a)) => {
             let b = a.borrow();
-            let r0 = b.get(0).and_then(|v| if let Value::Int(n) = v { Some(*n as usize) } else { None }).unwrap_or(0);
+            let r0 = b.first().and_then(|v| if let Value::Int(n) = v { Some(*n as usize) } else { None }).unwrap_or(0);
             let r1 = b.get(1).and_then(|v| if let Value::Int(n) = v { Some(*n as usize) } else { None }).unwrap_or(nd.shape[0]);
             (r0, r1)
         }
@@ -2656,7 +2897,7 @@ fn nd_slice_fn(args: &[Value]) -> Result<Value, String> {
     let col_range = match args.get(2) {
         Some(Value::Array(a)) => {
             let b = a.borrow();
-            let c0 = b.get(0).and_then(|v| if let Value::Int(n) = v { Some(*n as usize) } else { None }).unwrap_or(0);
+            let c0 = b.first().and_then(|v| if let Value::Int(n) = v { Some(*n as usize) } else { None }).unwrap_or(0);
             let c1 = b.get(1).and_then(|v| if let Value::Int(n) = v { Some(*n as usize) } else { None }).unwrap_or(if nd.ndim() > 1 { nd.shape[1] } else { nd.size() });
             (c0, c1)
         }
@@ -2765,7 +3006,7 @@ fn nd_where(args: &[Value]) -> Result<Value, String> {
     let b    = require_nd(&args[2], "nd.where")?;
     { let n = cond.size();
         if a.size() != n || b.size() != n { return Err("nd.where: size mismatch".into()); }
-        let mut out = Nd::zeros(cond.shape.clone(), crate::value::Dtype::Float64);
+        let out = Nd::zeros(cond.shape.clone(), crate::value::Dtype::Float64);
         for i in 0..n {
             let c = cond.get_flat(i).and_then(|v| v.as_float()).unwrap_or(0.0);
             let v = if c != 0.0 { a.get_flat(i).unwrap_or(Value::None) } else { b.get_flat(i).unwrap_or(Value::None) };
@@ -2831,7 +3072,7 @@ fn nd_hstack(args: &[Value]) -> Result<Value, String> {
     let rows = nds[0].shape[0];
     for nd in &nds { if nd.shape[0] != rows { return Err("nd.hstack: rows mismatch".into()); } }
     let total_cols: usize = nds.iter().map(|nd| nd.shape.get(1).copied().unwrap_or(1)).sum();
-    let mut out = Nd::zeros(vec![rows, total_cols], Dtype::Float64);
+    let out = Nd::zeros(vec![rows, total_cols], Dtype::Float64);
     let mut col_offset = 0;
     for nd in &nds {
         let cols = nd.shape.get(1).copied().unwrap_or(1);
@@ -2873,13 +3114,13 @@ fn nd_diag(args: &[Value]) -> Result<Value, String> {
     let nd = require_nd(args.first().unwrap_or(&Value::None), "nd.diag")?;
     if nd.ndim() == 1 {
         let n = nd.size();
-        let mut out = Nd::zeros(vec![n, n], Dtype::Float64);
+        let out = Nd::zeros(vec![n, n], Dtype::Float64);
         for i in 0..n { out.set_flat(i*n+i, nd.get_flat(i).unwrap()).unwrap(); }
         Ok(Value::nd_array(out))
     } else if nd.ndim() == 2 {
         let (rows, cols) = (nd.shape[0], nd.shape[1]);
         let n = rows.min(cols);
-        let mut out = Nd::zeros(vec![n], Dtype::Float64);
+        let out = Nd::zeros(vec![n], Dtype::Float64);
         for i in 0..n { out.set_flat(i, nd.get_flat(i*cols+i).unwrap()).unwrap(); }
         Ok(Value::nd_array(out))
     } else {
@@ -2914,7 +3155,7 @@ fn nd_astype(args: &[Value]) -> Result<Value, String> {
         },
         _ => return Err("nd.astype: dtype must be a string".into()),
     };
-    let mut out = Nd::zeros(nd_src.shape.clone(), dtype);
+    let out = Nd::zeros(nd_src.shape.clone(), dtype);
     for i in 0..nd_src.size() { out.set_flat(i, nd_src.get_flat(i).unwrap()).unwrap(); }
     Ok(Value::nd_array(out))
 }
@@ -2950,14 +3191,14 @@ fn nd_set(args: &[Value]) -> Result<Value, String> {
     if args.len() < 3 { return Err("nd.set(arr, [i,j], value) or nd.set(arr, i, j, ..., value)".into()); }
     match &args[0] {
         Value::NdArray(rc) => {
-            let mut nd = rc.borrow_mut();
+            let nd = rc.borrow_mut();
             let value = args.last().unwrap().clone();
             // Accetta sia nd.set(arr, [i,j], val) che nd.set(arr, i, j, val)
             let indices: Vec<usize> = if args.len() == 3 {
                 if let Value::Array(a) = &args[1] {
                     a.borrow().iter().map(|v| match v {
                         Value::Int(n) => Ok(*n as usize),
-                        _ => Err(format!("nd.set: indices must be Int")),
+                        _ => Err("nd.set: indices must be Int".to_string()),
                     }).collect::<Result<_,_>>()?
                 } else {
                     match &args[1] {
@@ -2968,7 +3209,7 @@ fn nd_set(args: &[Value]) -> Result<Value, String> {
             } else {
                 args[1..args.len()-1].iter().map(|v| match v {
                     Value::Int(n) => Ok(*n as usize),
-                    _ => Err(format!("nd.set: indices must be Int")),
+                    _ => Err("nd.set: indices must be Int".to_string()),
                 }).collect::<Result<_,_>>()?
             };
             nd.set_nd(&indices, value)?;
@@ -14,7 +14,65 @@ pub type RcArray    = Rc<RefCell<Vec<Value>>>;
 pub type RcInstance = Rc<RefCell<Instance>>;
 pub type RcClosure  = Rc<Closure>;
 /// Dict: mappa chiave→valore con ordine di inserimento preservato (IndexMap O(1) lookup).
-pub type RcDict     = Rc<RefCell<IndexMap<Value, Value>>>;
+pub type RcDict     = Rc<RefCell<DictMap>>;
+
+/// Voci di un Dict. Si usa come l'IndexMap che contiene (`Deref`); in più tiene,
+/// per le chiavi Instance, il risultato del loro `__hash__` all'inserimento,
+/// così `Vm::dict_key` chiama `__eq__` solo sulle chiavi con lo stesso hash.
+#[derive(Debug, Clone, Default)]
+pub struct DictMap {
+    map: IndexMap<Value, Value>,
+    /// `__hash__` → chiavi Instance registrate con quell'hash. Le chiavi non più
+    /// presenti nella mappa vengono scartate dalla lookup successiva del bucket.
+    instance_keys: HashMap<u64, Vec<Value>>,
+}
+
+impl DictMap {
+    /// Chiavi Instance ancora nel Dict registrate con l'hash `h`.
+    pub fn instance_keys(&mut self, h: u64) -> Vec<Value> {
+        let Some(bucket) = self.instance_keys.get_mut(&h) else { return Vec::new() };
+        bucket.retain(|k| self.map.contains_key(k));
+        let keys = bucket.clone();
+        if bucket.is_empty() { self.instance_keys.remove(&h); }
+        keys
+    }
+
+    /// Inserisce le voci di `other` (l'ultima vince), con i bucket delle sue chiavi Instance.
+    pub fn merge(&mut self, other: &DictMap) {
+        for (k, v) in other.map.iter() { self.map.insert(k.clone(), v.clone()); }
+        for (h, keys) in &other.instance_keys {
+            for k in keys { self.record_instance_key(*h, k.clone()); }
+        }
+    }
+
+    /// Registra `key` (una Instance) nel bucket del suo hash `h`.
+    pub fn record_instance_key(&mut self, h: u64, key: Value) {
+        let bucket = self.instance_keys.entry(h).or_default();
+        if !bucket.iter().any(|k| matches!((k, &key), (Value::Instance(a), Value::Instance(b)) if Rc::ptr_eq(a, b))) {
+            bucket.push(key);
+        }
+    }
+}
+
+/// Due Dict sono uguali se hanno le stesse voci; l'indice degli hash non conta.
+impl PartialEq for DictMap {
+    fn eq(&self, other: &Self) -> bool { self.map == other.map }
+}
+
+impl From<IndexMap<Value, Value>> for DictMap {
+    fn from(map: IndexMap<Value, Value>) -> Self {
+        DictMap { map, instance_keys: HashMap::new() }
+    }
+}
+
+impl std::ops::Deref for DictMap {
+    type Target = IndexMap<Value, Value>;
+    fn deref(&self) -> &Self::Target { &self.map }
+}
+
+impl std::ops::DerefMut for DictMap {
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.map }
+}
 
 // ── TypedArray (v0.2.6) ───────────────────────────────────────────────────
 
@@ -145,6 +203,127 @@ pub fn resolve_idx(i: i64, len: usize) -> Result<usize, String> {
     }
 }
 
+// ── Array congelati (freeze) ─────────────────────────────────────────────
+// Registro per identità: il Weak tiene viva l'allocazione, quindi l'indirizzo
+// non può essere riusato da un nuovo array finché la voce esiste.
+thread_local! {
+    static FROZEN: RefCell<HashMap<usize, std::rc::Weak<RefCell<Vec<Value>>>>> = RefCell::new(HashMap::new());
+}
+
+/// Rende l'array immutabile: push/pop/assegnazione per indice falliranno.
+pub fn freeze_array(arr: &RcArray) {
+    FROZEN.with(|f| {
+        let mut f = f.borrow_mut();
+        f.retain(|_, w| w.strong_count() > 0);
+        f.insert(Rc::as_ptr(arr) as usize, Rc::downgrade(arr));
+    });
+}
+
+pub fn is_frozen(arr: &RcArray) -> bool {
+    FROZEN.with(|f| {
+        let f = f.borrow();
+        !f.is_empty() && f.contains_key(&(Rc::as_ptr(arr) as usize))
+    })
+}
+
+/// Errore se l'array è congelato; `op` è il nome dell'operazione per il messaggio.
+pub fn check_mutable(arr: &RcArray, op: &str) -> Result<(), String> {
+    if is_frozen(arr) { Err(format!("{}: cannot mutate a frozen Array", op)) } else { Ok(()) }
+}
+
+// ── Array tipizzati a runtime (checked arrays) ───────────────────────────
+// Con `Vm::set_checked_arrays(true)`, `let a: Array[T] = ...` registra T come tipo
+// degli elementi e push/append/insert/assegnazioni lo verificano. Stesso registro
+// per identità di FROZEN: a opzione spenta resta vuoto e il controllo è gratuito.
+type ElemTag = (std::rc::Weak<RefCell<Vec<Value>>>, Rc<str>);
+thread_local! {
+    static ELEM_TYPES: RefCell<HashMap<usize, ElemTag>> = RefCell::new(HashMap::new());
+}
+
+/// Associa all'array il tipo elemento `elem`, verificando gli elementi già presenti.
+pub fn tag_array(arr: &RcArray, elem: &str) -> Result<(), String> {
+    if let Some(bad) = arr.borrow().iter().find(|v| !elem_matches(elem, v)) {
+        return Err(format!("Array[{}] cannot hold {}", elem, elem_type_name(bad)));
+    }
+    ELEM_TYPES.with(|t| {
+        let mut t = t.borrow_mut();
+        t.retain(|_, (w, _)| w.strong_count() > 0);
+        t.insert(Rc::as_ptr(arr) as usize, (Rc::downgrade(arr), Rc::from(elem)));
+    });
+    Ok(())
+}
+
+/// Errore se l'array è tipizzato e `val` non è del suo tipo elemento.
+pub fn check_elem(arr: &RcArray, val: &Value, op: &str) -> Result<(), String> {
+    ELEM_TYPES.with(|t| {
+        let t = t.borrow();
+        if t.is_empty() { return Ok(()); }
+        // La voce vale solo se è ancora quest'array, non uno liberato allo stesso indirizzo
+        let tagged = t.get(&(Rc::as_ptr(arr) as usize))
+            .filter(|(w, _)| w.upgrade().is_some_and(|a| Rc::ptr_eq(&a, arr)));
+        match tagged {
+            Some((_, elem)) if !elem_matches(elem, val) =>
+                Err(format!("{}: Array[{}] cannot hold {}", op, elem, elem_type_name(val))),
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Come il type checker: un Int è accettato dove serve un Float; un nome non
+/// builtin è una classe (o un trait implementato dall'istanza).
+fn elem_matches(elem: &str, v: &Value) -> bool {
+    match elem {
+        "Any"      => true,
+        "Int"      => matches!(v, Value::Int(_)),
+        "Float"    => matches!(v, Value::Int(_) | Value::Float(_)),
+        "Bool"     => matches!(v, Value::Bool(_)),
+        // Un Char vale come Str fuori da `is`/`typeof`
+        "Str"      => matches!(v, Value::Str(_) | Value::Char(_)),
+        "Char"     => matches!(v, Value::Char(_)),
+        "Array"    => matches!(v, Value::Array(_)),
+        "Dict"     => matches!(v, Value::Dict(_)),
+        "Function" => matches!(v, Value::Closure(_) | Value::NativeFn(..)),
+        "Range"    => matches!(v, Value::IntRange(..)),
+        "Option"   => matches!(v, Value::Some_(_) | Value::None),
+        "Result"   => matches!(v, Value::Ok_(_) | Value::Err_(_)),
+        class => match v {
+            Value::Instance(i) => { let i = i.borrow(); i.class_name == class || i.traits.iter().any(|t| t == class) }
+            _ => false,
+        },
+    }
+}
+
+fn elem_type_name(v: &Value) -> String {
+    match v {
+        Value::Instance(i) => i.borrow().class_name.clone(),
+        other => other.type_name().to_string(),
+    }
+}
+
+// ── Range interi ─────────────────────────────────────────────────────────
+// `s..e` / `s..=e` sono sempre crescenti: con `s > e` il range è vuoto
+// (`for i in 1..n` non esegue nulla per n = 0). Per scendere: `range(5, 0, -1)`.
+
+/// Numero di elementi del range.
+pub fn range_len(start: i64, end: i64, inclusive: bool) -> i64 {
+    (end - start + inclusive as i64).max(0)
+}
+
+/// Elemento `i`-esimo (0-based) del range.
+#[inline]
+pub fn range_nth(start: i64, i: i64) -> i64 {
+    start + i
+}
+
+pub fn range_contains(start: i64, end: i64, inclusive: bool, n: i64) -> bool {
+    n >= start && if inclusive { n <= end } else { n < end }
+}
+
+/// Materializza il range in un Vec (per map/filter/sorted…).
+pub fn range_values(start: i64, end: i64, inclusive: bool) -> Vec<Value> {
+    (0..range_len(start, end, inclusive)).map(|i| Value::Int(range_nth(start, i))).collect()
+}
+
 pub type RcTypedArray = Rc<RefCell<TypedArrayData>>;
 
 /// Closure = FnProto + upvalue catturati al momento della definizione.
@@ -197,6 +376,8 @@ pub enum Value {
     Int(i64),
     Float(f64),
     Bool(bool),
+    /// `'a'`: uguale alla Str di un solo carattere
+    Char(char),
     None,
 
     // Heap (Rc = reference counting GC)
@@ -228,12 +409,64 @@ pub enum Value {
     #[doc(hidden)] IntRange(i64, i64, bool), // start, end, inclusive
 }
 
+// ── Conversioni Rust ↔ Value (per l'embedding) ───────────────────────────
+
+impl From<i64> for Value { fn from(n: i64) -> Self { Value::Int(n) } }
+impl From<f64> for Value { fn from(f: f64) -> Self { Value::Float(f) } }
+impl From<bool> for Value { fn from(b: bool) -> Self { Value::Bool(b) } }
+impl From<String> for Value { fn from(s: String) -> Self { Value::str(s) } }
+impl From<&str> for Value { fn from(s: &str) -> Self { Value::str(s.to_string()) } }
+impl From<Vec<Value>> for Value { fn from(v: Vec<Value>) -> Self { Value::array(v) } }
+
+/// Messaggio d'errore comune dei `TryFrom<Value>`
+fn expected(ty: &str, got: &Value) -> String {
+    format!("expected {}, got {}", ty, got.type_name())
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Int(n) => Ok(n), other => Err(expected("Int", &other)) }
+    }
+}
+
+/// Accetta anche un Int, come le operazioni aritmetiche miste.
+impl TryFrom<Value> for f64 {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        v.as_float().ok_or_else(|| expected("Float", &v))
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Bool(b) => Ok(b), other => Err(expected("Bool", &other)) }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Str(s) => Ok(Rc::try_unwrap(s).unwrap_or_else(|s| (*s).clone())), other => Err(expected("Str", &other)) }
+    }
+}
+
+/// Copia superficiale degli elementi dell'array.
+impl TryFrom<Value> for Vec<Value> {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, String> {
+        match v { Value::Array(a) => Ok(a.borrow().clone()), other => Err(expected("Array", &other)) }
+    }
+}
+
 impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Value::Int(n)       => write!(f, "Int({})", n),
             Value::Float(n)     => write!(f, "Float({})", n),
             Value::Bool(b)      => write!(f, "Bool({})", b),
+            Value::Char(c)      => write!(f, "Char({:?})", c),
             Value::None         => write!(f, "None"),
             Value::Str(s)       => write!(f, "Str({:?})", s),
             Value::Array(a)     => write!(f, "Array({:?})", a.borrow()),
@@ -259,7 +492,7 @@ impl fmt::Debug for Value {
             Value::__Return(v)  => write!(f, "__Return({:?})", v),
             Value::__Break      => write!(f, "__Break"),
             Value::__Continue   => write!(f, "__Continue"),
-            Value::__Iter(a, i) => write!(f, "__Iter(pos={})", i),
+            Value::__Iter(_a, i) => write!(f, "__Iter(pos={})", i),
             Value::IntRange(s, e, inc) => write!(f, "IntRange({}..{}{})", s, e, if *inc {"="} else {""}),
         }
     }
@@ -275,15 +508,16 @@ impl fmt::Display for Value {
             Value::Bool(b)   => write!(f, "{}", if *b { "true" } else { "false" }),
             Value::None      => write!(f, "None"),
             Value::Str(s)    => write!(f, "{}", s),
-            Value::Array(a)  => {
+            Value::Char(c)   => write!(f, "{}", c),
+            Value::Array(a)  => fmt_guarded(f, Rc::as_ptr(a) as usize, "[...]", |f| {
                 let items: Vec<String> = a.borrow().iter().map(|v| format!("{}", v)).collect();
                 write!(f, "[{}]", items.join(", "))
-            }
-            Value::Dict(d)   => {
+            }),
+            Value::Dict(d)   => fmt_guarded(f, Rc::as_ptr(d) as usize, "{...}", |f| {
                 let items: Vec<String> = d.borrow().iter()
                     .map(|(k, v)| format!("{}: {}", k, v)).collect();
                 write!(f, "{{{}}}", items.join(", "))
-            }
+            }),
             Value::TypedArray(t) => {
                 let d = t.borrow();
                 let dtype = d.dtype().name();
@@ -316,7 +550,20 @@ impl fmt::Display for Value {
             Value::Some_(v)  => write!(f, "Some({})", v),
             Value::Ok_(v)    => write!(f, "Ok({})", v),
             Value::Err_(v)   => write!(f, "Err({})", v),
-            Value::Instance(i) => write!(f, "<{} instance>", i.borrow().class_name),
+            Value::Instance(i) => fmt_guarded(f, Rc::as_ptr(i) as usize, &format!("{}(...)", i.borrow().class_name), |f| {
+                // Fallback senza `__str__`: `Point(x=1, y=2)`, campi in ordine alfabetico, metodi esclusi
+                let inst = i.borrow();
+                let mut fields: Vec<_> = inst.fields.iter()
+                    .filter(|(_, v)| !matches!(v, Value::Closure(_) | Value::NativeFn(_, _)))
+                    .collect();
+                fields.sort_by(|a, b| a.0.cmp(b.0));
+                write!(f, "{}(", inst.class_name)?;
+                for (n, (k, v)) in fields.into_iter().enumerate() {
+                    if n > 0 { write!(f, ", ")?; }
+                    write!(f, "{}={}", k, v)?;
+                }
+                write!(f, ")")
+            }),
             Value::__Return(v) => write!(f, "{}", v),
             Value::__Break     => write!(f, "<break>"),
             Value::__Continue  => write!(f, "<continue>"),
@@ -326,6 +573,28 @@ impl fmt::Display for Value {
     }
 }
 
+// Contenitori in corso di stampa: un array che contiene sé stesso (`push(a, a)`)
+// viene stampato come `[...]` invece di ricorrere all'infinito.
+thread_local! {
+    static DISPLAY_PATH: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Esegue `body` con `ptr` sul percorso di stampa; se `ptr` è già presente
+/// (ciclo) scrive `marker`.
+fn fmt_guarded(
+    f: &mut fmt::Formatter<'_>, ptr: usize, marker: &str,
+    body: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    let cyclic = DISPLAY_PATH.with(|p| {
+        let mut p = p.borrow_mut();
+        if p.contains(&ptr) { true } else { p.push(ptr); false }
+    });
+    if cyclic { return write!(f, "{}", marker); }
+    let r = body(f);
+    DISPLAY_PATH.with(|p| { p.borrow_mut().pop(); });
+    r
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -335,6 +604,11 @@ impl PartialEq for Value {
             (Value::Float(a), Value::Int(b))   => *a == (*b as f64),
             (Value::Bool(a),  Value::Bool(b))  => a == b,
             (Value::Str(a),   Value::Str(b))   => a == b,
+            (Value::Char(a),  Value::Char(b))  => a == b,
+            (Value::Char(c),  Value::Str(s)) | (Value::Str(s), Value::Char(c)) => {
+                let mut chars = s.chars();
+                chars.next() == Some(*c) && chars.next().is_none()
+            }
             (Value::None,     Value::None)     => true,
             (Value::Some_(a), Value::Some_(b)) => a == b,
             (Value::Ok_(a),   Value::Ok_(b))   => a == b,
@@ -344,6 +618,8 @@ impl PartialEq for Value {
             // TypedArray: uguaglianza per identità (stesso Rc)
             (Value::TypedArray(a), Value::TypedArray(b)) => Rc::ptr_eq(a, b),
             (Value::NdArray(a),    Value::NdArray(b))    => Rc::ptr_eq(a, b),
+            // Instance: identità, coerente con Hash (le chiavi Dict usano __hash__/__eq__ nel Vm)
+            (Value::Instance(a),   Value::Instance(b))   => Rc::ptr_eq(a, b),
             _ => false,
         }
     }
@@ -359,13 +635,16 @@ impl Hash for Value {
             // Str: hasha solo il contenuto (senza discriminant) per compatibilità
             // con Equivalent<Value> for str — hash("foo") == hash(Value::Str("foo"))
             Value::Str(s)    => { s.hash(state); }
+            // Char: come la Str di un carattere, a cui è uguale
+            Value::Char(c)   => { c.encode_utf8(&mut [0; 4]).hash(state); }
             Value::Int(n)    => { 0u8.hash(state); n.hash(state); }
-            Value::Float(f)  => { 1u8.hash(state); f.to_bits().hash(state); }
+            Value::Float(f)  => { 1u8.hash(state); neba_runtime::float_key_bits(*f).hash(state); }
             Value::Bool(b)   => { 2u8.hash(state); b.hash(state); }
             Value::None      => { 4u8.hash(state); }
             // Tipi non scalari: hash per identità del puntatore
             Value::Array(a)  => { 5u8.hash(state); Rc::as_ptr(a).hash(state); }
             Value::Dict(d)   => { 6u8.hash(state); Rc::as_ptr(d).hash(state); }
+            // Instance: identità; le chiavi uguali per `__eq__` le risolve `Vm::dict_key`
             Value::Instance(i) => { 7u8.hash(state); Rc::as_ptr(i).hash(state); }
             Value::Closure(c)  => { 8u8.hash(state); Rc::as_ptr(c).hash(state); }
             Value::TypedArray(t) => { 9u8.hash(state); Rc::as_ptr(t).hash(state); }
@@ -402,6 +681,9 @@ impl PartialOrd for Value {
             (Value::Int(a),   Value::Float(b)) => (*a as f64).partial_cmp(b),
             (Value::Float(a), Value::Int(b))   => a.partial_cmp(&(*b as f64)),
             (Value::Str(a),   Value::Str(b))   => a.as_str().partial_cmp(b.as_str()),
+            (Value::Char(a),  Value::Char(b))  => a.partial_cmp(b),
+            (Value::Char(a),  Value::Str(b))   => (*a.encode_utf8(&mut [0; 4])).partial_cmp(b.as_str()),
+            (Value::Str(a),   Value::Char(b))  => a.as_str().partial_cmp(&*b.encode_utf8(&mut [0; 4])),
             _ => Option::None,
         }
     }
@@ -424,6 +706,12 @@ impl Value {
         }
     }
 
+    /// Un Char vale come la Str del suo carattere in operatori, indici e builtin
+    /// di testo; resta distinto solo per `is` e `typeof`.
+    pub fn char_as_str(self) -> Value {
+        match self { Value::Char(c) => Value::str(c.to_string()), v => v }
+    }
+
     pub fn type_name(&self) -> &'static str {
         match self {
             Value::Int(_)       => "Int",
@@ -431,6 +719,7 @@ impl Value {
             Value::Bool(_)      => "Bool",
             Value::None         => "None",
             Value::Str(_)       => "Str",
+            Value::Char(_)      => "Char",
             Value::Array(_)     => "Array",
             Value::Dict(_)      => "Dict",
             Value::TypedArray(t) => t.borrow().dtype().array_type_name(),
@@ -474,12 +763,12 @@ impl Value {
     pub fn dict(pairs: Vec<(Value, Value)>) -> Self {
         let mut map = IndexMap::with_capacity(pairs.len());
         for (k, v) in pairs { map.insert(k, v); }
-        Value::Dict(Rc::new(RefCell::new(map)))
+        Value::dict_from_map(map)
     }
 
     /// Costruisce un Value::Dict direttamente da un IndexMap.
     pub fn dict_from_map(map: IndexMap<Value, Value>) -> Self {
-        Value::Dict(Rc::new(RefCell::new(map)))
+        Value::Dict(Rc::new(RefCell::new(map.into())))
     }
 
     /// Costruisce un Value::TypedArray da TypedArrayData
@@ -635,7 +924,7 @@ impl NdArray {
         if axes.len() != ndim { return Err("transpose: wrong number of axes".into()); }
         let mut new_shape = vec![0usize; ndim];
         for (i, &a) in axes.iter().enumerate() { new_shape[i] = self.shape[a]; }
-        let mut out = NdArray::zeros(new_shape.clone(), self.data.borrow().dtype());
+        let out = NdArray::zeros(new_shape.clone(), self.data.borrow().dtype());
         let old_strides: Vec<usize> = {
             let mut s = vec![1usize; ndim]; for i in (0..ndim-1).rev() { s[i] = s[i+1] * self.shape[i+1]; } s
         };
@@ -670,7 +959,7 @@ impl NdArray {
             return Err(format!("shape mismatch: {:?} vs {:?} (use nd.add/sub/mul/div for broadcast)", self.shape, other.shape));
         }
         let n = self.size();
-        let mut out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
+        let out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
         for i in 0..n {
             let a = self.get_flat(i).and_then(|v| v.as_float()).unwrap_or(0.0);
             let b = other.get_flat(i).and_then(|v| v.as_float()).unwrap_or(0.0);
@@ -680,7 +969,7 @@ impl NdArray {
     }
 
     pub fn ewise_scalar<F: Fn(f64, f64) -> f64>(&self, scalar: f64, op: F) -> NdArray {
-        let mut out = NdArray::zeros(self.shape.clone(), self.data.borrow().dtype());
+        let out = NdArray::zeros(self.shape.clone(), self.data.borrow().dtype());
         for i in 0..self.size() {
             let a = self.get_flat(i).and_then(|v| v.as_float()).unwrap_or(0.0);
             out.set_flat(i, Value::Float(op(a, scalar))).unwrap();
@@ -689,7 +978,7 @@ impl NdArray {
     }
 
     pub fn ewise_unary<F: Fn(f64) -> f64>(&self, op: F) -> NdArray {
-        let mut out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
+        let out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
         for i in 0..self.size() {
             let a = self.get_flat(i).and_then(|v| v.as_float()).unwrap_or(0.0);
             out.set_flat(i, Value::Float(op(a))).unwrap();
@@ -720,7 +1009,7 @@ impl NdArray {
         var.sqrt()
     }
     pub fn cumsum(&self) -> NdArray {
-        let mut out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
+        let out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
         let mut acc = 0.0f64;
         for i in 0..self.size() {
             acc += self.get_flat(i).and_then(|v| v.as_float()).unwrap_or(0.0);
@@ -735,7 +1024,7 @@ impl NdArray {
         if axis >= self.ndim() { return Err(format!("axis {} out of range for ndim {}", axis, self.ndim())); }
         let mut new_shape = self.shape.clone(); new_shape.remove(axis);
         if new_shape.is_empty() { new_shape = vec![1]; }
-        let mut out = NdArray::zeros(new_shape.clone(), Dtype::Float64);
+        let out = NdArray::zeros(new_shape.clone(), Dtype::Float64);
         for i in 0..out.size() { out.set_flat(i, Value::Float(init)).unwrap(); }
         let mut strides = vec![1usize; self.ndim()];
         for i in (0..self.ndim()-1).rev() { strides[i] = strides[i+1] * self.shape[i+1]; }
@@ -769,7 +1058,7 @@ impl NdArray {
         }
         let (m, k, n) = (self.shape[0], self.shape[1], other.shape[1]);
         if k != other.shape[0] { return Err(format!("matmul: shape mismatch {}x{} @ {}x{}", m, k, other.shape[0], n)); }
-        let mut out = NdArray::zeros(vec![m, n], Dtype::Float64);
+        let out = NdArray::zeros(vec![m, n], Dtype::Float64);
         for i in 0..m { for j in 0..n {
             let mut s = 0.0f64;
             for p in 0..k {
@@ -795,7 +1084,7 @@ impl NdArray {
             else if sb[d] == 1 { out_shape[d] = sa[d]; }
             else { return Err(format!("broadcast: shapes {:?} and {:?} incompatible", self.shape, other.shape)); }
         }
-        let mut out = NdArray::zeros(out_shape.clone(), Dtype::Float64);
+        let out = NdArray::zeros(out_shape.clone(), Dtype::Float64);
         let mut out_strides = vec![1usize; ndim];
         for i in (0..ndim-1).rev() { out_strides[i] = out_strides[i+1] * out_shape[i+1]; }
         let sa_strides = broadcast_strides(&sa, &out_shape);
@@ -815,7 +1104,7 @@ impl NdArray {
     // ── Boolean ───────────────────────────────────────────────────────────
 
     pub fn cmp_scalar(&self, scalar: f64, op: &str) -> NdArray {
-        let mut out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
+        let out = NdArray::zeros(self.shape.clone(), Dtype::Float64);
         for i in 0..self.size() {
             let v = self.get_flat(i).and_then(|x| x.as_float()).unwrap_or(0.0);
             let r = match op {
@@ -843,7 +1132,7 @@ impl NdArray {
         let cols = self.shape[1];
         if r1 > self.shape[0] || c1 > cols { return Err("slice out of bounds".into()); }
         let out_rows = r1 - r0; let out_cols = c1 - c0;
-        let mut out = NdArray::zeros(vec![out_rows, out_cols], self.data.borrow().dtype());
+        let out = NdArray::zeros(vec![out_rows, out_cols], self.data.borrow().dtype());
         for r in 0..out_rows { for c in 0..out_cols {
             let v = self.get_flat((r0+r)*cols + c0+c).unwrap_or(Value::None);
             out.set_flat(r*out_cols+c, v).unwrap();
@@ -1,17 +1,22 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use rustc_hash::FxHashMap;
 use std::rc::Rc;
+use std::hash::{Hash, Hasher};
 
-use crate::chunk::{read_i16, read_u16, Chunk, FnProto};
-use crate::compiler::ClassInfo;
+use crate::chunk::Chunk;
+use crate::compiler::{ClassInfo, CompileState, Compiler};
 use crate::error::{VmError, VmResult};
 use crate::opcode::Op;
 use crate::stdlib;
 use crate::value::{Closure, Instance, Upvalue, Value, TypedArrayData};
 
-const STACK_MAX:  usize = 4096;
 const FRAMES_MAX: usize = 256;
+/// Budget di istruzioni di default per `Vm::sandboxed`.
+pub const SANDBOX_STEP_LIMIT: u64 = 10_000_000;
+/// Budget di memoria di default (byte, stimati) per `Vm::sandboxed`.
+pub const SANDBOX_ALLOC_LIMIT: usize = 64 << 20;
+const VALUE_SIZE: usize = std::mem::size_of::<Value>();
 
 // ── Aritmetica element-wise per TypedArray (v0.2.6/v0.2.7) ───────────────
 
@@ -113,7 +118,6 @@ struct CallFrame {
     chunk:    Rc<Chunk>,
     ip:       usize,
     base:     usize,
-    name:     String,
     upvalues: Rc<Vec<Upvalue>>,
 }
 
@@ -123,8 +127,26 @@ pub struct Vm {
     stack:          Vec<Value>,
     frames:         Vec<CallFrame>,
     globals:        FxHashMap<String, (Value, bool)>,
+    #[allow(dead_code)]
     class_registry: HashMap<String, ClassInfo>,
     step_limit:     u64,
+    /// Istruzioni eseguite dall'ultimo `run_chunk` (contate solo se `step_limit > 0`)
+    steps:          u64,
+    /// Tetto in byte alle allocazioni di ogni `run_chunk` (0 = nessun limite)
+    alloc_limit:    usize,
+    /// Byte allocati (stima, senza contare i rilasci) dall'ultimo `run_chunk`
+    allocated:      Cell<usize>,
+    /// Posizione `(line, column)` dell'istruzione che ha causato l'ultimo errore
+    error_pos:      Option<(u32, u32)>,
+    /// `/` con divisore Float zero dà inf/-inf/nan invece di `DivisionByZero`
+    ieee_division:  bool,
+    /// Indicizzazione, slicing e iterazione delle stringhe per grapheme cluster
+    #[cfg(feature = "graphemes")]
+    graphemes:      bool,
+    /// `let a: Array[T]` controlla a runtime il tipo degli elementi inseriti
+    checked_arrays: bool,
+    /// Classi/trait dichiarati dagli snippet eseguiti con `eval`/`run_program`
+    compile_state:  CompileState,
 }
 
 impl Vm {
@@ -135,13 +157,201 @@ impl Vm {
             globals:        FxHashMap::default(),
             class_registry: HashMap::new(),
             step_limit:     0,
+            steps:          0,
+            alloc_limit:    0,
+            allocated:      Cell::new(0),
+            error_pos:      None,
+            ieee_division:  false,
+            #[cfg(feature = "graphemes")]
+            graphemes:      false,
+            checked_arrays: false,
+            compile_state:  CompileState::default(),
         };
         stdlib::register_globals(&mut vm.globals);
         vm
     }
 
+    /// Compila ed esegue `source` nello stato corrente della VM: globali, funzioni
+    /// e classi definite dalle chiamate precedenti restano visibili (uso da REPL).
+    pub fn eval(&mut self, source: &str) -> VmResult<Value> {
+        let (program, lex_errors, parse_errors) = neba_parser::parse(source);
+        if let Some(e) = lex_errors.into_iter().next() {
+            return Err(VmError::CompileError(e.to_string()));
+        }
+        if let Some(e) = parse_errors.into_iter().next() {
+            return Err(VmError::CompileError(e.to_string()));
+        }
+        self.run_program(&program)
+    }
+
+    /// Come `eval`, per un programma già parsato.
+    pub fn run_program(&mut self, program: &neba_parser::ast::Program) -> VmResult<Value> {
+        let chunk = Compiler::compile_incremental(program, &mut self.compile_state)?;
+        // Scarta i frame rimasti da uno snippet precedente (Halt o errore)
+        self.frames.clear();
+        self.stack.clear();
+        self.run_chunk(chunk)
+    }
+
+    /// VM per codice non fidato: niente I/O (`print`, `println`, `input`, modulo `io`),
+    /// budget di `SANDBOX_STEP_LIMIT` istruzioni (`VmError::StepLimitExceeded`) e di
+    /// `SANDBOX_ALLOC_LIMIT` byte (`VmError::ResourceLimit`). I budget si cambiano con
+    /// `set_step_limit` e `set_alloc_limit`.
+    pub fn sandboxed() -> Self {
+        let mut vm = Self::new();
+        stdlib::remove_sandboxed(&mut vm.globals);
+        vm.step_limit = SANDBOX_STEP_LIMIT;
+        vm.alloc_limit = SANDBOX_ALLOC_LIMIT;
+        vm
+    }
+
+    /// Con `true` la divisione `/` per zero in cui almeno un operando è Float
+    /// segue IEEE 754 (`1.0 / 0.0 == inf`, `0.0 / 0.0` è `nan`); con `false` (default,
+    /// come l'interprete) è `VmError::DivisionByZero` come per gli Int. `Int / 0` è
+    /// sempre un errore.
+    pub fn set_ieee_division(&mut self, on: bool) { self.ieee_division = on; }
+
+    /// Con `true`, `s[i]`, `s[a..b]`, `s[a:b]` e `for c in s` lavorano su grapheme cluster
+    /// estesi (`"e\u{301}"` è un solo carattere) invece che su code point (default).
+    /// `len(s)` conta sempre i code point.
+    #[cfg(feature = "graphemes")]
+    pub fn set_grapheme_strings(&mut self, on: bool) { self.graphemes = on; }
+
+    /// Con `true`, un array dichiarato `let a: Array[T] = ...` (o `var`) ricorda T:
+    /// push/append/insert e le assegnazioni per indice o slice di un elemento di altro
+    /// tipo falliscono con `VmError::TypeError`. Spento (default) non costa nulla.
+    pub fn set_checked_arrays(&mut self, on: bool) { self.checked_arrays = on; }
+
+    /// Unità di indicizzazione di una stringa: code point o, con `set_grapheme_strings`, grafemi.
+    fn str_units<'a>(&self, s: &'a str) -> Vec<&'a str> {
+        #[cfg(feature = "graphemes")]
+        if self.graphemes { return neba_runtime::graphemes(s); }
+        s.char_indices().map(|(i, c)| &s[i..i + c.len_utf8()]).collect()
+    }
+
+    /// Massimo numero di istruzioni eseguite da ogni `run_chunk` (0 = nessun limite);
+    /// oltre il limite l'esecuzione si interrompe con `VmError::StepLimitExceeded`.
     pub fn set_step_limit(&mut self, limit: u64) { self.step_limit = limit; }
 
+    /// Tetto approssimativo ai byte allocati da ogni `run_chunk` (0 = nessun limite):
+    /// si contano array, dict, istanze e stringhe costruite dal programma o dai
+    /// builtin (anche la crescita in place, come `push`), non i rilasci. Oltre il tetto l'esecuzione si interrompe con `VmError::ResourceLimit`.
+    pub fn set_alloc_limit(&mut self, bytes: usize) { self.alloc_limit = bytes; }
+
+    /// Addebita `bytes` al budget di memoria, prima di allocarli.
+    fn charge(&self, bytes: usize) -> VmResult<()> {
+        if self.alloc_limit == 0 { return Ok(()); }
+        let total = self.allocated.get().saturating_add(bytes);
+        self.allocated.set(total);
+        if total > self.alloc_limit {
+            return Err(VmError::ResourceLimit(format!("allocation limit of {} bytes exceeded", self.alloc_limit)));
+        }
+        Ok(())
+    }
+
+    /// Conta un'istruzione contro `step_limit`.
+    fn tick(&mut self) -> VmResult<()> {
+        self.steps += 1;
+        if self.steps > self.step_limit {
+            return Err(VmError::StepLimitExceeded(self.step_limit));
+        }
+        Ok(())
+    }
+
+    /// Definisce (o sostituisce) la globale `name`, visibile agli script eseguiti
+    /// dopo la chiamata. La variabile è mutabile, come una `var` top-level.
+    pub fn set_global(&mut self, name: &str, value: Value) -> &mut Self {
+        self.globals.insert(name.to_string(), (value, true));
+        self
+    }
+
+    /// Valore corrente della globale `name` (es. un risultato definito dallo script).
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).map(|(v, _)| v.clone())
+    }
+
+    /// Espone agli script una funzione dell'host come globale `name`.
+    /// Firma: `fn(&[Value]) -> Result<Value, String>`; un `Err` diventa un errore
+    /// runtime dello script. Restituisce `&mut Self` per concatenare le registrazioni.
+    /// Sovrascrivere un built-in con fast-path nella VM (`len`, `push`, `print`…) non è supportato.
+    pub fn register_native(&mut self, name: &str, f: crate::value::NativeFn) -> &mut Self {
+        self.globals.insert(name.to_string(), (Value::native_fn(name, f), false));
+        self
+    }
+
+    /// Chiama la funzione globale `name` (definita da uno script già eseguito o
+    /// registrata dall'host) con `args`: per usare Neba come linguaggio di plugin.
+    /// I parametri mancanti prendono i valori di default; limiti di passi e memoria
+    /// ripartono da zero come per `run_chunk`.
+    pub fn call_function(&mut self, name: &str, mut args: Vec<Value>) -> VmResult<Value> {
+        let callee = self.get_global(name).ok_or_else(|| VmError::UndefinedVariable(name.to_string()))?;
+        match &callee {
+            Value::Closure(c) => {
+                let proto = &c.proto;
+                if args.len() < proto.arity || args.len() > proto.max_arity {
+                    return Err(VmError::ArityMismatch { name: proto.name.clone(), expected: proto.arity, got: args.len() });
+                }
+                let missing = proto.max_arity - args.len();
+                for i in 0..missing {
+                    let di = proto.defaults.len().saturating_sub(missing - i);
+                    args.push(proto.defaults.get(di).cloned().unwrap_or(Value::None));
+                }
+            }
+            Value::NativeFn(..) => {}
+            other => return Err(VmError::NotCallable(other.type_name().to_string())),
+        }
+        self.steps = 0;
+        self.allocated.set(0);
+        let (frames, stack) = (self.frames.len(), self.stack.len());
+        let result = self.call_value_sync(callee, args);
+        if result.is_err() {
+            // Un errore a metà chiamata lascia frame e valori parziali: la VM resta riusabile
+            self.frames.truncate(frames);
+            self.stack.truncate(stack);
+        }
+        result
+    }
+
+    /// `assert_raises(f)`: chiama `f()` e fallisce se termina senza errori.
+    /// I limiti di passi e memoria non contano come errore atteso e si propagano.
+    fn assert_raises(&mut self, args: Vec<Value>) -> VmResult<Value> {
+        let [f] = <[Value; 1]>::try_from(args)
+            .map_err(|_| VmError::Generic("assert_raises(fn) requires 1 argument".into()))?;
+        let (frames, stack) = (self.frames.len(), self.stack.len());
+        match self.call_value_sync(f, Vec::new()) {
+            Ok(_) => Err(VmError::Generic("assert_raises: function did not raise".into())),
+            Err(e @ (VmError::StepLimitExceeded(_) | VmError::ResourceLimit(_))) => Err(e),
+            Err(_) => {
+                self.frames.truncate(frames);
+                self.stack.truncate(stack);
+                Ok(Value::None)
+            }
+        }
+    }
+
+    /// Combinatori Option/Result che chiamano una callback:
+    /// - `and_then(v, f)`: `Some(x)`/`Ok(x)` → `f(x)`; `None`/`Err` restano invariati
+    /// - `or_else(v, f)`: `None` → `f()`, `Err(e)` → `f(e)`; `Some`/`Ok` restano invariati
+    /// - `map_err(r, f)`: `Err(e)` → `Err(f(e))`; `Ok` resta invariato
+    fn combinator(&mut self, name: &str, args: Vec<Value>) -> VmResult<Value> {
+        let [v, f] = <[Value; 2]>::try_from(args)
+            .map_err(|_| VmError::Generic(format!("{}(value, fn) requires 2 arguments", name)))?;
+        match (name, v) {
+            ("and_then", Value::Some_(x) | Value::Ok_(x)) => self.call_value_sync(f, vec![*x]),
+            ("and_then", v @ (Value::None | Value::Err_(_))) => Ok(v),
+            ("or_else", Value::None) => self.call_value_sync(f, Vec::new()),
+            ("or_else", Value::Err_(e)) => self.call_value_sync(f, vec![*e]),
+            ("or_else", v @ (Value::Some_(_) | Value::Ok_(_))) => Ok(v),
+            ("map_err", Value::Err_(e)) => Ok(Value::Err_(Box::new(self.call_value_sync(f, vec![*e])?))),
+            ("map_err", v @ Value::Ok_(_)) => Ok(v),
+            ("map_err", v) => Err(VmError::TypeError(format!("map_err: expected Result, got {}", v.type_name()))),
+            (_, v) => Err(VmError::TypeError(format!("{}: expected Option or Result, got {}", name, v.type_name()))),
+        }
+    }
+
+    /// Posizione sorgente `(line, column)` dell'ultimo errore di `run_chunk`, se nota.
+    pub fn error_position(&self) -> Option<(u32, u32)> { self.error_pos }
+
     // ── run_chunk — dispatch loop monolitico (v0.2.14) ────────────────────
     //
     // Eliminati rispetto a v0.2.13:
@@ -149,6 +359,8 @@ impl Vm {
     //   • Rc::clone(&frame.chunk): rimosso — uso raw pointer *const Chunk
     //   • self.frames.last_mut().unwrap().ip = ip in ogni macro: rimosso —
     //     ip è una variabile locale, scritta nel frame solo su call/return
+    //   • self.frames.last() in LoadUpval/StoreUpval: rimosso — anche gli
+    //     upvalue del frame attivo sono in cache (upvals_ptr, come chunk_ptr)
     //
     // SAFETY: chunk_ptr punta a Rc<Chunk> owned da self.frames.last().chunk.
     //   Il Rc non viene droppato mentre il frame esiste. I contenuti di Chunk
@@ -159,44 +371,47 @@ impl Vm {
         let rc_chunk = Rc::new(chunk);
         self.frames.push(CallFrame {
             chunk: rc_chunk, ip: 0, base: 0,
-            name: "<script>".to_string(), upvalues: Rc::new(Vec::new()),
+            upvalues: Rc::new(Vec::new()),
         });
 
         let mut ip:   usize = 0;
         let mut base: usize = 0;
         // SAFETY: vedi commento sopra.
         let mut chunk_ptr: *const Chunk = Rc::as_ptr(&self.frames.last().unwrap().chunk);
+        // SAFETY: come chunk_ptr — gli upvalue del frame attivo vivono finché il frame è sullo stack.
+        let mut upvals_ptr: *const Vec<Upvalue> = Rc::as_ptr(&self.frames.last().unwrap().upvalues);
 
         macro_rules! chunk     { () => { unsafe { &*chunk_ptr } } }
+        macro_rules! upvals    { () => { unsafe { &*upvals_ptr } } }
         macro_rules! read_u8   { () => {{ let v = chunk!().code[ip]; ip += 1; v }} }
         macro_rules! read_u16  { () => {{ let v = crate::chunk::read_u16(&chunk!().code, ip); ip += 2; v }} }
         macro_rules! read_i16  { () => {{ let v = crate::chunk::read_i16(&chunk!().code, ip); ip += 2; v }} }
         macro_rules! pop   { () => { self.stack.pop().ok_or_else(|| VmError::Generic("stack underflow".into()))? } }
         macro_rules! peek  { () => { self.stack.last().ok_or_else(|| VmError::Generic("stack empty".into()))?.clone() } }
+        macro_rules! top_truthy { () => { self.stack.last().ok_or_else(|| VmError::Generic("stack empty".into()))?.is_truthy() } }
         macro_rules! push  { ($v:expr) => { self.stack.push($v) } }
         macro_rules! save_ip   { () => { self.frames.last_mut().unwrap().ip = ip; } }
         macro_rules! load_frame { () => {{
             let f = self.frames.last().unwrap();
-            chunk_ptr = Rc::as_ptr(&f.chunk);  // SAFETY: frame appena pushato/attivo
+            chunk_ptr  = Rc::as_ptr(&f.chunk);      // SAFETY: frame appena pushato/attivo
+            upvals_ptr = Rc::as_ptr(&f.upvalues);
             ip   = f.ip;
             base = f.base;
         }} }
 
-        let mut step_count: u64 = 0;
+        self.steps = 0;
+        self.allocated.set(0);
+        self.error_pos = None;
 
-        'dispatch: loop {
-            #[cfg(debug_assertions)]
+        // Il loop gira in una closure così che ogni `?` passi da qui: in caso di
+        // errore `ip` punta ancora all'istruzione fallita del frame in cima.
+        let mut result = (|| -> VmResult<Value> { 'dispatch: loop {
             if self.step_limit > 0 {
-                step_count += 1;
-                if step_count > self.step_limit {
-                    let trace = self.build_trace();
-                    eprintln!("{}", trace);
+                if let Err(e) = self.tick() {
                     self.frames.clear(); self.stack.clear();
-                    return Err(VmError::Generic(format!("step limit {} exceeded (infinite loop?)", self.step_limit)));
+                    return Err(e);
                 }
             }
-            #[cfg(not(debug_assertions))]
-            let _ = step_count;
 
             let op_byte = chunk!().code[ip];
             let op = Op::from_u8(op_byte)
@@ -213,6 +428,8 @@ impl Vm {
                 Op::Dup  => { let v = peek!(); push!(v); }
                 Op::Swap => { let t = pop!(); let u = pop!(); push!(t); push!(u); }
                 Op::PopN => { let n = read_u8!() as usize; let l = self.stack.len(); self.stack.truncate(l - n); }
+                Op::Rot  => { let n = read_u8!() as usize; let l = self.stack.len(); self.stack[l - n..].rotate_right(1); }
+                Op::Over => { let v = self.stack[self.stack.len() - 2].clone(); push!(v); }
 
                 Op::LoadLocal  => { let idx = read_u8!() as usize; let v = self.stack[base + idx].clone(); push!(v); }
                 Op::StoreLocal => { let idx = read_u8!() as usize; let v = pop!(); self.stack[base + idx] = v; }
@@ -227,8 +444,8 @@ impl Vm {
                 Op::StoreLocal2 => { let v = pop!(); self.stack[base + 2] = v; }
                 Op::StoreLocal3 => { let v = pop!(); self.stack[base + 3] = v; }
 
-                Op::LoadUpval  => { let idx = read_u8!() as usize; let v = self.frames.last().unwrap().upvalues[idx].value.borrow().clone(); push!(v); }
-                Op::StoreUpval => { let idx = read_u8!() as usize; let v = pop!(); *self.frames.last_mut().unwrap().upvalues[idx].value.borrow_mut() = v; }
+                Op::LoadUpval  => { let idx = read_u8!() as usize; let v = upvals!()[idx].value.borrow().clone(); push!(v); }
+                Op::StoreUpval => { let idx = read_u8!() as usize; let v = pop!(); *upvals!()[idx].value.borrow_mut() = v; }
 
                 Op::LoadGlobal => {
                     let idx = read_u16!() as usize;
@@ -247,6 +464,24 @@ impl Vm {
                         None               => return Err(VmError::UndefinedVariable(name)),
                     }
                 }
+                Op::AddStoreLocal => {
+                    let idx = read_u8!() as usize; let r = pop!(); let l = pop!();
+                    self.add_store_local(base + idx, l, r)?;
+                }
+                Op::AddStoreGlobal => {
+                    let idx = read_u16!() as usize;
+                    let name = chunk!().names[idx].clone();
+                    let r = pop!(); let l = pop!();
+                    self.add_store_global(name, l, r)?;
+                }
+                Op::TagArray => {
+                    let idx = read_u16!() as usize;
+                    if self.checked_arrays {
+                        if let Some(Value::Array(a)) = self.stack.last() {
+                            crate::value::tag_array(a, &chunk!().names[idx]).map_err(VmError::TypeError)?;
+                        }
+                    }
+                }
                 Op::DefGlobal => {
                     let idx = read_u16!() as usize; let mutable = read_u8!() != 0;
                     let name = chunk!().names[idx].clone(); let v = pop!();
@@ -405,8 +640,8 @@ impl Vm {
                 Op::Jump          => { let o = read_i16!(); ip = (ip as isize + o as isize) as usize; }
                 Op::JumpFalse     => { let o = read_i16!(); let v = pop!(); if !v.is_truthy() { ip = (ip as isize + o as isize) as usize; } }
                 Op::JumpTrue      => { let o = read_i16!(); let v = pop!(); if  v.is_truthy() { ip = (ip as isize + o as isize) as usize; } }
-                Op::JumpFalsePeek => { let o = read_i16!(); if !peek!().is_truthy() { ip = (ip as isize + o as isize) as usize; } }
-                Op::JumpTruePeek  => { let o = read_i16!(); if  peek!().is_truthy() { ip = (ip as isize + o as isize) as usize; } }
+                Op::JumpFalseOrPop => { let o = read_i16!(); if top_truthy!()  { self.stack.pop(); } else { ip = (ip as isize + o as isize) as usize; } }
+                Op::JumpTrueOrPop  => { let o = read_i16!(); if !top_truthy!() { self.stack.pop(); } else { ip = (ip as isize + o as isize) as usize; } }
 
                 Op::MakeClosure => {
                     let idx = read_u16!() as usize; let n_up = read_u8!() as usize;
@@ -436,13 +671,10 @@ impl Vm {
                                 let (arr, cb) = (args[0].clone(), args[1].clone());
                                 let items: Vec<Value> = match &arr {
                                     Value::Array(a) => a.borrow().clone(),
-                                    Value::IntRange(s, e, inc) => {
-                                        let (s, e, inc) = (*s, *e, *inc);
-                                        if inc { (s..=e).map(Value::Int).collect() }
-                                        else   { (s..e).map(Value::Int).collect() }
-                                    }
+                                    Value::IntRange(s, e, inc) => crate::value::range_values(*s, *e, *inc),
                                     _ => return Err(VmError::TypeError(format!("map: first argument must be Array or Range, got {}", arr.type_name()))),
                                 };
+                                self.charge(items.len() * VALUE_SIZE)?;
                                 let mut result = Vec::with_capacity(items.len());
                                 save_ip!();
                                 for item in items { result.push(self.call_value_sync(cb.clone(), vec![item])?); }
@@ -455,16 +687,13 @@ impl Vm {
                                 let (arr, cb) = (args[0].clone(), args[1].clone());
                                 let items: Vec<Value> = match &arr {
                                     Value::Array(a) => a.borrow().clone(),
-                                    Value::IntRange(s, e, inc) => {
-                                        let (s, e, inc) = (*s, *e, *inc);
-                                        if inc { (s..=e).map(Value::Int).collect() }
-                                        else   { (s..e).map(Value::Int).collect() }
-                                    }
+                                    Value::IntRange(s, e, inc) => crate::value::range_values(*s, *e, *inc),
                                     _ => return Err(VmError::TypeError(format!("filter: first argument must be Array or Range, got {}", arr.type_name()))),
                                 };
                                 let mut result = Vec::new();
                                 save_ip!();
                                 for item in items { let k = self.call_value_sync(cb.clone(), vec![item.clone()])?; if k.is_truthy() { result.push(item); } }
+                                self.charge(result.len() * VALUE_SIZE)?;
                                 push!(Value::array(result));
                                 continue 'dispatch;
                             }
@@ -474,11 +703,7 @@ impl Vm {
                                 let (arr, cb) = (args[0].clone(), args[1].clone());
                                 let items: Vec<Value> = match &arr {
                                     Value::Array(a) => a.borrow().clone(),
-                                    Value::IntRange(s, e, inc) => {
-                                        let (s, e, inc) = (*s, *e, *inc);
-                                        if inc { (s..=e).map(Value::Int).collect() }
-                                        else   { (s..e).map(Value::Int).collect() }
-                                    }
+                                    Value::IntRange(s, e, inc) => crate::value::range_values(*s, *e, *inc),
                                     _ => return Err(VmError::TypeError(format!("reduce: first argument must be Array or Range, got {}", arr.type_name()))),
                                 };
                                 let (mut acc, si) = if argc == 3 { (args[2].clone(), 0) }
@@ -488,6 +713,20 @@ impl Vm {
                                 push!(acc);
                                 continue 'dispatch;
                             }
+                            "assert_raises" => {
+                                let args: Vec<Value> = self.stack.drain(fn_idx..).skip(1).collect();
+                                save_ip!();
+                                let r = self.assert_raises(args)?;
+                                push!(r);
+                                continue 'dispatch;
+                            }
+                            "and_then" | "or_else" | "map_err" => {
+                                let args: Vec<Value> = self.stack.drain(fn_idx..).skip(1).collect();
+                                save_ip!();
+                                let r = self.combinator(name, args)?;
+                                push!(r);
+                                continue 'dispatch;
+                            }
                             // str(v) → chiama __str__ se è un'Instance
                             "str" => {
                                 let args: Vec<Value> = self.stack.drain(fn_idx..).skip(1).collect();
@@ -505,13 +744,10 @@ impl Vm {
                                 if let Value::Bool(rev) = cmp {
                                     let items: Vec<Value> = match &arr_val {
                                         Value::Array(a) => a.borrow().clone(),
-                                        Value::IntRange(s, e, inc) => {
-                                            let (s, e, inc) = (*s, *e, *inc);
-                                            if inc { (s..=e).map(Value::Int).collect() }
-                                            else   { (s..e).map(Value::Int).collect() }
-                                        }
+                                        Value::IntRange(s, e, inc) => crate::value::range_values(*s, *e, *inc),
                                         _ => return Err(VmError::TypeError(format!("sorted: expected Array or Range, got {}", arr_val.type_name()))),
                                     };
+                                    self.charge(items.len() * VALUE_SIZE)?;
                                     let mut v = items;
                                     v.sort_by(|x, y| {
                                         let ord = x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal);
@@ -525,13 +761,10 @@ impl Vm {
                                     || matches!(&cmp, Value::NativeFn(_, _)); // native fns used as key
                                 let items: Vec<Value> = match &arr_val {
                                     Value::Array(a) => a.borrow().clone(),
-                                    Value::IntRange(s, e, inc) => {
-                                        let (s, e, inc) = (*s, *e, *inc);
-                                        if inc { (s..=e).map(Value::Int).collect() }
-                                        else   { (s..e).map(Value::Int).collect() }
-                                    }
+                                    Value::IntRange(s, e, inc) => crate::value::range_values(*s, *e, *inc),
                                     _ => return Err(VmError::TypeError(format!("sorted: expected Array or Range, got {}", arr_val.type_name()))),
                                 };
+                                self.charge(items.len() * VALUE_SIZE)?;
                                 save_ip!();
                                 if is_key_fn {
                                     // Key extractor: compute keys once, then sort by key
@@ -540,7 +773,7 @@ impl Vm {
                                         let k = self.call_value_sync(cmp.clone(), vec![item.clone()])?;
                                         keyed.push((k, item));
                                     }
-                                    let mut sort_err: Option<VmError> = None;
+                                    let sort_err: Option<VmError> = None;
                                     keyed.sort_by(|(ka, _), (kb, _)| {
                                         if sort_err.is_some() { return std::cmp::Ordering::Equal; }
                                         ka.partial_cmp(kb).unwrap_or(std::cmp::Ordering::Equal)
@@ -572,11 +805,7 @@ impl Vm {
                                 let (arr, cb) = (args[0].clone(), args[1].clone());
                                 let items: Vec<Value> = match &arr {
                                     Value::Array(a) => a.borrow().clone(),
-                                    Value::IntRange(s, e, inc) => {
-                                        let (s, e, inc) = (*s, *e, *inc);
-                                        if inc { (s..=e).map(Value::Int).collect() }
-                                        else   { (s..e).map(Value::Int).collect() }
-                                    }
+                                    Value::IntRange(s, e, inc) => crate::value::range_values(*s, *e, *inc),
                                     _ => return Err(VmError::TypeError(format!("find: first argument must be Array or Range, got {}", arr.type_name()))),
                                 };
                                 save_ip!();
@@ -612,7 +841,7 @@ impl Vm {
                                 let arr = self.stack.pop().ok_or_else(|| VmError::Generic("stack underflow".into()))?;
                                 self.stack.pop(); // callee slot
                                 match arr {
-                                    Value::Array(a) => { a.borrow_mut().push(val); push!(Value::None); }
+                                    Value::Array(a) => { crate::value::check_mutable(&a, "push").map_err(VmError::TypeError)?; crate::value::check_elem(&a, &val, "push").map_err(VmError::TypeError)?; self.charge(VALUE_SIZE)?; a.borrow_mut().push(val); push!(Value::None); }
                                     _ => return Err(VmError::TypeError("push: first arg must be Array".into())),
                                 }
                                 continue 'dispatch;
@@ -622,7 +851,7 @@ impl Vm {
                                 let arr = self.stack.pop().ok_or_else(|| VmError::Generic("stack underflow".into()))?;
                                 self.stack.pop(); // callee slot
                                 match arr {
-                                    Value::Array(a) => { push!(a.borrow_mut().pop().unwrap_or(Value::None)); }
+                                    Value::Array(a) => { crate::value::check_mutable(&a, "pop").map_err(VmError::TypeError)?; push!(a.borrow_mut().pop().unwrap_or(Value::None)); }
                                     _ => return Err(VmError::TypeError("pop: arg must be Array".into())),
                                 }
                                 continue 'dispatch;
@@ -634,26 +863,41 @@ impl Vm {
                                 let n = match &v {
                                     Value::Array(a)      => a.borrow().len() as i64,
                                     Value::Str(s)        => s.chars().count() as i64,
+                                    Value::Char(_)       => 1,
                                     Value::Dict(d)       => d.borrow().len() as i64,
                                     Value::TypedArray(t) => t.borrow().len() as i64,
                                     Value::NdArray(nd)   => nd.borrow().size() as i64,
-                                    Value::IntRange(s, e, inc) => {
-                                        let range = if *inc { e - s + 1 } else { e - s };
-                                        range.max(0)
-                                    }
+                                    Value::IntRange(s, e, inc) => crate::value::range_len(*s, *e, *inc),
                                     _ => return Err(VmError::TypeError(format!("len: unsupported type {}", v.type_name()))),
                                 };
                                 push!(Value::Int(n));
                                 continue 'dispatch;
                             }
+                            // print/println: le Instance con `__str__` vengono convertite prima
+                            // della chiamata nativa, che poi procede normalmente
+                            // funzioni Dict con chiave Instance: risolve la chiave con __hash__/__eq__
+                            "has_key" | "dict_get" | "del_key" if argc >= 2 && matches!(self.stack[fn_idx + 2], Value::Instance(_)) => {
+                                save_ip!();
+                                let (d, k) = (self.stack[fn_idx + 1].clone(), self.stack[fn_idx + 2].clone());
+                                self.stack[fn_idx + 2] = self.dict_key(&d, k)?;
+                            }
+                            "print" | "println" if self.stack[fn_idx + 1..].iter().any(may_hold_instance) => {
+                                save_ip!();
+                                for i in fn_idx + 1..self.stack.len() {
+                                    if may_hold_instance(&self.stack[i]) {
+                                        let s = self.value_display_string(self.stack[i].clone())?;
+                                        self.stack[i] = Value::str(s);
+                                    }
+                                }
+                            }
                             _ => {}
                         }
                     }
 
                     match callee {
-                        Value::NativeFn(_, f) => {
+                        Value::NativeFn(n, f) => {
                             let args: Vec<Value> = self.stack.drain(fn_idx..).skip(1).collect();
-                            push!(f(&args).map_err(VmError::Generic)?);
+                            push!(self.call_native(&n, f, args)?);
                         }
                         Value::Closure(c) => {
                             let proto = &c.proto;
@@ -670,7 +914,7 @@ impl Vm {
                             save_ip!();
                             self.frames.push(CallFrame {
                                 chunk: Rc::clone(&proto.chunk), ip: 0,
-                                base: new_base, name: proto.name.clone(),
+                                base: new_base,
                                 upvalues: Rc::clone(&c.upvalues),
                             });
                             load_frame!();
@@ -710,6 +954,12 @@ impl Vm {
                         // value alias for Option
                         (Value::Some_(inner), "value")  => { self.stack.drain(obj_idx..); Some(*inner.clone()) }
                         (Value::None, "value")          => { self.stack.drain(obj_idx..); Some(Value::None) }
+                        // Lunghezza in byte UTF-8 (`len` conta i code point)
+                        (Value::Str(st), "byte_len") if argc == 0 => { let n = st.len() as i64; self.stack.drain(obj_idx..); Some(Value::Int(n)) }
+                        (Value::Str(st), "format" | "format**") => {
+                            let args: Vec<Value> = self.stack.drain(obj_idx..).skip(1).collect();
+                            Some(self.str_format(st, args, name.as_str() == "format**")?)
+                        }
                         _ => None,
                     };
                     if let Some(result) = builtin_result { push!(result); continue 'dispatch; }
@@ -742,15 +992,15 @@ impl Vm {
                             save_ip!();
                             self.frames.push(CallFrame {
                                 chunk: Rc::clone(&proto.chunk), ip: 0,
-                                base: new_base, name: proto.name.clone(),
+                                base: new_base,
                                 upvalues: Rc::clone(&c.upvalues),
                             });
                             load_frame!();
                         }
-                        Value::NativeFn(_, f) => {
+                        Value::NativeFn(n, f) => {
                             let args: Vec<Value> = if is_module { self.stack.drain(obj_idx..).collect() }
                                                    else         { self.stack.drain(obj_idx..).skip(1).collect() };
-                            push!(f(&args).map_err(VmError::Generic)?);
+                            push!(self.call_native(&n, f, args)?);
                         }
                         other => return Err(VmError::NotCallable(other.type_name().to_string())),
                     }
@@ -775,18 +1025,26 @@ impl Vm {
 
                 Op::MakeArray => {
                     let count = read_u16!() as usize;
+                    self.charge(count * VALUE_SIZE)?;
                     let start = self.stack.len() - count;
                     let items: Vec<Value> = self.stack.drain(start..).collect();
                     push!(Value::array(items));
                 }
                 Op::MakeDict => {
                     let count = read_u16!() as usize;
+                    self.charge(count * 2 * VALUE_SIZE)?;
                     let start = self.stack.len() - count * 2;
                     let flat: Vec<Value> = self.stack.drain(start..).collect();
-                    let pairs: Vec<(Value, Value)> = flat.chunks(2).map(|c| (c[0].clone(), c[1].clone())).collect();
-                    push!(Value::dict(pairs));
+                    save_ip!();
+                    let dict = self.build_dict(flat)?;
+                    push!(dict);
+                }
+                Op::GetIndex => {
+                    let i = pop!(); let o = pop!();
+                    if let Value::Instance(_) = i { save_ip!(); }
+                    let i = self.dict_key(&o, i)?;
+                    push!(self.eval_index(o, i)?);
                 }
-                Op::GetIndex => { let i = pop!(); let o = pop!(); push!(self.eval_index(o, i)?); }
                 Op::GetSlice => {
                     let flags = read_u8!();
                     let step  = if flags & 4 != 0 { Some(pop!()) } else { None };
@@ -795,13 +1053,27 @@ impl Vm {
                     let obj   = pop!();
                     push!(self.eval_slice(obj, start, end, step)?);
                 }
+                Op::SetSlice => {
+                    let flags = read_u8!();
+                    let val   = pop!();
+                    let end   = if flags & 2 != 0 { Some(pop!()) } else { None };
+                    let start = if flags & 1 != 0 { Some(pop!()) } else { None };
+                    let obj   = pop!();
+                    self.assign_slice(obj, start, end, val)?;
+                }
                 Op::SetIndex  => {
                     let val = pop!(); let idx_v = pop!(); let obj = pop!();
+                    if let Value::Instance(_) = idx_v { save_ip!(); }
+                    let idx_v = self.dict_key(&obj, idx_v)?;
                     match (obj, &idx_v) {
-                        (Value::Array(arr), Value::Int(i)) => { let len = arr.borrow().len(); let i = self.resolve_idx(*i, len)?; arr.borrow_mut()[i] = val; }
+                        (Value::Array(arr), Value::Int(i)) => {
+                            crate::value::check_mutable(&arr, "index assignment").map_err(VmError::TypeError)?;
+                            crate::value::check_elem(&arr, &val, "index assignment").map_err(VmError::TypeError)?;
+                            let len = arr.borrow().len(); let i = self.resolve_idx(*i, len)?; arr.borrow_mut()[i] = val;
+                        }
                         (Value::TypedArray(t), Value::Int(i)) => { let len = t.borrow().len(); let i = crate::value::resolve_idx(*i, len).map_err(VmError::Generic)?; t.borrow_mut().set(i, val).map_err(VmError::TypeError)?; }
                         (Value::NdArray(nd), Value::Int(i)) => {
-                            let mut n = nd.borrow_mut();
+                            let n = nd.borrow_mut();
                             let ui = if *i < 0 { (n.shape[0] as i64 + i) as usize } else { *i as usize };
                             if n.ndim() == 1 { n.set_flat(ui, val).map_err(VmError::Generic)?; }
                             else { return Err(VmError::TypeError("NdArray 2D+: use nd.set(arr, [i,j], val) or arr[i,j]=val".into())); }
@@ -814,7 +1086,7 @@ impl Vm {
                             }).collect::<Result<_,_>>().map_err(VmError::Generic)?;
                             nd.borrow_mut().set_nd(&indices, val).map_err(VmError::Generic)?;
                         }
-                        (Value::Dict(d), key) => { d.borrow_mut().insert(key.clone(), val); }
+                        (Value::Dict(d), key) => { if !d.borrow().contains_key(key) { self.charge(2 * VALUE_SIZE)?; } d.borrow_mut().insert(key.clone(), val); }
                         _ => return Err(VmError::TypeError("index assignment requires Array, TypedArray, NdArray or Dict".into())),
                     }
                 }
@@ -840,6 +1112,7 @@ impl Vm {
                 }
                 Op::MakeInstance => {
                     let idx = read_u16!() as usize; let cn = chunk!().names[idx].clone();
+                    self.charge(std::mem::size_of::<Instance>())?;
                     push!(Value::Instance(Rc::new(RefCell::new(Instance::new(&cn)))));
                 }
                 Op::SetTraits => {
@@ -871,25 +1144,9 @@ impl Vm {
                         other => return Err(VmError::TypeError(format!("operatore ? applicato a {} (richiede Ok o Err)", other.type_name()))),
                     }
                 }
-                Op::In    => { let h = pop!(); let n = pop!(); push!(Value::Bool(self.eval_in(n, h)?)); }
-                Op::NotIn => { let h = pop!(); let n = pop!(); push!(Value::Bool(!self.eval_in(n, h)?)); }
-                Op::Is    => {
-                    let r = pop!(); let l = pop!();
-                    let result = match (&l, &r) {
-                        (Value::Instance(inst), Value::Str(s)) if !s.starts_with("trait:") => {
-                            inst.borrow().class_name == s.as_str()
-                        }
-                        (Value::Instance(inst), Value::Str(s)) if s.starts_with("trait:") => {
-                            let tn = &s["trait:".len()..];
-                            inst.borrow().traits.iter().any(|t| t == tn)
-                        }
-                        (Value::Instance(a), Value::Instance(b)) => {
-                            a.borrow().class_name == b.borrow().class_name
-                        }
-                        _ => std::mem::discriminant(&l) == std::mem::discriminant(&r),
-                    };
-                    push!(Value::Bool(result));
-                }
+                Op::In    => { let h = pop!(); let n = pop!(); save_ip!(); let n = self.dict_key(&h, n)?; push!(Value::Bool(self.eval_in(n, h)?)); }
+                Op::NotIn => { let h = pop!(); let n = pop!(); save_ip!(); let n = self.dict_key(&h, n)?; push!(Value::Bool(!self.eval_in(n, h)?)); }
+                Op::Is    => { let r = pop!(); let l = pop!(); push!(Value::Bool(op_is(&l, &r))); }
 
                 Op::IsSome    => { let o = read_i16!(); if !matches!(peek!(), Value::Some_(_)) { ip = (ip as isize + o as isize) as usize; } }
                 Op::IsNone    => { let o = read_i16!(); if !matches!(peek!(), Value::None)     { ip = (ip as isize + o as isize) as usize; } }
@@ -905,6 +1162,18 @@ impl Vm {
                     let lit = chunk!().constants[ci].clone();
                     if peek!() != lit { ip = (ip as isize + o as isize) as usize; }
                 }
+                Op::MatchAffix => {
+                    let (pi, si, o) = (read_u16!() as usize, read_u16!() as usize, read_i16!());
+                    if affix_middle(&peek!(), &chunk!().constants[pi], &chunk!().constants[si]).is_none() {
+                        ip = (ip as isize + o as isize) as usize;
+                    }
+                }
+                Op::StripAffix => {
+                    let (pi, si) = (read_u16!() as usize, read_u16!() as usize);
+                    let v = pop!();
+                    push!(affix_middle(&v, &chunk!().constants[pi], &chunk!().constants[si])
+                        .ok_or_else(|| VmError::TypeError(format!("string pattern on {}", v.type_name())))?);
+                }
                 Op::MatchRange => {
                     let li = read_u16!() as usize; let hi = read_u16!() as usize;
                     let incl = read_u8!() != 0; let off = read_i16!();
@@ -922,7 +1191,8 @@ impl Vm {
                         Value::Array(a) => a,
                         Value::Dict(d)  => { let pairs: Vec<Value> = d.borrow().iter().map(|(k,v): (&Value,&Value)| Value::array(vec![k.clone(),v.clone()])).collect(); Rc::new(RefCell::new(pairs)) }
                         Value::TypedArray(t) => { let d = t.borrow(); let elems: Vec<Value> = (0..d.len()).map(|i| d.get(i).unwrap()).collect(); Rc::new(RefCell::new(elems)) }
-                        Value::Str(s)   => { let chars: Vec<Value> = s.chars().map(|c| Value::str(c.to_string())).collect(); Rc::new(RefCell::new(chars)) }
+                        Value::Str(s)   => { let chars: Vec<Value> = self.str_units(&s).into_iter().map(Value::str).collect(); Rc::new(RefCell::new(chars)) }
+                        Value::Char(_)  => Rc::new(RefCell::new(vec![v])),
                         _ => return Err(VmError::TypeError(format!("'{}' is not iterable", v.type_name()))),
                     };
                     push!(Value::Array(arr));
@@ -935,12 +1205,10 @@ impl Vm {
                         // v0.2.14: range intero lazy — nessuna allocazione, solo aritmetica
                         Value::IntRange(start, end, inclusive) => {
                             let pos = match &self.stack[base + pl] { Value::Int(n) => *n, _ => 0 };
-                            let actual = start + pos;
-                            let done = if inclusive { actual > end } else { actual >= end };
-                            if done {
+                            if pos >= crate::value::range_len(start, end, inclusive) {
                                 ip = (ip as isize + off as isize) as usize;
                             } else {
-                                self.stack[base + vl] = Value::Int(actual);
+                                self.stack[base + vl] = Value::Int(crate::value::range_nth(start, pos));
                                 self.stack[base + pl] = Value::Int(pos + 1);
                             }
                         }
@@ -965,6 +1233,7 @@ impl Vm {
                     for val in vals {
                         parts.push(self.value_display_string(val)?);
                     }
+                    self.charge(parts.iter().map(String::len).sum())?;
                     push!(Value::str(parts.join("")));
                 }
                 Op::ToStr => {
@@ -975,11 +1244,20 @@ impl Vm {
                 Op::Nop   => {}
                 Op::Halt  => { return Ok(self.stack.pop().unwrap_or(Value::None)); }
             }
+        } })();
+
+        if result.is_err() {
+            self.error_pos = self.frames.last().map(|f| f.chunk.pos_at(ip.saturating_sub(1)));
+            if let Err(VmError::IndexOutOfBounds { pos: pos @ None, .. }) = &mut result {
+                *pos = self.error_pos;
+            }
         }
+        result
     }
 
     /// Esegue lo slicing: obj[start:end] o obj[start:end:step]
     fn eval_slice(&self, obj: Value, start: Option<Value>, end: Option<Value>, step: Option<Value>) -> VmResult {
+        let obj = obj.char_as_str();
         // Converti start/end in Option<i64>
         let to_opt_int = |v: Option<Value>| -> Result<Option<i64>, VmError> {
             match v {
@@ -1012,11 +1290,11 @@ impl Vm {
                 Ok(Value::array(result))
             }
             Value::Str(s_rc) => {
-                let chars: Vec<char> = s_rc.chars().collect();
+                let chars = self.str_units(&s_rc);
                 let n = chars.len() as i64;
                 let (s, e) = Self::resolve_slice_bounds(to_opt_int(start)?, to_opt_int(end)?, step_val, n);
                 let result: String = if step_val > 0 {
-                    (s..e).step_by(step_val as usize).filter_map(|i| chars.get(i as usize)).collect()
+                    (s..e).step_by(step_val as usize).filter_map(|i| chars.get(i as usize).copied()).collect()
                 } else {
                     let mut v = Vec::new();
                     let mut i = s;
@@ -1044,8 +1322,36 @@ impl Vm {
         }
     }
 
+    /// `obj[start:end] = val` — sostituisce l'intervallo con gli elementi di `val`
+    /// (splice: la lunghezza può cambiare). Gli estremi fuori range vengono limitati.
+    fn assign_slice(&self, obj: Value, start: Option<Value>, end: Option<Value>, val: Value) -> VmResult<()> {
+        let bound = |v: Option<Value>| -> VmResult<Option<i64>> {
+            match v {
+                None | Some(Value::None) => Ok(None),
+                Some(Value::Int(n)) => Ok(Some(n)),
+                Some(other) => Err(VmError::TypeError(format!("slice index must be Int, got {}", other.type_name()))),
+            }
+        };
+        let Value::Array(a) = obj else {
+            return Err(VmError::TypeError(format!("slice assignment requires Array, got {}", obj.type_name())));
+        };
+        crate::value::check_mutable(&a, "slice assignment").map_err(VmError::TypeError)?;
+        let items: Vec<Value> = match val {
+            // copia prima del borrow_mut: `a[0:1] = a` è valido
+            Value::Array(src) => src.borrow().clone(),
+            other => return Err(VmError::TypeError(format!("slice assignment requires an Array value, got {}", other.type_name()))),
+        };
+        for item in &items {
+            crate::value::check_elem(&a, item, "slice assignment").map_err(VmError::TypeError)?;
+        }
+        let n = a.borrow().len() as i64;
+        let (s, e) = Self::resolve_slice_bounds(bound(start)?, bound(end)?, 1, n);
+        a.borrow_mut().splice(s as usize..e as usize, items);
+        Ok(())
+    }
+
     fn resolve_slice_bounds(start: Option<i64>, end: Option<i64>, step: i64, n: i64) -> (i64, i64) {
-        let normalize = |i: i64, default_pos: i64, default_neg: i64| -> i64 {
+        let _normalize = |i: i64, _default_pos: i64, default_neg: i64| -> i64 {
             let _ = default_neg; // unused for now
             if i < 0 { (n + i).max(0) } else { i.min(n) }
         };
@@ -1054,42 +1360,187 @@ impl Vm {
             let e = end.map(|i| if i < 0 { (n + i).max(0) } else { i.min(n) }).unwrap_or(n);
             (s, e.max(s))
         } else {
-            let s = start.map(|i| if i < 0 { (n + i).max(-1) } else { (i.min(n - 1)) }).unwrap_or(n - 1);
+            let s = start.map(|i| if i < 0 { (n + i).max(-1) } else { i.min(n - 1)  }).unwrap_or(n - 1);
             let e = end.map(|i| if i < 0 { (n + i).max(-1) } else { i.min(n) }).unwrap_or(-1);
             (s, e)
         }
     }
 
-    /// Converte un Value in stringa per display, chiamando `__str__` se è un'Instance.
-    fn value_display_string(&mut self, val: Value) -> VmResult<String> {
-        if let Value::Instance(ref inst) = val {
-            let str_method = inst.borrow().fields.get("__str__").cloned();
-            if let Some(method) = str_method {
-                let result = self.call_value_sync(method, vec![val])?;
-                return Ok(result.to_string());
+    /// Risolve una chiave Instance verso la chiave equivalente già presente nel Dict.
+    /// Le Instance usate come chiave devono definire sia `__hash__` che `__eq__`:
+    /// `__hash__` viene ricalcolato a ogni accesso e sceglie il bucket del Dict
+    /// (`DictMap::instance_keys`), poi `__eq__` viene chiamato solo sulle chiavi di
+    /// quel bucket. Una chiave nuova viene registrata nel bucket con l'hash attuale.
+    fn dict_key(&mut self, dict: &Value, key: Value) -> VmResult<Value> {
+        let (Value::Dict(d), Value::Instance(inst)) = (dict, &key) else { return Ok(key) };
+        let (hash_m, eq_m) = {
+            let i = inst.borrow();
+            (i.fields.get("__hash__").cloned(), i.fields.get("__eq__").cloned())
+        };
+        let (Some(hash_m), Some(eq_m)) = (hash_m, eq_m) else {
+            return Err(VmError::TypeError(format!(
+                "unhashable instance of {}: define both __hash__ and __eq__ to use it as a Dict key",
+                inst.borrow().class_name)));
+        };
+        let mut state = neba_runtime::StableHasher::default();
+        self.call_value_sync(hash_m, vec![key.clone()])?.hash(&mut state);
+        let h = state.finish();
+        let candidates = d.borrow_mut().instance_keys(h);
+        for k in candidates {
+            if matches!(&k, Value::Instance(o) if Rc::ptr_eq(o, inst)) { return Ok(k); }
+            if self.call_value_sync(eq_m.clone(), vec![key.clone(), k.clone()])?.is_truthy() {
+                return Ok(k);
             }
         }
-        Ok(val.to_string())
+        d.borrow_mut().record_instance_key(h, key.clone());
+        Ok(key)
     }
 
-    fn build_trace(&self) -> String {
-        let mut out = String::from("Traceback:\n");
-        for frame in self.frames.iter().rev() {
-            let line = frame.chunk.line_at(frame.ip.saturating_sub(1));
-            out.push_str(&format!("  at {} (line {})\n", frame.name, line));
+    /// Costruisce un Dict da coppie chiave/valore, risolvendo le chiavi Instance con `dict_key`.
+    fn build_dict(&mut self, flat: Vec<Value>) -> VmResult<Value> {
+        let dict = Value::dict(Vec::new());
+        let mut it = flat.into_iter();
+        while let (Some(k), Some(v)) = (it.next(), it.next()) {
+            let k = self.dict_key(&dict, k)?;
+            if let Value::Dict(d) = &dict { d.borrow_mut().insert(k, v); }
         }
-        out
+        Ok(dict)
+    }
+
+    /// Converte un Value in stringa per display, chiamando `__str__` sulle Instance,
+    /// anche dentro Array, Dict e Option/Result.
+    fn value_display_string(&mut self, val: Value) -> VmResult<String> {
+        self.display_nested(val, &mut Vec::new())
+    }
+
+    /// `path`: contenitori in corso di stampa, come `fmt_guarded` per `Display`.
+    fn display_nested(&mut self, val: Value, path: &mut Vec<usize>) -> VmResult<String> {
+        Ok(match val {
+            Value::Instance(ref inst) => {
+                let str_method = inst.borrow().fields.get("__str__").cloned();
+                match str_method {
+                    Some(method) => self.call_value_sync(method, vec![val])?.to_string(),
+                    None => val.to_string(),
+                }
+            }
+            Value::Array(ref a) => {
+                let ptr = Rc::as_ptr(a) as usize;
+                if path.contains(&ptr) { return Ok("[...]".to_string()); }
+                path.push(ptr);
+                let items = a.borrow().clone();
+                let parts = items.into_iter().map(|x| self.display_nested(x, path)).collect::<VmResult<Vec<_>>>()?;
+                path.pop();
+                format!("[{}]", parts.join(", "))
+            }
+            Value::Dict(ref d) => {
+                let ptr = Rc::as_ptr(d) as usize;
+                if path.contains(&ptr) { return Ok("{...}".to_string()); }
+                path.push(ptr);
+                let pairs: Vec<(Value, Value)> = d.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                let mut parts = Vec::with_capacity(pairs.len());
+                for (k, v) in pairs {
+                    parts.push(format!("{}: {}", self.display_nested(k, path)?, self.display_nested(v, path)?));
+                }
+                path.pop();
+                format!("{{{}}}", parts.join(", "))
+            }
+            Value::Some_(x) => format!("Some({})", self.display_nested(*x, path)?),
+            Value::Ok_(x)   => format!("Ok({})", self.display_nested(*x, path)?),
+            Value::Err_(x)  => format!("Err({})", self.display_nested(*x, path)?),
+            other => other.to_string(),
+        })
+    }
+
+    /// `template.format(args)`; con `named` l'ultimo argomento è il Dict dei kwargs.
+    /// Gli argomenti passano da `value_display_string`, come in `str()`.
+    fn str_format(&mut self, template: &str, mut args: Vec<Value>, named: bool) -> VmResult<Value> {
+        let kwargs = match (named, args.pop()) {
+            (true, Some(Value::Dict(d))) => {
+                let pairs: Vec<(String, Value)> = d.borrow().iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+                pairs.into_iter().map(|(k, v)| Ok((k, self.value_display_string(v)?))).collect::<VmResult<Vec<_>>>()?
+            }
+            (_, last) => { args.extend(last); Vec::new() }
+        };
+        let positional = args.into_iter().map(|v| self.value_display_string(v)).collect::<VmResult<Vec<_>>>()?;
+        neba_runtime::format_template(template, &positional, &kwargs).map(Value::str).map_err(VmError::Generic)
+    }
+
+    /// Chiama un builtin; per quelli di `stdlib::STR_BUILTINS` un Char vale come Str.
+    /// Con `alloc_limit` addebita la crescita dei contenitori ricevuti (`push`,
+    /// `insert`, ...) e il risultato, se è un valore nuovo.
+    fn call_native(&self, name: &str, f: crate::value::NativeFn, args: Vec<Value>) -> VmResult {
+        let args = if args.iter().any(|a| matches!(a, Value::Char(_))) && stdlib::STR_BUILTINS.contains(&name) {
+            args.into_iter().map(Value::char_as_str).collect()
+        } else { args };
+        if self.alloc_limit == 0 { return f(&args).map_err(VmError::Generic); }
+        let before: usize = args.iter().map(footprint).sum();
+        let result = f(&args).map_err(VmError::Generic)?;
+        let grown = args.iter().map(footprint).sum::<usize>().saturating_sub(before);
+        let fresh = if args.iter().any(|a| same_container(a, &result)) { 0 } else { footprint(&result) };
+        self.charge(grown + fresh)?;
+        Ok(result)
     }
 
     // ── Operazioni aritmetiche ────────────────────────────────────────────
 
+    /// Implementa AddStoreLocal: `l` è la copia del local appena caricata.
+    fn add_store_local(&mut self, slot: usize, l: Value, r: Value) -> Result<(), VmError> {
+        // Fast-path Int + Int, con lo stesso wrapping di Op::Add
+        if let (Value::Int(a), Value::Int(b)) = (&l, &r) {
+            self.stack[slot] = Value::Int(a.wrapping_add(*b));
+            return Ok(());
+        }
+        if let Some(total) = append_len(&self.stack[slot], &l, &r) {
+            // Rilasciata la copia, il buffer del local è di norma unico: make_mut non clona
+            drop(l);
+            self.charge(total)?;
+            if let (Value::Str(cur), Value::Str(r)) = (&mut self.stack[slot], &r) { Rc::make_mut(cur).push_str(r); }
+            return Ok(());
+        }
+        self.stack[slot] = self.op_add(l, r)?;
+        Ok(())
+    }
+
+    /// Implementa AddStoreGlobal, con gli stessi controlli di StoreGlobal.
+    fn add_store_global(&mut self, name: String, l: Value, r: Value) -> Result<(), VmError> {
+        let total = match self.globals.get(&name) {
+            Some((cur, true)) => append_len(cur, &l, &r),
+            _ => None,
+        };
+        let v = match (total, &l, &r) {
+            (_, Value::Int(a), Value::Int(b)) => Value::Int(a.wrapping_add(*b)),
+            (Some(total), _, _) => {
+                drop(l);
+                self.charge(total)?;
+                if let (Some((Value::Str(cur), _)), Value::Str(r)) = (self.globals.get_mut(&name), &r) { Rc::make_mut(cur).push_str(r); }
+                return Ok(());
+            }
+            (None, _, _) => self.op_add(l, r)?,
+        };
+        match self.globals.get_mut(&name) {
+            Some((cur, true)) => *cur = v,
+            Some((_, false))  => return Err(VmError::AssignImmutable(name)),
+            None              => return Err(VmError::UndefinedVariable(name)),
+        }
+        Ok(())
+    }
+
     fn op_add(&self, l: Value, r: Value) -> VmResult {
         match (&l, &r) {
-            (Value::Int(a),   Value::Int(b))   => Ok(Value::Int(a + b)),
+            (Value::Int(a),   Value::Int(b))   => Ok(Value::Int(a.wrapping_add(*b))),
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
             (Value::Int(a),   Value::Float(b)) => Ok(Value::Float(*a as f64 + b)),
             (Value::Float(a), Value::Int(b))   => Ok(Value::Float(a + *b as f64)),
-            (Value::Str(a),   Value::Str(b))   => Ok(Value::str(format!("{}{}", a, b))),
+            (Value::Str(a),   Value::Str(b))   => {
+                self.charge(a.len() + b.len())?;
+                // Temporanei (`a + b + c`): se il sinistro non è condiviso ne riusa il buffer
+                let Value::Str(mut a) = l else { unreachable!() };
+                match Rc::get_mut(&mut a) {
+                    Some(s) => { s.push_str(b); Ok(Value::Str(a)) }
+                    None    => Ok(Value::str(format!("{}{}", a, b))),
+                }
+            }
+            (Value::Char(_), _) | (_, Value::Char(_)) => self.op_add(l.char_as_str(), r.char_as_str()),
             (Value::TypedArray(_), _) | (_, Value::TypedArray(_)) => typed_binop(&l, &r, |a,b| a+b, |a,b| a+b),
             (Value::NdArray(a), Value::NdArray(b)) => Ok(Value::nd_array(a.borrow().ewise_op(&b.borrow(), |x,y| x+y).map_err(VmError::TypeError)?)),
             (Value::NdArray(a), Value::Float(b)) => Ok(Value::nd_array(a.borrow().ewise_scalar(*b, |x,y| x+y))),
@@ -1120,8 +1571,12 @@ impl Vm {
             (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
             (Value::Int(a),   Value::Float(b)) => Ok(Value::Float(*a as f64 * b)),
             (Value::Float(a), Value::Int(b))   => Ok(Value::Float(a * *b as f64)),
-            (Value::Str(s),   Value::Int(n))   => Ok(Value::str(s.repeat((*n).max(0) as usize))),
-            (Value::Int(n),   Value::Str(s))   => Ok(Value::str(s.repeat((*n).max(0) as usize))),
+            (Value::Str(s),   Value::Int(n)) | (Value::Int(n), Value::Str(s)) => {
+                let n = (*n).max(0) as usize;
+                self.charge(s.len().saturating_mul(n))?;
+                Ok(Value::str(s.repeat(n)))
+            }
+            (Value::Char(_), Value::Int(_)) | (Value::Int(_), Value::Char(_)) => self.op_mul(l.char_as_str(), r.char_as_str()),
             (Value::TypedArray(_), _) | (_, Value::TypedArray(_)) => typed_binop(&l, &r, |a,b| a*b, |a,b| a*b),
             (Value::NdArray(a), Value::NdArray(b)) => Ok(Value::nd_array(a.borrow().ewise_op(&b.borrow(), |x,y| x*y).map_err(VmError::TypeError)?)),
             (Value::NdArray(a), Value::Float(b)) => Ok(Value::nd_array(a.borrow().ewise_scalar(*b, |x,y| x*y))),
@@ -1145,7 +1600,8 @@ impl Vm {
             _ => {
                 let b = r.as_float().ok_or_else(|| VmError::TypeError(format!("'/' on {}", r.type_name())))?;
                 let a = l.as_float().ok_or_else(|| VmError::TypeError(format!("'/' on {}", l.type_name())))?;
-                // Float/Float (o misto): segue IEEE 754 — 1.0/0.0=Inf, 0.0/0.0=NaN
+                // Float/Float (o misto): IEEE 754 — 1.0/0.0=Inf, 0.0/0.0=NaN — solo con `set_ieee_division(true)`
+                if b == 0.0 && !self.ieee_division { return Err(VmError::DivisionByZero); }
                 Ok(Value::Float(a / b))
             }
         }
@@ -1224,6 +1680,7 @@ impl Vm {
     }
 
     fn eval_index(&self, obj: Value, idx: Value) -> VmResult {
+        let obj = obj.char_as_str();
         // NdArray: m[i] → riga (get_axis0), m[[i,j]] → scalare, m[i] su 1D → scalare
         if let Value::NdArray(ref nd) = obj {
             return match &idx {
@@ -1253,7 +1710,7 @@ impl Vm {
                 match &idx {
                     Value::Int(i) => {
                         let ui = crate::value::resolve_idx(*i, len).map_err(VmError::Generic)?;
-                        ta.get(ui).ok_or_else(|| VmError::IndexOutOfBounds { index: *i, len })
+                        ta.get(ui).ok_or(VmError::IndexOutOfBounds { index: *i, len, pos: None })
                     }
                     // Lazy range (v0.2.14) — genera gli indici senza allocare un Vec intermedio
                     Value::IntRange(start, end, inclusive) => {
@@ -1287,18 +1744,18 @@ impl Vm {
                         Ok(Value::array(arr[s..e.max(s)].to_vec()))
                     }
                     (Value::Str(st), Value::IntRange(start, end, inclusive)) => {
-                        let chars: Vec<char> = st.chars().collect();
+                        let chars = self.str_units(st);
                         let len = chars.len() as i64;
                         let s = if *start < 0 { (len + start).max(0) } else { (*start).min(len) } as usize;
                         let e = if *end < 0 { (len + end).max(0) } else { (*end).min(len) } as usize;
                         let e = if *inclusive { (e + 1).min(chars.len()) } else { e };
-                        Ok(Value::str(chars[s..e.max(s)].iter().collect::<String>()))
+                        Ok(Value::str(chars[s..e.max(s)].concat()))
                     }
                     _ => {
                         let i = match &idx { Value::Int(n) => n, _ => return Err(VmError::TypeError("array/string index must be Int".into())) };
                         match obj {
                             Value::Array(arr) => { let len = arr.borrow().len(); let a = self.resolve_idx(*i, len)?; Ok(arr.borrow()[a].clone()) }
-                            Value::Str(st) => { let chars: Vec<char> = st.chars().collect(); let a = self.resolve_idx(*i, chars.len())?; Ok(Value::str(chars[a].to_string())) }
+                            Value::Str(st) => { let chars = self.str_units(&st); let a = self.resolve_idx(*i, chars.len())?; Ok(Value::str(chars[a])) }
                             _ => Err(VmError::TypeError(format!("cannot index {}", obj.type_name()))),
                         }
                     }
@@ -1309,7 +1766,7 @@ impl Vm {
 
     fn resolve_idx(&self, i: i64, len: usize) -> VmResult<usize> {
         let a = if i < 0 { len as i64 + i } else { i };
-        if a < 0 || a as usize >= len { Err(VmError::IndexOutOfBounds { index: i, len }) }
+        if a < 0 || a as usize >= len { Err(VmError::IndexOutOfBounds { index: i, len, pos: None }) }
         else { Ok(a as usize) }
     }
 
@@ -1317,9 +1774,13 @@ impl Vm {
         match haystack {
             Value::Array(arr) => Ok(arr.borrow().contains(&needle)),
             Value::Dict(d)    => Ok(d.borrow().contains_key(&needle)),
-            Value::Str(s)     => match &needle { Value::Str(n) => Ok(s.contains(n.as_str())), _ => Ok(false) },
+            Value::Str(s)     => match &needle {
+                Value::Str(n)  => Ok(s.contains(n.as_str())),
+                Value::Char(c) => Ok(s.contains(*c)),
+                _ => Ok(false),
+            },
             Value::IntRange(start, end, inclusive) => match &needle {
-                Value::Int(n) => Ok(if inclusive { *n >= start && *n <= end } else { *n >= start && *n < end }),
+                Value::Int(n) => Ok(crate::value::range_contains(start, end, inclusive, *n)),
                 _ => Ok(false),
             },
             _ => Err(VmError::TypeError(format!("'in' on {}", haystack.type_name()))),
@@ -1332,7 +1793,7 @@ impl Vm {
 
     pub fn call_value_sync(&mut self, callee: Value, args: Vec<Value>) -> VmResult<Value> {
         match callee {
-            Value::NativeFn(_, f) => f(&args).map_err(VmError::Generic),
+            Value::NativeFn(n, f) => self.call_native(&n, f, args),
             Value::Closure(c) => {
                 let depth    = self.frames.len();
                 let base_idx = self.stack.len();
@@ -1342,7 +1803,7 @@ impl Vm {
                 let new_base = base_idx + 1;
                 self.frames.push(CallFrame {
                     chunk: Rc::clone(&proto.chunk), ip: 0,
-                    base: new_base, name: proto.name.clone(),
+                    base: new_base,
                     upvalues: Rc::clone(&c.upvalues),
                 });
 
@@ -1362,6 +1823,7 @@ impl Vm {
                 macro_rules! lf     { () => {{ let f = self.frames.last().unwrap(); chunk_ptr = Rc::as_ptr(&f.chunk); ip = f.ip; base = f.base; }} }
 
                 loop {
+                    if self.step_limit > 0 { self.tick()?; }
                     let op_byte = cc!().code[ip];
                     let op = Op::from_u8(op_byte).ok_or_else(|| VmError::Generic(format!("bad opcode {}", op_byte)))?;
                     ip += 1;
@@ -1375,6 +1837,8 @@ impl Vm {
                         Op::Dup    => { let v = ck!(); ps!(v); }
                         Op::Swap   => { let t = cp!(); let u = cp!(); ps!(t); ps!(u); }
                         Op::PopN   => { let n = ru8!() as usize; let l = self.stack.len(); self.stack.truncate(l - n); }
+                        Op::Rot    => { let n = ru8!() as usize; let l = self.stack.len(); self.stack[l - n..].rotate_right(1); }
+                        Op::Over   => { let v = self.stack[self.stack.len() - 2].clone(); ps!(v); }
                         Op::LoadLocal  => { let i = ru8!() as usize; let v = self.stack[base+i].clone(); ps!(v); }
                         Op::StoreLocal => { let i = ru8!() as usize; let v = cp!(); self.stack[base+i] = v; }
                         Op::LoadLocal0 => { ps!(self.stack[base].clone()); }
@@ -1389,6 +1853,9 @@ impl Vm {
                         Op::StoreUpval => { let i = ru8!() as usize; let v = cp!(); *self.frames.last_mut().unwrap().upvalues[i].value.borrow_mut() = v; }
                         Op::LoadGlobal  => { let i = ru16!() as usize; let n = &cc!().names[i]; let v = self.globals.get(n).map(|(v,_)| v.clone()).ok_or_else(|| VmError::UndefinedVariable(n.clone()))?; ps!(v); }
                         Op::StoreGlobal => { let i = ru16!() as usize; let n = cc!().names[i].clone(); let v = cp!(); match self.globals.get_mut(&n) { Some((c,true)) => *c=v, Some((_,false)) => return Err(VmError::AssignImmutable(n)), None => return Err(VmError::UndefinedVariable(n)) } }
+                        Op::AddStoreLocal  => { let i = ru8!() as usize; let r = cp!(); let l = cp!(); self.add_store_local(base+i, l, r)?; }
+                        Op::AddStoreGlobal => { let i = ru16!() as usize; let n = cc!().names[i].clone(); let r = cp!(); let l = cp!(); self.add_store_global(n, l, r)?; }
+                        Op::TagArray    => { let i = ru16!() as usize; if self.checked_arrays { if let Some(Value::Array(a)) = self.stack.last() { crate::value::tag_array(a, &cc!().names[i]).map_err(VmError::TypeError)?; } } }
                         Op::DefGlobal   => { let i = ru16!() as usize; let m = ru8!()!=0; let n = cc!().names[i].clone(); let v = cp!(); self.globals.insert(n,(v,m)); }
                         Op::Add    => { let r = cp!(); let l = cp!(); ps!(self.op_add(l,r)?); }
                         Op::Sub    => { let r = cp!(); let l = cp!(); ps!(self.op_sub(l,r)?); }
@@ -1414,8 +1881,8 @@ impl Vm {
                         Op::Jump          => { let o = ri16!(); ip = (ip as isize + o as isize) as usize; }
                         Op::JumpFalse     => { let o = ri16!(); let v = cp!(); if !v.is_truthy() { ip = (ip as isize+o as isize) as usize; } }
                         Op::JumpTrue      => { let o = ri16!(); let v = cp!(); if  v.is_truthy() { ip = (ip as isize+o as isize) as usize; } }
-                        Op::JumpFalsePeek => { let o = ri16!(); if !ck!().is_truthy() { ip = (ip as isize+o as isize) as usize; } }
-                        Op::JumpTruePeek  => { let o = ri16!(); if  ck!().is_truthy() { ip = (ip as isize+o as isize) as usize; } }
+                        Op::JumpFalseOrPop => { let o = ri16!(); if  ck!().is_truthy() { self.stack.pop(); } else { ip = (ip as isize+o as isize) as usize; } }
+                        Op::JumpTrueOrPop  => { let o = ri16!(); if !ck!().is_truthy() { self.stack.pop(); } else { ip = (ip as isize+o as isize) as usize; } }
                         Op::MakeClosure => {
                             let i = ru16!() as usize; let nu = ru8!() as usize;
                             let proto = Rc::new(cc!().fn_protos[i].clone());
@@ -1428,7 +1895,15 @@ impl Vm {
                             let fi = self.stack.len() - argc - 1;
                             let callee = self.stack[fi].clone();
                             match callee {
-                                Value::NativeFn(_, f) => { let args: Vec<Value> = self.stack.drain(fi..).skip(1).collect(); ps!(f(&args).map_err(VmError::Generic)?); }
+                                Value::NativeFn(n, _) if n.as_str() == "assert_raises" => {
+                                    let args: Vec<Value> = self.stack.drain(fi..).skip(1).collect();
+                                    sip!(); let r = self.assert_raises(args)?; ps!(r); lf!();
+                                }
+                                Value::NativeFn(n, _) if matches!(n.as_str(), "and_then" | "or_else" | "map_err") => {
+                                    let args: Vec<Value> = self.stack.drain(fi..).skip(1).collect();
+                                    sip!(); let r = self.combinator(&n, args)?; ps!(r); lf!();
+                                }
+                                Value::NativeFn(n, f) => { let args: Vec<Value> = self.stack.drain(fi..).skip(1).collect(); let v = self.call_native(&n, f, args)?; ps!(v); }
                                 Value::Closure(c2) => {
                                     let p = &c2.proto;
                                     if argc < p.arity || argc > p.max_arity { return Err(VmError::ArityMismatch { name: p.name.clone(), expected: p.arity, got: argc }); }
@@ -1436,7 +1911,7 @@ impl Vm {
                                     for i2 in 0..miss { let di = p.defaults.len().saturating_sub(miss-i2); ps!(p.defaults.get(di).cloned().unwrap_or(Value::None)); }
                                     let nb = fi + 1; self.stack[fi] = Value::None;
                                     sip!();
-                                    self.frames.push(CallFrame { chunk: Rc::clone(&p.chunk), ip: 0, base: nb, name: p.name.clone(), upvalues: Rc::clone(&c2.upvalues) });
+                                    self.frames.push(CallFrame { chunk: Rc::clone(&p.chunk), ip: 0, base: nb, upvalues: Rc::clone(&c2.upvalues) });
                                     lf!();
                                 }
                                 other => return Err(VmError::NotCallable(other.type_name().to_string())),
@@ -1465,11 +1940,11 @@ impl Vm {
                         }
                         Op::GetField => { let i = ru16!() as usize; let n = cc!().names[i].clone(); let o = cp!(); ps!(self.get_field(o, &n)?); }
                         Op::SetField => { let i = ru16!() as usize; let n = cc!().names[i].clone(); let v = cp!(); let o = cp!(); match o { Value::Instance(inst) => { inst.borrow_mut().fields.insert(n,v); } _ => return Err(VmError::TypeError(format!("cannot set field on {}", o.type_name()))) } }
-                        Op::MakeInstance => { let i = ru16!() as usize; let cn = cc!().names[i].clone(); ps!(Value::Instance(Rc::new(RefCell::new(Instance::new(&cn))))); }
+                        Op::MakeInstance => { let i = ru16!() as usize; let cn = cc!().names[i].clone(); self.charge(std::mem::size_of::<Instance>())?; ps!(Value::Instance(Rc::new(RefCell::new(Instance::new(&cn))))); }
                         Op::SetTraits => { let n = ru8!() as usize; let mut tr = Vec::with_capacity(n); for _ in 0..n { let i = ru16!() as usize; tr.push(cc!().names[i].clone()); } if let Some(Value::Instance(inst)) = self.stack.last() { inst.borrow_mut().traits = tr; } }
-                        Op::MakeArray => { let c = ru16!() as usize; let s = self.stack.len()-c; let items: Vec<Value> = self.stack.drain(s..).collect(); ps!(Value::array(items)); }
-                        Op::MakeDict  => { let c = ru16!() as usize; let s = self.stack.len()-c*2; let flat: Vec<Value> = self.stack.drain(s..).collect(); let pairs: Vec<(Value,Value)> = flat.chunks(2).map(|c| (c[0].clone(),c[1].clone())).collect(); ps!(Value::dict(pairs)); }
-                        Op::GetIndex  => { let i = cp!(); let o = cp!(); ps!(self.eval_index(o,i)?); }
+                        Op::MakeArray => { let c = ru16!() as usize; self.charge(c * VALUE_SIZE)?; let s = self.stack.len()-c; let items: Vec<Value> = self.stack.drain(s..).collect(); ps!(Value::array(items)); }
+                        Op::MakeDict  => { let c = ru16!() as usize; self.charge(c * 2 * VALUE_SIZE)?; let s = self.stack.len()-c*2; let flat: Vec<Value> = self.stack.drain(s..).collect(); let dict = self.build_dict(flat)?; ps!(dict); }
+                        Op::GetIndex  => { let i = cp!(); let o = cp!(); let i = self.dict_key(&o,i)?; ps!(self.eval_index(o,i)?); }
                         Op::GetSlice  => {
                             let flags = ru8!();
                             let step  = if flags & 4 != 0 { Some(cp!()) } else { None };
@@ -1481,15 +1956,15 @@ impl Vm {
                         Op::MakeSome  => { let v = cp!(); ps!(Value::Some_(Box::new(v))); }
                         Op::MakeOk    => { let v = cp!(); ps!(Value::Ok_(Box::new(v)));   }
                         Op::MakeErr   => { let v = cp!(); ps!(Value::Err_(Box::new(v)));  }
-                        Op::In    => { let h = cp!(); let n = cp!(); ps!(Value::Bool(self.eval_in(n,h)?)); }
-                        Op::NotIn => { let h = cp!(); let n = cp!(); ps!(Value::Bool(!self.eval_in(n,h)?)); }
-                        Op::Is    => { let r = cp!(); let l = cp!(); let res = match (&l,&r) { (Value::Instance(i),Value::Str(s)) if !s.starts_with("trait:") => i.borrow().class_name==s.as_str(), (Value::Instance(i),Value::Str(s)) if s.starts_with("trait:") => { let tn=&s["trait:".len()..]; i.borrow().traits.iter().any(|t|t==tn) } (Value::Instance(a),Value::Instance(b)) => a.borrow().class_name==b.borrow().class_name, _ => std::mem::discriminant(&l)==std::mem::discriminant(&r) }; ps!(Value::Bool(res)); }
+                        Op::In    => { let h = cp!(); let n = cp!(); let n = self.dict_key(&h,n)?; ps!(Value::Bool(self.eval_in(n,h)?)); }
+                        Op::NotIn => { let h = cp!(); let n = cp!(); let n = self.dict_key(&h,n)?; ps!(Value::Bool(!self.eval_in(n,h)?)); }
+                        Op::Is    => { let r = cp!(); let l = cp!(); ps!(Value::Bool(op_is(&l, &r))); }
                         Op::IsSome => { let o = ri16!(); if !matches!(ck!(), Value::Some_(_)) { ip = (ip as isize+o as isize) as usize; } }
                         Op::IsNone => { let o = ri16!(); if !matches!(ck!(), Value::None)     { ip = (ip as isize+o as isize) as usize; } }
                         Op::IsOk   => { let o = ri16!(); if !matches!(ck!(), Value::Ok_(_))   { ip = (ip as isize+o as isize) as usize; } }
                         Op::IsErr  => { let o = ri16!(); if !matches!(ck!(), Value::Err_(_))  { ip = (ip as isize+o as isize) as usize; } }
                         Op::Unwrap => { let v = cp!(); let inner = match v { Value::Some_(i)|Value::Ok_(i)|Value::Err_(i) => *i, _ => return Err(VmError::TypeError(format!("cannot unwrap {}", v.type_name()))) }; ps!(inner); }
-                        Op::BuildStr => { let n = ru16!() as usize; let s = self.stack.len()-n; let parts: Vec<String> = self.stack.drain(s..).map(|v| v.to_string()).collect(); ps!(Value::str(parts.join(""))); }
+                        Op::BuildStr => { let n = ru16!() as usize; let s = self.stack.len()-n; let parts: Vec<String> = self.stack.drain(s..).map(|v| v.to_string()).collect(); self.charge(parts.iter().map(String::len).sum())?; ps!(Value::str(parts.join(""))); }
                         Op::ToStr => { let v = cp!(); ps!(Value::str(v.to_string())); }
                         Op::Nop  => {}
                         Op::Halt => { let r = self.stack.pop().unwrap_or(Value::None); self.stack.truncate(base_idx); return Ok(r); }
@@ -1500,7 +1975,8 @@ impl Vm {
                                 Value::Array(a) => a,
                                 Value::Dict(d) => { let p: Vec<Value> = d.borrow().iter().map(|(k,v): (&Value,&Value)| Value::array(vec![k.clone(),v.clone()])).collect(); Rc::new(RefCell::new(p)) }
                                 Value::TypedArray(t) => { let d = t.borrow(); let e: Vec<Value> = (0..d.len()).map(|i| d.get(i).unwrap()).collect(); Rc::new(RefCell::new(e)) }
-                                Value::Str(s) => { let ch: Vec<Value> = s.chars().map(|c| Value::str(c.to_string())).collect(); Rc::new(RefCell::new(ch)) }
+                                Value::Str(s) => { let ch: Vec<Value> = self.str_units(&s).into_iter().map(Value::str).collect(); Rc::new(RefCell::new(ch)) }
+                                Value::Char(_) => Rc::new(RefCell::new(vec![v])),
                                 _ => return Err(VmError::TypeError(format!("'{}' is not iterable", v.type_name()))),
                             };
                             ps!(Value::Array(arr));
@@ -1511,10 +1987,8 @@ impl Vm {
                             match self.stack[base+il].clone() {
                                 Value::IntRange(start, end, inclusive) => {
                                     let pos = match &self.stack[base+pl] { Value::Int(n) => *n, _ => 0 };
-                                    let actual = start + pos;
-                                    let done = if inclusive { actual > end } else { actual >= end };
-                                    if done { ip = (ip as isize+off as isize) as usize; }
-                                    else { self.stack[base+vl] = Value::Int(actual); self.stack[base+pl] = Value::Int(pos+1); }
+                                    if pos >= crate::value::range_len(start, end, inclusive) { ip = (ip as isize+off as isize) as usize; }
+                                    else { self.stack[base+vl] = Value::Int(crate::value::range_nth(start, pos)); self.stack[base+pl] = Value::Int(pos+1); }
                                 }
                                 Value::Array(arr) => {
                                     let pos = match &self.stack[base+pl] { Value::Int(n) => *n as usize, _ => 0 };
@@ -1526,6 +2000,16 @@ impl Vm {
                             }
                         }
                         Op::MatchLit => { let ci = ru16!() as usize; let o = ri16!(); let lit = cc!().constants[ci].clone(); if ck!() != lit { ip = (ip as isize+o as isize) as usize; } }
+                        Op::MatchAffix => {
+                            let (pi, si, o) = (ru16!() as usize, ru16!() as usize, ri16!());
+                            if affix_middle(&ck!(), &cc!().constants[pi], &cc!().constants[si]).is_none() { ip = (ip as isize+o as isize) as usize; }
+                        }
+                        Op::StripAffix => {
+                            let (pi, si) = (ru16!() as usize, ru16!() as usize);
+                            let v = cp!();
+                            ps!(affix_middle(&v, &cc!().constants[pi], &cc!().constants[si])
+                                .ok_or_else(|| VmError::TypeError(format!("string pattern on {}", v.type_name())))?);
+                        }
                         Op::MatchRange => {
                             let li = ru16!() as usize; let hii = ru16!() as usize; let incl = ru8!()!=0; let off = ri16!();
                             let lo = match &cc!().constants[li] { Value::Int(n) => n, _ => return Err(VmError::TypeError("range pattern needs Int".into())) };
@@ -1540,12 +2024,21 @@ impl Vm {
                                 _ => return Err(VmError::TypeError("range bounds must be Int".into())),
                             }
                         }
+                        Op::SetSlice => {
+                            let flags = ru8!();
+                            let val   = cp!();
+                            let end   = if flags & 2 != 0 { Some(cp!()) } else { None };
+                            let start = if flags & 1 != 0 { Some(cp!()) } else { None };
+                            let obj   = cp!();
+                            self.assign_slice(obj, start, end, val)?;
+                        }
                         Op::SetIndex => {
                             let val = cp!(); let idx_v = cp!(); let obj = cp!();
+                            let idx_v = self.dict_key(&obj, idx_v)?;
                             match (obj, &idx_v) {
-                                (Value::Array(arr), Value::Int(i)) => { let len = arr.borrow().len(); let i = self.resolve_idx(*i,len)?; arr.borrow_mut()[i] = val; }
+                                (Value::Array(arr), Value::Int(i)) => { crate::value::check_mutable(&arr, "index assignment").map_err(VmError::TypeError)?; crate::value::check_elem(&arr, &val, "index assignment").map_err(VmError::TypeError)?; let len = arr.borrow().len(); let i = self.resolve_idx(*i,len)?; arr.borrow_mut()[i] = val; }
                                 (Value::TypedArray(t), Value::Int(i)) => { let len = t.borrow().len(); let i = crate::value::resolve_idx(*i,len).map_err(VmError::Generic)?; t.borrow_mut().set(i,val).map_err(VmError::TypeError)?; }
-                                (Value::Dict(d), key) => { d.borrow_mut().insert(key.clone(), val); }
+                                (Value::Dict(d), key) => { if !d.borrow().contains_key(key) { self.charge(2 * VALUE_SIZE)?; } d.borrow_mut().insert(key.clone(), val); }
                                 _ => return Err(VmError::TypeError("index assignment requires Array, TypedArray or Dict".into())),
                             }
                         }
@@ -1570,6 +2063,12 @@ impl Vm {
                             let name = cc!().names[ni].clone();
                             let oi = self.stack.len() - argc - 1;
                             let obj = self.stack[oi].clone();
+                            if let (Value::Str(st), "format" | "format**") = (&obj, name.as_str()) {
+                                let args: Vec<Value> = self.stack.drain(oi..).skip(1).collect();
+                                let v = self.str_format(st, args, name.as_str() == "format**")?;
+                                ps!(v);
+                                continue;
+                            }
 
                             let method = match &obj {
                                 Value::Instance(inst) => inst.borrow().fields.get(&name).cloned()
@@ -1588,12 +2087,13 @@ impl Vm {
                                     for i2 in 0..miss { let di = p.defaults.len().saturating_sub(miss-i2); ps!(p.defaults.get(di).cloned().unwrap_or(Value::None)); }
                                     let nb = oi+1; self.stack[oi] = Value::None;
                                     sip!();
-                                    self.frames.push(CallFrame { chunk: Rc::clone(&p.chunk), ip: 0, base: nb, name: p.name.clone(), upvalues: Rc::clone(&c2.upvalues) });
+                                    self.frames.push(CallFrame { chunk: Rc::clone(&p.chunk), ip: 0, base: nb, upvalues: Rc::clone(&c2.upvalues) });
                                     lf!();
                                 }
-                                Value::NativeFn(_, f) => {
+                                Value::NativeFn(n, f) => {
                                     let args: Vec<Value> = if is_mod { self.stack.drain(oi..).collect() } else { self.stack.drain(oi..).skip(1).collect() };
-                                    ps!(f(&args).map_err(VmError::Generic)?);
+                                    let v = self.call_native(&n, f, args)?;
+                                    ps!(v);
                                 }
                                 other => return Err(VmError::NotCallable(other.type_name().to_string())),
                             }
@@ -1609,3 +2109,67 @@ impl Vm {
 impl Default for Vm {
     fn default() -> Self { Self::new() }
 }
+
+/// Valori che `value_display_string` può stampare diversamente da `Display` (`__str__`).
+fn may_hold_instance(v: &Value) -> bool {
+    matches!(v, Value::Instance(_) | Value::Array(_) | Value::Dict(_) | Value::Some_(_) | Value::Ok_(_) | Value::Err_(_))
+}
+
+/// Memoria stimata di un valore per `alloc_limit`: contenitori e stringhe, non il contenuto annidato.
+fn footprint(v: &Value) -> usize {
+    match v {
+        Value::Array(a)      => a.borrow().len() * VALUE_SIZE,
+        Value::Dict(d)       => d.borrow().len() * 2 * VALUE_SIZE,
+        Value::TypedArray(t) => t.borrow().len() * 8,
+        Value::Str(s)        => s.len(),
+        _ => 0,
+    }
+}
+
+fn same_container(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Array(x), Value::Array(y)) => Rc::ptr_eq(x, y),
+        (Value::Dict(x), Value::Dict(y))   => Rc::ptr_eq(x, y),
+        (Value::TypedArray(x), Value::TypedArray(y)) => Rc::ptr_eq(x, y),
+        _ => false,
+    }
+}
+
+/// `l is r`. A destra il compilatore emette il nome di classe (`"Point"`),
+/// di trait (`"trait:Show"`) o di tipo built-in (`"type:Int"`) come Str.
+fn op_is(l: &Value, r: &Value) -> bool {
+    match (l, r) {
+        // Un range lazy vale come Array, come nell'interprete che lo materializza
+        (Value::IntRange(..), Value::Str(s)) if s.as_str() == "type:Array" => true,
+        (_, Value::Str(s)) if s.starts_with("type:") => l.type_name() == &s["type:".len()..],
+        (Value::Instance(inst), Value::Str(s)) if !s.starts_with("trait:") => {
+            inst.borrow().class_name == s.as_str()
+        }
+        (Value::Instance(inst), Value::Str(s)) => {
+            let tn = &s["trait:".len()..];
+            inst.borrow().traits.iter().any(|t| t == tn)
+        }
+        (Value::Instance(a), Value::Instance(b)) => {
+            a.borrow().class_name == b.borrow().class_name
+        }
+        _ => std::mem::discriminant(l) == std::mem::discriminant(r),
+    }
+}
+
+/// Lunghezza risultante se `cur + r` può essere accodato in place: `cur` è una Str,
+/// `l` è la copia appena caricata dalla stessa variabile e `r` è una Str.
+fn append_len(cur: &Value, l: &Value, r: &Value) -> Option<usize> {
+    match (cur, l, r) {
+        (Value::Str(c), Value::Str(a), Value::Str(b)) if Rc::ptr_eq(c, a) => Some(c.len() + b.len()),
+        _ => None,
+    }
+}
+
+/// Parte centrale di `v` per un pattern `"pre" + rest + "suf"` (None se non è una
+/// Str con quel prefisso e suffisso).
+fn affix_middle(v: &Value, prefix: &Value, suffix: &Value) -> Option<Value> {
+    match (v, prefix, suffix) {
+        (Value::Str(s), Value::Str(p), Value::Str(x)) => neba_parser::ast::Pattern::affix_middle(s, p, x).map(Value::str),
+        _ => None,
+    }
+}